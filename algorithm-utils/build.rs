@@ -0,0 +1,14 @@
+use std::env;
+use std::process::Command;
+
+/// Passes the version of the compiler to the crate, algorithms built with another one are refused
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=RUSTC_VERSION={}", version.trim());
+}
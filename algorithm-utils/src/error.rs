@@ -0,0 +1,53 @@
+use std::error;
+use std::fmt;
+
+/// An error with a kind and a message for the user
+#[derive(Debug, PartialEq, Clone)]
+pub struct Error<K> {
+    kind: K,
+    msg: String,
+}
+
+impl<K> Error<K> {
+    pub fn new<M: Into<String>>(kind: K, msg: M) -> Self {
+        Self {
+            kind,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn kind(&self) -> &K { &self.kind }
+    pub fn msg(&self) -> &str { &self.msg }
+}
+
+impl<K: fmt::Debug> fmt::Display for Error<K> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{:?}: {}", self.kind, self.msg)
+    }
+}
+
+impl<K: fmt::Debug> error::Error for Error<K> {}
+
+/// The errors an algorithm returns to trading-desk
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TradingErrorKind {
+    /// the algorithm can't go on, trading-desk stops the session
+    Panic,
+    /// the data the algorithm received can't be used
+    InvalidData,
+    Other,
+}
+
+/// The errors of an algorithm are trading errors
+pub type ErrorKind = TradingErrorKind;
+
+/// The errors of loading the library of an algorithm
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LoadErrorKind {
+    Io,
+    Library,
+    /// the library was built with another compiler or another version of algorithm-utils
+    Version,
+    /// an algorithm with the same name is already loaded
+    Duplicate,
+}
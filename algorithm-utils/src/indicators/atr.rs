@@ -0,0 +1,67 @@
+use crate::Price;
+use crate::indicators::{check_period, Indicator, Ohlc};
+
+/// The average true range with Wilder's smoothing
+///
+/// The true range of a bar reaches from the close of the previous bar to its
+/// high or low if they are outside of its range, so the first bar only
+/// provides a close. With raw prices it's the average absolute price change.
+pub struct Atr {
+    period: usize,
+    previous_close: Option<Price>,
+    /// the true ranges seen so far, up to the period
+    ranges: usize,
+    value: f64,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Result<Self, String> {
+        check_period("the ATR", period)?;
+        Ok(Self {
+            period,
+            previous_close: None,
+            ranges: 0,
+            value: 0.0,
+        })
+    }
+
+    /// the bars needed for the first value (one more than the period, since the first bar has no true range)
+    pub const fn min_data_length(period: usize) -> usize { period + 1 }
+}
+
+impl Indicator for Atr {
+    type Input = Ohlc;
+    type Output = f64;
+
+    fn update(&mut self, bar: Ohlc) -> Option<f64> {
+        // the first bar only provides the close
+        let previous_close = self.previous_close.replace(bar.close)?;
+        let true_range = (bar.high - bar.low)
+            .max((bar.high - previous_close).abs())
+            .max((bar.low - previous_close).abs());
+
+        if self.ranges < self.period {
+            self.value += true_range;
+            self.ranges += 1;
+            if self.ranges == self.period {
+                self.value /= self.period as f64;
+            }
+        } else {
+            let period = self.period as f64;
+            self.value = (self.value * (period - 1.0) + true_range) / period;
+        }
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.ranges < self.period { None } else { Some(self.value) }
+    }
+
+    fn warm_up(&self) -> usize { Self::min_data_length(self.period) }
+
+    fn reset(&mut self) {
+        self.previous_close = None;
+        self.ranges = 0;
+        self.value = 0.0;
+    }
+}
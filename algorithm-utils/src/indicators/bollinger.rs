@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+
+use crate::Price;
+use crate::indicators::{check_period, Indicator};
+
+/// The Bollinger bands: the simple moving average and a number of (population) standard deviations around it
+pub struct Bollinger {
+    period: usize,
+    width: f64,
+    window: VecDeque<Price>,
+    sum: f64,
+    sum_squares: f64,
+    /// the updates since the sums were last computed from the window
+    updates: usize,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Bands {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+impl Bollinger {
+    /// `width` is the number of standard deviations, commonly 2 with a period of 20
+    pub fn new(period: usize, width: f64) -> Result<Self, String> {
+        check_period("the Bollinger bands", period)?;
+        if !width.is_finite() || width < 0.0 {
+            return Err(format!("the width of the Bollinger bands needs to be a positive number, not {}", width));
+        }
+        Ok(Self {
+            period,
+            width,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+            sum_squares: 0.0,
+            updates: 0,
+        })
+    }
+
+    /// the prices needed for the first value
+    pub const fn min_data_length(period: usize) -> usize { period }
+}
+
+impl Indicator for Bollinger {
+    type Input = Price;
+    type Output = Bands;
+
+    fn update(&mut self, price: Price) -> Option<Bands> {
+        self.window.push_back(price);
+        self.sum += price;
+        self.sum_squares += price * price;
+        if self.window.len() > self.period {
+            if let Some(oldest) = self.window.pop_front() {
+                self.sum -= oldest;
+                self.sum_squares -= oldest * oldest;
+            }
+        }
+
+        // like the SMA the running sums are computed again once per period
+        self.updates += 1;
+        if self.updates >= self.period {
+            self.sum = self.window.iter().sum();
+            self.sum_squares = self.window.iter().map(|price| price * price).sum();
+            self.updates = 0;
+        }
+        self.value()
+    }
+
+    fn value(&self) -> Option<Bands> {
+        if self.window.len() < self.period {
+            return None;
+        }
+
+        let period = self.period as f64;
+        let middle = self.sum / period;
+        // rounding can make the variance of equal prices slightly negative
+        let deviation = (self.sum_squares / period - middle * middle).max(0.0).sqrt();
+        Some(Bands {
+            upper: middle + self.width * deviation,
+            middle,
+            lower: middle - self.width * deviation,
+        })
+    }
+
+    fn warm_up(&self) -> usize { Self::min_data_length(self.period) }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.sum = 0.0;
+        self.sum_squares = 0.0;
+        self.updates = 0;
+    }
+}
@@ -0,0 +1,72 @@
+use crate::Price;
+use crate::indicators::Indicator;
+use crate::indicators::moving_average::Ema;
+
+/// The moving average convergence divergence
+///
+/// The MACD line is the fast EMA minus the slow EMA, the signal line is the
+/// EMA of the MACD line. Values are returned once the signal line is ready.
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+    value: Option<MacdValue>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MacdValue {
+    pub macd: f64,
+    pub signal: f64,
+    /// the MACD line minus the signal line
+    pub histogram: f64,
+}
+
+impl Macd {
+    /// the common periods are 12, 26 and 9
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Result<Self, String> {
+        if fast >= slow {
+            return Err(format!("the fast period of the MACD ({}) needs to be shorter than the slow one ({})", fast, slow));
+        }
+        Ok(Self {
+            fast: Ema::new(fast)?,
+            slow: Ema::new(slow)?,
+            signal: Ema::new(signal)?,
+            value: None,
+        })
+    }
+
+    /// the prices needed for the first value
+    pub const fn min_data_length(slow: usize, signal: usize) -> usize { slow + signal - 1 }
+}
+
+impl Indicator for Macd {
+    type Input = Price;
+    type Output = MacdValue;
+
+    fn update(&mut self, price: Price) -> Option<MacdValue> {
+        let fast = self.fast.update(price);
+        let slow = self.slow.update(price);
+        if let (Some(fast), Some(slow)) = (fast, slow) {
+            let macd = fast - slow;
+            if let Some(signal) = self.signal.update(macd) {
+                self.value = Some(MacdValue {
+                    macd,
+                    signal,
+                    histogram: macd - signal,
+                });
+            }
+        }
+        self.value
+    }
+
+    fn value(&self) -> Option<MacdValue> { self.value }
+
+    fn warm_up(&self) -> usize { Self::min_data_length(self.slow.period(), self.signal.period()) }
+
+    fn reset(&mut self) {
+        self.fast.reset();
+        self.slow.reset();
+        self.signal.reset();
+        self.value = None;
+    }
+}
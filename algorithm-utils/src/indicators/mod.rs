@@ -0,0 +1,103 @@
+//! Streaming technical indicators for algorithms
+//!
+//! Every indicator is updated with one price at a time in constant time, so
+//! an algorithm doesn't have to go through its whole price window on every
+//! call. Feed the newest price (`prices.last()`) in `collect_prices` and
+//! `algorithm`, the indicators keep the history they need themselves.
+//!
+//! #### warm up
+//! `update` returns `None` until the indicator has seen enough prices and the
+//! value afterwards. The number of prices an indicator needs is returned by
+//! `Indicator::warm_up` and by the `min_data_length` function of each
+//! indicator. Use the largest of them as the MIN_DATA_LENGTH of your
+//! algorithm, then all indicators are ready the first time `algorithm` is
+//! called:
+//! ```
+//! use algorithm_utils::indicators::{Indicator, Rsi, Sma};
+//!
+//! const MIN_DATA_LENGTH: usize = Sma::min_data_length(20);
+//!
+//! let mut sma = Sma::new(20).unwrap();
+//! let mut rsi = Rsi::new(14).unwrap();
+//! for price in (0..MIN_DATA_LENGTH).map(|i| 100.0 + i as f64) {
+//!     sma.update(price);
+//!     rsi.update(price);
+//! }
+//! assert_eq!(sma.value(), Some(109.5));
+//! assert_eq!(rsi.value(), Some(100.0));
+//! ```
+//!
+//! The constructors return an error instead of panicking if a period is
+//! zero, since algorithms must not panic.
+
+mod atr;
+mod bollinger;
+mod macd;
+mod moving_average;
+mod rsi;
+
+pub use atr::Atr;
+pub use bollinger::{Bands, Bollinger};
+pub use macd::{Macd, MacdValue};
+pub use moving_average::{Ema, Sma};
+pub use rsi::Rsi;
+
+use crate::{Bar, Price};
+
+/// An indicator that is updated with one input at a time
+pub trait Indicator {
+    type Input;
+    type Output: Copy;
+
+    /// Adds the input and returns the new value (None while warming up)
+    fn update(&mut self, input: Self::Input) -> Option<Self::Output>;
+
+    /// the value after the last update (None while warming up)
+    fn value(&self) -> Option<Self::Output>;
+
+    /// the number of inputs needed for the first value
+    fn warm_up(&self) -> usize;
+
+    /// Forgets all inputs, like after a gap in the prices
+    fn reset(&mut self);
+
+    fn is_ready(&self) -> bool { self.value().is_some() }
+}
+
+/// The high, low and close price of a bar for indicators that need the range
+///
+/// A raw price is a bar whose high, low and close are the price.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Ohlc {
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+}
+
+impl From<Price> for Ohlc {
+    fn from(price: Price) -> Self {
+        Self {
+            high: price,
+            low: price,
+            close: price,
+        }
+    }
+}
+
+impl From<&Bar> for Ohlc {
+    fn from(bar: &Bar) -> Self {
+        Self {
+            high: bar.high(),
+            low: bar.low(),
+            close: bar.close(),
+        }
+    }
+}
+
+fn check_period(name: &str, period: usize) -> Result<(), String> {
+    if period == 0 {
+        Err(format!("the period of {} needs to be at least 1", name))
+    } else {
+        Ok(())
+    }
+}
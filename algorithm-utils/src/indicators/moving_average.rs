@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+
+use crate::Price;
+use crate::indicators::{check_period, Indicator};
+
+/// The simple moving average of the last `period` prices
+pub struct Sma {
+    period: usize,
+    window: VecDeque<Price>,
+    sum: f64,
+    /// the updates since the sum was last computed from the window
+    updates: usize,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Result<Self, String> {
+        check_period("the SMA", period)?;
+        Ok(Self {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+            updates: 0,
+        })
+    }
+
+    /// the prices needed for the first value
+    pub const fn min_data_length(period: usize) -> usize { period }
+
+    pub fn period(&self) -> usize { self.period }
+}
+
+impl Indicator for Sma {
+    type Input = Price;
+    type Output = f64;
+
+    fn update(&mut self, price: Price) -> Option<f64> {
+        self.window.push_back(price);
+        self.sum += price;
+        if self.window.len() > self.period {
+            if let Some(oldest) = self.window.pop_front() {
+                self.sum -= oldest;
+            }
+        }
+
+        // the running sum drifts, so it's computed again once per period (still O(1) on average)
+        self.updates += 1;
+        if self.updates >= self.period {
+            self.sum = self.window.iter().sum();
+            self.updates = 0;
+        }
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.window.len() == self.period { Some(self.sum / self.period as f64) } else { None }
+    }
+
+    fn warm_up(&self) -> usize { Self::min_data_length(self.period) }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.sum = 0.0;
+        self.updates = 0;
+    }
+}
+
+/// The exponential moving average with a smoothing of `2 / (period + 1)`
+///
+/// It starts with the simple average of the first `period` prices.
+pub struct Ema {
+    period: usize,
+    alpha: f64,
+    seed: Sma,
+    value: Option<f64>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Result<Self, String> {
+        check_period("the EMA", period)?;
+        Ok(Self {
+            period,
+            alpha: 2.0 / (period as f64 + 1.0),
+            seed: Sma::new(period)?,
+            value: None,
+        })
+    }
+
+    /// the prices needed for the first value
+    pub const fn min_data_length(period: usize) -> usize { period }
+
+    pub fn period(&self) -> usize { self.period }
+}
+
+impl Indicator for Ema {
+    type Input = Price;
+    type Output = f64;
+
+    fn update(&mut self, price: Price) -> Option<f64> {
+        self.value = match self.value {
+            Some(value) => Some(value + self.alpha * (price - value)),
+            None => self.seed.update(price),
+        };
+        self.value
+    }
+
+    fn value(&self) -> Option<f64> { self.value }
+
+    fn warm_up(&self) -> usize { Self::min_data_length(self.period) }
+
+    fn reset(&mut self) {
+        self.seed.reset();
+        self.value = None;
+    }
+}
@@ -0,0 +1,80 @@
+use crate::Price;
+use crate::indicators::{check_period, Indicator};
+
+/// The relative strength index (0 to 100) with Wilder's smoothing
+///
+/// The first averages are the simple averages of the gains and losses of the
+/// first `period` price changes. Prices that didn't change at all give 50.
+pub struct Rsi {
+    period: usize,
+    previous: Option<Price>,
+    /// the price changes seen so far, up to the period
+    changes: usize,
+    avg_gain: f64,
+    avg_loss: f64,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Result<Self, String> {
+        check_period("the RSI", period)?;
+        Ok(Self {
+            period,
+            previous: None,
+            changes: 0,
+            avg_gain: 0.0,
+            avg_loss: 0.0,
+        })
+    }
+
+    /// the prices needed for the first value (one more than the period, since it works on changes)
+    pub const fn min_data_length(period: usize) -> usize { period + 1 }
+
+    pub fn period(&self) -> usize { self.period }
+}
+
+impl Indicator for Rsi {
+    type Input = Price;
+    type Output = f64;
+
+    fn update(&mut self, price: Price) -> Option<f64> {
+        let change = price - self.previous.replace(price)?;
+        let gain = change.max(0.0);
+        let loss = (-change).max(0.0);
+
+        if self.changes < self.period {
+            self.avg_gain += gain;
+            self.avg_loss += loss;
+            self.changes += 1;
+            if self.changes == self.period {
+                self.avg_gain /= self.period as f64;
+                self.avg_loss /= self.period as f64;
+            }
+        } else {
+            let period = self.period as f64;
+            self.avg_gain = (self.avg_gain * (period - 1.0) + gain) / period;
+            self.avg_loss = (self.avg_loss * (period - 1.0) + loss) / period;
+        }
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.changes < self.period {
+            return None;
+        }
+
+        Some(if self.avg_loss == 0.0 {
+            if self.avg_gain == 0.0 { 50.0 } else { 100.0 }
+        } else {
+            100.0 - 100.0 / (1.0 + self.avg_gain / self.avg_loss)
+        })
+    }
+
+    fn warm_up(&self) -> usize { Self::min_data_length(self.period) }
+
+    fn reset(&mut self) {
+        self.previous = None;
+        self.changes = 0;
+        self.avg_gain = 0.0;
+        self.avg_loss = 0.0;
+    }
+}
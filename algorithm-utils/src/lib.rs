@@ -0,0 +1,406 @@
+//! The traits, structs, enums and macros shared by trading-desk and its algorithms
+//!
+//! An algorithm is a `cdylib` crate that implements [`AlgorithmInterface`]
+//! for its own struct and exports it with [`export_algorithm!`].
+//! trading-desk loads the libraries with [`load::Algorithms`] and drives
+//! them through the same trait.
+//!
+//! The crate lives in the trading-desk repository, so the runtime and the
+//! algorithms built from the template are always compiled against the same
+//! interface. Algorithms are only loaded if they were built with the same
+//! compiler and the same version of this crate, since the interface is not
+//! a stable ABI.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::rc::Rc;
+
+use chrono::{DateTime, Duration, Utc};
+
+pub use error::{Error, TradingErrorKind};
+
+pub mod error;
+pub mod indicators;
+pub mod load;
+
+/// The version of the compiler this crate was built with
+pub const RUSTC_VERSION: &str = env!("RUSTC_VERSION");
+/// The version of this crate
+pub const CORE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub type Price = f64;
+
+/// A tradable instrument, identified by its ISIN
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Derivative {
+    isin: String,
+}
+
+impl Derivative {
+    pub fn new(isin: String) -> Self {
+        Self { isin }
+    }
+
+    pub fn isin(&self) -> &str { &self.isin }
+}
+
+impl fmt::Display for Derivative {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.isin)
+    }
+}
+
+impl<'a> From<&'a Derivative> for Cow<'a, Derivative> {
+    fn from(derivative: &'a Derivative) -> Self { Cow::Borrowed(derivative) }
+}
+
+impl<'a> From<Derivative> for Cow<'a, Derivative> {
+    fn from(derivative: Derivative) -> Self { Cow::Owned(derivative) }
+}
+
+/// A held position (negative quantities are short positions)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Position {
+    derivative: Derivative,
+    quantity: f64,
+    average_price: Price,
+}
+
+impl Position {
+    pub fn new(derivative: Derivative, quantity: f64, average_price: Price) -> Self {
+        Self {
+            derivative,
+            quantity,
+            average_price,
+        }
+    }
+
+    pub fn derivative(&self) -> &Derivative { &self.derivative }
+    pub fn quantity(&self) -> f64 { self.quantity }
+    pub fn average_price(&self) -> Price { self.average_price }
+}
+
+/// How long an order stays in the market
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum TimeInForce {
+    /// until the end of the trading day
+    #[default]
+    Day,
+    /// good till cancelled
+    Gtc,
+    /// good till the date
+    Gtd(DateTime<Utc>),
+}
+
+/// An order an algorithm wants to place
+///
+/// The order type follows from the prices that are set: a trailing distance
+/// makes a trailing stop, a stop a stop order (a stop-limit order with a
+/// limit) and a limit alone a limit order. Instructions without any of them
+/// are market orders. trading-desk rounds the prices to the tick size of the
+/// derivative before it sends the order.
+///
+/// Instructions with the same OCO group cancel each other once one of them
+/// is filled.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Instruction<'a> {
+    derivative: Cow<'a, Derivative>,
+    is_buy: bool,
+    amount: f64,
+    limit: Option<Price>,
+    stop: Option<Price>,
+    trailing_distance: Option<Price>,
+    time_in_force: TimeInForce,
+    oco_group: Option<u64>,
+}
+
+impl<'a> Instruction<'a> {
+    pub fn buy<D: Into<Cow<'a, Derivative>>>(derivative: D, amount: f64) -> Self {
+        Self::new(derivative.into(), true, amount)
+    }
+
+    pub fn sell<D: Into<Cow<'a, Derivative>>>(derivative: D, amount: f64) -> Self {
+        Self::new(derivative.into(), false, amount)
+    }
+
+    fn new(derivative: Cow<'a, Derivative>, is_buy: bool, amount: f64) -> Self {
+        Self {
+            derivative,
+            is_buy,
+            amount,
+            limit: None,
+            stop: None,
+            trailing_distance: None,
+            time_in_force: TimeInForce::default(),
+            oco_group: None,
+        }
+    }
+
+    /// Makes the instruction a limit order (or a stop-limit order together with a stop)
+    pub fn with_limit(mut self, limit: Price) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Makes the instruction a stop order that becomes a market order once the stop is traded
+    pub fn with_stop(mut self, stop: Price) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Makes the instruction a stop that follows the best price at the distance
+    pub fn with_trailing_distance(mut self, distance: Price) -> Self {
+        self.trailing_distance = Some(distance);
+        self
+    }
+
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn with_oco_group(mut self, group: u64) -> Self {
+        self.oco_group = Some(group);
+        self
+    }
+
+    pub fn derivative(&self) -> &Derivative { &self.derivative }
+    pub fn is_buy(&self) -> bool { self.is_buy }
+    pub fn amount(&self) -> f64 { self.amount }
+    pub fn limit(&self) -> Option<Price> { self.limit }
+    pub fn stop(&self) -> Option<Price> { self.stop }
+    pub fn trailing_distance(&self) -> Option<Price> { self.trailing_distance }
+    pub fn time_in_force(&self) -> TimeInForce { self.time_in_force }
+    pub fn oco_group(&self) -> Option<u64> { self.oco_group }
+}
+
+/// The open, high, low and close prices and the traded volume of a period
+///
+/// Algorithms that declare bars in their metadata receive bars instead of prices:
+/// ```toml
+/// [data]
+/// bars = "5m"
+/// ```
+/// The size may be a time (`30s`, `5m`, `1h`, `1d`), a number of ticks
+/// (`100t`) or a volume (`5000v`).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bar {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    open: Price,
+    high: Price,
+    low: Price,
+    close: Price,
+    volume: f64,
+}
+
+impl Bar {
+    pub fn new(
+        start: DateTime<Utc>, end: DateTime<Utc>, open: Price, high: Price, low: Price, close: Price, volume: f64,
+    ) -> Self {
+        Self {
+            start,
+            end,
+            open,
+            high,
+            low,
+            close,
+            volume,
+        }
+    }
+
+    /// the time of the first tick
+    pub fn start(&self) -> DateTime<Utc> { self.start }
+    /// the time of the last tick
+    pub fn end(&self) -> DateTime<Utc> { self.end }
+    pub fn open(&self) -> Price { self.open }
+    pub fn high(&self) -> Price { self.high }
+    pub fn low(&self) -> Price { self.low }
+    pub fn close(&self) -> Price { self.close }
+    pub fn volume(&self) -> f64 { self.volume }
+}
+
+/// An order of the algorithm that is not completely filled yet
+#[derive(Debug, PartialEq, Clone)]
+pub struct WorkingOrder {
+    derivative: Derivative,
+    id: u64,
+    is_buy: bool,
+    remaining: f64,
+    filled: f64,
+    limit: Option<Price>,
+}
+
+impl WorkingOrder {
+    pub fn new(derivative: Derivative, id: u64, is_buy: bool, remaining: f64, filled: f64, limit: Option<Price>) -> Self {
+        Self {
+            derivative,
+            id,
+            is_buy,
+            remaining,
+            filled,
+            limit,
+        }
+    }
+
+    pub fn derivative(&self) -> &Derivative { &self.derivative }
+    /// the id trading-desk gave the order
+    pub fn id(&self) -> u64 { self.id }
+    pub fn is_buy(&self) -> bool { self.is_buy }
+    /// the quantity that is still to be filled
+    pub fn remaining(&self) -> f64 { self.remaining }
+    pub fn filled(&self) -> f64 { self.filled }
+    pub fn limit(&self) -> Option<Price> { self.limit }
+}
+
+/// How important a line of the algorithm log is
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// Writes to the algorithm log of trading-desk
+///
+/// Algorithms get it with [`AlgorithmInterface::set_logger`] before `init`.
+/// The `log` macros and `println!` don't reach the log, since the library of
+/// an algorithm has its own copy of the `log` crate without a logger. The
+/// default logger discards everything.
+#[derive(Clone)]
+pub struct Logger {
+    callback: Rc<LogCallback>,
+}
+
+type LogCallback = dyn Fn(LogLevel, &str);
+
+impl Logger {
+    pub fn new<F: Fn(LogLevel, &str) + 'static>(callback: F) -> Self {
+        Self { callback: Rc::new(callback) }
+    }
+
+    pub fn log(&self, level: LogLevel, message: &str) {
+        (self.callback)(level, message)
+    }
+
+    pub fn debug(&self, message: &str) { self.log(LogLevel::Debug, message) }
+    pub fn info(&self, message: &str) { self.log(LogLevel::Info, message) }
+    pub fn warn(&self, message: &str) { self.log(LogLevel::Warn, message) }
+    pub fn error(&self, message: &str) { self.log(LogLevel::Error, message) }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self::new(|_, _| {})
+    }
+}
+
+impl fmt::Debug for Logger {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "Logger")
+    }
+}
+
+/// The interface trading-desk uses to communicate with an algorithm
+///
+/// None of the methods may panic. Return an [`Error`] with
+/// [`TradingErrorKind::Panic`] instead, trading-desk then stops the session
+/// in an orderly way.
+pub trait AlgorithmInterface {
+    /// Called once before `init` with the logger of the algorithm log, does nothing by default
+    fn set_logger(&mut self, _logger: Logger) {}
+
+    /// Called once before the first price
+    ///
+    /// The parameters are a toml table with a value for every parameter the
+    /// algorithm declares in its metadata, already validated by trading-desk.
+    fn init(&mut self, derivative: &Derivative, time_steps: Duration, parameters: &str) -> Result<(), Error<TradingErrorKind>>;
+
+    /// Called with every new price while there are fewer prices than the min data length
+    ///
+    /// Algorithms that declare bars count bars instead of prices for the min and max data length.
+    fn collect_prices(&mut self, prices: &[Price]) -> Result<(), Error<TradingErrorKind>>;
+
+    /// Called with every new price once the min data length is reached
+    ///
+    /// The orders are the ones of the algorithm that are still pending, so
+    /// it doesn't send an order twice.
+    fn algorithm(&mut self, positions: &[Position], orders: &[WorkingOrder], prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>>;
+
+    /// Like `collect_prices` for algorithms that declare bars, called with every completed bar
+    fn collect_bars(&mut self, _bars: &[Bar]) -> Result<(), Error<TradingErrorKind>> {
+        Ok(())
+    }
+
+    /// Like `algorithm` for algorithms that declare bars, called with every completed bar
+    fn algorithm_bars(&mut self, _positions: &[Position], _orders: &[WorkingOrder], _bars: &[Bar]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        Ok(&[])
+    }
+
+    /// Called once per trading day before the exchange closes, if the profile asks for it
+    ///
+    /// Algorithms that don't hold positions over night close them here. Does
+    /// nothing by default.
+    fn session_close(&mut self, _positions: &[Position], _orders: &[WorkingOrder], _prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        Ok(&[])
+    }
+
+    /// Called once at the end of the session, the instructions are still executed
+    fn shutdown(&mut self, positions: &[Position], orders: &[WorkingOrder], prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>>;
+}
+
+/// What [`export_algorithm!`] exports from the library of an algorithm
+#[doc(hidden)]
+pub struct AlgorithmDeclaration {
+    pub rustc_version: &'static str,
+    pub core_version: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub min_data_length: usize,
+    pub max_data_length: usize,
+    /// the toml metadata with the parameter schema
+    pub parameters: &'static str,
+    pub create: fn() -> Box<dyn AlgorithmInterface>,
+}
+
+/// Exports an algorithm from its crate
+///
+/// The arguments are the name, the description, optionally the min and max
+/// data length (0 for no limit) followed by optionally the toml metadata with
+/// the parameter schema and the `[data]` table of [`Bar`], and finally the
+/// initial value of the algorithm.
+/// ```ignore
+/// export_algorithm!("Name", "Description", Context::default());
+/// export_algorithm!("Name", "Description", 10, 20, Context::default());
+/// export_algorithm!("Name", "Description", 10, 20, PARAMETERS, Context::default());
+/// ```
+/// Only one algorithm can be exported per crate.
+#[macro_export]
+macro_rules! export_algorithm {
+    ($name:expr, $description:expr, $algorithm:expr) => {
+        $crate::export_algorithm!($name, $description, 0, 0, "", $algorithm);
+    };
+    ($name:expr, $description:expr, $min_data_length:expr, $max_data_length:expr, $algorithm:expr) => {
+        $crate::export_algorithm!($name, $description, $min_data_length, $max_data_length, "", $algorithm);
+    };
+    ($name:expr, $description:expr, $min_data_length:expr, $max_data_length:expr, $parameters:expr, $algorithm:expr) => {
+        #[doc(hidden)]
+        fn __create_algorithm() -> Box<dyn $crate::AlgorithmInterface> {
+            Box::new($algorithm)
+        }
+
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static ALGORITHM_DECLARATION: $crate::AlgorithmDeclaration = $crate::AlgorithmDeclaration {
+            rustc_version: $crate::RUSTC_VERSION,
+            core_version: $crate::CORE_VERSION,
+            name: $name,
+            description: $description,
+            min_data_length: $min_data_length,
+            max_data_length: $max_data_length,
+            parameters: $parameters,
+            create: __create_algorithm,
+        };
+    };
+}
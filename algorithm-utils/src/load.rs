@@ -0,0 +1,197 @@
+//! Loads the libraries of the algorithms
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use chrono::Duration;
+use libloading::Library;
+
+use crate::{AlgorithmDeclaration, AlgorithmInterface, Bar, CORE_VERSION, Derivative, Instruction, Logger, Position, Price, RUSTC_VERSION, WorkingOrder};
+use crate::error::{Error, LoadErrorKind, TradingErrorKind};
+
+/// A loaded algorithm with the metadata it was exported with
+///
+/// It implements [`AlgorithmInterface`] itself by passing the calls on.
+pub struct Algorithm {
+    name: String,
+    description: String,
+    min_data_length: usize,
+    max_data_length: usize,
+    parameters: String,
+    /// dropped before the library it was created by
+    algorithm: Box<dyn AlgorithmInterface>,
+    library: Option<Library>,
+}
+
+impl Algorithm {
+    /// An algorithm that is not loaded from a library (mostly for tests)
+    pub fn new(
+        name: String, description: String, min_data_length: usize, max_data_length: usize, parameters: String,
+        algorithm: Box<dyn AlgorithmInterface>,
+    ) -> Self {
+        Self {
+            name,
+            description,
+            min_data_length,
+            max_data_length,
+            parameters,
+            algorithm,
+            library: None,
+        }
+    }
+
+    /// Loads the algorithm exported by a library
+    pub fn load<P: AsRef<OsStr>>(path: P) -> Result<Self, Error<LoadErrorKind>> {
+        let path = path.as_ref();
+        let library = Library::new(path).map_err(|err| Error::new(
+            LoadErrorKind::Library, format!("Could not load {} ({})", path.to_string_lossy(), err),
+        ))?;
+
+        let declaration = unsafe {
+            match library.get::<*const AlgorithmDeclaration>(b"ALGORITHM_DECLARATION\0") {
+                Ok(symbol) => &**symbol,
+                Err(err) => return Err(Error::new(
+                    LoadErrorKind::Library, format!("{} exports no algorithm ({})", path.to_string_lossy(), err),
+                ))
+            }
+        };
+        if declaration.rustc_version != RUSTC_VERSION || declaration.core_version != CORE_VERSION {
+            return Err(Error::new(LoadErrorKind::Version, format!(
+                "{} was built with {} and algorithm-utils {} but trading-desk with {} and algorithm-utils {}",
+                path.to_string_lossy(), declaration.rustc_version, declaration.core_version, RUSTC_VERSION, CORE_VERSION
+            )));
+        }
+
+        let mut algorithm = Self::new(
+            declaration.name.to_string(),
+            declaration.description.to_string(),
+            declaration.min_data_length,
+            declaration.max_data_length,
+            declaration.parameters.to_string(),
+            (declaration.create)(),
+        );
+        algorithm.library = Some(library);
+        Ok(algorithm)
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn description(&self) -> &str { &self.description }
+    pub fn min_data_length(&self) -> usize { self.min_data_length }
+    /// 0 for no limit
+    pub fn max_data_length(&self) -> usize { self.max_data_length }
+    /// the toml metadata with the parameter schema
+    pub fn parameters(&self) -> &str { &self.parameters }
+}
+
+impl AlgorithmInterface for Algorithm {
+    fn set_logger(&mut self, logger: Logger) {
+        self.algorithm.set_logger(logger)
+    }
+
+    fn init(&mut self, derivative: &Derivative, time_steps: Duration, parameters: &str) -> Result<(), Error<TradingErrorKind>> {
+        self.algorithm.init(derivative, time_steps, parameters)
+    }
+
+    fn collect_prices(&mut self, prices: &[Price]) -> Result<(), Error<TradingErrorKind>> {
+        self.algorithm.collect_prices(prices)
+    }
+
+    fn algorithm(&mut self, positions: &[Position], orders: &[WorkingOrder], prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        self.algorithm.algorithm(positions, orders, prices)
+    }
+
+    fn collect_bars(&mut self, bars: &[Bar]) -> Result<(), Error<TradingErrorKind>> {
+        self.algorithm.collect_bars(bars)
+    }
+
+    fn algorithm_bars(&mut self, positions: &[Position], orders: &[WorkingOrder], bars: &[Bar]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        self.algorithm.algorithm_bars(positions, orders, bars)
+    }
+
+    fn session_close(&mut self, positions: &[Position], orders: &[WorkingOrder], prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        self.algorithm.session_close(positions, orders, prices)
+    }
+
+    fn shutdown(&mut self, positions: &[Position], orders: &[WorkingOrder], prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        self.algorithm.shutdown(positions, orders, prices)
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "ALGORITHM:\n\
+            \tname: {}\n\
+            \tdescription: {}\n\
+            \tmin data length: {}\n\
+            \tmax data length: {}",
+            self.name,
+            self.description,
+            self.min_data_length,
+            self.max_data_length
+        )
+    }
+}
+
+/// The loaded algorithms by their name
+#[derive(Default)]
+pub struct Algorithms {
+    algorithms: BTreeMap<String, Algorithm>,
+}
+
+impl Algorithms {
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Loads every library in the directory, a missing directory has no algorithms
+    pub fn load_all<P: AsRef<Path>>(&mut self, directory: P) -> Result<(), Error<LoadErrorKind>> {
+        let directory = directory.as_ref();
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) if !directory.exists() => return Ok(()),
+            Err(err) => return Err(Error::new(
+                LoadErrorKind::Io, format!("Could not read {} ({})", directory.display(), err),
+            ))
+        };
+
+        for entry in entries {
+            let path = entry.map_err(|err| Error::new(LoadErrorKind::Io, err.to_string()))?.path();
+            if path.is_file() && path.extension() == Some(OsStr::new(std::env::consts::DLL_EXTENSION)) {
+                self.insert(Algorithm::load(&path)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, algorithm: Algorithm) -> Result<(), Error<LoadErrorKind>> {
+        if self.contains(algorithm.name()) {
+            return Err(Error::new(LoadErrorKind::Duplicate, format!("The algorithm {} is loaded twice", algorithm.name())));
+        }
+        self.algorithms.insert(algorithm.name().to_string(), algorithm);
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool { self.algorithms.contains_key(name) }
+    pub fn get(&self, name: &str) -> Option<&Algorithm> { self.algorithms.get(name) }
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Algorithm> { self.algorithms.get_mut(name) }
+    pub fn iter(&self) -> impl Iterator<Item=(&String, &Algorithm)> { self.algorithms.iter() }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=(&String, &mut Algorithm)> { self.algorithms.iter_mut() }
+}
+
+impl fmt::Display for Algorithms {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.algorithms.is_empty() {
+            return write!(formatter, "ALGORITHMS: None");
+        }
+
+        write!(formatter, "ALGORITHMS:")?;
+        for algorithm in self.algorithms.values() {
+            write!(formatter, "\n\t{}: {}", algorithm.name, algorithm.description)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use algorithm_utils::{AlgorithmInterface, Derivative, Error, Instruction, LogLevel, Logger, Position, Price, TimeInForce, TradingErrorKind, WorkingOrder};
+use algorithm_utils::load::{Algorithm, Algorithms};
+use chrono::Duration;
+
+algorithm_utils::export_algorithm!("Buy And Hold", "buys once", 2, 5, PARAMETERS, BuyAndHold::default());
+
+const PARAMETERS: &str = r#"
+[[parameter]]
+name = "amount"
+type = "float"
+default = 1.0
+"#;
+
+#[derive(Default)]
+struct BuyAndHold {
+    derivative: Option<Derivative>,
+    instructions: Vec<Instruction<'static>>,
+    logger: Logger,
+}
+
+impl AlgorithmInterface for BuyAndHold {
+    fn set_logger(&mut self, logger: Logger) {
+        self.logger = logger;
+    }
+
+    fn init(&mut self, derivative: &Derivative, _time_steps: Duration, _parameters: &str) -> Result<(), Error<TradingErrorKind>> {
+        self.derivative = Some(derivative.clone());
+        self.logger.info(&format!("trading {}", derivative));
+        Ok(())
+    }
+
+    fn collect_prices(&mut self, _prices: &[Price]) -> Result<(), Error<TradingErrorKind>> {
+        Ok(())
+    }
+
+    fn algorithm(&mut self, positions: &[Position], orders: &[WorkingOrder], _prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        self.instructions.clear();
+        if positions.is_empty() && orders.is_empty() {
+            let derivative = self.derivative.clone().ok_or_else(|| Error::new(TradingErrorKind::Panic, "not initialised"))?;
+            self.instructions.push(Instruction::buy(derivative, 1.0).with_limit(10.0));
+        }
+        Ok(&self.instructions)
+    }
+
+    fn shutdown(&mut self, _positions: &[Position], _orders: &[WorkingOrder], _prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        Ok(&[])
+    }
+}
+
+fn exported() -> Algorithm {
+    Algorithm::new(
+        ALGORITHM_DECLARATION.name.to_string(),
+        ALGORITHM_DECLARATION.description.to_string(),
+        ALGORITHM_DECLARATION.min_data_length,
+        ALGORITHM_DECLARATION.max_data_length,
+        ALGORITHM_DECLARATION.parameters.to_string(),
+        (ALGORITHM_DECLARATION.create)(),
+    )
+}
+
+#[test]
+fn the_declaration_holds_the_metadata() {
+    let algorithm = exported();
+    assert_eq!(algorithm.name(), "Buy And Hold");
+    assert_eq!(algorithm.min_data_length(), 2);
+    assert_eq!(algorithm.max_data_length(), 5);
+    assert_eq!(algorithm.parameters(), PARAMETERS);
+    assert_eq!(ALGORITHM_DECLARATION.rustc_version, algorithm_utils::RUSTC_VERSION);
+}
+
+#[test]
+fn calls_are_passed_on_to_the_algorithm() {
+    let mut algorithm = exported();
+    let derivative = Derivative::new(String::from("DE0005140008"));
+    algorithm.init(&derivative, Duration::seconds(1), "amount = 1.0").unwrap();
+
+    let instructions = algorithm.algorithm(&[], &[], &[9.0, 10.0]).unwrap();
+    assert_eq!(instructions.len(), 1);
+    assert!(instructions[0].is_buy());
+    assert_eq!(instructions[0].derivative(), &derivative);
+    assert_eq!(instructions[0].limit(), Some(10.0));
+
+    assert!(algorithm.session_close(&[], &[], &[10.0]).unwrap().is_empty());
+    let pending = WorkingOrder::new(derivative.clone(), 1, true, 1.0, 0.0, Some(10.0));
+    assert!(algorithm.algorithm(&[], &[pending], &[10.0]).unwrap().is_empty());
+    let position = Position::new(derivative, 1.0, 10.0);
+    assert!(algorithm.algorithm(&[position], &[], &[10.0]).unwrap().is_empty());
+}
+
+#[test]
+fn names_are_unique() {
+    let mut algorithms = Algorithms::empty();
+    algorithms.insert(exported()).unwrap();
+    assert!(algorithms.contains("Buy And Hold"));
+    assert!(algorithms.insert(exported()).is_err());
+    assert!(algorithms.load_all("./does-not-exist").is_ok());
+}
+
+#[test]
+fn instructions_are_day_orders_by_default() {
+    let derivative = Derivative::new(String::from("DE0005140008"));
+    let market = Instruction::sell(&derivative, 2.0);
+    assert_eq!((market.limit(), market.stop(), market.trailing_distance()), (None, None, None));
+    assert_eq!(market.time_in_force(), TimeInForce::Day);
+
+    let stop = Instruction::sell(&derivative, 2.0).with_stop(9.5).with_time_in_force(TimeInForce::Gtc).with_oco_group(1);
+    assert_eq!(stop.stop(), Some(9.5));
+    assert_eq!(stop.time_in_force(), TimeInForce::Gtc);
+    assert_eq!(stop.oco_group(), Some(1));
+}
+
+#[test]
+fn the_logger_is_passed_on_to_the_algorithm() {
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let mut algorithm = exported();
+    let written = lines.clone();
+    algorithm.set_logger(Logger::new(move |level, message| written.borrow_mut().push((level, message.to_string()))));
+    algorithm.init(&Derivative::new(String::from("DE0005140008")), Duration::seconds(1), "amount = 1.0").unwrap();
+    assert_eq!(*lines.borrow(), vec![(LogLevel::Info, String::from("trading DE0005140008"))]);
+
+    // without a logger the lines are discarded
+    Logger::default().error("lost");
+}
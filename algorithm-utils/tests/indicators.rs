@@ -0,0 +1,130 @@
+//! The indicators compared with values computed over the whole window the
+//! textbook way (the prices are the example of Wilder's RSI)
+
+use algorithm_utils::indicators::{Atr, Bollinger, Ema, Indicator, Macd, Ohlc, Rsi, Sma};
+
+const PRICES: [f64; 33] = [
+    44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61, 46.28, 46.28, 46.00,
+    46.03, 46.41, 46.22, 45.64, 46.21, 46.25, 45.71, 46.45, 45.78, 45.35, 44.03, 44.18, 44.22, 44.57, 43.42, 42.66,
+    43.13,
+];
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+}
+
+/// the values of all prices
+fn run<I: Indicator<Input = f64>>(indicator: &mut I) -> Vec<Option<I::Output>> {
+    PRICES.iter().map(|price| indicator.update(*price)).collect()
+}
+
+/// asserts that the first value comes with the input of the warm up
+fn assert_warm_up<T>(values: &[Option<T>], warm_up: usize) {
+    assert!(values[..warm_up - 1].iter().all(Option::is_none));
+    assert!(values[warm_up - 1..].iter().all(Option::is_some));
+}
+
+#[test]
+fn sma() {
+    let mut sma = Sma::new(5).unwrap();
+    let values = run(&mut sma);
+
+    assert_warm_up(&values, Sma::min_data_length(5));
+    assert_close(values[4].unwrap(), 44.104);
+    assert_close(values[30].unwrap(), 44.084);
+    assert_close(values[31].unwrap(), 43.81);
+    assert_close(values[32].unwrap(), 43.6);
+}
+
+#[test]
+fn ema() {
+    let mut ema = Ema::new(10).unwrap();
+    let values = run(&mut ema);
+
+    assert_warm_up(&values, Ema::min_data_length(10));
+    assert_close(values[9].unwrap(), 44.779);
+    assert_close(values[30].unwrap(), 44.7122861832326);
+    assert_close(values[32].unwrap(), 44.11929901522182);
+}
+
+#[test]
+fn rsi() {
+    let mut rsi = Rsi::new(14).unwrap();
+    let values = run(&mut rsi);
+
+    assert_warm_up(&values, Rsi::min_data_length(14));
+    assert_close(values[14].unwrap(), 70.46413502109705);
+    assert_close(values[30].unwrap(), 37.322778313379956);
+    assert_close(values[32].unwrap(), 37.788771982057824);
+}
+
+#[test]
+fn rsi_of_constant_prices() {
+    let mut rsi = Rsi::new(3).unwrap();
+    for _ in 0..4 {
+        rsi.update(10.0);
+    }
+    assert_eq!(rsi.value(), Some(50.0));
+}
+
+#[test]
+fn macd() {
+    let mut macd = Macd::new(5, 10, 4).unwrap();
+    let values = run(&mut macd);
+
+    assert_warm_up(&values, Macd::min_data_length(10, 4));
+    let last = values[32].unwrap();
+    assert_close(last.macd, -0.6082205441402024);
+    assert_close(last.signal, -0.5410112423137581);
+    assert_close(last.histogram, -0.06720930182644436);
+}
+
+#[test]
+fn bollinger() {
+    let mut bollinger = Bollinger::new(20, 2.0).unwrap();
+    let values = run(&mut bollinger);
+
+    assert_warm_up(&values, Bollinger::min_data_length(20));
+    let last = values[32].unwrap();
+    assert_close(last.upper, 47.62015026847822);
+    assert_close(last.middle, 45.241);
+    assert_close(last.lower, 42.86184973152178);
+}
+
+#[test]
+fn atr() {
+    let mut atr = Atr::new(14).unwrap();
+    let values: Vec<_> = PRICES
+        .iter()
+        .enumerate()
+        .map(|(i, price)| atr.update(Ohlc {
+            high: price + 0.3 + 0.1 * (i % 3) as f64,
+            low: price - 0.25 - 0.05 * (i % 4) as f64,
+            close: *price,
+        }))
+        .collect();
+
+    assert_warm_up(&values, Atr::min_data_length(14));
+    assert_close(values[14].unwrap(), 0.8442857142857133);
+    assert_close(values[32].unwrap(), 0.9408479138443953);
+}
+
+#[test]
+fn reset_starts_over() {
+    let mut sma = Sma::new(5).unwrap();
+    run(&mut sma);
+    sma.reset();
+
+    assert!(!sma.is_ready());
+    let values = run(&mut sma);
+    assert_warm_up(&values, 5);
+    assert_close(values[32].unwrap(), 43.6);
+}
+
+#[test]
+fn invalid_periods() {
+    assert!(Sma::new(0).is_err());
+    assert!(Rsi::new(0).is_err());
+    assert!(Macd::new(26, 12, 9).is_err());
+    assert!(Bollinger::new(20, -1.0).is_err());
+}
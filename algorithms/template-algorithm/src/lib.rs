@@ -23,28 +23,47 @@
 //! ##### arguments
 //! * __NAME__: The first argument to the export_algorithm is your algorithms name.
 //! * __DESCRIPTION__: The second argument is a brief description of what your algorithm
-//! does and how it works. This description is just for the user.
+//!   does and how it works. This description is just for the user.
 //! * __MIN_DATA_LENGTH__: This argument is optional (If you use it you also have
-//! to use MAX_DATA_LENGTH) and describe the minimum amount of prices you need.
-//! This signals trading-desk that your algorithm needs at least x prices for working
-//! correctly. While the amount of collected prices is less then your minimum the
-//! `collect_prices` method will be called (For more information about `collect_prices`
-//! have a look at the algorithm-utils documentation).
-//! (default = 0)
+//!   to use MAX_DATA_LENGTH) and describe the minimum amount of prices you need.
+//!   This signals trading-desk that your algorithm needs at least x prices for working
+//!   correctly. While the amount of collected prices is less then your minimum the
+//!   `collect_prices` method will be called (For more information about `collect_prices`
+//!   have a look at the algorithm-utils documentation).
+//!   (default = 0)
 //! * __MAX_DATA_LENGTH__: This argument is optional (If you use it you also have
-//! to use MIN_DATA_LENGTH) and describe the maximum amount of prices you can handle.
-//! The maximum data length is an indicator for trading-desk for how many prices
-//! have to be saved internally. Your algorithm will never get more prices then
-//! this (what does not always mean it will ever get that amount).
-//! Please note that max_data_length has to be greater or equal to min_data_length or 0.
-//! Please note that a value of 0 corresponds to infinity.
-//! (default = 0)
+//!   to use MIN_DATA_LENGTH) and describe the maximum amount of prices you can handle.
+//!   The maximum data length is an indicator for trading-desk for how many prices
+//!   have to be saved internally. Your algorithm will never get more prices then
+//!   this (what does not always mean it will ever get that amount).
+//!   Please note that max_data_length has to be greater or equal to min_data_length or 0.
+//!   Please note that a value of 0 corresponds to infinity.
+//!   (default = 0)
+//! * __PARAMETERS__: This argument is optional and declares the parameters
+//!   of your algorithm as a toml string with one `[[parameter]]` table per parameter
+//!   (`name`, `type` = integer | float | bool | string, `default` and optionally
+//!   `min`, `max` and `description`). trading-desk shows them in
+//!   `settings algorithms --description`, lets the user set them per profile or with
+//!   `start --param key=value`, validates them and passes them to `init` as a toml table.
+//!   (default = no parameters)
 //! * __ALGORITHM__: Finally you have to pass in an instance of your algorithm.
-//! Since trading-desk cannot call a constructor on your algorithm you have to help
-//! it out here. This initial state will be the one received by `init`, so please do
-//! heavy computations there if needed. Otherwise this will lower the startup performance
-//! of trading-desk drastically. Calling the default constructor will solve this problem
-//! in most cases.
+//!   Since trading-desk cannot call a constructor on your algorithm you have to help
+//!   it out here. This initial state will be the one received by `init`, so please do
+//!   heavy computations there if needed. Otherwise this will lower the startup performance
+//!   of trading-desk drastically. Calling the default constructor will solve this problem
+//!   in most cases.
+//!
+//! #### logging
+//! trading-desk passes a `Logger` to `set_logger` before `init`. Everything
+//! written with it ends up in the algorithm log and, above debug, on the
+//! screen of the session. Neither `println!` nor the `log` macros reach the log.
+//!
+//! #### indicators
+//! `algorithm_utils::indicators` provides streaming versions of
+//! the common technical indicators (SMA, EMA, RSI, MACD, Bollinger bands and ATR).
+//! Update them with the newest price in `collect_prices` and `algorithm` and use
+//! their `min_data_length` as MIN_DATA_LENGTH, then they are ready the first
+//! time `algorithm` is called.
 //!
 //! # NOTICE:
 //! Even if it's possible to manually export your algorithm it's highly
@@ -84,17 +103,39 @@
 
 
 use algorithm_utils as utils;
-use algorithm_utils::{Derivative, Instruction, Position, Price, TradingErrorKind};
+use algorithm_utils::{Derivative, Instruction, Logger, Position, Price, TradingErrorKind, WorkingOrder};
 use chrono::Duration;
+use utils::indicators::{Indicator, Sma};
+use serde::Deserialize;
 use utils::{AlgorithmInterface, Error, export_algorithm};
 
 export_algorithm!(
     "Template Algorithm",               // name
     "A little template description",    // description
-    10,                                 // min data length
+    MIN_DATA_LENGTH,                    // min data length
     20,                                 // max data length
+    PARAMETERS,                         // parameter schema
     Context::default()                  // initial struct value
 );
+
+const SMA_PERIOD: usize = 10;
+const MIN_DATA_LENGTH: usize = Sma::min_data_length(SMA_PERIOD);
+
+const PARAMETERS: &str = r#"
+[[parameter]]
+name = "threshold"
+type = "float"
+default = 0.5
+min = 0.0
+max = 1.0
+description = "a parameter that does nothing but show how parameters work"
+"#;
+
+/// the values trading-desk passes to `init` (already validated against PARAMETERS)
+#[derive(Deserialize, Default)]
+pub struct Parameters {
+    threshold: f64,
+}
 // export_algorithm!(
 //      "Template Algorithm",           // name
 //      "A little template description",// description
@@ -104,42 +145,67 @@ export_algorithm!(
 
 #[derive(Default)]
 pub struct Context {
+    parameters: Parameters,
     first_init_price: Option<f64>,
     last_init_price: Option<f64>,
     first_algorithm_price: Option<f64>,
     last_algorithm_price: Option<f64>,
+    /// None until `init`
+    sma: Option<Sma>,
+    /// writes to the algorithm log of trading-desk
+    logger: Logger,
 }
 
 impl AlgorithmInterface for Context {
-    fn init(&mut self, _derivative: &Derivative, _time_steps: Duration) -> Result<(), Error<TradingErrorKind>> {
-        println!("init");
+    fn set_logger(&mut self, logger: Logger) {
+        self.logger = logger;
+    }
+
+    fn init(&mut self, _derivative: &Derivative, _time_steps: Duration, parameters: &str) -> Result<(), Error<TradingErrorKind>> {
+        // trading-desk validated the parameters, so this only fails if the schema and the struct differ
+        self.parameters = toml::from_str(parameters).map_err(|err| Error::new(
+            TradingErrorKind::InvalidData, format!("invalid parameters ({})", err),
+        ))?;
+        self.logger.info(&format!("threshold: {}", self.parameters.threshold));
+        self.sma = Sma::new(SMA_PERIOD).ok();
         Ok(())
     }
 
     fn collect_prices(&mut self, prices: &[Price]) -> Result<(), Error<TradingErrorKind>> {
-        println!("collect");
         if self.first_init_price.is_none() {
             self.first_init_price = Some(prices[0])
         }
         self.last_init_price = Some(*prices.iter().last().unwrap());
+        // the indicators keep their own history, so they only get the newest price
+        if let (Some(sma), Some(price)) = (&mut self.sma, prices.last()) {
+            sma.update(*price);
+        }
         Ok(())
     }
 
-    fn algorithm(&mut self, _positions: &[Position], prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
-        println!("algorithm");
+    fn algorithm(&mut self, _positions: &[Position], _orders: &[WorkingOrder], prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
         if self.first_algorithm_price.is_none() {
             self.first_algorithm_price = Some(prices[0])
         }
         self.last_algorithm_price = Some(*prices.iter().last().unwrap());
+        if let (Some(sma), Some(price)) = (&mut self.sma, prices.last()) {
+            let average = sma.update(*price);
+            self.logger.debug(&format!("sma: {:?}", average));
+        }
+        Ok(&[])
+    }
+
+    // optional, only called if the profile sets `before_close`
+    fn session_close(&mut self, _positions: &[Position], _orders: &[WorkingOrder], _prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        self.logger.info("the exchange closes soon");
         Ok(&[])
     }
 
-    fn shutdown(&mut self, _positions: &[Position], _prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
-        println!("shutdown");
-        println!("first init price:\t{:?}", self.first_init_price);
-        println!("last init price:\t{:?}", self.last_init_price);
-        println!("first algorithm price:\t{:?}", self.first_algorithm_price);
-        println!("last algorithm price:\t{:?}", self.last_algorithm_price);
+    fn shutdown(&mut self, _positions: &[Position], _orders: &[WorkingOrder], _prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+        self.logger.info(&format!(
+            "first init price: {:?}, last init price: {:?}, first algorithm price: {:?}, last algorithm price: {:?}",
+            self.first_init_price, self.last_init_price, self.first_algorithm_price, self.last_algorithm_price
+        ));
         Ok(&[])
     }
 }
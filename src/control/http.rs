@@ -0,0 +1,88 @@
+//! Just enough HTTP/1.1 for the control API
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// a client that doesn't send its request in this time is dropped
+const READ_TIMEOUT_SECS: u64 = 5;
+
+/// the headers a client may send at most
+const MAX_HEADERS: usize = 100;
+
+/// A request without its body (the API doesn't need one)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Request {
+    pub method: String,
+    /// without the query
+    pub path: String,
+    /// the names are lowercase
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// the value of the first header with the name (case insensitive)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads the request line and the headers
+pub fn read_request(stream: &mut TcpStream) -> Result<Request, io::Error> {
+    stream.set_read_timeout(Some(Duration::from_secs(READ_TIMEOUT_SECS)))?;
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid request {:?}", request_line)))
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "too many headers"));
+        }
+        match header.split_once(':') {
+            Some((name, value)) => headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string())),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid header {:?}", header)))
+        }
+    }
+
+    // the query is ignored
+    let path = target.split('?').next().unwrap_or_default().to_string();
+    Ok(Request { method, path, headers })
+}
+
+pub fn write_response(stream: &mut TcpStream, status: u16, body: &str) -> Result<(), io::Error> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n\r\n\
+        {}",
+        status, reason, body.len(), body
+    )?;
+    stream.flush()
+}
@@ -0,0 +1,348 @@
+//! A local HTTP API to watch and control a running session
+//!
+//! The API only listens on localhost and answers with json. Every request
+//! needs the `control_token` of the configuration file as a bearer token,
+//! and requests a browser sends for another site (found by their `Host`
+//! and `Origin` headers) are refused:
+//!
+//! * `GET /status`, `/positions`, `/orders`, `/prices` and `/pnl` return the
+//!   state of the session after the last tick
+//! * `POST /pause`, `/resume`, `/flatten` and `/stop` are handed to the
+//!   session, which executes them before it calls the algorithm the next time
+//!
+//! For example `curl -X POST -H "Authorization: Bearer <token>" localhost:7878/flatten`.
+//!
+//! The commands can also come from the key bindings of the dashboard, which
+//! use the same channel.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use algorithm_utils::Price;
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::trading::decimal::Decimal;
+use crate::trading::order_manager::TrackedOrder;
+
+mod http;
+
+/// the number of prices `/prices` returns
+const RECENT_PRICES: usize = 500;
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Command {
+    /// stops calling the algorithm, the open orders and positions stay untouched
+    Pause,
+    Resume,
+    /// cancels the open orders and closes all positions
+    Flatten,
+    /// ends the session like the end of the price feed would
+    Stop,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Pause => write!(formatter, "pause"),
+            Command::Resume => write!(formatter, "resume"),
+            Command::Flatten => write!(formatter, "flatten"),
+            Command::Stop => write!(formatter, "stop"),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct Status {
+    pub algorithm: String,
+    pub isin: String,
+    pub trading_type: String,
+    pub paused: bool,
+    /// the time of the last tick
+    pub time: DateTime<Utc>,
+    pub position: Decimal,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Default)]
+pub struct Pnl {
+    pub session: Decimal,
+    pub daily: Decimal,
+    pub realised: Decimal,
+    pub fees: Decimal,
+    pub drawdown: Decimal,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct PositionView {
+    pub isin: String,
+    pub quantity: Decimal,
+    pub average_price: Decimal,
+    pub last_price: Option<Decimal>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+struct PriceView {
+    time: DateTime<Utc>,
+    price: Price,
+}
+
+/// The state of the session as the API returns it
+#[derive(Default)]
+struct Snapshot {
+    status: Option<Status>,
+    pnl: Pnl,
+    positions: Vec<PositionView>,
+    orders: Vec<TrackedOrder>,
+    prices: VecDeque<PriceView>,
+}
+
+/// The session side of the API
+pub struct Control {
+    snapshot: Arc<Mutex<Snapshot>>,
+    commands: Receiver<Command>,
+    sender: Sender<Command>,
+    /// None until the server is started
+    address: Option<SocketAddr>,
+}
+
+impl Control {
+    pub fn new() -> Self {
+        let (sender, commands) = mpsc::channel();
+        Self {
+            snapshot: Arc::new(Mutex::new(Snapshot::default())),
+            commands,
+            sender,
+            address: None,
+        }
+    }
+
+    /// Starts the server thread on localhost, the requests have to send the token
+    pub fn serve(&mut self, port: u16, token: String) -> Result<SocketAddr, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|err| format!("Could not start the control API on port {} ({})", port, err))?;
+        let address = listener.local_addr().map_err(|err| err.to_string())?;
+
+        let (shared, sender) = (self.snapshot.clone(), self.sender.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let handled = stream.and_then(|stream| handle(stream, &token, &shared, &sender));
+                if let Err(err) = handled {
+                    warn!("Control API: {}", err);
+                }
+            }
+        });
+
+        self.address = Some(address);
+        Ok(address)
+    }
+
+    /// whether the server runs, the state only needs to be published then
+    pub fn is_serving(&self) -> bool { self.address.is_some() }
+
+    /// a sender for other sources of commands
+    pub fn sender(&self) -> Sender<Command> { self.sender.clone() }
+
+    /// the commands that arrived since the last call
+    pub fn commands(&self) -> Vec<Command> {
+        self.commands.try_iter().collect()
+    }
+
+    pub fn push_price(&self, time: DateTime<Utc>, price: Price) {
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            snapshot.prices.push_back(PriceView { time, price });
+            if snapshot.prices.len() > RECENT_PRICES {
+                snapshot.prices.pop_front();
+            }
+        }
+    }
+
+    pub fn publish(&self, status: Status, pnl: Pnl, positions: Vec<PositionView>, orders: Vec<TrackedOrder>) {
+        if let Ok(mut snapshot) = self.snapshot.lock() {
+            snapshot.status = Some(status);
+            snapshot.pnl = pnl;
+            snapshot.positions = positions;
+            snapshot.orders = orders;
+        }
+    }
+}
+
+fn handle(mut stream: TcpStream, token: &str, snapshot: &Mutex<Snapshot>, commands: &Sender<Command>) -> Result<(), io::Error> {
+    let request = http::read_request(&mut stream)?;
+    let (status, body) = match authorize(&request, token) {
+        Ok(()) => route(&request.method, &request.path, snapshot, commands),
+        Err(denied) => denied,
+    };
+    http::write_response(&mut stream, status, &body.to_string())
+}
+
+/// Checks the token and refuses the requests of other sites (like DNS rebinding or cross-site requests)
+fn authorize(request: &http::Request, token: &str) -> Result<(), (u16, serde_json::Value)> {
+    if !request.header("host").is_none_or(is_localhost) {
+        return Err((403, json!({ "error": "the control API only answers requests for localhost" })));
+    }
+    // browsers send the origin of the page that made the request
+    if let Some(origin) = request.header("origin") {
+        let host = origin.strip_prefix("http://").or_else(|| origin.strip_prefix("https://"));
+        if !host.is_some_and(is_localhost) {
+            return Err((403, json!({ "error": format!("requests from {} are not allowed", origin) })));
+        }
+    }
+
+    let sent = request.header("authorization").and_then(|value| value.strip_prefix("Bearer "));
+    match sent {
+        Some(sent) if equal_in_constant_time(sent.trim().as_bytes(), token.as_bytes()) => Ok(()),
+        _ => Err((401, json!({ "error": "the control token is missing or wrong" })))
+    }
+}
+
+/// whether a host with an optional port is this machine
+fn is_localhost(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        // IPv6 like [::1]:7878
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(name.to_ascii_lowercase().as_str(), "localhost" | "127.0.0.1" | "::1")
+}
+
+/// compares the tokens without revealing how much of them matched by the time it takes
+fn equal_in_constant_time(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |differences, (a, b)| differences | (a ^ b)) == 0
+}
+
+fn route(method: &str, path: &str, snapshot: &Mutex<Snapshot>, commands: &Sender<Command>) -> (u16, serde_json::Value) {
+    let command = match (method, path) {
+        ("GET", _) => {
+            let snapshot = match snapshot.lock() {
+                Ok(snapshot) => snapshot,
+                Err(_) => return (500, json!({ "error": "the session crashed" }))
+            };
+            let body = match path {
+                "/status" => serde_json::to_value(&snapshot.status),
+                "/pnl" => serde_json::to_value(&snapshot.pnl),
+                "/positions" => serde_json::to_value(&snapshot.positions),
+                "/orders" => serde_json::to_value(&snapshot.orders),
+                "/prices" => serde_json::to_value(&snapshot.prices),
+                _ => return (404, json!({ "error": format!("unknown endpoint {}", path) }))
+            };
+            return match body {
+                Ok(body) => (200, body),
+                Err(err) => (500, json!({ "error": err.to_string() }))
+            };
+        }
+        ("POST", "/pause") => Command::Pause,
+        ("POST", "/resume") => Command::Resume,
+        ("POST", "/flatten") => Command::Flatten,
+        ("POST", "/stop") => Command::Stop,
+        ("POST", _) => return (404, json!({ "error": format!("unknown endpoint {}", path) })),
+        _ => return (405, json!({ "error": format!("{} is not supported", method) }))
+    };
+
+    match commands.send(command) {
+        Ok(()) => (202, json!({ "accepted": command })),
+        Err(_) => (503, json!({ "error": "the session already ended" }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    const TOKEN: &str = "secret";
+
+    fn request(method: &str, path: &str, headers: &[(&str, &str)]) -> http::Request {
+        http::Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        }
+    }
+
+    fn authorized(method: &str, path: &str) -> http::Request {
+        request(method, path, &[("host", "localhost:7878"), ("authorization", "Bearer secret")])
+    }
+
+    fn status(request: &http::Request) -> u16 {
+        match authorize(request, TOKEN) {
+            Ok(()) => 200,
+            Err((status, _)) => status,
+        }
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        assert_eq!(status(&authorized("GET", "/status")), 200);
+        assert_eq!(status(&request("GET", "/status", &[("host", "localhost")])), 401);
+        assert_eq!(status(&request("GET", "/status", &[("authorization", "Bearer wrong")])), 401);
+        assert_eq!(status(&request("GET", "/status", &[("authorization", "secret")])), 401);
+    }
+
+    #[test]
+    fn requests_for_other_hosts_are_refused() {
+        let rebound = request("POST", "/stop", &[("host", "evil.example:7878"), ("authorization", "Bearer secret")]);
+        assert_eq!(status(&rebound), 403);
+        for host in ["127.0.0.1:7878", "[::1]:7878", "LOCALHOST"] {
+            let local = request("GET", "/status", &[("host", host), ("authorization", "Bearer secret")]);
+            assert_eq!(status(&local), 200, "{}", host);
+        }
+    }
+
+    #[test]
+    fn requests_from_other_sites_are_refused() {
+        let mut cross_site = authorized("POST", "/stop");
+        cross_site.headers.push((String::from("origin"), String::from("https://evil.example")));
+        assert_eq!(status(&cross_site), 403);
+        let mut same_site = authorized("POST", "/stop");
+        same_site.headers.push((String::from("origin"), String::from("http://localhost:7878")));
+        assert_eq!(status(&same_site), 200);
+    }
+
+    #[test]
+    fn commands_are_handed_to_the_session() {
+        let control = Control::new();
+        let (status, _) = route("POST", "/flatten", &control.snapshot, &control.sender);
+        assert_eq!(status, 202);
+        assert_eq!(control.commands(), vec![Command::Flatten]);
+        assert_eq!(route("POST", "/unknown", &control.snapshot, &control.sender).0, 404);
+        assert_eq!(route("DELETE", "/stop", &control.snapshot, &control.sender).0, 405);
+    }
+
+    #[test]
+    fn the_state_is_returned_as_json() {
+        let control = Control::new();
+        control.push_price(Utc::now(), 100.5);
+        let (status, body) = route("GET", "/prices", &control.snapshot, &control.sender);
+        assert_eq!(status, 200);
+        assert_eq!(body[0]["price"], json!(100.5));
+        assert_eq!(route("GET", "/status", &control.snapshot, &control.sender).1, json!(null));
+    }
+
+    #[test]
+    fn the_server_answers_on_localhost() {
+        let mut control = Control::new();
+        let address = control.serve(0, TOKEN.to_string()).unwrap();
+
+        let send = |request: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let stopped = send("POST /stop HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer secret\r\n\r\n");
+        assert!(stopped.starts_with("HTTP/1.1 202 Accepted"), "{}", stopped);
+        let denied = send("POST /stop HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(denied.starts_with("HTTP/1.1 401 Unauthorized"), "{}", denied);
+        assert_eq!(control.commands(), vec![Command::Stop]);
+    }
+}
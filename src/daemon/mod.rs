@@ -0,0 +1,232 @@
+//! Unattended sessions
+//!
+//! The daemon starts the sessions of the schedules in the configuration file
+//! as `start` child processes and tells them to stop at the end of their
+//! schedule with `--until`. After every session it rewrites the summary of
+//! the day in `DAEMON_DIR`.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use log::{error, info, warn};
+
+pub use schedule::Schedule;
+
+use crate::report::PerformanceReport;
+use crate::trading::calendar::Calendar;
+use crate::trading::journal::Journal;
+
+mod schedule;
+
+pub const DAEMON_DIR: &str = "./data/daemon/";
+/// how often the daemon checks the schedules and sessions
+const POLL_INTERVAL_SECS: u64 = 5;
+/// how long a session may take to shut down after its stop time before it is killed
+const STOP_GRACE_MINUTES: i64 = 5;
+
+pub struct Daemon {
+    pub schedules: Vec<Schedule>,
+    /// the calendars of the exchanges set for the ISINs of the schedules
+    pub calendars: HashMap<String, Calendar>,
+}
+
+/// A session the daemon started
+struct Running {
+    schedule: usize,
+    child: Child,
+    started: DateTime<Local>,
+    stop: DateTime<Local>,
+    journal: PathBuf,
+    report: PathBuf,
+}
+
+/// The outcome of a session for the daily summary
+struct Summary {
+    schedule: String,
+    started: DateTime<Local>,
+    ended: DateTime<Local>,
+    status: String,
+    report: Option<PerformanceReport>,
+    journal: PathBuf,
+}
+
+pub fn daemon(daemon: Daemon) -> Result<(), String> {
+    let exe = env::current_exe().map_err(|err| format!("Could not find the trading-desk executable ({})", err))?;
+    info!("Daemon started with {} schedules", daemon.schedules.len());
+    for schedule in daemon.schedules.iter() {
+        info!("\t{}", schedule);
+    }
+
+    let mut running: Vec<Running> = Vec::new();
+    // the start of the last run of each schedule, so a run isn't started again after it stopped early
+    let mut last_runs: HashMap<usize, DateTime<Local>> = HashMap::new();
+    let mut day = Local::now().naive_local().date();
+    let mut summaries: Vec<Summary> = Vec::new();
+
+    loop {
+        let now = Local::now();
+        if now.naive_local().date() != day {
+            day = now.naive_local().date();
+            summaries.clear();
+        }
+
+        for (index, schedule) in daemon.schedules.iter().enumerate() {
+            if running.iter().any(|run| run.schedule == index) {
+                continue;
+            }
+            let (start, stop) = match schedule.current_run(now, daemon.calendars.get(&schedule.isin)) {
+                Some(run) => run,
+                None => continue
+            };
+            if last_runs.get(&index) == Some(&start) {
+                continue;
+            }
+
+            last_runs.insert(index, start);
+            match spawn(&exe, schedule, stop) {
+                Ok((child, journal, report)) => {
+                    info!("Started {} (until {})", schedule.name, stop.format("%H:%M"));
+                    running.push(Running { schedule: index, child, started: now, stop, journal, report });
+                }
+                Err(err) => {
+                    error!("Could not start {} ({})", schedule.name, err);
+                    summaries.push(Summary {
+                        schedule: schedule.name.clone(),
+                        started: now,
+                        ended: now,
+                        status: format!("not started ({})", err),
+                        report: None,
+                        journal: PathBuf::new(),
+                    });
+                    write_summary(day, &summaries);
+                }
+            }
+        }
+
+        let mut index = 0;
+        while index < running.len() {
+            let run = &mut running[index];
+            let status = match run.child.try_wait() {
+                Ok(Some(status)) if status.success() => String::from("finished"),
+                Ok(Some(status)) => match status.code() {
+                    Some(code) => format!("failed (exit code {})", code),
+                    None => String::from("failed (killed)")
+                },
+                Ok(None) => {
+                    if now > run.stop + Duration::minutes(STOP_GRACE_MINUTES) {
+                        warn!("{} did not stop in time and is killed", daemon.schedules[run.schedule].name);
+                        if let Err(err) = run.child.kill() {
+                            error!("Could not kill {} ({})", daemon.schedules[run.schedule].name, err);
+                        }
+                    }
+                    index += 1;
+                    continue;
+                }
+                Err(err) => format!("unknown ({})", err)
+            };
+
+            let run = running.remove(index);
+            let name = daemon.schedules[run.schedule].name.clone();
+            info!("{} {}", name, status);
+            let report = fs::read_to_string(&run.report)
+                .ok()
+                .and_then(|json| serde_json::from_str(&json).ok());
+            summaries.push(Summary {
+                schedule: name,
+                started: run.started,
+                ended: Local::now(),
+                status,
+                report,
+                journal: run.journal,
+            });
+            write_summary(day, &summaries);
+        }
+
+        thread::sleep(StdDuration::from_secs(POLL_INTERVAL_SECS));
+    }
+}
+
+/// starts the session of a schedule and returns it with the locations of its journal and report
+fn spawn(exe: &Path, schedule: &Schedule, stop: DateTime<Local>) -> Result<(Child, PathBuf, PathBuf), String> {
+    let journal = Journal::journal_file(Utc::now(), &schedule.isin, schedule.trading_type);
+    let report = Path::new(DAEMON_DIR)
+        .join("reports")
+        .join(format!("{}-{}.json", Local::now().format("%Y%m%d-%H%M%S"), file_name(&schedule.name)));
+    if let Some(dir) = report.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+
+    let mut command = Command::new(exe);
+    command
+        .args(["start", &schedule.trading_type.to_string(), "--isin", &schedule.isin, "--output", "none"])
+        .args(["--until", &stop.with_timezone(&Utc).to_rfc3339()])
+        .arg("--journal").arg(&journal)
+        .arg("--report").arg(&report)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    if let Some(ref algorithm) = schedule.algorithm {
+        command.args(["--algorithm", algorithm]);
+    }
+    if let Some(ref profile) = schedule.profile {
+        command.args(["--profile", profile]);
+    }
+    if let Some(interval) = schedule.interval {
+        command.args(["--interval", &interval.to_string()]);
+    }
+    for (name, value) in schedule.parameters.iter() {
+        command.arg("--param").arg(format!("{}={}", name, value));
+    }
+
+    let child = command.spawn().map_err(|err| err.to_string())?;
+    Ok((child, journal, report))
+}
+
+/// the location of the summary of a day
+pub fn summary_file(day: NaiveDate) -> PathBuf {
+    Path::new(DAEMON_DIR).join(format!("summary-{}.md", day.format("%Y-%m-%d")))
+}
+
+/// Rewrites the summary of the day with all sessions that ended so far
+fn write_summary(day: NaiveDate, summaries: &[Summary]) {
+    let mut markdown = format!(
+        "# Sessions of {}\n\n\
+        | schedule | started | ended | status | return | trades | fees | journal |\n\
+        | --- | --- | --- | --- | --- | --- | --- | --- |\n",
+        day.format("%Y-%m-%d")
+    );
+    for summary in summaries {
+        let (total_return, trades, fees) = match summary.report {
+            Some(ref report) => (
+                format!("{:.2}%", report.total_return * 100.0),
+                report.trades.to_string(),
+                format!("{:.2}", report.fees),
+            ),
+            None => (String::from("-"), String::from("-"), String::from("-"))
+        };
+        markdown.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} | {} | {} |\n",
+            summary.schedule, summary.started.format("%H:%M:%S"), summary.ended.format("%H:%M:%S"), summary.status,
+            total_return, trades, fees, summary.journal.display()
+        ));
+    }
+
+    let path = summary_file(day);
+    let written = fs::create_dir_all(DAEMON_DIR).and_then(|_| fs::write(&path, markdown));
+    match written {
+        Ok(()) => info!("Updated the summary {}", path.display()),
+        Err(err) => error!("Could not write the summary {} ({})", path.display(), err)
+    }
+}
+
+/// the name of a schedule without the characters that don't belong in a file name
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
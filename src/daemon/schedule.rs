@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{Datelike, DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::trading::calendar::Calendar;
+use crate::trading::params::ParamValue;
+use crate::trading::TradingType;
+
+/// A session the daemon starts and stops at fixed local times
+///
+/// ```toml
+/// [[schedules]]
+/// name = "xetra paper"
+/// trading_type = "paper"
+/// isin = "DE0005140008"
+/// profile = "paper"
+/// start = "09:00"
+/// stop = "17:30"
+/// days = ["mon", "tue", "wed", "thu", "fri"]
+///
+/// [schedules.parameters]
+/// threshold = 0.3
+/// ```
+///
+/// If an exchange is set for the ISIN, the session only runs on its trading days.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Schedule {
+    pub name: String,
+    pub trading_type: TradingType,
+    pub isin: String,
+    /// the current algorithm if not set
+    #[serde(default)]
+    pub algorithm: Option<String>,
+    /// the current profile if not set
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// local times like "09:00"
+    pub start: String,
+    pub stop: String,
+    /// every day if empty
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// the interval in seconds (the default of `start` if not set)
+    #[serde(default)]
+    pub interval: Option<u64>,
+    /// passed on with `--param`
+    #[serde(default)]
+    pub parameters: BTreeMap<String, ParamValue>,
+}
+
+impl Schedule {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.trading_type {
+            TradingType::Paper => {}
+            TradingType::Back => return Err(format!("{}: back tests can't be scheduled", self.name)),
+            // the session would fail every time it is started
+            TradingType::Live => return Err(format!("{}: live trading is not supported yet", self.name)),
+        }
+        let (start, stop) = self.times()?;
+        if start >= stop {
+            return Err(format!("{}: the session starts at {} but stops at {}", self.name, self.start, self.stop));
+        }
+        self.weekdays()?;
+        Ok(())
+    }
+
+    /// the start and stop of the run that should be active at `now` (None if there is none)
+    ///
+    /// The times are in the time zone of `now` (the local one for the daemon).
+    pub fn current_run<Tz: TimeZone>(&self, now: DateTime<Tz>, calendar: Option<&Calendar>) -> Option<(DateTime<Tz>, DateTime<Tz>)> {
+        let date = now.naive_local().date();
+        if !self.runs_on(date, calendar) {
+            return None;
+        }
+
+        let (start, stop) = self.times().ok()?;
+        let timezone = now.timezone();
+        let start = at(&timezone, date, start)?;
+        let stop = at(&timezone, date, stop)?;
+        if now >= start && now < stop { Some((start, stop)) } else { None }
+    }
+
+    fn runs_on(&self, date: NaiveDate, calendar: Option<&Calendar>) -> bool {
+        // validated when the daemon starts
+        let weekdays = self.weekdays().unwrap_or_default();
+        (weekdays.is_empty() || weekdays.contains(&date.weekday()))
+            && calendar.is_none_or(|calendar| calendar.is_trading_day(date))
+    }
+
+    fn times(&self) -> Result<(NaiveTime, NaiveTime), String> {
+        let time = |value: &str| NaiveTime::parse_from_str(value, "%H:%M")
+            .map_err(|err| format!("{}: {} is not a time like 09:00 ({})", self.name, value, err));
+        Ok((time(&self.start)?, time(&self.stop)?))
+    }
+
+    fn weekdays(&self) -> Result<Vec<Weekday>, String> {
+        self.days
+            .iter()
+            .map(|day| day.parse::<Weekday>().map_err(|_| format!("{}: {} is not a day like mon", self.name, day)))
+            .collect()
+    }
+}
+
+/// the time on the date, the earlier one if it occurs twice and the one after the clock change if it is skipped
+fn at<Tz: TimeZone>(timezone: &Tz, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Tz>> {
+    let local = date.and_time(time);
+    timezone.from_local_datetime(&local)
+            .earliest()
+            .or_else(|| timezone.from_local_datetime(&(local + Duration::hours(1))).earliest())
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = if self.days.is_empty() { String::from("every day") } else { self.days.join(", ") };
+        write!(
+            formatter,
+            "{}: {} {} from {} to {} ({})",
+            self.name, self.trading_type, self.isin, self.start, self.stop, days
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use chrono_tz::Europe::Berlin;
+    use chrono_tz::Tz;
+
+    use super::*;
+
+    fn schedule(start: &str, stop: &str, days: &[&str]) -> Schedule {
+        Schedule {
+            name: String::from("test"),
+            trading_type: TradingType::Paper,
+            isin: String::from("DE0005140008"),
+            algorithm: None,
+            profile: None,
+            start: start.to_string(),
+            stop: stop.to_string(),
+            days: days.iter().map(|day| day.to_string()).collect(),
+            interval: None,
+            parameters: BTreeMap::new(),
+        }
+    }
+
+    fn berlin(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Tz> {
+        Berlin.with_ymd_and_hms(2021, month, day, hour, minute, 0).unwrap()
+    }
+
+    fn utc(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, month, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn only_paper_sessions_can_be_scheduled() {
+        assert!(schedule("09:00", "17:30", &[]).validate().is_ok());
+        let back = Schedule { trading_type: TradingType::Back, ..schedule("09:00", "17:30", &[]) };
+        assert!(back.validate().is_err());
+        let live = Schedule { trading_type: TradingType::Live, ..schedule("09:00", "17:30", &[]) };
+        assert!(live.validate().is_err());
+    }
+
+    #[test]
+    fn invalid_times_and_days_are_rejected() {
+        assert!(schedule("17:30", "09:00", &[]).validate().is_err());
+        assert!(schedule("9 am", "17:30", &[]).validate().is_err());
+        assert!(schedule("09:00", "17:30", &["someday"]).validate().is_err());
+    }
+
+    #[test]
+    fn a_run_is_active_from_its_start_until_its_stop() {
+        let schedule = schedule("09:00", "17:30", &[]);
+        let run = Some((berlin(4, 1, 9, 0), berlin(4, 1, 17, 30)));
+        assert_eq!(schedule.current_run(berlin(4, 1, 8, 59), None), None);
+        assert_eq!(schedule.current_run(berlin(4, 1, 9, 0), None), run);
+        assert_eq!(schedule.current_run(berlin(4, 1, 17, 29), None), run);
+        assert_eq!(schedule.current_run(berlin(4, 1, 17, 30), None), None);
+    }
+
+    #[test]
+    fn runs_are_limited_to_their_days() {
+        let schedule = schedule("09:00", "17:30", &["mon", "thu"]);
+        // 2021-04-01 is a Thursday
+        assert!(schedule.current_run(berlin(4, 1, 10, 0), None).is_some());
+        assert!(schedule.current_run(berlin(4, 2, 10, 0), None).is_none());
+        assert!(schedule.current_run(berlin(4, 5, 10, 0), None).is_some());
+    }
+
+    #[test]
+    fn runs_are_limited_to_the_trading_days_of_the_exchange() {
+        let schedule = schedule("09:00", "17:30", &[]);
+        let xetra = Calendar::builtin("xetra").unwrap();
+        assert!(schedule.current_run(berlin(4, 3, 10, 0), None).is_some());
+        assert!(schedule.current_run(berlin(4, 3, 10, 0), Some(&xetra)).is_none());
+    }
+
+    #[test]
+    fn runs_follow_the_daylight_saving_time() {
+        let schedule = schedule("09:00", "17:30", &[]);
+        let (winter, _) = schedule.current_run(berlin(3, 26, 10, 0), None).unwrap();
+        let (summer, _) = schedule.current_run(berlin(3, 29, 10, 0), None).unwrap();
+        assert_eq!((winter.with_timezone(&Utc), summer.with_timezone(&Utc)), (utc(3, 26, 8, 0), utc(3, 29, 7, 0)));
+    }
+
+    #[test]
+    fn runs_starting_in_a_clock_change_start_after_it() {
+        // 02:30 was skipped on 2021-03-28 and happened twice on 2021-10-31
+        let schedule = schedule("02:30", "04:00", &[]);
+        let (start, stop) = schedule.current_run(berlin(3, 28, 3, 45), None).unwrap();
+        assert_eq!((start.with_timezone(&Utc), stop.with_timezone(&Utc)), (utc(3, 28, 1, 30), utc(3, 28, 2, 0)));
+        let (start, _) = schedule.current_run(berlin(10, 31, 3, 0), None).unwrap();
+        assert_eq!(start.with_timezone(&Utc), utc(10, 31, 0, 30));
+    }
+}
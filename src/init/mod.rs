@@ -1,38 +1,90 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::time::Duration;
 
-use algorithm_utils::Derivative;
+use algorithm_utils::{Derivative, load::Algorithms};
+use chrono::{DateTime, Utc};
+use log::error;
+use log4rs::Handle;
+
+use crate::daemon::Daemon;
+use crate::optimize::Optimize;
+use crate::output::{ChartStyle, OutputMode};
+use crate::trading::allocation::Allocation;
+use crate::trading::bars::BarSize;
+use crate::trading::calendar::Calendar;
+use crate::trading::checkpoint::Checkpoint;
+use crate::trading::currency::FxRates;
+use crate::trading::decimal::Decimal;
+use crate::trading::fill_model::FillModel;
+use crate::trading::TradingType;
 
 mod parse_args;
-mod settings;
+pub mod settings;
 
-pub fn init() -> Action {
-    let parse_args = parse_args::parse_args();
+pub fn init(logger: &Handle) -> Action {
+    let parse_args = parse_args::parse_args(logger);
 
     match parse_args {
+        // the output of the commands is meant for the user, not for the log
         Action::Exit(msg) => {
             println!("\n{}", msg);
             std::process::exit(0);
         }
         Action::Panic(msg) => {
-            eprintln!("\n{}", msg);
+            error!("\n{}", msg);
             std::process::exit(1);
         }
         Action::None => std::process::exit(0),
-        Action::Start(_) => return parse_args
+        Action::Start(_) | Action::Optimize(_) | Action::Daemon(_) => parse_args
     }
 }
 
 #[allow(unused)] // todo
 pub struct Start {
-    isins: Vec<Derivative>,
-    interval: Duration,
-    api: settings::ApiConfig,
-    algorithm: String,
+    pub trading_type: TradingType,
+    pub derivative: Derivative,
+    pub interval: Duration,
+    pub api: Option<settings::ApiConfig>,
+    /// the algorithms of the session with their parameters and parts of the capital
+    pub allocations: Vec<Allocation>,
+    pub algorithms: Algorithms,
+    pub profile: settings::Profile,
+    pub save_config: settings::SaveConfig,
+    pub fill_model: FillModel,
+    /// the trading hours of the exchange (None to trade around the clock)
+    pub calendar: Option<Calendar>,
+    /// the exchange rates to the base currency of the profile
+    pub fx: FxRates,
+    /// ISIN = the smallest price step of the instrument
+    pub tick_sizes: BTreeMap<String, Decimal>,
+    /// the bar size passed with `--bars`, kept for a resumed session
+    pub bars: Option<BarSize>,
+    pub output: Vec<OutputMode>,
+    /// the full output is shown as a dashboard even in back tests
+    pub dashboard: bool,
+    pub chart_style: ChartStyle,
+    /// the time a live or paper session stops
+    pub until: Option<DateTime<Utc>>,
+    /// the part of the recorded prices a back test uses
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    /// a file the journal is written to regardless of the save settings
+    pub journal: Option<PathBuf>,
+    /// a file the performance report is written to as json
+    pub report: Option<PathBuf>,
+    /// the port of the control API and the token its requests need (it isn't started if not set)
+    pub control: Option<(u16, String)>,
+    /// the state of the session that is resumed
+    pub resume: Option<Checkpoint>,
 }
 
 #[allow(unused)] // todo
+#[allow(clippy::large_enum_variant)] // there is only one action per run
 pub enum Action {
     Start(Start),
+    Optimize(Optimize),
+    Daemon(Daemon),
     Exit(String),
     None,
     Panic(String),
@@ -41,18 +93,12 @@ pub enum Action {
 #[allow(unused)]
 impl Action {
     pub fn is_none(&self) -> bool {
-        if let Action::None = self {
-            true
-        } else { false }
+        matches!(self, Action::None)
     }
     pub fn is_exit(&self) -> bool {
-        if let Action::Exit(_) = self {
-            true
-        } else { false }
+        matches!(self, Action::Exit(_))
     }
     pub fn is_panic(&self) -> bool {
-        if let Action::Panic(_) = self {
-            true
-        } else { false }
+        matches!(self, Action::Panic(_))
     }
 }
\ No newline at end of file
@@ -1,13 +1,26 @@
 use std::path::Path;
 
+use chrono::DateTime;
 use clap::{App, Arg, ArgMatches, crate_authors, crate_version, SubCommand};
+use log::warn;
+use log4rs::Handle;
 
+use parse_daemon::parse_daemon;
+use parse_optimize::parse_optimize;
+use parse_report::parse_report;
 use parse_settings::parse_settings;
 use parse_start::parse_start;
 
 use crate::init::Action;
 use crate::init::settings::{ConfigFile, Settings};
+use crate::logging;
+use crate::trading::allocation;
+use crate::trading::bars::BarSize;
+use crate::trading::decimal::Decimal;
 
+mod parse_daemon;
+mod parse_optimize;
+mod parse_report;
 mod parse_settings;
 mod parse_start;
 
@@ -22,24 +35,35 @@ const BROKER_REQUIREMENTS: [(&str, &str); 4] = [
 const TRADING_TYPES: [&str; 3] = ["live", "paper", "back"];
 const OUTPUT: [&str; 6] = ["text", "chart", "full", "trade", "price", "none"];
 const ON_OFF: [&str; 2] = ["on", "off"];
+const REPORT_FORMATS: [&str; 3] = ["text", "json", "markdown"];
+const TAX_FORMATS: [&str; 3] = ["text", "csv", "json"];
+const CHART_STYLES: [&str; 2] = ["line", "candles"];
+const SEARCH_METHODS: [&str; 2] = ["grid", "random"];
+const METRICS: [&str; 4] = ["sharpe", "sortino", "return", "drawdown"];
+const RESAMPLING: [&str; 2] = ["shuffle", "bootstrap"];
 
 
-pub fn parse_args() -> Action {
+pub fn parse_args(logger: &Handle) -> Action {
     let matches = clap_parser();
 
-    let mut current_settings: Settings = ConfigFile::from_config_file()
-        .expect("Could not read configuration!")
-        .into();
+    let config_file = ConfigFile::from_config_file().expect("Could not read configuration!");
+    if let Err(err) = logging::configure(logger, &config_file.log_config) {
+        warn!("{}\nlogging to the console only", err);
+    }
+    let mut current_settings: Settings = config_file.into();
 
     if let Err(err) = current_settings
         .algorithms_mut()
-        .load_all(&ALGORITHM_DIR) {
+        .load_all(ALGORITHM_DIR) {
         return Action::Panic(err.msg().to_string());
     }
 
     match matches.subcommand() {
         ("settings", Some(settings)) => parse_settings(settings, current_settings),
         ("start", Some(start)) => parse_start(start, current_settings),
+        ("optimize", Some(optimize)) => parse_optimize(optimize, current_settings),
+        ("daemon", Some(daemon)) => parse_daemon(daemon, current_settings),
+        ("report", Some(report)) => parse_report(report),
         _ => Action::None
     }
 }
@@ -115,6 +139,167 @@ fn clap_parser<'a>() -> ArgMatches<'a> {
                     .long("list")
                 )
             )
+            .subcommand(SubCommand::with_name("profiles")
+                .about("manages the profiles that hold the trading limits\n\
+                the limits themselves are configured in the configuration file")
+                .arg(Arg::with_name("list")
+                    .help("shows the available profiles")
+                    .short("l")
+                    .long("list")
+                )
+                .arg(Arg::with_name("show")
+                    .help("shows the limits of a profile")
+                    .value_name("profile-name")
+                    .short("s")
+                    .long("show")
+                    .takes_value(true)
+                )
+                .arg(Arg::with_name("change")
+                    .help("changes the profile used by default")
+                    .value_name("profile-name")
+                    .short("c")
+                    .long("change")
+                    .takes_value(true)
+                )
+                .arg(Arg::with_name("add")
+                    .help("adds a new profile without any limits")
+                    .value_name("profile-name")
+                    .short("a")
+                    .long("add")
+                    .takes_value(true)
+                )
+                .arg(Arg::with_name("rearm")
+                    .help("re-arms a profile that was halted by the kill switch")
+                    .value_name("profile-name")
+                    .long("rearm")
+                    .takes_value(true)
+                )
+                .arg(Arg::with_name("remove")
+                    .help("removes a profile")
+                    .value_name("profile-name")
+                    .short("r")
+                    .long("remove")
+                    .takes_value(true)
+                )
+            )
+            .subcommand(SubCommand::with_name("exchanges")
+                .about("assigns the exchanges whose trading hours apply to an ISIN\n\
+                holidays and other exchanges are configured in calendar files in ./data/calendars/")
+                .arg(Arg::with_name("list")
+                    .help("shows the exchanges assigned to ISINs")
+                    .short("l")
+                    .long("list")
+                )
+                .arg(Arg::with_name("set")
+                    .help("assigns an exchange to an ISIN")
+                    .value_name("ISIN=exchange")
+                    .long("set")
+                    .takes_value(true)
+                    .validator(|value| {
+                        match value.find('=') {
+                            Some(12) => Ok(()),
+                            _ => Err(String::from("set takes a 12 chars long ISIN and an exchange like ISIN=xetra"))
+                        }
+                    })
+                )
+                .arg(Arg::with_name("remove")
+                    .help("removes the exchange of an ISIN, so it's traded around the clock")
+                    .value_name("ISIN")
+                    .short("r")
+                    .long("remove")
+                    .takes_value(true)
+                )
+                .arg(Arg::with_name("show")
+                    .help("shows the trading hours and holidays of an exchange")
+                    .value_name("exchange")
+                    .short("s")
+                    .long("show")
+                    .takes_value(true)
+                )
+            )
+            .subcommand(SubCommand::with_name("currencies")
+                .about("assigns the currencies ISINs trade in and imports exchange rates\n\
+                ISINs without a currency trade in the base currency of the profile. \
+                The rates are kept in ./data/fx/.")
+                .arg(Arg::with_name("list")
+                    .help("shows the currencies assigned to ISINs")
+                    .short("l")
+                    .long("list")
+                )
+                .arg(Arg::with_name("set")
+                    .help("assigns a currency to an ISIN")
+                    .value_name("ISIN=currency")
+                    .long("set")
+                    .takes_value(true)
+                    .validator(|value| {
+                        match value.find('=') {
+                            Some(12) if currency_code(&value[13..]) => Ok(()),
+                            _ => Err(String::from("set takes a 12 chars long ISIN and a currency like ISIN=USD"))
+                        }
+                    })
+                )
+                .arg(Arg::with_name("remove")
+                    .help("removes the currency of an ISIN, so it trades in the base currency")
+                    .value_name("ISIN")
+                    .short("r")
+                    .long("remove")
+                    .takes_value(true)
+                )
+                .arg(Arg::with_name("import")
+                    .help("imports exchange rates from a csv file with `time,rate` or `date,rate` lines\n\
+                    the rate is the amount of the base currency one unit of the currency is worth")
+                    .value_name("file")
+                    .long("import")
+                    .takes_value(true)
+                    .requires("pair")
+                    .validator(|path| {
+                        let path = Path::new(&path);
+                        if path.is_file() { Ok(()) } else { Err(String::from("import takes a valid path to a file")) }
+                    })
+                )
+                .arg(Arg::with_name("pair")
+                    .help("the currency and the base currency of the imported rates")
+                    .value_name("currency/base")
+                    .long("pair")
+                    .takes_value(true)
+                    .validator(|value| {
+                        let mut currencies = value.split('/');
+                        match (currencies.next(), currencies.next(), currencies.next()) {
+                            (Some(currency), Some(base), None) if currency_code(currency) && currency_code(base) => Ok(()),
+                            _ => Err(String::from("pair takes two currencies like USD/EUR"))
+                        }
+                    })
+                )
+            )
+            .subcommand(SubCommand::with_name("tick-sizes")
+                .about("sets the smallest price steps of ISINs\n\
+                The limits and stops of the algorithms and the simulated quotes are rounded to them.")
+                .arg(Arg::with_name("list")
+                    .help("shows the tick sizes of all ISINs")
+                    .short("l")
+                    .long("list")
+                )
+                .arg(Arg::with_name("set")
+                    .help("sets the tick size of an ISIN")
+                    .value_name("ISIN=tick size")
+                    .long("set")
+                    .takes_value(true)
+                    .validator(|value| {
+                        let tick_size = value.get(13..).map(str::parse::<Decimal>);
+                        match (value.find('='), tick_size) {
+                            (Some(12), Some(Ok(tick_size))) if tick_size.is_positive() => Ok(()),
+                            _ => Err(String::from("set takes a 12 chars long ISIN and a positive tick size like ISIN=0.01"))
+                        }
+                    })
+                )
+                .arg(Arg::with_name("remove")
+                    .help("removes the tick size of an ISIN, so its prices aren't rounded")
+                    .value_name("ISIN")
+                    .short("r")
+                    .long("remove")
+                    .takes_value(true)
+                )
+            )
             .subcommand(SubCommand::with_name("apis") // TODO: load to load from different folders
                 .about("A CLI for manually changing API settings")
                 .arg(Arg::with_name("list")
@@ -186,8 +371,10 @@ fn clap_parser<'a>() -> ArgMatches<'a> {
         .subcommand(SubCommand::with_name("start")
             .about("Starts the trading-desk algorithm")
             .arg(Arg::with_name("trading-desk type")
-                .help("determine weather you want to trade live (with real money!), paper (without money) or back (back tests you algorithm)")
+                .help("determine weather you want to trade live (with real money!), paper (without money) or back (back tests you algorithm)\n\
+                paper sessions trade on the prices while they are recorded to data/prices/<ISIN>.csv")
                 .takes_value(true)
+                .required(true)
                 .possible_values(&TRADING_TYPES)
             )
             .arg(Arg::with_name("ISIN")
@@ -208,6 +395,49 @@ fn clap_parser<'a>() -> ArgMatches<'a> {
                 .required_unless("ISIN")
                 .conflicts_with("ISIN")
             )
+            .arg(Arg::with_name("algorithm")
+                .help("the algorithm to trade with [default: the current algorithm]\n\
+                Pass it several times to run algorithms side by side, each with its own positions and an \
+                optional share of the capital (like `-a momentum=0.6 -a mean-reversion`). Algorithms \
+                without a share split the rest of the capital equally. Opposing market orders of the \
+                algorithms are netted before they reach the broker.")
+                .value_name("algorithm-name[=share]")
+                .short("a")
+                .long("algorithm")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(|value| allocation::parse_share(&value).map(|_| ()))
+            )
+            .arg(Arg::with_name("profile")
+                .help("the profile whose trading limits are enforced [default: the current profile]")
+                .value_name("profile-name")
+                .short("p")
+                .long("profile")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("param")
+                .help("sets a parameter of the algorithm and overrides the value of the profile\n\
+                to see the parameters have a look at `settings algorithms --description`. \
+                With several algorithms prefix it with the algorithm like `momentum:window=20`.")
+                .value_name("[algorithm:]key=value")
+                .long("param")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("interval")
+                .help("the interval in seconds in which prices are requested")
+                .long("interval")
+                .takes_value(true)
+                .default_value("60")
+                .validator(|value| {
+                    match value.parse::<u64>() {
+                        Ok(interval) if interval > 0 => Ok(()),
+                        _ => Err(String::from("interval needs to be a positive number of seconds"))
+                    }
+                })
+            )
             .arg(Arg::with_name("output")
                 .help("Specifies the amount of date that should be displayed [default: trades]\
                 \nThis argument let's you decide what amount of information should be displayed \
@@ -215,6 +445,8 @@ fn clap_parser<'a>() -> ArgMatches<'a> {
                 recommended to output the trades, since this gives you the ability to check if \
                 the algorithm goes crazy. full and none can't be specified together! \
                 If full or none is set it overrides all other values. \
+                In live and paper sessions full is a dashboard with the key bindings \
+                p (pause), r (resume), f (flatten) and q (stop). \
                 Notice that you always have the option to look at the data afterwards if you save it.")
                 .short("o")
                 .long("output")
@@ -224,6 +456,91 @@ fn clap_parser<'a>() -> ArgMatches<'a> {
                 .default_value("text")
                 .default_value("trade")
             )
+            .arg(Arg::with_name("dashboard")
+                .help("shows the dashboard in back tests too\n\
+                it replaces the output and has the same key bindings as in live and paper sessions. \
+                The back test slows down to the speed the dashboard is redrawn with.")
+                .long("dashboard")
+            )
+            .arg(Arg::with_name("chart-style")
+                .help("the style of the chart if chart is one of the outputs")
+                .long("chart-style")
+                .takes_value(true)
+                .possible_values(&CHART_STYLES)
+                .default_value("line")
+            )
+            .arg(Arg::with_name("from")
+                .help("back tests only use the prices from this time on (RFC 3339)")
+                .value_name("time")
+                .long("from")
+                .takes_value(true)
+                .validator(rfc3339)
+            )
+            .arg(Arg::with_name("to")
+                .help("back tests only use the prices before this time (RFC 3339)")
+                .value_name("time")
+                .long("to")
+                .takes_value(true)
+                .validator(rfc3339)
+            )
+            .arg(Arg::with_name("until")
+                .help("live and paper sessions stop at this time (RFC 3339)")
+                .value_name("time")
+                .long("until")
+                .takes_value(true)
+                .validator(rfc3339)
+            )
+            .arg(Arg::with_name("journal")
+                .help("writes the trade journal to a file regardless of the save settings")
+                .value_name("file")
+                .long("journal")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("report")
+                .help("writes the performance report of the session as json to a file")
+                .value_name("file")
+                .long("report")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("exchange")
+                .help("the exchange whose trading hours apply [default: the exchange set for the ISIN]\n\
+                without an exchange the algorithm trades around the clock")
+                .long("exchange")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("bars")
+                .help("the size of the bars the algorithm is called with [default: the size the algorithm declares]\n\
+                like 30s, 5m, 1h or 1d for time bars, 100t for tick bars or 5000v for volume bars. \
+                Algorithms that work on raw prices can't be called with bars.")
+                .value_name("size")
+                .long("bars")
+                .takes_value(true)
+                .validator(|value| value.parse::<BarSize>().map(|_| ()))
+            )
+            .arg(Arg::with_name("control")
+                .help("starts the control API on this port of localhost\n\
+                it shows the status, positions, orders, prices and PnL of the session and can \
+                pause, resume, flatten or stop it. Every request needs the control_token of the \
+                configuration file (like `curl -X POST -H \"Authorization: Bearer <token>\" localhost:<port>/pause`)")
+                .value_name("port")
+                .long("control")
+                .takes_value(true)
+                .validator(|value| {
+                    match value.parse::<u16>() {
+                        Ok(_) => Ok(()),
+                        Err(_) => Err(String::from("control needs to be a port number"))
+                    }
+                })
+            )
+            .arg(Arg::with_name("resume")
+                .help("continues the last session of the ISIN from its checkpoint\n\
+                The algorithm, profile, parameters and interval are taken from the checkpoint. \
+                Live and paper sessions write a checkpoint every minute, back tests can just be run again. \
+                Sessions with several algorithms can't be resumed yet, they remove the checkpoint of an \
+                earlier session of the ISIN.")
+                .long("resume")
+                .conflicts_with_all(&["algorithm", "profile", "param", "from", "to", "journal"])
+            )
             .arg(Arg::with_name("save")
                 .help("Weather or not data like trades should be saved\
                 \nThis argument let's you decide how much data should be saved. \
@@ -239,5 +556,316 @@ fn clap_parser<'a>() -> ArgMatches<'a> {
                 .default_value("trade")
             )
         )
+        .subcommand(SubCommand::with_name("optimize")
+            .about("Back tests an algorithm with different parameters and ranks the results")
+            .arg(Arg::with_name("ISIN")
+                .help("the ISIN of the product the back tests trade (prices are read from ./data/prices/)")
+                .short("i")
+                .long("isin")
+                .takes_value(true)
+                .required(true)
+                .validator(|value| {
+                    if value.len() == 12 { Ok(()) } else { Err("ISIN needs to be 12 chars long!".to_string()) }
+                })
+            )
+            .arg(Arg::with_name("algorithm")
+                .help("the algorithm to optimise [default: the current algorithm]")
+                .value_name("algorithm-name")
+                .short("a")
+                .long("algorithm")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("profile")
+                .help("the profile whose limits and parameters the back tests use [default: the current profile]")
+                .value_name("profile-name")
+                .short("p")
+                .long("profile")
+                .takes_value(true)
+            )
+            .arg(Arg::with_name("range")
+                .help("the values of a parameter, either as name=start:end:step or as name=a,b,c\n\
+                parameters without a range keep the value of the profile or their default")
+                .value_name("range")
+                .short("r")
+                .long("range")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(true)
+            )
+            .arg(Arg::with_name("search")
+                .help("back tests every combination (grid) or a random sample of them (random)")
+                .long("search")
+                .takes_value(true)
+                .possible_values(&SEARCH_METHODS)
+                .default_value("grid")
+            )
+            .arg(Arg::with_name("samples")
+                .help("the number of combinations a random search back tests")
+                .long("samples")
+                .takes_value(true)
+                .default_value("100")
+                .validator(positive_number)
+            )
+            .arg(Arg::with_name("metric")
+                .help("the metric the results are ranked by")
+                .short("m")
+                .long("metric")
+                .takes_value(true)
+                .possible_values(&METRICS)
+                .default_value("sharpe")
+            )
+            .arg(Arg::with_name("jobs")
+                .help("the number of back tests that run at the same time [default: the number of CPU cores]")
+                .short("j")
+                .long("jobs")
+                .takes_value(true)
+                .validator(positive_number)
+            )
+            .arg(Arg::with_name("interval")
+                .help("the interval in seconds in which prices are requested")
+                .long("interval")
+                .takes_value(true)
+                .default_value("60")
+                .validator(positive_number)
+            )
+            .arg(Arg::with_name("in-sample")
+                .help("runs a walk-forward analysis that optimises on rolling windows of this many days\n\
+                and back tests the best parameters on the out-of-sample window that follows")
+                .value_name("days")
+                .long("in-sample")
+                .takes_value(true)
+                .requires("out-of-sample")
+                .validator(positive_number)
+            )
+            .arg(Arg::with_name("out-of-sample")
+                .help("the days the parameters of a walk-forward window are back tested on (and the windows move by)")
+                .value_name("days")
+                .long("out-of-sample")
+                .takes_value(true)
+                .requires("in-sample")
+                .validator(positive_number)
+            )
+            .arg(Arg::with_name("output")
+                .help("the csv file the results are written to [default: ./data/optimize/<time>-<isin>.csv]\n\
+                a walk-forward analysis writes the equity curves next to it (<output>-equity.csv)")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("daemon")
+            .about("Runs the schedules of the configuration file until it's stopped\n\
+            Every session is started at its start time, stopped at its stop time and added to the \
+            summary of the day in ./data/daemon/.")
+            .arg(Arg::with_name("list")
+                .help("shows the schedules without running them")
+                .short("l")
+                .long("list")
+            )
+        )
+        .subcommand(SubCommand::with_name("report")
+            .about("Creates reports from the trade journal of a session")
+            .subcommand(SubCommand::with_name("performance")
+                .about("summarises how the algorithm performed")
+                .arg(Arg::with_name("journal")
+                    .help("the journal of the session (saved in ./data/journal/)")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(|path| {
+                        let path = Path::new(&path);
+                        if path.is_file() { Ok(()) } else { Err(String::from("journal takes a valid path to a file")) }
+                    })
+                )
+                .arg(Arg::with_name("format")
+                    .help("the format of the report")
+                    .short("f")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&REPORT_FORMATS)
+                    .default_value("text")
+                )
+                .arg(Arg::with_name("output")
+                    .help("writes the report to a file instead of printing it")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                )
+            )
+            .subcommand(SubCommand::with_name("monte-carlo")
+                .about("resamples the trades of the journal to show how much of the result was luck")
+                .arg(Arg::with_name("journal")
+                    .help("the journal of the session (saved in ./data/journal/)")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(|path| {
+                        let path = Path::new(&path);
+                        if path.is_file() { Ok(()) } else { Err(String::from("journal takes a valid path to a file")) }
+                    })
+                )
+                .arg(Arg::with_name("runs")
+                    .help("the number of resampled trade sequences")
+                    .short("r")
+                    .long("runs")
+                    .takes_value(true)
+                    .default_value("10000")
+                    .validator(positive_number)
+                )
+                .arg(Arg::with_name("method")
+                    .help("draws the trades with replacement (bootstrap) or shuffles them\n\
+                    shuffling only changes the final return with slippage")
+                    .short("m")
+                    .long("method")
+                    .takes_value(true)
+                    .possible_values(&RESAMPLING)
+                    .default_value("bootstrap")
+                )
+                .arg(Arg::with_name("slippage")
+                    .help("the standard deviation of the noise added to the fill prices in basis points\n\
+                    the noise always works against the trade")
+                    .value_name("bps")
+                    .long("slippage")
+                    .takes_value(true)
+                    .default_value("0")
+                    .validator(|value| {
+                        match value.parse::<f64>() {
+                            Ok(slippage) if slippage >= 0.0 => Ok(()),
+                            _ => Err(String::from("slippage needs to be a non negative number of basis points"))
+                        }
+                    })
+                )
+                .arg(Arg::with_name("confidence")
+                    .help("the width of the confidence intervals in percent")
+                    .short("c")
+                    .long("confidence")
+                    .takes_value(true)
+                    .default_value("95")
+                    .validator(|value| {
+                        match value.parse::<u8>() {
+                            Ok(confidence) if confidence > 0 && confidence < 100 => Ok(()),
+                            _ => Err(String::from("confidence needs to be a percentage between 1 and 99"))
+                        }
+                    })
+                )
+                .arg(Arg::with_name("seed")
+                    .help("makes the resampling reproducible")
+                    .long("seed")
+                    .takes_value(true)
+                    .validator(|value| {
+                        match value.parse::<u64>() {
+                            Ok(_) => Ok(()),
+                            Err(_) => Err(String::from("seed needs to be a positive number"))
+                        }
+                    })
+                )
+                .arg(Arg::with_name("format")
+                    .help("the format of the report")
+                    .short("f")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&REPORT_FORMATS)
+                    .default_value("text")
+                )
+                .arg(Arg::with_name("output")
+                    .help("writes the report to a file instead of printing it")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                )
+            )
+            .subcommand(SubCommand::with_name("chart")
+                .about("draws the prices and trades of a finished session into the terminal")
+                .arg(Arg::with_name("journal")
+                    .help("the journal of the session (saved in ./data/journal/)")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(|path| {
+                        let path = Path::new(&path);
+                        if path.is_file() { Ok(()) } else { Err(String::from("journal takes a valid path to a file")) }
+                    })
+                )
+                .arg(Arg::with_name("style")
+                    .help("the style of the chart")
+                    .short("s")
+                    .long("style")
+                    .takes_value(true)
+                    .possible_values(&CHART_STYLES)
+                    .default_value("candles")
+                )
+            )
+            .subcommand(SubCommand::with_name("tax")
+                .about("computes the realised gains of a year with FIFO lot matching\n\
+                Lots opened in earlier years are taken into account, so the journals of these years are needed too.")
+                .arg(Arg::with_name("journals")
+                    .help("the journals of the sessions [default: all journals in ./data/journal/]")
+                    .takes_value(true)
+                    .multiple(true)
+                    .validator(|path| {
+                        let path = Path::new(&path);
+                        if path.is_file() { Ok(()) } else { Err(String::from("journals takes valid paths to files")) }
+                    })
+                )
+                .arg(Arg::with_name("year")
+                    .help("the year the gains were realised in (in the time of Europe/Berlin)")
+                    .short("y")
+                    .long("year")
+                    .takes_value(true)
+                    .required(true)
+                    .validator(|value| {
+                        match value.parse::<i32>() {
+                            Ok(year) if year > 0 => Ok(()),
+                            _ => Err(String::from("year needs to be a year like 2020"))
+                        }
+                    })
+                )
+                .arg(Arg::with_name("trading-type")
+                    .help("only the journals of sessions of this type are used")
+                    .short("t")
+                    .long("trading-type")
+                    .takes_value(true)
+                    .required(true)
+                    .possible_values(&TRADING_TYPES)
+                )
+                .arg(Arg::with_name("lots")
+                    .help("shows how the fills of the instrument opened and closed its lots instead of the report")
+                    .value_name("isin")
+                    .long("lots")
+                    .takes_value(true)
+                )
+                .arg(Arg::with_name("format")
+                    .help("the format of the report, csv has one line per closed lot")
+                    .short("f")
+                    .long("format")
+                    .takes_value(true)
+                    .possible_values(&TAX_FORMATS)
+                    .default_value("text")
+                )
+                .arg(Arg::with_name("output")
+                    .help("writes the report to a file instead of printing it")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                )
+            )
+        )
         .get_matches()
 }
+
+fn positive_number(value: String) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(number) if number > 0 => Ok(()),
+        _ => Err(format!("{} needs to be a positive number", value))
+    }
+}
+
+/// a three letter code like EUR
+fn currency_code(value: &str) -> bool {
+    value.len() == 3 && value.chars().all(|c| c.is_ascii_uppercase())
+}
+
+fn rfc3339(value: String) -> Result<(), String> {
+    match DateTime::parse_from_rfc3339(&value) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("{} is not a valid RFC 3339 time like 2020-05-01T09:00:00Z", value))
+    }
+}
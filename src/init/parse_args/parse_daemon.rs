@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use clap::ArgMatches;
+
+use crate::daemon::Daemon;
+use crate::init::Action;
+use crate::init::settings::{self, Settings};
+use crate::trading::calendar::Calendar;
+
+pub fn parse_daemon(args: &ArgMatches, current_settings: Settings) -> Action {
+    if args.is_present("list") {
+        println!("\n{}", settings::schedules_to_string(&current_settings.schedules));
+        return Action::None;
+    }
+    if current_settings.schedules.is_empty() {
+        return Action::Panic(String::from(
+            "There are no schedules to run\nadd some as [[schedules]] to the configuration file"
+        ));
+    }
+
+    let mut calendars = HashMap::new();
+    for schedule in current_settings.schedules.iter() {
+        if let Err(err) = schedule.validate() {
+            return Action::Panic(format!("Invalid schedule {}", err));
+        }
+        if let Some(exchange) = current_settings.exchanges.get(&schedule.isin) {
+            match Calendar::load(exchange) {
+                Ok(calendar) => calendars.insert(schedule.isin.clone(), calendar),
+                Err(err) => return Action::Panic(err)
+            };
+        }
+    }
+
+    Action::Daemon(Daemon {
+        schedules: current_settings.schedules,
+        calendars,
+    })
+}
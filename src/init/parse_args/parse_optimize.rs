@@ -0,0 +1,67 @@
+use chrono::Duration;
+use clap::ArgMatches;
+
+use crate::init::Action;
+use crate::init::parse_args::parse_start::{parameter_schema, select_algorithm, select_profile};
+use crate::init::settings::Settings;
+use crate::optimize::{Optimize, ParamRange, Search, WalkForward};
+
+pub fn parse_optimize(args: &ArgMatches, current_settings: Settings) -> Action {
+    // the ISIN is required and validated by clap
+    let isin = args.value_of("ISIN").unwrap().to_string();
+
+    let algorithm = match select_algorithm(args, &current_settings) {
+        Ok(algorithm) => algorithm,
+        Err(err) => return Action::Panic(err)
+    };
+    // the profile is resolved by the back tests again, but a missing or halted one should fail right away
+    if let Err(err) = select_profile(args, &current_settings) {
+        return Action::Panic(err);
+    }
+
+    let schema = match parameter_schema(&algorithm, &current_settings) {
+        Ok(schema) => schema,
+        Err(err) => return Action::Panic(err)
+    };
+    // range is required
+    let ranges = match args
+        .values_of("range")
+        .unwrap()
+        .map(|range| ParamRange::parse(range, &schema))
+        .collect::<Result<Vec<_>, _>>() {
+        Ok(ranges) => ranges,
+        Err(err) => return Action::Panic(err)
+    };
+
+    // search, samples, metric, jobs and interval have default values and are validated by clap
+    let search = match args.value_of("search").unwrap() {
+        "random" => Search::Random(args.value_of("samples").unwrap().parse().unwrap()),
+        _ => Search::Grid
+    };
+    let metric = args.value_of("metric").unwrap().parse().unwrap();
+    let jobs = match args.value_of("jobs") {
+        Some(jobs) => jobs.parse().unwrap(),
+        None => num_cpus::get()
+    };
+    let interval = args.value_of("interval").unwrap().parse().unwrap();
+
+    // in-sample and out-of-sample require each other and are validated by clap
+    let days = |name| args.value_of(name).map(|days| Duration::days(days.parse().unwrap()));
+    let walk_forward = match (days("in-sample"), days("out-of-sample")) {
+        (Some(in_sample), Some(out_of_sample)) => Some(WalkForward { in_sample, out_of_sample }),
+        _ => None
+    };
+
+    Action::Optimize(Optimize {
+        isin,
+        algorithm,
+        profile: args.value_of("profile").map(str::to_string),
+        interval,
+        ranges,
+        search,
+        metric,
+        jobs,
+        output: args.value_of("output").map(Into::into),
+        walk_forward,
+    })
+}
@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+
+use crate::init::Action;
+use crate::output::{self, Chart};
+use crate::report::{LotHistory, MonteCarlo, MonteCarloReport, PerformanceReport, TaxReport};
+use crate::trading::journal::{Journal, JournalEntry, JOURNAL_DIR};
+use crate::trading::order::Fill;
+use crate::trading::TradingType;
+
+pub fn parse_report(args: &ArgMatches) -> Action {
+    match args.subcommand() {
+        ("performance", Some(performance)) => parse_performance(performance),
+        ("monte-carlo", Some(monte_carlo)) => parse_monte_carlo(monte_carlo),
+        ("chart", Some(chart)) => parse_chart(chart),
+        ("tax", Some(tax)) => parse_tax(tax),
+        _ => Action::None
+    }
+}
+
+fn parse_performance(args: &ArgMatches) -> Action {
+    // the journal is required and validated by clap
+    let path = args.value_of("journal").unwrap();
+    let entries = match Journal::read(path) {
+        Ok(entries) => entries,
+        Err(err) => return Action::Panic(format!("Could not read the journal {} ({})", path, err))
+    };
+    let report = match PerformanceReport::from_journal(&entries) {
+        Ok(report) => report,
+        Err(err) => return Action::Panic(format!("Could not create the report ({})", err))
+    };
+
+    // format has a default value
+    let format = args.value_of("format").unwrap();
+    let report = match format {
+        "json" => match report.to_json() {
+            Ok(json) => json,
+            Err(err) => return Action::Panic(format!("Could not serialize the report ({})", err))
+        },
+        "markdown" => report.to_markdown(),
+        _ => report.to_string()
+    };
+
+    print_or_write(args.value_of("output"), report, format)
+}
+
+fn parse_monte_carlo(args: &ArgMatches) -> Action {
+    // the journal is required and validated by clap
+    let path = args.value_of("journal").unwrap();
+    let entries = match Journal::read(path) {
+        Ok(entries) => entries,
+        Err(err) => return Action::Panic(format!("Could not read the journal {} ({})", path, err))
+    };
+
+    // runs, method, slippage and confidence have default values and are validated by clap
+    let monte_carlo = MonteCarlo {
+        runs: args.value_of("runs").unwrap().parse().unwrap(),
+        resampling: args.value_of("method").unwrap().parse().unwrap(),
+        slippage: args.value_of("slippage").unwrap().parse::<f64>().unwrap() / 10_000.0,
+        confidence: args.value_of("confidence").unwrap().parse::<f64>().unwrap() / 100.0,
+        seed: args.value_of("seed").map(|seed| seed.parse().unwrap()),
+    };
+    let report = match MonteCarloReport::from_journal(&entries, &monte_carlo) {
+        Ok(report) => report,
+        Err(err) => return Action::Panic(format!("Could not create the report ({})", err))
+    };
+
+    // format has a default value
+    let format = args.value_of("format").unwrap();
+    let report = match format {
+        "json" => match report.to_json() {
+            Ok(json) => json,
+            Err(err) => return Action::Panic(format!("Could not serialize the report ({})", err))
+        },
+        "markdown" => report.to_markdown(),
+        _ => report.to_string()
+    };
+
+    print_or_write(args.value_of("output"), report, format)
+}
+
+fn parse_chart(args: &ArgMatches) -> Action {
+    // the journal is required and validated by clap
+    let path = args.value_of("journal").unwrap();
+    let entries = match Journal::read(path) {
+        Ok(entries) => entries,
+        Err(err) => return Action::Panic(format!("Could not read the journal {} ({})", path, err))
+    };
+    // style has a default value and is restricted to CHART_STYLES
+    let style = args.value_of("style").unwrap().parse().unwrap();
+
+    let (width, height) = output::terminal_size();
+    print!("{}", Chart::from_journal(&entries, style).render(width, height));
+    Action::None
+}
+
+fn parse_tax(args: &ArgMatches) -> Action {
+    // year and trading-type are required and validated by clap, format has a default value
+    let year = args.value_of("year").unwrap().parse::<i32>().unwrap();
+    let trading_type = args.value_of("trading-type").unwrap().parse::<TradingType>().unwrap();
+
+    let paths = match args.values_of("journals") {
+        Some(paths) => paths.map(PathBuf::from).collect(),
+        None => match journal_files() {
+            Ok(paths) => paths,
+            Err(err) => return Action::Panic(format!("Could not read the journals in {} ({})", JOURNAL_DIR, err))
+        }
+    };
+    let fills = match fills_of_type(&paths, trading_type) {
+        Ok(fills) => fills,
+        Err(err) => return Action::Panic(err)
+    };
+
+    if let Some(isin) = args.value_of("lots") {
+        return print_or_write(args.value_of("output"), LotHistory::from_fills(&fills, isin).to_string(), "text");
+    }
+
+    let report = TaxReport::from_fills(&fills, year);
+    let format = args.value_of("format").unwrap();
+    let report = match format {
+        "csv" => report.to_csv(),
+        "json" => match report.to_json() {
+            Ok(json) => json,
+            Err(err) => return Action::Panic(format!("Could not serialize the report ({})", err))
+        },
+        _ => report.to_string()
+    };
+
+    print_or_write(args.value_of("output"), report, format)
+}
+
+/// all journals in `JOURNAL_DIR`
+fn journal_files() -> Result<Vec<PathBuf>, std::io::Error> {
+    if !Path::new(JOURNAL_DIR).is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(JOURNAL_DIR)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "jsonl") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// the fills of the journals whose session was of the trading type
+fn fills_of_type(paths: &[PathBuf], trading_type: TradingType) -> Result<Vec<Fill>, String> {
+    let mut fills = Vec::new();
+    for path in paths {
+        let entries = Journal::read(path)
+            .map_err(|err| format!("Could not read the journal {} ({})", path.display(), err))?;
+
+        let session_type = entries.iter().find_map(|entry| match entry {
+            JournalEntry::Start { trading_type, .. } => Some(*trading_type),
+            _ => None
+        });
+        if session_type != Some(trading_type) {
+            continue;
+        }
+
+        fills.extend(entries.into_iter().filter_map(|entry| match entry {
+            JournalEntry::Fill(fill) => Some(fill),
+            _ => None
+        }));
+    }
+    Ok(fills)
+}
+
+/// prints the report or writes it to `output` if set
+///
+/// Only text reports are set apart from the log by an empty line, so the
+/// other formats can be piped into a file or another program.
+fn print_or_write(output: Option<&str>, report: String, format: &str) -> Action {
+    match output {
+        Some(path) => match fs::write(path, report) {
+            Ok(()) => Action::Exit(format!("Saved the report in {}", path)),
+            Err(err) => Action::Panic(format!("Could not write the report to {} ({})", path, err))
+        },
+        None if format == "text" => {
+            println!("\n{}", report);
+            Action::None
+        }
+        None => {
+            // csv ends with a line break already
+            println!("{}", report.trim_end_matches('\n'));
+            Action::None
+        }
+    }
+}
@@ -1,7 +1,11 @@
 use clap::ArgMatches;
 
 use crate::init::Action;
-use crate::init::settings::{ApiConfig, BrokerApi, ConfigFile, Settings};
+use crate::init::settings;
+use crate::init::settings::{ApiConfig, BrokerApi, ConfigFile, Profile, Settings};
+use crate::trading;
+use crate::trading::calendar::Calendar;
+use crate::trading::currency::{FX_DIR, FxRates};
 
 pub fn parse_settings(args: &ArgMatches, mut current_settings: Settings) -> Action {
     // lets the user load settings from a different file
@@ -13,9 +17,13 @@ pub fn parse_settings(args: &ArgMatches, mut current_settings: Settings) -> Acti
     // }
 
     let action = match args.subcommand() {
-        ("save", Some(save)) => parse_save(&save, &mut current_settings),
-        ("algorithms", Some(algorithms)) => parse_algorithms(&algorithms, &mut current_settings),
-        ("apis", Some(apis)) => parse_apis(&apis, &mut current_settings),
+        ("save", Some(save)) => parse_save(save, &mut current_settings),
+        ("algorithms", Some(algorithms)) => parse_algorithms(algorithms, &mut current_settings),
+        ("profiles", Some(profiles)) => parse_profiles(profiles, &mut current_settings),
+        ("exchanges", Some(exchanges)) => parse_exchanges(exchanges, &mut current_settings),
+        ("currencies", Some(currencies)) => parse_currencies(currencies, &mut current_settings),
+        ("tick-sizes", Some(tick_sizes)) => parse_tick_sizes(tick_sizes, &mut current_settings),
+        ("apis", Some(apis)) => parse_apis(apis, &mut current_settings),
         _ => Action::None
     };
 
@@ -52,14 +60,30 @@ fn parse_algorithms(args: &ArgMatches, current_settings: &mut Settings) -> Actio
 
     // lets the user change the currently used default algorithm
     if let Some(algorithm_name) = args.value_of("change") {
-        if let Err(_) = current_settings.set_current_algorithm(algorithm_name.to_string()) {
+        if current_settings.set_current_algorithm(algorithm_name.to_string()).is_err() {
             action = Action::Panic(format!("Could not find the algorithm {}", algorithm_name));
         }
     }
 
     if let Some(algorithm_name) = args.value_of("description") {
         match current_settings.algorithms().get(algorithm_name) {
-            Some(ref algorithm) => println!("{}", algorithm),
+            Some(ref algorithm) => {
+                println!("{}", algorithm);
+                match trading::parameter_schema(algorithm) {
+                    Ok(ref schema) if schema.is_empty() => println!("PARAMETERS: none"),
+                    Ok(schema) => {
+                        println!("PARAMETERS:");
+                        for spec in schema {
+                            println!("\t{}", spec);
+                        }
+                    }
+                    Err(err) => action = Action::Panic(err)
+                }
+                match trading::input(algorithm) {
+                    Ok(input) => println!("DATA: {}", input),
+                    Err(err) => action = Action::Panic(err)
+                }
+            }
             None => action = Action::Panic(format!("Could not find the algorithm {}", algorithm_name))
         }
     }
@@ -71,6 +95,138 @@ fn parse_algorithms(args: &ArgMatches, current_settings: &mut Settings) -> Actio
     action
 }
 
+fn parse_profiles(args: &ArgMatches, current_settings: &mut Settings) -> Action {
+    let mut action = Action::None;
+
+    if let Some(profile_name) = args.value_of("add") {
+        if current_settings.profile(profile_name).is_some() {
+            return Action::Panic(format!("The profile {} already exists", profile_name));
+        }
+        current_settings.profiles.push(Profile::new(profile_name.to_string()));
+    }
+
+    if let Some(profile_name) = args.value_of("remove") {
+        if current_settings.remove_profile(profile_name).is_err() {
+            action = Action::Panic(format!("Could not find the profile {}", profile_name));
+        }
+    }
+
+    if let Some(profile_name) = args.value_of("rearm") {
+        match current_settings.rearm_profile(profile_name) {
+            Ok(()) => println!("Re-armed the profile {}", profile_name),
+            Err(_) => action = Action::Panic(format!("Could not find the profile {}", profile_name))
+        }
+    }
+
+    // lets the user change the profile used when no profile is passed to start
+    if let Some(profile_name) = args.value_of("change") {
+        if current_settings.set_current_profile(profile_name.to_string()).is_err() {
+            action = Action::Panic(format!("Could not find the profile {}", profile_name));
+        }
+    }
+
+    if let Some(profile_name) = args.value_of("show") {
+        match current_settings.profile(profile_name) {
+            Some(profile) => println!("\n{}", profile),
+            None => action = Action::Panic(format!("Could not find the profile {}", profile_name))
+        }
+    }
+
+    if args.is_present("list") {
+        println!("\n{}", settings::profiles_to_string(&current_settings.profiles));
+    }
+
+    action
+}
+
+fn parse_exchanges(args: &ArgMatches, current_settings: &mut Settings) -> Action {
+    let mut action = Action::None;
+
+    // set is validated by clap
+    if let Some(value) = args.value_of("set") {
+        let (isin, exchange) = value.split_at(12);
+        let exchange = &exchange[1..];
+        match Calendar::load(exchange) {
+            Ok(_) => {
+                current_settings.exchanges.insert(isin.to_string(), exchange.to_string());
+            }
+            Err(err) => action = Action::Panic(err)
+        }
+    }
+
+    if let Some(isin) = args.value_of("remove") {
+        if current_settings.exchanges.remove(isin).is_none() {
+            action = Action::Panic(format!("No exchange is set for {}", isin));
+        }
+    }
+
+    if let Some(exchange) = args.value_of("show") {
+        match Calendar::load(exchange) {
+            Ok(calendar) => println!("\n{}", calendar),
+            Err(err) => action = Action::Panic(err)
+        }
+    }
+
+    if args.is_present("list") {
+        println!("\n{}", settings::exchanges_to_string(&current_settings.exchanges));
+    }
+
+    action
+}
+
+fn parse_currencies(args: &ArgMatches, current_settings: &mut Settings) -> Action {
+    let mut action = Action::None;
+
+    // set is validated by clap
+    if let Some(value) = args.value_of("set") {
+        let (isin, currency) = value.split_at(12);
+        current_settings.currencies.insert(isin.to_string(), currency[1..].to_string());
+    }
+
+    if let Some(isin) = args.value_of("remove") {
+        if current_settings.currencies.remove(isin).is_none() {
+            action = Action::Panic(format!("No currency is set for {}", isin));
+        }
+    }
+
+    // import is validated by clap and requires pair, which is validated too
+    if let Some(path) = args.value_of("import") {
+        let (currency, base) = args.value_of("pair").unwrap().split_at(3);
+        match FxRates::import(path, currency, &base[1..]) {
+            Ok(count) => println!("Imported {} {} rates into {}", count, args.value_of("pair").unwrap(), FX_DIR),
+            Err(err) => action = Action::Panic(err)
+        }
+    }
+
+    if args.is_present("list") {
+        println!("\n{}", settings::currencies_to_string(&current_settings.currencies));
+    }
+
+    action
+}
+
+fn parse_tick_sizes(args: &ArgMatches, current_settings: &mut Settings) -> Action {
+    let mut action = Action::None;
+
+    // set is validated by clap
+    if let Some(value) = args.value_of("set") {
+        let (isin, tick_size) = value.split_at(12);
+        current_settings.tick_sizes.insert(isin.to_string(), tick_size[1..].parse().unwrap());
+    }
+
+    if let Some(isin) = args.value_of("remove") {
+        if current_settings.tick_sizes.remove(isin).is_none() {
+            action = Action::Panic(format!("No tick size is set for {}", isin));
+        }
+    }
+
+    if args.is_present("list") {
+        println!("\n{}", settings::tick_sizes_to_string(&current_settings.tick_sizes));
+    }
+
+    action
+}
+
 fn parse_apis(args: &ArgMatches, current_settings: &mut Settings) -> Action {
     let mut action = match args.subcommand() {
         ("add", Some(add)) => parse_apis_add(add, current_settings),
@@ -110,7 +266,7 @@ fn parse_apis(args: &ArgMatches, current_settings: &mut Settings) -> Action {
 fn parse_apis_add(args: &ArgMatches, current_settings: &mut Settings) -> Action {
     let id = match args.value_of("id") {
         Some(id) => {
-            if BrokerApi::id_exists(&current_settings, &id) {
+            if BrokerApi::id_exists(current_settings, id) {
                 return Action::Panic("This id is already in use".to_string());
             }
 
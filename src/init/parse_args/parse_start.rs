@@ -1,8 +1,332 @@
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use algorithm_utils::Derivative;
+use chrono::{DateTime, Utc};
 use clap::ArgMatches;
+use rand::Rng;
+
+use crate::init::{Action, Start};
+use crate::init::settings::{Profile, Settings};
+use crate::output::OutputMode;
+use crate::trading;
+use crate::trading::TradingType;
+use crate::trading::allocation::{self, Allocation};
+use crate::trading::bars::{BarSize, Input};
+use crate::trading::calendar::Calendar;
+use crate::trading::checkpoint::Checkpoint;
+use crate::trading::currency::FxRates;
+use crate::trading::params::{Parameters, ParamSpec};
+
+pub fn parse_start(args: &ArgMatches, current_settings: Settings) -> Action {
+    // the trading type is required and restricted to TRADING_TYPES by clap
+    let trading_type: TradingType = match args.value_of("trading-desk type").unwrap().parse() {
+        Ok(trading_type) => trading_type,
+        Err(err) => return Action::Panic(err)
+    };
+
+    let isin = match args.value_of("ISIN") {
+        Some(isin) => isin.to_string(),
+        None => return Action::Panic("Trading by symbol is not supported yet, please use the ISIN".to_string())
+    };
+
+    let resume = if args.is_present("resume") {
+        match load_checkpoint(&isin, trading_type) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(err) => return Action::Panic(err)
+        }
+    } else { None };
+
+    // a resumed session continues with the algorithm, profile, parameters and interval it was started with
+    let (algorithms, profile, interval) = match resume {
+        Some(ref checkpoint) => {
+            let session = &checkpoint.session;
+            if !current_settings.algorithms().contains(&session.algorithm) {
+                return Action::Panic(format!("Could not find the algorithm {} of the checkpoint", session.algorithm));
+            }
+            let profile = match named_profile(Some(&session.profile), &current_settings) {
+                Ok(profile) => profile,
+                Err(err) => return Action::Panic(err)
+            };
+            let algorithms = vec![(session.algorithm.clone(), 1.0, session.parameters.clone())];
+            (algorithms, profile, Duration::from_secs(session.interval))
+        }
+        None => {
+            let shares = match select_allocations(args, &current_settings) {
+                Ok(shares) => shares,
+                Err(err) => return Action::Panic(err)
+            };
+            let profile = match select_profile(args, &current_settings) {
+                Ok(profile) => profile,
+                Err(err) => return Action::Panic(err)
+            };
+
+            let args_params: Vec<&str> = args.values_of("param").map(|values| values.collect()).unwrap_or_default();
+            let names: Vec<&str> = shares.iter().map(|(name, _)| name.as_str()).collect();
+            let mut algorithms = Vec::new();
+            for (algorithm, share) in shares.iter() {
+                // the schema defaults are overridden by the profile which is overridden by `--param`
+                let parameters = parameter_schema(algorithm, &current_settings)
+                    .and_then(|schema| {
+                        let args_params = algorithm_params(&args_params, algorithm, &names)?;
+                        Parameters::resolve(&schema, profile.parameters.get(algorithm), &args_params)
+                    });
+                match parameters {
+                    Ok(parameters) => algorithms.push((algorithm.clone(), *share, parameters)),
+                    Err(err) => return Action::Panic(err)
+                }
+            }
+
+            // the interval is validated by clap
+            let interval = Duration::from_secs(args.value_of("interval").unwrap().parse().unwrap());
+            (algorithms, profile, interval)
+        }
+    };
+
+    // a resumed session keeps the bar size it was started with, bars is validated by clap
+    let bars: Option<BarSize> = match resume {
+        Some(ref checkpoint) => checkpoint.session.bars,
+        None => args.value_of("bars").map(|bars| bars.parse().unwrap()),
+    };
+    let mut allocations = Vec::new();
+    for (algorithm, share, parameters) in algorithms {
+        match algorithm_input(&algorithm, bars, &current_settings) {
+            Ok(input) => allocations.push(Allocation { algorithm, parameters, input, share }),
+            Err(err) => return Action::Panic(err)
+        }
+    }
+
+    // without an exchange the algorithm trades around the clock
+    let exchange = args
+        .value_of("exchange")
+        .map(String::from)
+        .or_else(|| current_settings.exchanges.get(&isin).cloned());
+    let calendar = match exchange {
+        Some(exchange) => match Calendar::load(&exchange) {
+            Ok(calendar) => Some(calendar),
+            Err(err) => return Action::Panic(err)
+        },
+        None => None
+    };
+
+    // instruments without a currency trade in the base currency of the profile
+    let instruments = current_settings
+        .currencies
+        .get(&isin)
+        .map(|currency| (isin.clone(), currency.clone()))
+        .into_iter()
+        .collect();
+    let fx = match FxRates::load(&profile.currency, instruments) {
+        Ok(fx) => fx,
+        Err(err) => return Action::Panic(err)
+    };
+
+    // output has a default value, the dashboard replaces it
+    let dashboard = args.is_present("dashboard");
+    let output = match OutputMode::from_args(args.values_of("output").unwrap()) {
+        Ok(_) if dashboard => vec![OutputMode::Full],
+        Ok(output) => output,
+        Err(err) => return Action::Panic(err)
+    };
+    // chart-style has a default value and is restricted to CHART_STYLES
+    let chart_style = args.value_of("chart-style").unwrap().parse().unwrap();
+    let report = args.value_of("report").map(PathBuf::from);
+    let journal = args.value_of("journal").map(PathBuf::from);
+
+    // from and to are validated by clap
+    let time = |name| args
+        .value_of(name)
+        .map(|time| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc));
+    let (from, to, until) = (time("from"), time("to"), time("until"));
+    if (from.is_some() || to.is_some()) && trading_type != TradingType::Back {
+        return Action::Panic(String::from("--from and --to are only supported for back tests"));
+    }
+    if until.is_some() && trading_type == TradingType::Back {
+        return Action::Panic(String::from("--until is not supported for back tests, use --to instead"));
+    }
+
+    // control is validated by clap
+    let control = match (args.value_of("control"), current_settings.control_token.clone()) {
+        (Some(port), Some(token)) if !token.trim().is_empty() => Some((port.parse().unwrap(), token)),
+        (Some(_), _) => return Action::Panic(format!(
+            "The control API needs a token\n\
+            add a secret like `control_token = \"{}\"` at the top of the configuration file", random_token()
+        )),
+        (None, _) => None
+    };
+
+    Action::Start(Start {
+        trading_type,
+        derivative: Derivative::new(isin),
+        interval,
+        api: current_settings.api_config.clone(),
+        allocations,
+        profile,
+        save_config: current_settings.save_config.clone(),
+        fill_model: current_settings.fill_model.clone(),
+        calendar,
+        fx,
+        tick_sizes: current_settings.tick_sizes.clone(),
+        bars,
+        output,
+        dashboard,
+        chart_style,
+        until,
+        from,
+        to,
+        journal,
+        report,
+        resume,
+        control,
+        algorithms: current_settings.into_algorithms(),
+    })
+}
+
+/// a token to suggest for the control API
+fn random_token() -> String {
+    let mut rng = rand::thread_rng();
+    (0..32).map(|_| format!("{:x}", rng.gen_range(0, 16))).collect()
+}
+
+/// the algorithm passed with `--algorithm` or the current algorithm
+pub(super) fn select_algorithm(args: &ArgMatches, current_settings: &Settings) -> Result<String, String> {
+    match args.value_of("algorithm") {
+        Some(name) => {
+            if !current_settings.algorithms().contains(name) {
+                return Err(format!("Could not find the algorithm {}", name));
+            }
+            Ok(name.to_string())
+        }
+        None => match current_settings.current_algorithm() {
+            Some(name) => Ok(name.clone()),
+            None => Err(
+                "No algorithm selected\n\
+                either pass one with `--algorithm` or set one with `settings algorithms --change`".to_string()
+            )
+        }
+    }
+}
+
+/// the algorithms passed with `--algorithm` with their shares of the capital or the current algorithm with all of it
+fn select_allocations(args: &ArgMatches, current_settings: &Settings) -> Result<Vec<(String, f64)>, String> {
+    let values = match args.values_of("algorithm") {
+        Some(values) => values,
+        None => return select_algorithm(args, current_settings).map(|algorithm| vec![(algorithm, 1.0)])
+    };
+
+    let mut shares: Vec<(String, Option<f64>)> = Vec::new();
+    for value in values {
+        // algorithm is validated by clap
+        let (name, share) = allocation::parse_share(value).unwrap();
+        if !current_settings.algorithms().contains(&name) {
+            return Err(format!("Could not find the algorithm {}", name));
+        }
+        if shares.iter().any(|(selected, _)| *selected == name) {
+            return Err(format!("The algorithm {} was passed more than once", name));
+        }
+        shares.push((name, share));
+    }
+    allocation::split_shares(shares)
+}
+
+/// the `--param` values of the algorithm without their `algorithm:` prefix
+///
+/// Parameters without a prefix are only allowed if there is a single algorithm.
+fn algorithm_params<'a>(args_params: &[&'a str], algorithm: &str, names: &[&str]) -> Result<Vec<&'a str>, String> {
+    let mut params = Vec::new();
+    for param in args_params {
+        // the prefix ends before the key (values may contain colons as well)
+        let prefix = param.find(':').filter(|&colon| param.find('=').is_none_or(|equals| colon < equals));
+        match prefix {
+            Some(colon) => {
+                let name = &param[..colon];
+                if !names.contains(&name) {
+                    return Err(format!("{} is not an algorithm of the session (in --param {})", name, param));
+                }
+                if name == algorithm {
+                    params.push(&param[colon + 1..]);
+                }
+            }
+            None if names.len() == 1 => params.push(*param),
+            None => return Err(format!(
+                "With several algorithms parameters have to be passed as algorithm:key=value but got {}", param
+            ))
+        }
+    }
+    Ok(params)
+}
+
+/// the checkpoint the last session of the ISIN and trading type left behind
+fn load_checkpoint(isin: &str, trading_type: TradingType) -> Result<Checkpoint, String> {
+    if trading_type == TradingType::Back {
+        return Err(String::from("Back tests can't be resumed, just run them again"));
+    }
+
+    let path = Checkpoint::checkpoint_file(isin, trading_type);
+    match Checkpoint::load(&path) {
+        Ok(checkpoint) => Ok(checkpoint),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            Err(format!(
+                "There is no {} session of {} to resume (sessions with several algorithms can't be resumed)",
+                trading_type, isin
+            ))
+        }
+        Err(err) => Err(format!("Could not read the checkpoint {} ({})", path.display(), err))
+    }
+}
+
+/// the profile passed with `--profile`, the current profile or the default profile
+pub(super) fn select_profile(args: &ArgMatches, current_settings: &Settings) -> Result<Profile, String> {
+    named_profile(args.value_of("profile"), current_settings)
+}
+
+/// the profile with the name, the current profile or the default profile
+///
+/// Halted profiles are refused.
+fn named_profile(name: Option<&str>, current_settings: &Settings) -> Result<Profile, String> {
+    let profile = match name.or(current_settings.current_profile().as_deref()) {
+        Some(name) => match current_settings.profile(name) {
+            Some(profile) => profile.clone(),
+            // the default profile can be used without being saved (like by a resumed session)
+            None if name == Profile::default().name => Profile::default(),
+            None => return Err(format!("Could not find the profile {}", name))
+        },
+        None => current_settings
+            .profile(&Profile::default().name)
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    if let Some(ref reason) = profile.halted {
+        return Err(format!(
+            "The profile {} was halted by the kill switch ({})\n\
+            to start it again re-arm it with `settings profiles --rearm {}`",
+            profile.name, reason, profile.name
+        ));
+    }
+    profile.notifications
+           .validate()
+           .map_err(|err| format!("The profile {} has an invalid notification hook ({})", profile.name, err))?;
+
+    Ok(profile)
+}
 
-use crate::init::Action;
-use crate::init::settings::Settings;
+pub(super) fn parameter_schema(algorithm: &str, current_settings: &Settings) -> Result<Vec<ParamSpec>, String> {
+    match current_settings.algorithms().get(algorithm) {
+        Some(loaded) => trading::parameter_schema(loaded)
+            .map_err(|err| format!("The algorithm {} has an {}", algorithm, err)),
+        None => Err(format!("Could not find the algorithm {}", algorithm))
+    }
+}
 
-pub fn parse_start(_args: &ArgMatches, mut _current_settings: Settings) -> Action {
-    unimplemented!()
-}
\ No newline at end of file
+/// the input the algorithm declares with the bar size of `--bars`
+fn algorithm_input(algorithm: &str, bars: Option<BarSize>, current_settings: &Settings) -> Result<Input, String> {
+    match current_settings.algorithms().get(algorithm) {
+        Some(loaded) => trading::input(loaded)
+            .map_err(|err| format!("The algorithm {} has an {}", algorithm, err))?
+            .with_bars(bars)
+            .map_err(|err| format!("--bars can't be used with {} ({})", algorithm, err)),
+        None => Err(format!("Could not find the algorithm {}", algorithm))
+    }
+}
@@ -1,14 +1,26 @@
-use std::fmt;
+use std::collections::BTreeMap;
+use std::fmt::{self, Formatter};
 use std::fs;
 use std::io;
 use std::path::Path;
 
 use algorithm_utils::load::Algorithms;
+use log::{info, warn};
 use serde::Deserialize;
-use serde::export::Formatter;
 use serde::Serialize;
 use toml;
 
+use crate::daemon::Schedule;
+use crate::logging::LogConfig;
+use crate::notify::Notifications;
+use crate::trading::currency::DEFAULT_CURRENCY;
+use crate::trading::decimal::Decimal;
+use crate::trading::fill_model::FillModel;
+use crate::trading::kill_switch::KillSwitchLimits;
+use crate::trading::params::ParamValue;
+use crate::trading::risk::RiskLimits;
+use crate::trading::ShutdownPolicy;
+
 pub const CONFIG_FILE: &str = "./conf.conf";
 pub const OLD_CONFIG: &str = "./old_conf.conf";
 
@@ -17,7 +29,21 @@ pub const OLD_CONFIG: &str = "./old_conf.conf";
 pub struct Settings {
     pub api_config: Option<ApiConfig>,
     pub save_config: SaveConfig,
+    pub log_config: LogConfig,
+    pub fill_model: FillModel,
+    /// the exchange whose calendar applies to an ISIN
+    pub exchanges: BTreeMap<String, String>,
+    /// the currency an ISIN trades in if it isn't the base currency of the profile
+    pub currencies: BTreeMap<String, String>,
+    /// the smallest price step of an ISIN, prices of ISINs without one aren't rounded
+    pub tick_sizes: BTreeMap<String, Decimal>,
+    pub profiles: Vec<Profile>,
+    /// the sessions the daemon runs
+    pub schedules: Vec<Schedule>,
+    /// the bearer token the requests of the control API need (never displayed)
+    pub control_token: Option<String>,
     current_algorithm: Option<String>,
+    current_profile: Option<String>,
     algorithms: Algorithms,
 }
 
@@ -35,6 +61,48 @@ impl Settings {
             Ok(())
         } else { Err(()) }
     }
+
+    pub fn current_profile(&self) -> &Option<String> { &self.current_profile }
+
+    pub fn set_current_profile(&mut self, name: String) -> Result<(), ()> {
+        if self.profile(&name).is_some() {
+            self.current_profile = Some(name);
+            Ok(())
+        } else { Err(()) }
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.name == name)
+    }
+
+    pub fn remove_profile(&mut self, name: &str) -> Result<(), ()> {
+        let len = self.profiles.len();
+        self.profiles.retain(|profile| profile.name != name);
+        if self.profiles.len() == len {
+            return Err(());
+        }
+
+        if self.current_profile.as_deref() == Some(name) {
+            self.current_profile = None;
+        }
+        Ok(())
+    }
+
+    /// clears the halt of a profile so it can be started again
+    pub fn rearm_profile(&mut self, name: &str) -> Result<(), ()> {
+        match self.profiles.iter_mut().find(|profile| profile.name == name) {
+            Some(profile) => {
+                profile.halted = None;
+                Ok(())
+            }
+            None => Err(())
+        }
+    }
+
+    /// consumes the settings and returns the loaded algorithms
+    pub fn into_algorithms(self) -> Algorithms { self.algorithms }
 }
 
 impl From<ConfigFile> for Settings {
@@ -42,7 +110,16 @@ impl From<ConfigFile> for Settings {
         Self {
             api_config: config_file.api_config,
             current_algorithm: config_file.current_algorithm,
+            current_profile: config_file.current_profile,
             save_config: config_file.save_config,
+            log_config: config_file.log_config,
+            fill_model: config_file.fill_model,
+            exchanges: config_file.exchanges,
+            currencies: config_file.currencies,
+            tick_sizes: config_file.tick_sizes,
+            profiles: config_file.profiles,
+            schedules: config_file.schedules,
+            control_token: config_file.control_token,
             algorithms: Algorithms::empty(),
         }
     }
@@ -58,6 +135,10 @@ impl fmt::Display for Settings {
             Some(ref name) => format!("CURRENT ALGORITHM: {}", name),
             None => String::from("CURRENT ALGORITHM: None")
         };
+        let current_profile = match self.current_profile {
+            Some(ref name) => format!("CURRENT PROFILE: {}", name),
+            None => String::from("CURRENT PROFILE: None")
+        };
 
         write!(
             formatter,
@@ -65,20 +146,60 @@ impl fmt::Display for Settings {
             {}\n\n\
             {}\n\n\
             {}\n\n\
+            {}\n\n\
+            {}\n\n\
+            {}\n\n\
+            {}\n\n\
+            {}\n\n\
+            {}\n\n\
+            {}\n\n\
+            {}\n\n\
             {}\n",
             api_config,
             current_algorithm,
             self.algorithms,
-            self.save_config
+            current_profile,
+            profiles_to_string(&self.profiles),
+            self.save_config,
+            self.log_config,
+            self.fill_model,
+            exchanges_to_string(&self.exchanges),
+            currencies_to_string(&self.currencies),
+            tick_sizes_to_string(&self.tick_sizes),
+            schedules_to_string(&self.schedules)
         )
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 pub struct ConfigFile {
     pub current_algorithm: Option<String>,
+    #[serde(default)]
+    pub current_profile: Option<String>,
+    /// the control API can't be started without it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub control_token: Option<String>,
     pub api_config: Option<ApiConfig>,
     pub save_config: SaveConfig,
+    #[serde(default)]
+    pub log_config: LogConfig,
+    /// how back tests and paper trading fill orders
+    #[serde(default)]
+    pub fill_model: FillModel,
+    /// ISIN = exchange
+    #[serde(default)]
+    pub exchanges: BTreeMap<String, String>,
+    /// ISIN = currency
+    #[serde(default)]
+    pub currencies: BTreeMap<String, String>,
+    /// ISIN = tick size
+    #[serde(default)]
+    pub tick_sizes: BTreeMap<String, Decimal>,
+    // empty arrays would be written as values after the tables above, which toml doesn't allow
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<Profile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<Schedule>,
 }
 
 impl ConfigFile {
@@ -107,7 +228,7 @@ impl ConfigFile {
             Ok(config) => Ok(config),
             Err(err) => {
                 use io::ErrorKind;
-                eprintln!("Could not read configuration file! ({})", CONFIG_FILE);
+                warn!("Could not read configuration file! ({})", CONFIG_FILE);
 
                 match err.kind() {
                     ErrorKind::NotFound => Self::new_config_file(false),
@@ -123,7 +244,7 @@ impl ConfigFile {
     pub fn to_config_file(&self) -> Result<(), io::Error> {
         let toml = match self.to_toml() {
             Ok(toml) => toml,
-            Err(err) => return Err(io::Error::new(io::ErrorKind::Other, err))
+            Err(err) => return Err(io::Error::other(err))
         };
         fs::write(CONFIG_FILE, toml)
     }
@@ -133,7 +254,7 @@ impl ConfigFile {
         if save_old {
             let old_data = fs::read_to_string(CONFIG_FILE)?;
             fs::write(OLD_CONFIG, old_data)?;
-            println!("Saved old configuration in {}\n\
+            info!("Saved old configuration in {}\n\
                       to use parts of the old configuration use the load command",
                      OLD_CONFIG
             );
@@ -141,28 +262,27 @@ impl ConfigFile {
 
         let new_config = ConfigFile::default();
         new_config.to_config_file()?;
-        println!("Created new configuration file");
+        info!("Created new configuration file");
 
         Ok(new_config)
     }
 }
 
-impl Default for ConfigFile {
-    fn default() -> Self {
-        Self {
-            api_config: None,
-            current_algorithm: None,
-            save_config: SaveConfig::default(),
-        }
-    }
-}
-
 impl From<Settings> for ConfigFile {
     fn from(settings: Settings) -> Self {
         Self {
             api_config: settings.api_config,
             current_algorithm: settings.current_algorithm,
+            current_profile: settings.current_profile,
             save_config: settings.save_config,
+            log_config: settings.log_config,
+            fill_model: settings.fill_model,
+            exchanges: settings.exchanges,
+            currencies: settings.currencies,
+            tick_sizes: settings.tick_sizes,
+            profiles: settings.profiles,
+            schedules: settings.schedules,
+            control_token: settings.control_token,
         }
     }
 }
@@ -285,7 +405,7 @@ impl BrokerApiBuilder {
     pub fn build(self, current_settings: &mut Settings) -> BrokerApi {
         let id = match self.id {
             Some(id) => {
-                if BrokerApi::id_exists(&current_settings, &id) {
+                if BrokerApi::id_exists(current_settings, &id) {
                     unreachable!("Cannot create two BrokerApis with the same id!");
                 }
 
@@ -296,7 +416,7 @@ impl BrokerApiBuilder {
                 Some(_) => {
                     let mut id = self.broker.clone();
                     let mut counter: u32 = 1;
-                    while BrokerApi::id_exists(&current_settings, &id) {
+                    while BrokerApi::id_exists(current_settings, &id) {
                         id.push_str(&counter.to_string());
                         counter += 1;
                     }
@@ -345,3 +465,177 @@ impl fmt::Display for SaveConfig {
         )
     }
 }
+
+/// A named set of trading limits that is selected when starting a session
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Profile {
+    pub name: String,
+    /// the starting capital returns are calculated against
+    #[serde(default = "default_capital")]
+    pub capital: f64,
+    /// the base currency of the capital, the limits and all reports
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    #[serde(default)]
+    pub on_shutdown: ShutdownPolicy,
+    /// the minutes before the close of the exchange the session close hook of the algorithm is called
+    #[serde(default)]
+    pub before_close: Option<u32>,
+    /// the reason the kill switch fired (a halted profile can't be started)
+    #[serde(default)]
+    pub halted: Option<String>,
+    #[serde(default)]
+    pub risk: RiskLimits,
+    #[serde(default)]
+    pub kill_switch: KillSwitchLimits,
+    #[serde(default)]
+    pub notifications: Notifications,
+    /// parameter values per algorithm like `[profiles.parameters."Template Algorithm"]`
+    #[serde(default)]
+    pub parameters: BTreeMap<String, BTreeMap<String, ParamValue>>,
+}
+
+impl Profile {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            capital: default_capital(),
+            currency: default_currency(),
+            on_shutdown: ShutdownPolicy::default(),
+            halted: None,
+            risk: RiskLimits::default(),
+            kill_switch: KillSwitchLimits::default(),
+            notifications: Notifications::default(),
+            before_close: None,
+            parameters: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new(String::from("default"))
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        let halted = match self.halted {
+            Some(ref reason) => format!("HALTED: {}", reason),
+            None => String::from("HALTED: no")
+        };
+        let before_close = match self.before_close {
+            Some(minutes) => format!("{} minutes", minutes),
+            None => String::from("never")
+        };
+
+        write!(
+            formatter,
+            "PROFILE: {}\n\
+            {}\n\
+            CAPITAL: {} {}\n\
+            ON SHUTDOWN: {}\n\
+            SESSION CLOSE HOOK: {}\n\
+            {}\n\
+            {}\n\
+            {}",
+            self.name, halted, self.capital, self.currency, self.on_shutdown, before_close, self.risk, self.kill_switch,
+            self.notifications
+        )?;
+
+        for (algorithm, parameters) in self.parameters.iter() {
+            write!(formatter, "\nPARAMETERS: {}", algorithm)?;
+            for (name, value) in parameters.iter() {
+                write!(formatter, "\n\t{}: {}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_capital() -> f64 { 10_000.0 }
+
+fn default_currency() -> String { String::from(DEFAULT_CURRENCY) }
+
+pub fn exchanges_to_string(exchanges: &BTreeMap<String, String>) -> String {
+    exchanges
+        .iter()
+        .fold(
+            String::from("EXCHANGES:"),
+            |mut prev, (isin, exchange)| {
+                prev.push_str(&format!("\n\t{}: {}", isin, exchange));
+                prev
+            },
+        )
+}
+
+pub fn currencies_to_string(currencies: &BTreeMap<String, String>) -> String {
+    currencies
+        .iter()
+        .fold(
+            String::from("CURRENCIES:"),
+            |mut prev, (isin, currency)| {
+                prev.push_str(&format!("\n\t{}: {}", isin, currency));
+                prev
+            },
+        )
+}
+
+pub fn tick_sizes_to_string(tick_sizes: &BTreeMap<String, Decimal>) -> String {
+    tick_sizes
+        .iter()
+        .fold(
+            String::from("TICK SIZES:"),
+            |mut prev, (isin, tick_size)| {
+                prev.push_str(&format!("\n\t{}: {}", isin, tick_size));
+                prev
+            },
+        )
+}
+
+pub fn schedules_to_string(schedules: &[Schedule]) -> String {
+    schedules
+        .iter()
+        .fold(
+            String::from("SCHEDULES:"),
+            |mut prev, cur| {
+                prev.push_str("\n\t");
+                prev.push_str(&cur.to_string());
+                prev
+            },
+        )
+}
+
+pub fn profiles_to_string(profiles: &[Profile]) -> String {
+    profiles
+        .iter()
+        .fold(
+            String::from("PROFILES:"),
+            |mut prev, cur| {
+                prev.push_str("\n\t");
+                prev.push_str(&cur.name);
+                prev
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_config_file_can_be_written_and_read() {
+        let config = ConfigFile::default();
+        let toml = config.to_toml().unwrap();
+        assert_eq!(ConfigFile::from_toml(&toml).unwrap(), config);
+    }
+
+    #[test]
+    fn a_config_file_with_profiles_can_be_written_and_read() {
+        let mut config = ConfigFile::default();
+        config.profiles.push(Profile::new(String::from("default")));
+        config.current_profile = Some(String::from("default"));
+        let toml = config.to_toml().unwrap();
+        assert_eq!(ConfigFile::from_toml(&toml).unwrap(), config);
+    }
+}
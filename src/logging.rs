@@ -0,0 +1,176 @@
+//! Logging based on log4rs
+//!
+//! Until the configuration file is read everything is logged to the console.
+//! Afterwards the `log_config` section decides where the logs end up:
+//! * the root logger writes to the console and a rolling `trading.log`
+//! * the `session` target (orders, fills, ...) only goes to `trading.log`
+//! * the `algorithm` target only goes to a separate rolling `algorithm.log`
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use log::LevelFilter;
+use log4rs::append::console::{ConsoleAppender, Target};
+use log4rs::append::rolling_file::policy::compound::CompoundPolicy;
+use log4rs::append::rolling_file::policy::compound::roll::fixed_window::FixedWindowRoller;
+use log4rs::append::rolling_file::policy::compound::trigger::size::SizeTrigger;
+use log4rs::append::rolling_file::RollingFileAppender;
+use log4rs::config::{Appender, Config, Logger, Root};
+use log4rs::encode::Encode;
+use log4rs::encode::json::JsonEncoder;
+use log4rs::encode::pattern::PatternEncoder;
+use log4rs::filter::threshold::ThresholdFilter;
+use log4rs::Handle;
+use serde::{Deserialize, Serialize};
+
+/// the target used for everything that happens during a session
+pub const SESSION_TARGET: &str = "session";
+/// the target used for everything the algorithm does
+pub const ALGORITHM_TARGET: &str = "algorithm";
+
+const FILE_PATTERN: &str = "{d(%Y-%m-%d %H:%M:%S%.3f)} {l:<5} {t} - {m}{n}";
+const CONSOLE_PATTERN: &str = "{m}{n}";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// human readable lines
+    Pattern,
+    /// one json object per line
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct LogConfig {
+    /// a log4rs configuration file that is used instead of all other values
+    pub file: Option<String>,
+    pub directory: String,
+    pub level: String,
+    pub console_level: String,
+    pub format: LogFormat,
+    /// the size in bytes after which a log file is rolled
+    pub max_size: u64,
+    /// the number of rolled log files that are kept
+    pub max_files: u32,
+    /// levels for single modules like `trading_desk::trading = "debug"`
+    pub modules: BTreeMap<String, String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            directory: String::from("./logs/"),
+            level: String::from("info"),
+            console_level: String::from("info"),
+            format: LogFormat::Pattern,
+            max_size: 10 * 1024 * 1024,
+            max_files: 5,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+impl fmt::Display for LogConfig {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            return write!(formatter, "LOG:\n\tfile: {}", file);
+        }
+
+        let format = match self.format {
+            LogFormat::Pattern => "pattern",
+            LogFormat::Json => "json",
+        };
+        write!(
+            formatter,
+            "LOG:\n\
+            \tdirectory: {}\n\
+            \tlevel: {}\n\
+            \tconsole level: {}\n\
+            \tformat: {}",
+            self.directory, self.level, self.console_level, format
+        )
+    }
+}
+
+/// Starts logging to the console
+///
+/// This has to be called before anything is logged.
+pub fn init() -> Handle {
+    let config = Config::builder()
+        .appender(console_appender(LevelFilter::Info))
+        .build(Root::builder().appender("console").build(LevelFilter::Info))
+        .expect("Could not build the console logger!");
+
+    log4rs::init_config(config).expect("Could not initialise the logger!")
+}
+
+/// Replaces the console logger with the configured loggers
+pub fn configure(handle: &Handle, log_config: &LogConfig) -> Result<(), String> {
+    let config = match log_config.file {
+        Some(ref file) => log4rs::load_config_file(file, Default::default())
+            .map_err(|err| format!("Could not load the log configuration {} ({})", file, err))?,
+        None => build_config(log_config)?
+    };
+
+    handle.set_config(config);
+    Ok(())
+}
+
+fn build_config(log_config: &LogConfig) -> Result<Config, String> {
+    let level = parse_level(&log_config.level)?;
+    let console_level = parse_level(&log_config.console_level)?;
+
+    let mut builder = Config::builder()
+        .appender(console_appender(console_level))
+        .appender(Appender::builder().build("file", Box::new(rolling_appender(log_config, "trading")?)))
+        .appender(Appender::builder().build("algorithm", Box::new(rolling_appender(log_config, "algorithm")?)))
+        .logger(Logger::builder().appender("file").additive(false).build(SESSION_TARGET, level))
+        .logger(Logger::builder().appender("algorithm").additive(false).build(ALGORITHM_TARGET, level));
+
+    for (module, module_level) in log_config.modules.iter() {
+        builder = builder.logger(Logger::builder().build(module.as_str(), parse_level(module_level)?));
+    }
+
+    builder
+        .build(Root::builder().appender("console").appender("file").build(level))
+        .map_err(|err| format!("Invalid log configuration ({})", err))
+}
+
+fn console_appender(level: LevelFilter) -> Appender {
+    Appender::builder()
+        .filter(Box::new(ThresholdFilter::new(level)))
+        .build("console", Box::new(
+            ConsoleAppender::builder()
+                .target(Target::Stderr)
+                .encoder(Box::new(PatternEncoder::new(CONSOLE_PATTERN)))
+                .build()
+        ))
+}
+
+/// a log file in the log directory that is rolled once it reaches the max size
+fn rolling_appender(log_config: &LogConfig, name: &str) -> Result<RollingFileAppender, String> {
+    let directory = Path::new(&log_config.directory);
+    let rolled = directory.join(format!("{}.{{}}.log", name));
+    let roller = FixedWindowRoller::builder()
+        .build(&rolled.to_string_lossy(), log_config.max_files)
+        .map_err(|err| format!("Invalid log file name {} ({})", rolled.display(), err))?;
+    let policy = CompoundPolicy::new(Box::new(SizeTrigger::new(log_config.max_size)), Box::new(roller));
+
+    let encoder: Box<dyn Encode> = match log_config.format {
+        LogFormat::Pattern => Box::new(PatternEncoder::new(FILE_PATTERN)),
+        LogFormat::Json => Box::new(JsonEncoder::new()),
+    };
+
+    let path = directory.join(format!("{}.log", name));
+    RollingFileAppender::builder()
+        .encoder(encoder)
+        .build(&path, Box::new(policy))
+        .map_err(|err| format!("Could not open the log file {} ({})", path.display(), err))
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.parse().map_err(|_| format!("Invalid log level {}", level))
+}
@@ -1,5 +1,24 @@
+mod control;
+mod daemon;
 mod init;
+mod logging;
+mod notify;
+mod optimize;
+mod output;
+mod report;
+mod trading;
 
 fn main() {
-    init::init();
+    let logger = logging::init();
+
+    let result = match init::init(&logger) {
+        init::Action::Start(start) => trading::start(start),
+        init::Action::Optimize(optimize) => optimize::optimize(optimize),
+        init::Action::Daemon(daemon) => daemon::daemon(daemon),
+        _ => Ok(())
+    };
+    if let Err(err) = result {
+        log::error!("\n{}", err);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,447 @@
+//! Notifications about trades and incidents for when nobody watches the terminal
+//!
+//! Every profile can configure hooks that are notified about some kinds of
+//! session events:
+//!
+//! ```toml
+//! [profiles.notifications]
+//! max_per_minute = 10
+//!
+//! [[profiles.notifications.hooks]]
+//! events = ["fill", "kill_switch"]
+//! command = "notify-send \"$TRADING_DESK_MESSAGE\""
+//!
+//! [[profiles.notifications.hooks]]
+//! events = ["kill_switch", "error"]
+//! webhook = "http://localhost:9000/alerts"
+//! ```
+//!
+//! A hook runs a command (with the notification in the environment and as
+//! json on stdin), writes a json line to a FIFO or posts the json to a
+//! webhook. Every hook delivers its notifications on its own thread, so a
+//! slow hook never blocks the session or the other hooks. Notifications
+//! about the kill switch are never dropped by the rate limit.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{self, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::output::{SessionEvent, Subscriber};
+
+mod webhook;
+
+/// how long the end of the session waits for the last notifications to be delivered
+const FINISH_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MAX_PER_MINUTE: u32 = 10;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Order,
+    Fill,
+    Rejected,
+    Cancelled,
+    KillSwitch,
+    Error,
+    Command,
+    Finished,
+}
+
+impl fmt::Display for NotificationKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotificationKind::Order => write!(formatter, "order"),
+            NotificationKind::Fill => write!(formatter, "fill"),
+            NotificationKind::Rejected => write!(formatter, "rejected"),
+            NotificationKind::Cancelled => write!(formatter, "cancelled"),
+            NotificationKind::KillSwitch => write!(formatter, "kill_switch"),
+            NotificationKind::Error => write!(formatter, "error"),
+            NotificationKind::Command => write!(formatter, "command"),
+            NotificationKind::Finished => write!(formatter, "finished"),
+        }
+    }
+}
+
+/// The notification settings of a profile
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct Notifications {
+    /// the maximum number of notifications per minute and hook, the rest is dropped
+    pub max_per_minute: u32,
+    pub hooks: Vec<Hook>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            max_per_minute: DEFAULT_MAX_PER_MINUTE,
+            hooks: Vec::new(),
+        }
+    }
+}
+
+impl Notifications {
+    pub fn validate(&self) -> Result<(), String> {
+        self.hooks.iter().map(Hook::target).collect::<Result<Vec<_>, _>>().map(|_| ())
+    }
+}
+
+impl fmt::Display for Notifications {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "NOTIFICATIONS:\n\tmax per minute: {}", self.max_per_minute)?;
+        if self.hooks.is_empty() {
+            write!(formatter, "\n\thooks: none")?;
+        }
+        for hook in self.hooks.iter() {
+            write!(formatter, "\n\t{}", hook)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the notifications about some kinds of events go (exactly one target has to be set)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Hook {
+    /// all kinds of events if empty
+    #[serde(default)]
+    pub events: Vec<NotificationKind>,
+    /// a shell command
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub fifo: Option<PathBuf>,
+    /// an http:// URL the notification is posted to
+    #[serde(default)]
+    pub webhook: Option<String>,
+}
+
+impl Hook {
+    fn target(&self) -> Result<Target, String> {
+        match (&self.command, &self.fifo, &self.webhook) {
+            (Some(command), None, None) => Ok(Target::Command(command.clone())),
+            (None, Some(fifo), None) => Ok(Target::Fifo(fifo.clone())),
+            (None, None, Some(url)) => Ok(Target::Webhook(webhook::Url::parse(url)?)),
+            (None, None, None) => Err(String::from("a notification hook needs a command, fifo or webhook")),
+            _ => Err(String::from("a notification hook can only have one of command, fifo and webhook"))
+        }
+    }
+
+    fn wants(&self, kind: NotificationKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.command, &self.fifo, &self.webhook) {
+            (Some(command), _, _) => write!(formatter, "command `{}`", command)?,
+            (_, Some(fifo), _) => write!(formatter, "fifo {}", fifo.display())?,
+            (_, _, Some(url)) => write!(formatter, "webhook {}", url)?,
+            _ => write!(formatter, "no target")?,
+        }
+
+        if self.events.is_empty() {
+            write!(formatter, " on all events")
+        } else {
+            let events: Vec<String> = self.events.iter().map(NotificationKind::to_string).collect();
+            write!(formatter, " on {}", events.join(", "))
+        }
+    }
+}
+
+enum Target {
+    Command(String),
+    /// the FIFO needs a reader, otherwise the hook waits for one
+    Fifo(PathBuf),
+    Webhook(webhook::Url),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    pub time: DateTime<Utc>,
+    pub profile: String,
+    pub isin: String,
+    pub message: String,
+}
+
+/// the kind and message of the events there are notifications about
+fn describe(event: &SessionEvent) -> Option<(NotificationKind, String)> {
+    let notification = match event {
+        SessionEvent::Order(order) => (NotificationKind::Order, format!("Sent {}", order)),
+        SessionEvent::Fill(fill) => (NotificationKind::Fill, format!("Filled {}", fill)),
+        SessionEvent::Rejected { order, reason } => (NotificationKind::Rejected, format!("Rejected {}: {}", order, reason)),
+        SessionEvent::Cancelled { order, reason } => (NotificationKind::Cancelled, format!("Cancelled {}: {}", order, reason)),
+        SessionEvent::KillSwitch(reason) => (NotificationKind::KillSwitch, format!("Kill switch fired: {}", reason)),
+        SessionEvent::Error(err) => (NotificationKind::Error, err.clone()),
+        SessionEvent::Command(command) => (NotificationKind::Command, format!("Received the command {}", command)),
+        SessionEvent::Finished(msg) => (NotificationKind::Finished, msg.clone()),
+        _ => return None
+    };
+    Some(notification)
+}
+
+/// The rate limit of a single hook
+struct Limiter {
+    sent: VecDeque<Instant>,
+    /// the notifications dropped since the last one that was sent
+    dropped: usize,
+}
+
+/// Subscribes to the session events and hands the notifications to the delivery thread
+pub struct Notifier {
+    profile: String,
+    isin: String,
+    hooks: Vec<Hook>,
+    limiters: Vec<Limiter>,
+    max_per_minute: u32,
+    /// the delivery threads of the hooks
+    senders: Vec<Sender<Notification>>,
+    done: Receiver<()>,
+}
+
+impl Notifier {
+    pub fn start(profile: String, isin: String, notifications: Notifications) -> Result<Self, String> {
+        let targets = notifications.hooks.iter().map(Hook::target).collect::<Result<Vec<_>, _>>()?;
+
+        let (finished, done) = mpsc::channel();
+        let senders = targets
+            .into_iter()
+            .map(|target| {
+                let (sender, receiver) = mpsc::channel();
+                let finished = finished.clone();
+                thread::spawn(move || {
+                    deliver(receiver, target);
+                    let _ = finished.send(());
+                });
+                sender
+            })
+            .collect();
+
+        Ok(Self {
+            profile,
+            isin,
+            limiters: notifications.hooks.iter().map(|_| Limiter { sent: VecDeque::new(), dropped: 0 }).collect(),
+            hooks: notifications.hooks,
+            max_per_minute: notifications.max_per_minute,
+            senders,
+            done,
+        })
+    }
+
+    /// whether the hook may send another notification now
+    fn allow(&mut self, hook: usize, now: Instant) -> bool {
+        let limiter = &mut self.limiters[hook];
+        while limiter.sent.front().is_some_and(|sent| now.duration_since(*sent) >= Duration::from_secs(60)) {
+            limiter.sent.pop_front();
+        }
+
+        if limiter.sent.len() >= self.max_per_minute as usize {
+            limiter.dropped += 1;
+            return false;
+        }
+        limiter.sent.push_back(now);
+        true
+    }
+}
+
+impl Subscriber for Notifier {
+    fn on_event(&mut self, event: &SessionEvent) {
+        let (kind, message) = match describe(event) {
+            Some(notification) => notification,
+            None => return
+        };
+
+        let now = Instant::now();
+        for hook in 0..self.hooks.len() {
+            // nobody may miss that the kill switch fired
+            if !self.hooks[hook].wants(kind) || (kind != NotificationKind::KillSwitch && !self.allow(hook, now)) {
+                continue;
+            }
+
+            let dropped = std::mem::replace(&mut self.limiters[hook].dropped, 0);
+            let message = match dropped {
+                0 => message.clone(),
+                dropped => format!("{} ({} notifications were dropped by the rate limit)", message, dropped),
+            };
+            let notification = Notification {
+                kind,
+                time: Utc::now(),
+                profile: self.profile.clone(),
+                isin: self.isin.clone(),
+                message,
+            };
+            if let Some(sender) = self.senders.get(hook) {
+                let _ = sender.send(notification);
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        let hooks = self.senders.len();
+        self.senders.clear();
+
+        let deadline = Instant::now() + Duration::from_secs(FINISH_TIMEOUT_SECS);
+        for _ in 0..hooks {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if self.done.recv_timeout(left).is_err() {
+                warn!("Not all notifications were delivered before the end of the session");
+                return;
+            }
+        }
+    }
+}
+
+fn deliver(receiver: Receiver<Notification>, target: Target) {
+    for notification in receiver {
+        let json = match serde_json::to_string(&notification) {
+            Ok(json) => json,
+            Err(err) => {
+                error!("Could not serialize the notification ({})", err);
+                continue;
+            }
+        };
+
+        let delivered = match target {
+            Target::Command(ref command) => run_command(command, &notification, &json),
+            Target::Fifo(ref path) => OpenOptions::new()
+                .write(true)
+                .open(path)
+                .and_then(|mut fifo| writeln!(fifo, "{}", json))
+                .map_err(|err| format!("{} ({})", path.display(), err)),
+            Target::Webhook(ref url) => webhook::post(url, &json),
+        };
+        if let Err(err) = delivered {
+            error!("Could not deliver the {} notification: {}", notification.kind, err);
+        }
+    }
+}
+
+/// Runs the command with `sh -c`, the notification is passed in the environment and as json on stdin
+fn run_command(command: &str, notification: &Notification, json: &str) -> Result<(), String> {
+    let mut child = process::Command::new("sh")
+        .args(["-c", command])
+        .env("TRADING_DESK_EVENT", notification.kind.to_string())
+        .env("TRADING_DESK_MESSAGE", &notification.message)
+        .env("TRADING_DESK_PROFILE", &notification.profile)
+        .env("TRADING_DESK_ISIN", &notification.isin)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("`{}` ({})", command, err))?;
+
+    // the command doesn't have to read its stdin
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(json.as_bytes());
+    }
+
+    let status = child.wait().map_err(|err| format!("`{}` ({})", command, err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{}` failed ({})", command, status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{BufRead, BufReader};
+
+    use serde_json::Value;
+
+    use super::*;
+
+    fn webhook(url: &str, events: Vec<NotificationKind>) -> Hook {
+        Hook { events, command: None, fifo: None, webhook: Some(url.to_string()) }
+    }
+
+    fn notifier(max_per_minute: u32, hooks: Vec<Hook>) -> Notifier {
+        Notifier::start(
+            String::from("default"), String::from("DE0005140008"), Notifications { max_per_minute, hooks },
+        ).unwrap()
+    }
+
+    fn received(body: &str) -> (String, String) {
+        let json: Value = serde_json::from_str(body).unwrap();
+        (json["kind"].as_str().unwrap().to_string(), json["message"].as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn hooks_need_one_target() {
+        let mut hook = Hook { events: Vec::new(), command: None, fifo: None, webhook: None };
+        assert!(hook.target().is_err());
+        hook.command = Some(String::from("true"));
+        assert!(hook.target().is_ok());
+        hook.webhook = Some(String::from("http://localhost:9000/alerts"));
+        assert!(hook.target().is_err());
+        hook.command = None;
+        assert!(hook.target().is_ok());
+        hook.webhook = Some(String::from("https://example.com/alerts"));
+        assert!(hook.target().is_err());
+    }
+
+    #[test]
+    fn only_sends_the_wanted_events() {
+        let (url, bodies) = webhook::tests::stand_in(200);
+        let mut notifier = notifier(10, vec![webhook(&url, vec![NotificationKind::Error])]);
+        notifier.on_event(&SessionEvent::KillSwitch(String::from("drawdown")));
+        notifier.on_event(&SessionEvent::Error(String::from("the feed failed")));
+        notifier.finish();
+
+        assert_eq!(received(&bodies.recv().unwrap()), (String::from("error"), String::from("the feed failed")));
+        assert!(bodies.try_recv().is_err());
+    }
+
+    #[test]
+    fn the_rate_limit_spares_the_kill_switch() {
+        let (url, bodies) = webhook::tests::stand_in(200);
+        let mut notifier = notifier(1, vec![webhook(&url, Vec::new())]);
+        notifier.on_event(&SessionEvent::Error(String::from("first")));
+        notifier.on_event(&SessionEvent::Error(String::from("second")));
+        notifier.on_event(&SessionEvent::KillSwitch(String::from("drawdown")));
+        notifier.on_event(&SessionEvent::Error(String::from("third")));
+        notifier.finish();
+
+        assert_eq!(received(&bodies.recv().unwrap()), (String::from("error"), String::from("first")));
+        assert_eq!(received(&bodies.recv().unwrap()), (
+            String::from("kill_switch"),
+            String::from("Kill switch fired: drawdown (1 notifications were dropped by the rate limit)"),
+        ));
+        assert!(bodies.try_recv().is_err());
+    }
+
+    #[test]
+    fn a_fifo_without_a_reader_does_not_hold_up_the_other_hooks() {
+        let path = env::temp_dir().join(format!("trading-desk-{}-notifications", process::id()));
+        let _ = fs::remove_file(&path);
+        assert!(process::Command::new("mkfifo").arg(&path).status().unwrap().success());
+
+        let (url, bodies) = webhook::tests::stand_in(200);
+        let fifo = Hook { events: Vec::new(), command: None, fifo: Some(path.clone()), webhook: None };
+        let mut notifier = notifier(10, vec![fifo, webhook(&url, Vec::new())]);
+        notifier.on_event(&SessionEvent::Error(String::from("the feed failed")));
+
+        let body = bodies.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(received(&body).1, "the feed failed");
+
+        // a reader lets the FIFO hook deliver as well
+        let mut line = String::new();
+        BufReader::new(File::open(&path).unwrap()).read_line(&mut line).unwrap();
+        assert_eq!(received(&line).1, "the feed failed");
+        notifier.finish();
+        fs::remove_file(&path).unwrap();
+    }
+}
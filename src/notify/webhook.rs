@@ -0,0 +1,175 @@
+//! Just enough of an HTTP/1.1 client to post notifications
+//!
+//! Only plain `http://` URLs are supported, so a webhook that needs TLS has to
+//! be reached through a local relay.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// a webhook that can't be reached or doesn't answer in this time counts as failed
+const TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Url {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = if let Some(rest) = url.strip_prefix("http://") {
+            rest
+        } else if url.starts_with("https://") {
+            return Err(format!("{}: https is not supported", url));
+        } else {
+            return Err(format!("{} is not an http:// URL", url));
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/")
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(index) => {
+                let port = authority[index + 1..]
+                    .parse::<u16>()
+                    .map_err(|_| format!("{} has an invalid port", url))?;
+                (&authority[..index], port)
+            }
+            None => (authority, 80)
+        };
+        if host.is_empty() {
+            return Err(format!("{} has no host", url));
+        }
+
+        Ok(Self { host: host.to_string(), port, path: path.to_string() })
+    }
+}
+
+/// Posts the json body and fails if the webhook doesn't answer with a 2xx status
+pub fn post(url: &Url, body: &str) -> Result<(), String> {
+    let timeout = Some(Duration::from_secs(TIMEOUT_SECS));
+    let mut stream = connect(url)?;
+    stream.set_read_timeout(timeout).map_err(|err| err.to_string())?;
+    stream.set_write_timeout(timeout).map_err(|err| err.to_string())?;
+
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\n\
+        Host: {}:{}\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n\r\n\
+        {}",
+        url.path, url.host, url.port, body.len(), body
+    ).map_err(|err| err.to_string())?;
+    stream.flush().map_err(|err| err.to_string())?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).map_err(|err| err.to_string())?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| format!("invalid response {:?}", status_line.trim()))?;
+
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(format!("the webhook answered with {}", status))
+    }
+}
+
+/// Tries the addresses of the host one after another
+fn connect(url: &Url) -> Result<TcpStream, String> {
+    let addresses = (url.host.as_str(), url.port).to_socket_addrs().map_err(|err| err.to_string())?;
+    let mut last_err = format!("{} has no address", url.host);
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, Duration::from_secs(TIMEOUT_SECS)) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = format!("{} ({})", address, err),
+        }
+    }
+    Err(last_err)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    use super::*;
+
+    /// A local webhook that answers every request with the status and passes the bodies on
+    pub(in crate::notify) fn stand_in(status: u16) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("Content-Length: ") {
+                        length = value.parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                write!(reader.get_mut(), "HTTP/1.1 {} Status\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+                if sender.send(String::from_utf8(body).unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+        (format!("http://127.0.0.1:{}/hook", port), receiver)
+    }
+
+    #[test]
+    fn parses_urls() {
+        assert_eq!(
+            Url::parse("http://localhost:9000/alerts"),
+            Ok(Url { host: String::from("localhost"), port: 9000, path: String::from("/alerts") })
+        );
+        assert_eq!(
+            Url::parse("http://example.com"),
+            Ok(Url { host: String::from("example.com"), port: 80, path: String::from("/") })
+        );
+        assert!(Url::parse("https://example.com/hook").is_err());
+        assert!(Url::parse("example.com/hook").is_err());
+        assert!(Url::parse("http://example.com:port/hook").is_err());
+        assert!(Url::parse("http://:9000/hook").is_err());
+    }
+
+    #[test]
+    fn posts_the_body() {
+        let (url, bodies) = stand_in(204);
+        assert_eq!(post(&Url::parse(&url).unwrap(), "{\"kind\":\"fill\"}"), Ok(()));
+        assert_eq!(bodies.recv().unwrap(), "{\"kind\":\"fill\"}");
+    }
+
+    #[test]
+    fn fails_without_a_2xx_status() {
+        let (url, bodies) = stand_in(500);
+        assert_eq!(post(&Url::parse(&url).unwrap(), "{}"), Err(String::from("the webhook answered with 500")));
+        assert_eq!(bodies.recv().unwrap(), "{}");
+    }
+
+    #[test]
+    fn fails_without_a_webhook() {
+        // the port is free again once the listener is dropped
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let url = Url::parse(&format!("http://127.0.0.1:{}/hook", port)).unwrap();
+        assert!(post(&url, "{}").is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Parameter optimisation over back tests
+//!
+//! Every combination of parameters is back tested by a `start back` child
+//! process, since a loaded algorithm is a single instance that can't be run
+//! several times at once. The runs are spread over a number of worker
+//! threads, ranked by a metric and written to a csv table.
+//!
+//! A walk-forward analysis repeats this on rolling windows of the prices, see
+//! [`walk_forward`](walk_forward/index.html).
+
+use std::cmp::Ordering;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::str::FromStr;
+use std::sync::{Arc, mpsc, Mutex};
+use std::thread;
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+
+pub use search::{ParamRange, Search};
+pub use walk_forward::WalkForward;
+
+use crate::report::PerformanceReport;
+use crate::trading::journal::{Journal, JournalEntry};
+use crate::trading::params::ParamValue;
+
+mod search;
+mod walk_forward;
+
+pub const OPTIMIZE_DIR: &str = "./data/optimize/";
+/// the number of runs printed after the optimisation
+const TOP_RUNS: usize = 10;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Metric {
+    Sharpe,
+    Sortino,
+    Return,
+    Drawdown,
+}
+
+impl Metric {
+    /// the score the runs are ranked by (higher is better)
+    pub fn score(&self, report: &PerformanceReport) -> Option<f64> {
+        match self {
+            Metric::Sharpe => report.sharpe_ratio,
+            Metric::Sortino => report.sortino_ratio,
+            Metric::Return => Some(report.total_return),
+            Metric::Drawdown => Some(-report.max_drawdown),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sharpe" => Ok(Metric::Sharpe),
+            "sortino" => Ok(Metric::Sortino),
+            "return" => Ok(Metric::Return),
+            "drawdown" => Ok(Metric::Drawdown),
+            _ => Err(format!("Unknown metric {}", s))
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Sharpe => write!(formatter, "sharpe"),
+            Metric::Sortino => write!(formatter, "sortino"),
+            Metric::Return => write!(formatter, "return"),
+            Metric::Drawdown => write!(formatter, "drawdown"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Optimize {
+    pub isin: String,
+    pub algorithm: String,
+    /// passed on to the back tests, they fall back to the current profile otherwise
+    pub profile: Option<String>,
+    /// the interval in seconds
+    pub interval: u64,
+    pub ranges: Vec<ParamRange>,
+    pub search: Search,
+    pub metric: Metric,
+    pub jobs: usize,
+    pub output: Option<PathBuf>,
+    /// runs a walk-forward analysis instead of a single optimisation over all prices
+    pub walk_forward: Option<WalkForward>,
+}
+
+impl Optimize {
+    /// the default location of the results table
+    pub fn results_file(time: DateTime<Utc>, isin: &str) -> PathBuf {
+        Path::new(OPTIMIZE_DIR).join(format!("{}-{}.csv", time.format("%Y%m%d-%H%M%S"), isin))
+    }
+}
+
+/// The part of the recorded prices a back test runs on (everything if not set)
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+struct Period {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+/// A single back test of the optimisation
+struct Run {
+    parameters: Vec<(String, ParamValue)>,
+    report: Option<PerformanceReport>,
+    /// only read if the back test was asked to keep it
+    journal: Vec<JournalEntry>,
+    /// why the back test failed or was stopped (it may still have a report)
+    error: Option<String>,
+}
+
+pub fn optimize(optimize: Optimize) -> Result<(), String> {
+    let combinations = optimize.search.combinations(&optimize.ranges)?;
+    let exe = env::current_exe().map_err(|err| format!("Could not find the trading-desk executable ({})", err))?;
+
+    if let Some(ref walk_forward) = optimize.walk_forward {
+        return walk_forward.run(&exe, &optimize, combinations);
+    }
+
+    info!("Optimising {} with {} back tests on {} threads", optimize.algorithm, combinations.len(), optimize.jobs);
+    let runs = sweep(&exe, &optimize, combinations, Period::default());
+
+    let path = optimize
+        .output
+        .clone()
+        .unwrap_or_else(|| Optimize::results_file(Utc::now(), &optimize.isin));
+    write_file(&path, results_table(&optimize.ranges, &runs))?;
+
+    println!("\nTOP RUNS BY {}:", optimize.metric.to_string().to_uppercase());
+    for (rank, run) in runs.iter().take(TOP_RUNS).enumerate() {
+        println!(
+            "\t{}. {} ({} {})",
+            rank + 1, parameters_to_string(&run.parameters), optimize.metric, optional(run.score(optimize.metric))
+        );
+    }
+    info!("Saved the results in {}", path.display());
+    Ok(())
+}
+
+/// Back tests all combinations in parallel and returns the runs ranked by the metric
+fn sweep(exe: &Path, optimize: &Optimize, combinations: Vec<Vec<(String, ParamValue)>>, period: Period) -> Vec<Run> {
+    let total = combinations.len();
+    let shared = Arc::new((exe.to_path_buf(), optimize.clone()));
+    let queue = Arc::new(Mutex::new(combinations.into_iter().enumerate()));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = (0..optimize.jobs.min(total))
+        .map(|_| {
+            let (shared, queue, sender) = (shared.clone(), queue.clone(), sender.clone());
+            thread::spawn(move || loop {
+                let next = queue.lock().map(|mut queue| queue.next());
+                let (index, parameters) = match next {
+                    Ok(Some(next)) => next,
+                    _ => break
+                };
+                let (ref exe, ref optimize) = *shared;
+                let run = back_test(exe, optimize, &index.to_string(), parameters, period, false);
+                if sender.send(run).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut runs = Vec::with_capacity(total);
+    for run in receiver {
+        match run.error {
+            Some(ref err) => warn!("[{}/{}] {}: {}", runs.len() + 1, total, parameters_to_string(&run.parameters), err),
+            None => info!(
+                "[{}/{}] {}: {} {}",
+                runs.len() + 1, total, parameters_to_string(&run.parameters),
+                optimize.metric, optional(run.score(optimize.metric))
+            )
+        }
+        runs.push(run);
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    // the best score first, runs without a score last
+    runs.sort_by(|a, b| match (a.score(optimize.metric), b.score(optimize.metric)) {
+        (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    runs
+}
+
+impl Run {
+    fn score(&self, metric: Metric) -> Option<f64> {
+        self.report.as_ref().and_then(|report| metric.score(report))
+    }
+}
+
+/// runs a single back test in a child process and reads its report
+fn back_test(
+    exe: &Path,
+    optimize: &Optimize,
+    id: &str,
+    parameters: Vec<(String, ParamValue)>,
+    period: Period,
+    keep_journal: bool,
+) -> Run {
+    let file = |extension| env::temp_dir().join(format!("trading-desk-{}-{}.{}", process::id(), id, extension));
+    let (report_file, journal_file) = (file("json"), file("jsonl"));
+
+    let mut command = Command::new(exe);
+    command
+        .args(["start", "back", "--isin", &optimize.isin, "--algorithm", &optimize.algorithm])
+        .args(["--interval", &optimize.interval.to_string(), "--output", "none", "--report"])
+        .arg(&report_file);
+    if let Some(ref profile) = optimize.profile {
+        command.args(["--profile", profile]);
+    }
+    if let Some(from) = period.from {
+        command.args(["--from", &from.to_rfc3339()]);
+    }
+    if let Some(to) = period.to {
+        command.args(["--to", &to.to_rfc3339()]);
+    }
+    // a unique journal even if it is not kept, the default name of the children only differs by the second they started
+    command.arg("--journal").arg(&journal_file);
+    for (name, value) in parameters.iter() {
+        command.arg("--param").arg(format!("{}={}", name, value));
+    }
+
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(err) => return Run {
+            parameters,
+            report: None,
+            journal: Vec::new(),
+            error: Some(format!("could not start the back test ({})", err)),
+        }
+    };
+
+    // a back test stopped by the kill switch still writes its report
+    let report = fs::read_to_string(&report_file)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok());
+    let _ = fs::remove_file(&report_file);
+    let journal = if keep_journal {
+        Journal::read(&journal_file).unwrap_or_default()
+    } else { Vec::new() };
+    let _ = fs::remove_file(&journal_file);
+
+    let error = if output.status.success() {
+        None
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Some(stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or("the back test failed")
+            .trim()
+            .to_string())
+    };
+
+    Run { parameters, report, journal, error }
+}
+
+fn write_file(path: &Path, data: String) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("Could not create {} ({})", dir.display(), err))?;
+    }
+    fs::write(path, data).map_err(|err| format!("Could not write the results to {} ({})", path.display(), err))
+}
+
+fn results_table(ranges: &[ParamRange], runs: &[Run]) -> String {
+    let mut table = String::from("rank");
+    for range in ranges {
+        table.push_str(&format!(",{}", range.name));
+    }
+    table.push_str(",total_return,annualised_return,sharpe_ratio,sortino_ratio,max_drawdown,trades,win_rate,fees,error\n");
+
+    for (rank, run) in runs.iter().enumerate() {
+        table.push_str(&(rank + 1).to_string());
+        for (_, value) in run.parameters.iter() {
+            table.push_str(&format!(",{}", csv_field(&value.to_string())));
+        }
+        match run.report {
+            Some(ref report) => table.push_str(&format!(
+                ",{},{},{},{},{},{},{},{}",
+                report.total_return, optional(report.annualised_return), optional(report.sharpe_ratio),
+                optional(report.sortino_ratio), report.max_drawdown, report.trades, optional(report.win_rate),
+                report.fees
+            )),
+            None => table.push_str(",,,,,,,,"),
+        }
+        table.push_str(&format!(",{}\n", csv_field(run.error.as_deref().unwrap_or_default())));
+    }
+
+    table
+}
+
+fn parameters_to_string(parameters: &[(String, ParamValue)]) -> String {
+    let parameters: Vec<String> = parameters
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    parameters.join(" ")
+}
+
+fn optional(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/// keeps a field from breaking the columns of the table
+fn csv_field(field: &str) -> String {
+    field.replace(',', ";").replace('\n', " ")
+}
@@ -0,0 +1,215 @@
+use rand::seq::index;
+
+use crate::trading::params::{ParamKind, ParamSpec, ParamValue};
+
+/// the maximum number of combinations a grid search runs
+const MAX_GRID: usize = 100_000;
+
+/// The values a single parameter takes during the optimisation
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParamRange {
+    pub name: String,
+    pub values: Vec<ParamValue>,
+}
+
+impl ParamRange {
+    /// Parses `name=start:end:step` (integers and floats) or `name=value,value,...`
+    pub fn parse(arg: &str, schema: &[ParamSpec]) -> Result<Self, String> {
+        let mut split = arg.splitn(2, '=');
+        let (name, range) = match (split.next(), split.next()) {
+            (Some(name), Some(range)) => (name.trim(), range.trim()),
+            _ => return Err(format!("ranges have to be passed as name=start:end:step or name=a,b,c but got {}", arg))
+        };
+        let spec = schema
+            .iter()
+            .find(|spec| spec.name == name)
+            .ok_or_else(|| format!("unknown parameter {}", name))?;
+
+        let values = if range.contains(':') {
+            spec_steps(spec, range)?
+        } else {
+            range
+                .split(',')
+                .map(|value| spec.parse_value(value.trim()))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("invalid value for {} ({})", name, err))?
+        };
+
+        if values.is_empty() {
+            return Err(format!("the range of {} is empty", name));
+        }
+        Ok(Self { name: name.to_string(), values })
+    }
+}
+
+fn spec_steps(spec: &ParamSpec, range: &str) -> Result<Vec<ParamValue>, String> {
+    let bounds: Vec<&str> = range.split(':').map(str::trim).collect();
+    if bounds.len() != 3 {
+        return Err(format!("the range of {} needs to be start:end:step but is {}", spec.name, range));
+    }
+    let parse = |bound: &str| bound
+        .parse::<f64>()
+        .map_err(|_| format!("{} is not a valid number in the range of {}", bound, spec.name));
+    let (start, end, step) = (parse(bounds[0])?, parse(bounds[1])?, parse(bounds[2])?);
+    if step <= 0.0 || end < start {
+        return Err(format!("the range of {} needs start <= end and a positive step", spec.name));
+    }
+
+    // multiplying instead of adding up the steps keeps the floats from drifting
+    let steps = ((end - start) / step + 1e-9).floor();
+    if steps.is_nan() || steps >= MAX_GRID as f64 {
+        return Err(format!(
+            "the range of {} has more than {} values, please use a larger step", spec.name, MAX_GRID
+        ));
+    }
+    let steps = steps as usize;
+    (0..=steps)
+        .map(|step_index| {
+            let value = start + step_index as f64 * step;
+            let value = match spec.kind {
+                ParamKind::Integer if value.fract() == 0.0 => ParamValue::Integer(value as i64),
+                ParamKind::Integer => return Err(format!("{} is not a valid integer in the range of {}", value, spec.name)),
+                ParamKind::Float => ParamValue::Float(value),
+                _ => return Err(format!("{} can't be stepped through, list its values instead", spec.name))
+            };
+            spec.validate(&value).map_err(|err| format!("invalid value for {} ({})", spec.name, err))
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Search {
+    /// every combination of the ranges
+    Grid,
+    /// a number of distinct combinations picked at random
+    Random(usize),
+}
+
+impl Search {
+    pub fn combinations(&self, ranges: &[ParamRange]) -> Result<Vec<Vec<(String, ParamValue)>>, String> {
+        let total = ranges
+            .iter()
+            .try_fold(1usize, |total, range| total.checked_mul(range.values.len()))
+            .ok_or_else(|| String::from("there are too many combinations, please narrow down the ranges"))?;
+
+        let indices = match *self {
+            Search::Grid if total > MAX_GRID => return Err(format!(
+                "the grid has {} combinations (at most {} are allowed), please narrow down the ranges \
+                or use a random search", total, MAX_GRID
+            )),
+            Search::Grid => (0..total).collect(),
+            Search::Random(samples) => index::sample(&mut rand::thread_rng(), total, samples.min(total)).into_vec(),
+        };
+
+        Ok(indices.into_iter().map(|index| combination(ranges, index)).collect())
+    }
+}
+
+/// the combination at `index` when counting through the ranges like through the digits of a number
+fn combination(ranges: &[ParamRange], mut index: usize) -> Vec<(String, ParamValue)> {
+    let mut combination = Vec::with_capacity(ranges.len());
+    for range in ranges.iter().rev() {
+        let value = range.values[index % range.values.len()].clone();
+        index /= range.values.len();
+        combination.push((range.name.clone(), value));
+    }
+    combination.reverse();
+    combination
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn spec(name: &str, kind: ParamKind) -> ParamSpec {
+        let default = match kind {
+            ParamKind::Integer => ParamValue::Integer(0),
+            ParamKind::Float => ParamValue::Float(0.0),
+            ParamKind::Bool => ParamValue::Bool(false),
+            ParamKind::String => ParamValue::String(String::new()),
+        };
+        ParamSpec { name: name.to_string(), kind, default, min: None, max: None, description: String::new() }
+    }
+
+    fn range(name: &str, values: &[i64]) -> ParamRange {
+        ParamRange { name: name.to_string(), values: values.iter().map(|value| ParamValue::Integer(*value)).collect() }
+    }
+
+    fn integers<'a>(values: impl Iterator<Item = &'a ParamValue>) -> Vec<i64> {
+        values
+            .map(|value| match value {
+                ParamValue::Integer(value) => *value,
+                _ => panic!("{} is no integer", value),
+            })
+            .collect()
+    }
+
+    fn values(combination: &[(String, ParamValue)]) -> Vec<i64> {
+        integers(combination.iter().map(|(_, value)| value))
+    }
+
+    #[test]
+    fn combinations_count_through_the_ranges_like_digits() {
+        let ranges = vec![range("a", &[1, 2]), range("b", &[10, 20, 30])];
+        let grid: Vec<Vec<i64>> = (0..6).map(|index| values(&combination(&ranges, index))).collect();
+        assert_eq!(grid, vec![
+            vec![1, 10], vec![1, 20], vec![1, 30],
+            vec![2, 10], vec![2, 20], vec![2, 30],
+        ]);
+        let names: Vec<String> = combination(&ranges, 4).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["a", "b"]);
+
+        let all = Search::Grid.combinations(&ranges).unwrap();
+        assert_eq!(all.iter().map(|combination| values(combination)).collect::<Vec<_>>(), grid);
+    }
+
+    #[test]
+    fn steps_include_the_end_despite_rounding() {
+        let steps = ParamRange::parse("x=0.1:0.3:0.1", &[spec("x", ParamKind::Float)]).unwrap();
+        assert_eq!(steps.values.len(), 3);
+        assert_eq!(steps.values[2], ParamValue::Float(0.1 + 2.0 * 0.1));
+
+        let steps = ParamRange::parse("n=5:20:5", &[spec("n", ParamKind::Integer)]).unwrap();
+        assert_eq!(integers(steps.values.iter()), vec![5, 10, 15, 20]);
+        // the end is left out if the steps don't reach it
+        let steps = ParamRange::parse("n=5:19:5", &[spec("n", ParamKind::Integer)]).unwrap();
+        assert_eq!(steps.values.len(), 3);
+
+        assert!(ParamRange::parse("n=1:2:0.5", &[spec("n", ParamKind::Integer)]).is_err());
+        assert!(ParamRange::parse("n=2:1:1", &[spec("n", ParamKind::Integer)]).is_err());
+        assert!(ParamRange::parse("s=1:2:1", &[spec("s", ParamKind::Bool)]).is_err());
+    }
+
+    #[test]
+    fn ranges_with_too_many_steps_are_rejected_before_they_are_built() {
+        let schema = [spec("x", ParamKind::Float)];
+        assert_eq!(
+            ParamRange::parse("x=0:1e18:1", &schema),
+            Err(format!("the range of x has more than {} values, please use a larger step", MAX_GRID)),
+        );
+        assert!(ParamRange::parse("x=0:inf:1", &schema).is_err());
+        assert!(ParamRange::parse("x=0:1:1e-300", &schema).is_err());
+    }
+
+    #[test]
+    fn random_searches_sample_distinct_combinations() {
+        let ranges = vec![range("a", &[1, 2, 3, 4]), range("b", &[1, 2, 3, 4, 5])];
+        let samples = Search::Random(7).combinations(&ranges).unwrap();
+        assert_eq!(samples.len(), 7);
+        let distinct: HashSet<Vec<i64>> = samples.iter().map(|combination| values(combination)).collect();
+        assert_eq!(distinct.len(), 7);
+
+        // asking for more samples than there are combinations runs each once
+        assert_eq!(Search::Random(100).combinations(&ranges).unwrap().len(), 20);
+    }
+
+    #[test]
+    fn grids_that_are_too_large_are_rejected() {
+        let large: Vec<i64> = (0..1000).collect();
+        let ranges = vec![range("a", &large), range("b", &large)];
+        assert!(Search::Grid.combinations(&ranges).is_err());
+        assert_eq!(Search::Random(3).combinations(&ranges).unwrap().len(), 3);
+    }
+}
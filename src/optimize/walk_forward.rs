@@ -0,0 +1,331 @@
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+
+use crate::optimize::{back_test, csv_field, optional, Optimize, parameters_to_string, Period, Run, sweep, write_file};
+use crate::report::equity_curve;
+use crate::trading::feed::{self, RecordedFeed};
+use crate::trading::params::ParamValue;
+
+/// Rolling in-sample and out-of-sample windows
+///
+/// The parameters are optimised on every in-sample window and then back tested
+/// on the out-of-sample window that directly follows it. The windows move
+/// forward by the length of the out-of-sample window, so the out-of-sample
+/// windows follow each other without gaps and can be stitched together.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct WalkForward {
+    pub in_sample: Duration,
+    pub out_of_sample: Duration,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Window {
+    in_sample: Period,
+    out_of_sample: Period,
+}
+
+/// The best parameters of a window back tested on both of its periods
+struct Step {
+    window: Window,
+    in_sample: Run,
+    out_of_sample: Run,
+}
+
+impl WalkForward {
+    pub fn run(&self, exe: &Path, optimize: &Optimize, combinations: Vec<Vec<(String, ParamValue)>>) -> Result<(), String> {
+        let windows = self.windows(&optimize.isin)?;
+        info!(
+            "Walking forward with {} over {} windows with {} back tests each",
+            optimize.algorithm, windows.len(), combinations.len()
+        );
+
+        let mut steps = Vec::with_capacity(windows.len());
+        for (index, window) in windows.into_iter().enumerate() {
+            info!("Window {}: optimising on {}", index + 1, period_to_string(window.in_sample));
+            let runs = sweep(exe, optimize, combinations.clone(), window.in_sample);
+            let best = match runs.into_iter().find(|run| run.score(optimize.metric).is_some()) {
+                Some(best) => best.parameters,
+                None => {
+                    warn!("Window {}: no back test has a {} score, the window is skipped", index + 1, optimize.metric);
+                    continue;
+                }
+            };
+
+            // the best parameters run again to keep the journals for the equity curves
+            let in_sample = back_test(exe, optimize, &format!("{}-in", index), best.clone(), window.in_sample, true);
+            let out_of_sample = back_test(exe, optimize, &format!("{}-out", index), best, window.out_of_sample, true);
+            info!(
+                "Window {}: {} scored {} {} out of sample",
+                index + 1, parameters_to_string(&out_of_sample.parameters),
+                optimize.metric, optional(out_of_sample.score(optimize.metric))
+            );
+            steps.push(Step { window, in_sample, out_of_sample });
+        }
+        if steps.is_empty() {
+            return Err(String::from("None of the windows could be optimised"));
+        }
+
+        let path = optimize
+            .output
+            .clone()
+            .unwrap_or_else(|| {
+                let path = Optimize::results_file(Utc::now(), &optimize.isin);
+                path.with_file_name(format!("{}-walk-forward.csv", file_stem(&path)))
+            });
+        let equity_path = path.with_file_name(format!("{}-equity.csv", file_stem(&path)));
+
+        let in_sample = stitch(steps.iter().map(|step| &step.in_sample));
+        let out_of_sample = stitch(steps.iter().map(|step| &step.out_of_sample));
+        write_file(&path, steps_table(optimize, &steps))?;
+        write_file(&equity_path, equity_table(&in_sample, &out_of_sample))?;
+
+        print_summary(optimize, &steps, &in_sample, &out_of_sample);
+        info!("Saved the results in {} and the equity curves in {}", path.display(), equity_path.display());
+        Ok(())
+    }
+
+    /// the windows that fit into the recorded prices of the ISIN
+    fn windows(&self, isin: &str) -> Result<Vec<Window>, String> {
+        let path = RecordedFeed::price_file(isin);
+        let ticks = feed::read_ticks(&path)
+            .map_err(|err| format!("Could not read the prices from {} ({})", path.display(), err))?;
+        let mut times = ticks.iter().filter(|tick| tick.isin == isin).map(|tick| tick.time);
+        let first = times.next().ok_or_else(|| format!("There are no prices for {} in {}", isin, path.display()))?;
+        let last = times.next_back().unwrap_or(first);
+
+        let windows = self.windows_between(first, last);
+        if windows.is_empty() {
+            return Err(format!(
+                "The prices of {} only cover {} days, which is not enough for a {} day in-sample window",
+                isin, (last - first).num_days(), self.in_sample.num_days()
+            ));
+        }
+        Ok(windows)
+    }
+
+    /// the windows between the first and the last price
+    fn windows_between(&self, first: DateTime<Utc>, last: DateTime<Utc>) -> Vec<Window> {
+        let mut windows = Vec::new();
+        let mut start = first;
+        // every out-of-sample window needs at least one price
+        while start + self.in_sample < last {
+            let split = start + self.in_sample;
+            windows.push(Window {
+                in_sample: Period { from: Some(start), to: Some(split) },
+                out_of_sample: Period { from: Some(split), to: Some(split + self.out_of_sample) },
+            });
+            start += self.out_of_sample;
+        }
+        windows
+    }
+}
+
+fn print_summary(
+    optimize: &Optimize,
+    steps: &[Step],
+    in_sample: &[(DateTime<Utc>, f64)],
+    out_of_sample: &[(DateTime<Utc>, f64)],
+) {
+    let total_return = |curve: &[(DateTime<Utc>, f64)]| match (curve.first(), curve.last()) {
+        (Some(&(_, first)), Some(&(_, last))) if first > 0.0 => Some(last / first - 1.0),
+        _ => None
+    };
+    let percent = |value: Option<f64>| value.map(|value| format!("{:.2}%", value * 100.0)).unwrap_or_else(|| String::from("-"));
+    let run_return = |run: &Run| run.report.as_ref().map(|report| report.total_return);
+
+    println!("\nWALK FORWARD: {} (by {})", optimize.algorithm, optimize.metric);
+    for (index, step) in steps.iter().enumerate() {
+        println!(
+            "\t{}. {} | {}\n\t\tin sample: {} {} ({})\n\t\tout of sample: {} {} ({})",
+            index + 1, period_to_string(step.window.out_of_sample), parameters_to_string(&step.out_of_sample.parameters),
+            optimize.metric, optional(step.in_sample.score(optimize.metric)), percent(run_return(&step.in_sample)),
+            optimize.metric, optional(step.out_of_sample.score(optimize.metric)), percent(run_return(&step.out_of_sample)),
+        );
+    }
+
+    // the walk-forward efficiency compares the annualised returns out of and in sample
+    let annualised = |run: &Run| run.report.as_ref().and_then(|report| report.annualised_return);
+    let mean = |values: Vec<f64>| if values.is_empty() { None } else { Some(values.iter().sum::<f64>() / values.len() as f64) };
+    let efficiency = match (
+        mean(steps.iter().filter_map(|step| annualised(&step.in_sample)).collect()),
+        mean(steps.iter().filter_map(|step| annualised(&step.out_of_sample)).collect()),
+    ) {
+        (Some(in_sample), Some(out_of_sample)) if in_sample != 0.0 => format!("{:.2}", out_of_sample / in_sample),
+        _ => String::from("-")
+    };
+
+    println!(
+        "STITCHED RETURN:\n\
+        \tin sample: {}\n\
+        \tout of sample: {}\n\
+        \twalk-forward efficiency: {}",
+        percent(total_return(in_sample)), percent(total_return(out_of_sample)), efficiency
+    );
+}
+
+/// Chains the equity curves of the runs, every run continues with the equity the previous one ended with
+///
+/// The in-sample windows overlap if they are longer than the out-of-sample
+/// windows, so the stitched in-sample curve is only meaningful as a whole.
+fn stitch<'a>(runs: impl Iterator<Item=&'a Run>) -> Vec<(DateTime<Utc>, f64)> {
+    let mut stitched: Vec<(DateTime<Utc>, f64)> = Vec::new();
+
+    for run in runs {
+        let curve = equity_curve(&run.journal);
+        let first = match curve.first() {
+            Some(&(_, first)) if first > 0.0 => first,
+            _ => continue
+        };
+        let level = stitched.last().map(|&(_, equity)| equity).unwrap_or(first);
+        stitched.extend(curve.iter().map(|&(time, equity)| (time, equity / first * level)));
+    }
+
+    stitched
+}
+
+fn steps_table(optimize: &Optimize, steps: &[Step]) -> String {
+    let mut table = String::from("window,in_sample_from,in_sample_to,out_of_sample_from,out_of_sample_to");
+    for range in optimize.ranges.iter() {
+        table.push_str(&format!(",{}", range.name));
+    }
+    table.push_str(&format!(
+        ",in_sample_{metric},in_sample_return,out_of_sample_{metric},out_of_sample_return,error\n",
+        metric = optimize.metric
+    ));
+
+    let time = |time: Option<DateTime<Utc>>| time.map(|time| time.to_rfc3339()).unwrap_or_default();
+    let total_return = |run: &Run| optional(run.report.as_ref().map(|report| report.total_return));
+    for (index, step) in steps.iter().enumerate() {
+        let Window { in_sample, out_of_sample } = step.window;
+        table.push_str(&format!(
+            "{},{},{},{},{}",
+            index + 1, time(in_sample.from), time(in_sample.to), time(out_of_sample.from), time(out_of_sample.to)
+        ));
+        for (_, value) in step.out_of_sample.parameters.iter() {
+            table.push_str(&format!(",{}", csv_field(&value.to_string())));
+        }
+        let error = step.in_sample.error.as_ref().or(step.out_of_sample.error.as_ref());
+        table.push_str(&format!(
+            ",{},{},{},{},{}\n",
+            optional(step.in_sample.score(optimize.metric)), total_return(&step.in_sample),
+            optional(step.out_of_sample.score(optimize.metric)), total_return(&step.out_of_sample),
+            csv_field(error.map(String::as_str).unwrap_or_default())
+        ));
+    }
+
+    table
+}
+
+fn equity_table(in_sample: &[(DateTime<Utc>, f64)], out_of_sample: &[(DateTime<Utc>, f64)]) -> String {
+    let mut table = String::from("time,sample,equity\n");
+    for (sample, curve) in [("in", in_sample), ("out", out_of_sample)].iter() {
+        for (time, equity) in curve.iter() {
+            table.push_str(&format!("{},{},{}\n", time.to_rfc3339(), sample, equity));
+        }
+    }
+    table
+}
+
+fn period_to_string(period: Period) -> String {
+    let time = |time: Option<DateTime<Utc>>| time.map(|time| time.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default();
+    format!("{} - {}", time(period.from), time(period.to))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::trading::decimal::Decimal;
+    use crate::trading::journal::JournalEntry;
+    use crate::trading::order::{Fill, Side};
+    use crate::trading::TradingType;
+
+    use super::*;
+
+    fn day(day: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap() + Duration::days(day)
+    }
+
+    fn period(from: i64, to: i64) -> Period {
+        Period { from: Some(day(from)), to: Some(day(to)) }
+    }
+
+    fn walk_forward() -> WalkForward {
+        WalkForward { in_sample: Duration::days(10), out_of_sample: Duration::days(5) }
+    }
+
+    /// buys 10 at 100 on the first day and marks the position at `close` on the second
+    fn run(first_day: i64, close: f64) -> Run {
+        let journal = vec![
+            JournalEntry::Start {
+                time: day(first_day),
+                trading_type: TradingType::Back,
+                algorithm: String::from("algorithm"),
+                profile: String::from("default"),
+                capital: 1000.0,
+                currency: String::from("EUR"),
+                allocations: Default::default(),
+            },
+            JournalEntry::Fill(Fill {
+                order: 1,
+                time: day(first_day),
+                isin: String::from("DE0005140008"),
+                side: Side::Buy,
+                quantity: Decimal::from_f64(10.0),
+                price: Decimal::from_f64(100.0),
+                fee: Decimal::ZERO,
+                fx_rate: 1.0,
+            }),
+            JournalEntry::Mark {
+                time: day(first_day + 1),
+                isin: String::from("DE0005140008"),
+                price: Decimal::from_f64(close),
+                fx_rate: 1.0,
+            },
+        ];
+        Run { parameters: Vec::new(), report: None, journal, error: None }
+    }
+
+    #[test]
+    fn the_out_of_sample_windows_follow_the_in_sample_windows_without_gaps() {
+        let windows = walk_forward().windows_between(day(0), day(22));
+        assert_eq!(windows, vec![
+            Window { in_sample: period(0, 10), out_of_sample: period(10, 15) },
+            Window { in_sample: period(5, 15), out_of_sample: period(15, 20) },
+            Window { in_sample: period(10, 20), out_of_sample: period(20, 25) },
+        ]);
+        for pair in windows.windows(2) {
+            assert_eq!(pair[0].out_of_sample.to, pair[1].out_of_sample.from);
+        }
+    }
+
+    #[test]
+    fn every_out_of_sample_window_starts_before_the_last_price() {
+        // the third window would start with the last price and have none to test on
+        assert_eq!(walk_forward().windows_between(day(0), day(20)).len(), 2);
+        assert_eq!(walk_forward().windows_between(day(0), day(10)), Vec::new());
+        assert_eq!(walk_forward().windows_between(day(0), day(0)), Vec::new());
+    }
+
+    #[test]
+    fn stitching_continues_with_the_equity_of_the_previous_run() {
+        let runs = [
+            run(0, 110.0),
+            Run { parameters: Vec::new(), report: None, journal: Vec::new(), error: None },
+            run(5, 90.0),
+        ];
+        let stitched = stitch(runs.iter());
+        assert_eq!(stitched, vec![
+            (day(0), 1000.0),
+            (day(1), 1100.0),
+            (day(5), 1100.0),
+            (day(6), 990.0),
+        ]);
+    }
+}
@@ -0,0 +1,368 @@
+use std::env;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use algorithm_utils::Price;
+use chrono::{DateTime, Utc};
+
+use crate::trading::book::Book;
+use crate::trading::decimal::Decimal;
+use crate::trading::journal::JournalEntry;
+use crate::trading::order::{Fill, Side};
+
+const DEFAULT_SIZE: (usize, usize) = (100, 30);
+/// the width of the price labels on the left
+const LABEL_WIDTH: usize = 11;
+/// older prices are dropped to keep long sessions from eating up the memory
+const MAX_PRICES: usize = 100_000;
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChartStyle {
+    Line,
+    Candles,
+}
+
+impl FromStr for ChartStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "line" => Ok(ChartStyle::Line),
+            "candles" => Ok(ChartStyle::Candles),
+            _ => Err(format!("unknown chart style {}", value))
+        }
+    }
+}
+
+/// A price chart with buy and sell markers that is drawn into the terminal
+pub struct Chart {
+    style: ChartStyle,
+    prices: Vec<(DateTime<Utc>, Price)>,
+    fills: Vec<(DateTime<Utc>, Side)>,
+    position: Decimal,
+    pnl: Decimal,
+}
+
+/// the prices that are drawn into a single column
+struct Bucket {
+    end: DateTime<Utc>,
+    open: Price,
+    high: Price,
+    low: Price,
+    close: Price,
+}
+
+#[derive(Clone, Copy)]
+struct Cell {
+    symbol: char,
+    color: Option<&'static str>,
+}
+
+impl Chart {
+    pub fn new(style: ChartStyle) -> Self {
+        Self {
+            style,
+            prices: Vec::new(),
+            fills: Vec::new(),
+            position: Decimal::ZERO,
+            pnl: Decimal::ZERO,
+        }
+    }
+
+    /// Creates the chart of the first instrument in the journal
+    pub fn from_journal(entries: &[JournalEntry], style: ChartStyle) -> Self {
+        let mut chart = Self::new(style);
+        let isin = entries
+            .iter()
+            .find_map(|entry| match entry {
+                JournalEntry::Mark { isin, .. } => Some(isin.clone()),
+                JournalEntry::Fill(fill) => Some(fill.isin.clone()),
+                _ => None
+            });
+        let isin = match isin {
+            Some(isin) => isin,
+            None => return chart
+        };
+        let mut book = Book::new();
+        let mut pnl = Decimal::ZERO;
+
+        for entry in entries {
+            match entry {
+                JournalEntry::Mark { time, isin: mark_isin, price, fx_rate } if *mark_isin == isin => {
+                    book.update_rate(&isin, *fx_rate);
+                    book.update_price(&isin, *price);
+                    chart.push_price(*time, price.to_f64());
+                }
+                JournalEntry::Fill(fill) if fill.isin == isin => {
+                    pnl += book.apply_fill(fill) - fill.base_fee();
+                    chart.push_fill(fill);
+                }
+                _ => {}
+            }
+        }
+
+        chart.set_status(book.position(&isin), pnl + book.unrealised_pnl());
+        chart
+    }
+
+    pub fn push_price(&mut self, time: DateTime<Utc>, price: Price) {
+        self.prices.push((time, price));
+        if self.prices.len() > MAX_PRICES {
+            // drop a whole chunk, so the prices don't have to be moved on every push
+            self.prices.drain(..MAX_PRICES / 10);
+        }
+    }
+
+    pub fn push_fill(&mut self, fill: &Fill) {
+        self.fills.push((fill.time, fill.side));
+    }
+
+    /// sets the position and profit shown below the chart
+    pub fn set_status(&mut self, position: Decimal, pnl: Decimal) {
+        self.position = position;
+        self.pnl = pnl;
+    }
+
+    /// Clears the terminal and draws the chart in the size of the terminal
+    pub fn draw(&self) {
+        let (width, height) = terminal_size();
+        print!("{}{}", CLEAR_SCREEN, self.render(width, height));
+    }
+
+    /// Renders the chart including the axes and the status line
+    pub fn render(&self, width: usize, height: usize) -> String {
+        let plot_width = width.saturating_sub(LABEL_WIDTH).max(1);
+        let plot_height = height.saturating_sub(3).max(2);
+        let buckets = self.buckets(plot_width);
+        if buckets.is_empty() {
+            return format!("waiting for prices...\n{}\n", self.status_line());
+        }
+
+        let mut low = buckets.iter().map(|bucket| bucket.low).fold(f64::INFINITY, f64::min);
+        let mut high = buckets.iter().map(|bucket| bucket.high).fold(f64::NEG_INFINITY, f64::max);
+        if (high - low).abs() < f64::EPSILON {
+            low -= 1.0;
+            high += 1.0;
+        }
+        let row = |price: Price| (((high - price) / (high - low)) * (plot_height - 1) as f64).round() as usize;
+
+        let mut grid = vec![vec![Cell { symbol: ' ', color: None }; buckets.len()]; plot_height];
+        let mut previous_close = None;
+
+        for (column, bucket) in buckets.iter().enumerate() {
+            match self.style {
+                ChartStyle::Line => {
+                    let close = row(bucket.close);
+                    let from = previous_close.unwrap_or(close);
+                    for line in grid[close.min(from)..=close.max(from)].iter_mut() {
+                        line[column] = Cell { symbol: '│', color: None };
+                    }
+                    grid[close][column] = Cell { symbol: '•', color: None };
+                    previous_close = Some(close);
+                }
+                ChartStyle::Candles => {
+                    let color = if bucket.close >= bucket.open { GREEN } else { RED };
+                    for line in grid[row(bucket.high)..=row(bucket.low)].iter_mut() {
+                        line[column] = Cell { symbol: '│', color: Some(color) };
+                    }
+                    let (body_top, body_bottom) = (row(bucket.open.max(bucket.close)), row(bucket.open.min(bucket.close)));
+                    for line in grid[body_top..=body_bottom].iter_mut() {
+                        line[column] = Cell { symbol: '█', color: Some(color) };
+                    }
+                }
+            }
+        }
+
+        for &(time, side) in self.fills.iter() {
+            let column = match buckets.iter().position(|bucket| time <= bucket.end) {
+                Some(column) => column,
+                None => continue
+            };
+            let bucket = &buckets[column];
+            let (line, cell) = match side {
+                Side::Buy => ((row(bucket.low) + 1).min(plot_height - 1), Cell { symbol: '▲', color: Some(GREEN) }),
+                Side::Sell => (row(bucket.high).saturating_sub(1), Cell { symbol: '▼', color: Some(RED) }),
+            };
+            grid[line][column] = cell;
+        }
+
+        let mut chart = String::new();
+        for (line, cells) in grid.iter().enumerate() {
+            let label = if line == 0 || line == plot_height - 1 || line == plot_height / 2 {
+                let price = high - (high - low) * line as f64 / (plot_height - 1) as f64;
+                format!("{:>9.2} ┤", price)
+            } else {
+                format!("{:>9} │", "")
+            };
+            chart.push_str(&label);
+
+            for cell in cells {
+                match cell.color {
+                    Some(color) => { let _ = write!(chart, "{}{}{}", color, cell.symbol, RESET); }
+                    None => chart.push(cell.symbol),
+                }
+            }
+            chart.push('\n');
+        }
+
+        let start = self.prices[0].0.format("%Y-%m-%d %H:%M").to_string();
+        let end = self.prices[self.prices.len() - 1].0.format("%Y-%m-%d %H:%M").to_string();
+        let gap = buckets.len().saturating_sub(start.len() + end.len()).max(1);
+        let _ = writeln!(chart, "{:>width$}{}{}{}", "", start, " ".repeat(gap), end, width = LABEL_WIDTH);
+        chart.push_str(&self.status_line());
+        chart.push('\n');
+        chart
+    }
+
+    fn status_line(&self) -> String {
+        let color = if self.pnl >= 0.0 { GREEN } else { RED };
+        format!("POSITION: {}    PNL: {}{:.2}{}", self.position, color, self.pnl, RESET)
+    }
+
+    /// splits the prices into at most `columns` buckets of equal size
+    fn buckets(&self, columns: usize) -> Vec<Bucket> {
+        let columns = match self.style {
+            ChartStyle::Line => columns.min(self.prices.len()),
+            // candles only make sense if they consist of multiple prices
+            ChartStyle::Candles => columns.min((self.prices.len() / 2).max(1)).min(self.prices.len()),
+        };
+
+        (0..columns)
+            .map(|column| {
+                let from = column * self.prices.len() / columns;
+                let to = ((column + 1) * self.prices.len() / columns).max(from + 1);
+                let prices = &self.prices[from..to];
+
+                Bucket {
+                    end: prices[prices.len() - 1].0,
+                    open: prices[0].1,
+                    high: prices.iter().map(|&(_, price)| price).fold(f64::NEG_INFINITY, f64::max),
+                    low: prices.iter().map(|&(_, price)| price).fold(f64::INFINITY, f64::min),
+                    close: prices[prices.len() - 1].1,
+                }
+            })
+            .collect()
+    }
+}
+
+/// the size of the terminal as exported by the shell (COLUMNS and LINES)
+pub fn terminal_size() -> (usize, usize) {
+    let read = |name: &str, default: usize| {
+        env::var(name)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+
+    (read("COLUMNS", DEFAULT_SIZE.0), read("LINES", DEFAULT_SIZE.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn time(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 9, minute, 0).unwrap()
+    }
+
+    fn chart(style: ChartStyle, prices: &[Price]) -> Chart {
+        let mut chart = Chart::new(style);
+        for (minute, price) in prices.iter().enumerate() {
+            chart.push_price(time(minute as u32), *price);
+        }
+        chart
+    }
+
+    fn fill(minute: u32, side: Side) -> Fill {
+        Fill {
+            order: 1,
+            time: time(minute),
+            isin: String::from("DE0005140008"),
+            side,
+            quantity: Decimal::from_f64(1.0),
+            price: Decimal::from_f64(100.0),
+            fee: Decimal::ZERO,
+            fx_rate: 1.0,
+        }
+    }
+
+    fn plain(text: &str) -> String {
+        text.replace(GREEN, "").replace(RED, "").replace(RESET, "")
+    }
+
+    #[test]
+    fn candles_span_their_range_and_markers_sit_next_to_them() {
+        // four candles of three prices, the range of 100 to 104 is one price per row
+        let mut chart = chart(ChartStyle::Candles, &[
+            101.0, 104.0, 102.0,
+            102.0, 100.0, 101.0,
+            101.0, 101.0, 101.0,
+            103.0, 103.0, 100.0,
+        ]);
+        chart.push_fill(&fill(4, Side::Buy));
+        chart.push_fill(&fill(10, Side::Sell));
+        // fills after the last price have no candle
+        chart.push_fill(&fill(30, Side::Sell));
+
+        let rendered = chart.render(LABEL_WIDTH + 4, 8);
+        let lines: Vec<String> = rendered.lines().map(plain).collect();
+        assert_eq!(lines, vec![
+            "   104.00 ┤│  ▼",
+            "          ││  █",
+            "   102.00 ┤██ █",
+            "          │████",
+            "   100.00 ┤ ▲ █",
+            "           2024-01-02 09:00 2024-01-02 09:11",
+            "POSITION: 0    PNL: 0.00",
+        ]);
+
+        // rising candles are green and falling ones red
+        let row = rendered.lines().nth(2).unwrap();
+        let (green, red) = (format!("{}█{}", GREEN, RESET), format!("{}█{}", RED, RESET));
+        assert_eq!(row, format!("   102.00 ┤{}{} {}", green, red, red));
+        assert!(rendered.contains(&format!("{}▲{}", GREEN, RESET)));
+    }
+
+    #[test]
+    fn lines_connect_the_closes_of_the_columns() {
+        let chart = chart(ChartStyle::Line, &[100.0, 104.0, 102.0]);
+        let lines: Vec<String> = chart.render(LABEL_WIDTH + 10, 8).lines().map(plain).collect();
+        // there are fewer prices than columns, so every price gets its own
+        assert_eq!(&lines[..5], &[
+            "   104.00 ┤ •│",
+            "          │ ││",
+            "   102.00 ┤ │•",
+            "          │ │ ",
+            "   100.00 ┤•│ ",
+        ]);
+    }
+
+    #[test]
+    fn the_prices_are_bucketed_into_the_columns() {
+        let prices: Vec<Price> = (0..10).map(|price| price as f64).collect();
+        let lines: Vec<String> = chart(ChartStyle::Line, &prices).render(LABEL_WIDTH + 5, 6).lines().map(plain).collect();
+        // a height of 6 leaves three rows for the plot, all of them labelled
+        assert_eq!(lines.len(), 5);
+        assert!(lines[..3].iter().all(|line| line.chars().count() == LABEL_WIDTH + 5));
+        // two prices per column, the first closes at 1 and the last at 9
+        assert_eq!(&lines[..3], &[
+            "     9.00 ┤   ••",
+            "     4.50 ┤ ••│ ",
+            "     0.00 ┤•│   ",
+        ]);
+    }
+
+    #[test]
+    fn an_empty_chart_waits_for_prices() {
+        let mut chart = Chart::new(ChartStyle::Candles);
+        chart.set_status(Decimal::from_f64(3.0), Decimal::from_f64(-1.5));
+        assert_eq!(plain(&chart.render(80, 20)), "waiting for prices...\nPOSITION: 3    PNL: -1.50\n");
+    }
+}
@@ -0,0 +1,468 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+
+use crate::control::Command;
+use crate::output::{Chart, ChartStyle};
+use crate::output::event::SessionEvent;
+use crate::output::subscriber::Subscriber;
+use crate::trading::book::Book;
+use crate::trading::decimal::Decimal;
+use crate::trading::kill_switch::KillSwitchLimits;
+use crate::trading::order::{Order, OrderId};
+
+/// the number of lines kept for the journal and the algorithm log
+const MAX_LINES: usize = 200;
+/// how often the key bindings check whether the dashboard was closed
+const INPUT_POLL_MILLIS: u64 = 100;
+/// the risk status turns red once this share of a limit is used up
+const WARN_RATIO: f64 = 0.8;
+const KEY_BINDINGS: &str = "p pause   r resume   f flatten   q stop";
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+const CLEAR_LINE: &str = "\x1b[K";
+const CLEAR_BELOW: &str = "\x1b[J";
+
+/// The full output of real time sessions
+///
+/// The dashboard takes over the terminal and splits it into panes for the
+/// chart, the positions, the open orders, the tail of the journal, the
+/// algorithm log and the risk status. Everything it shows is built from the
+/// session events, the key bindings are sent back to the session as commands.
+pub struct Dashboard {
+    chart: Chart,
+    book: Book,
+    /// the remaining quantity of the orders that weren't filled or cancelled yet
+    orders: BTreeMap<OrderId, Order>,
+    journal: VecDeque<String>,
+    algorithm_log: VecDeque<String>,
+    risk: Option<Risk>,
+    isin: String,
+    /// the time of the last price
+    time: Option<DateTime<Utc>>,
+    paused: bool,
+    /// the last error or command
+    message: Option<String>,
+    errors: Vec<String>,
+    finished: Option<String>,
+    report: Option<String>,
+    /// tells the key bindings thread to stop
+    running: Arc<AtomicBool>,
+    input: Option<JoinHandle<()>>,
+    /// whether the terminal is still in raw mode and on the alternate screen
+    active: bool,
+}
+
+struct Risk {
+    daily_pnl: f64,
+    drawdown: f64,
+    exposure: f64,
+    limits: KillSwitchLimits,
+}
+
+impl Dashboard {
+    /// Switches the terminal to the dashboard and starts listening for the key bindings
+    pub fn start(style: ChartStyle, commands: Sender<Command>) -> Result<Self, String> {
+        terminal::enable_raw_mode().map_err(|err| err.to_string())?;
+        if let Err(err) = execute!(io::stdout(), EnterAlternateScreen, Hide) {
+            let _ = terminal::disable_raw_mode();
+            return Err(err.to_string());
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        let input = thread::spawn(move || read_keys(commands, flag));
+
+        Ok(Self {
+            chart: Chart::new(style),
+            book: Book::new(),
+            orders: BTreeMap::new(),
+            journal: VecDeque::new(),
+            algorithm_log: VecDeque::new(),
+            risk: None,
+            isin: String::new(),
+            time: None,
+            paused: false,
+            message: None,
+            errors: Vec::new(),
+            finished: None,
+            report: None,
+            running,
+            input: Some(input),
+            active: true,
+        })
+    }
+
+    fn draw(&self) {
+        let (width, height) = match terminal::size() {
+            Ok((width, height)) => (width as usize, height as usize),
+            Err(_) => return
+        };
+
+        let mut frame = self.render(width, height).join(&format!("{}\r\n", CLEAR_LINE));
+        frame.push_str(CLEAR_LINE);
+        frame.push_str(CLEAR_BELOW);
+
+        let mut stdout = io::stdout();
+        let _ = execute!(stdout, MoveTo(0, 0));
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    /// Renders the lines of the dashboard
+    ///
+    /// The header, the chart and the footer span the whole width, the other
+    /// panes are split into two columns.
+    fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let chart_height = (height * 2 / 5).max(6);
+        let pane_height = height.saturating_sub(chart_height + 4) / 2;
+        let left = width.saturating_sub(3) / 2;
+        let right = width.saturating_sub(3) - left;
+
+        let mut lines = vec![self.header()];
+        let chart = self.chart.render(width, chart_height);
+        lines.extend(chart.lines().map(String::from).chain(std::iter::repeat(String::new())).take(chart_height));
+
+        if pane_height >= 2 {
+            let positions = pane("POSITIONS", self.positions(), left, pane_height);
+            let orders = pane("OPEN ORDERS", self.orders.values().map(Order::to_string).collect(), right, pane_height);
+            lines.extend(side_by_side(positions, orders));
+
+            let journal = pane("JOURNAL", self.journal.iter().cloned().collect(), left, pane_height);
+            let algorithm_log = pane("ALGORITHM LOG", self.algorithm_log.iter().cloned().collect(), right, pane_height);
+            lines.extend(side_by_side(journal, algorithm_log));
+        }
+
+        lines.push(self.risk_status());
+        lines.push(fit(self.message.as_deref().unwrap_or_default(), width));
+        lines.push(fit(KEY_BINDINGS, width));
+        lines.truncate(height);
+        lines
+    }
+
+    fn header(&self) -> String {
+        let state = match (&self.finished, self.paused) {
+            (Some(_), _) => "FINISHED",
+            (None, true) => "PAUSED",
+            (None, false) => "RUNNING",
+        };
+        let time = self.time.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default();
+        format!("{}  {}  {}", self.isin, state, time)
+    }
+
+    fn positions(&self) -> Vec<String> {
+        self.book
+            .holdings()
+            .map(|(isin, holding)| {
+                let last = self.book.last_price(isin).unwrap_or(holding.average_price);
+                format!(
+                    "{} {} @ {:.2}  last {:.2}  pnl {:.2}",
+                    isin, holding.quantity, holding.average_price, last, (last - holding.average_price) * holding.quantity
+                )
+            })
+            .collect()
+    }
+
+    fn risk_status(&self) -> String {
+        let risk = match self.risk {
+            Some(ref risk) => risk,
+            None => return String::from("RISK: waiting for prices...")
+        };
+
+        // the daily pnl only uses up the maximum daily loss while it's negative
+        let daily_loss = format!("daily pnl {}", usage(risk.daily_pnl, -risk.daily_pnl, risk.limits.max_daily_loss));
+        let drawdown = format!("drawdown {}", usage(risk.drawdown, risk.drawdown, risk.limits.max_drawdown));
+        format!("RISK: {}   {}   exposure {:.2}", daily_loss, drawdown, risk.exposure)
+    }
+
+    fn push_journal(&mut self, line: String) {
+        let time = self.time.map(|time| time.format("%H:%M:%S").to_string()).unwrap_or_default();
+        push_line(&mut self.journal, format!("{} {}", time, line));
+    }
+
+    /// leaves the alternate screen and gives the terminal back to the shell
+    fn restore(&mut self) {
+        if !self.active {
+            return;
+        }
+        self.active = false;
+
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(input) = self.input.take() {
+            let _ = input.join();
+        }
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Subscriber for Dashboard {
+    fn on_event(&mut self, event: &SessionEvent) {
+        match event {
+            SessionEvent::Price { time, isin, price } => {
+                self.time = Some(*time);
+                self.isin = isin.clone();
+                self.chart.push_price(*time, *price);
+                self.book.update_price(isin, Decimal::from_f64(*price));
+            }
+            // these are followed by a price, so the dashboard isn't drawn twice
+            SessionEvent::Status { position, pnl } => {
+                self.chart.set_status(*position, *pnl);
+                return;
+            }
+            SessionEvent::Risk { daily_pnl, drawdown, exposure, limits } => {
+                self.risk = Some(Risk {
+                    daily_pnl: daily_pnl.to_f64(),
+                    drawdown: drawdown.to_f64(),
+                    exposure: exposure.to_f64(),
+                    limits: limits.clone(),
+                });
+                return;
+            }
+            SessionEvent::Order(order) => {
+                self.orders.insert(order.id, order.clone());
+                self.push_journal(format!("sent {}", order));
+            }
+            SessionEvent::Rejected { order, reason } => {
+                self.push_journal(format!("rejected {}: {}", order, reason));
+            }
+            SessionEvent::Fill(fill) => {
+                // the prices don't carry a rate, so the rate of the last fill is used
+                self.book.update_rate(&fill.isin, fill.fx_rate);
+                self.book.apply_fill(fill);
+                self.chart.push_fill(fill);
+                let filled = match self.orders.get_mut(&fill.order) {
+                    Some(order) => {
+                        order.quantity -= fill.quantity;
+                        !order.quantity.is_positive()
+                    }
+                    None => false
+                };
+                if filled {
+                    self.orders.remove(&fill.order);
+                }
+                self.push_journal(format!("filled {}", fill));
+            }
+            SessionEvent::Cancelled { order, reason } => {
+                self.orders.remove(&order.id);
+                self.push_journal(format!("cancelled {}: {}", order, reason));
+            }
+            SessionEvent::AlgorithmLog(message) => push_line(&mut self.algorithm_log, message.clone()),
+            SessionEvent::Command(command) => {
+                match command {
+                    Command::Pause => self.paused = true,
+                    Command::Resume => self.paused = false,
+                    Command::Flatten | Command::Stop => {}
+                }
+                self.message = Some(format!("executed {}", command));
+            }
+            SessionEvent::KillSwitch(reason) => {
+                self.message = Some(format!("kill switch fired: {}", reason));
+                self.errors.push(format!("Kill switch fired: {}", reason));
+            }
+            SessionEvent::Error(err) => {
+                self.message = Some(format!("error: {}", err));
+                self.errors.push(err.clone());
+            }
+            SessionEvent::Finished(msg) => self.finished = Some(msg.clone()),
+            SessionEvent::Report(report) => self.report = Some(report.to_string()),
+        }
+
+        self.draw();
+    }
+
+    /// Restores the terminal and prints what the text output would have printed
+    fn finish(&mut self) {
+        self.restore();
+
+        for err in self.errors.iter() {
+            eprintln!("Error: {}", err);
+        }
+        if let Some(ref msg) = self.finished {
+            println!("\n{}", msg);
+        }
+        if let Some(ref report) = self.report {
+            println!("\n{}", report);
+        }
+    }
+}
+
+impl Drop for Dashboard {
+    /// the terminal has to be restored even if the output thread panics
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// Sends the commands of the key bindings until the dashboard is closed
+fn read_keys(commands: Sender<Command>, running: Arc<AtomicBool>) {
+    while running.load(Ordering::Relaxed) {
+        match event::poll(Duration::from_millis(INPUT_POLL_MILLIS)) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(_) => return
+        }
+
+        let key = match event::read() {
+            Ok(Event::Key(key)) => key,
+            Ok(_) => continue,
+            Err(_) => return
+        };
+        let command = match key {
+            // raw mode swallows ctrl-c, so it's handled like q
+            KeyEvent { code: KeyCode::Char('c'), modifiers, .. } if modifiers.contains(KeyModifiers::CONTROL) => Command::Stop,
+            KeyEvent { code: KeyCode::Char('p'), .. } => Command::Pause,
+            KeyEvent { code: KeyCode::Char('r'), .. } => Command::Resume,
+            KeyEvent { code: KeyCode::Char('f'), .. } => Command::Flatten,
+            KeyEvent { code: KeyCode::Char('q'), .. } | KeyEvent { code: KeyCode::Esc, .. } => Command::Stop,
+            _ => continue
+        };
+
+        // the session already ended
+        if commands.send(command).is_err() {
+            return;
+        }
+    }
+}
+
+fn push_line(lines: &mut VecDeque<String>, line: String) {
+    lines.push_back(line);
+    if lines.len() > MAX_LINES {
+        lines.pop_front();
+    }
+}
+
+/// a value and the share of its limit that is used up (`used`), red once it gets close
+fn usage(value: f64, used: f64, limit: Option<f64>) -> String {
+    match limit {
+        Some(limit) if limit > 0.0 => {
+            let ratio = used.max(0.0) / limit;
+            let color = if ratio >= WARN_RATIO { RED } else { GREEN };
+            format!("{}{:.2} ({:.0}% of {}){}", color, value, ratio * 100.0, limit, RESET)
+        }
+        _ => format!("{:.2}", value)
+    }
+}
+
+/// A pane with a title line and the last lines that fit below it
+fn pane(title: &str, lines: Vec<String>, width: usize, height: usize) -> Vec<String> {
+    let mut pane = vec![fit(&format!("── {} {}", title, "─".repeat(width)), width)];
+    let skip = lines.len().saturating_sub(height - 1);
+    pane.extend(lines.iter().skip(skip).map(|line| fit(line, width)));
+    pane.resize(height, " ".repeat(width));
+    pane
+}
+
+fn side_by_side(left: Vec<String>, right: Vec<String>) -> Vec<String> {
+    left.into_iter()
+        .zip(right)
+        .map(|(left, right)| format!("{} │ {}", left, right))
+        .collect()
+}
+
+/// cuts or pads the text to exactly `width` characters
+fn fit(text: &str, width: usize) -> String {
+    let mut line: String = text.chars().take(width).collect();
+    let padding = width.saturating_sub(line.chars().count());
+    line.push_str(&" ".repeat(padding));
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a dashboard that never touched the terminal
+    fn dashboard() -> Dashboard {
+        Dashboard {
+            chart: Chart::new(ChartStyle::Line),
+            book: Book::new(),
+            orders: BTreeMap::new(),
+            journal: VecDeque::new(),
+            algorithm_log: VecDeque::new(),
+            risk: None,
+            isin: String::from("DE0005140008"),
+            time: None,
+            paused: false,
+            message: None,
+            errors: Vec::new(),
+            finished: None,
+            report: None,
+            running: Arc::new(AtomicBool::new(false)),
+            input: None,
+            active: false,
+        }
+    }
+
+    #[test]
+    fn lines_are_cut_or_padded_to_the_width() {
+        assert_eq!(fit("position", 5), "posit");
+        assert_eq!(fit("pnl", 5), "pnl  ");
+        assert_eq!(fit("▲ buy", 3), "▲ b");
+        assert_eq!(fit("", 2), "  ");
+    }
+
+    #[test]
+    fn panes_show_the_last_lines_below_their_title() {
+        let lines = vec![String::from("first"), String::from("second"), String::from("a third line")];
+        assert_eq!(pane("LOG", lines.clone(), 10, 3), vec!["── LOG ───", "second    ", "a third li"]);
+        // short panes are filled up with empty lines
+        assert_eq!(pane("LOG", lines[..1].to_vec(), 6, 3), vec!["── LOG", "first ", "      "]);
+        assert_eq!(pane("LOG", Vec::new(), 4, 1), vec!["── L"]);
+    }
+
+    #[test]
+    fn panes_are_joined_line_by_line() {
+        let left = pane("A", vec![String::from("left")], 6, 2);
+        let right = pane("B", vec![String::from("right"), String::from("more")], 4, 2);
+        assert_eq!(side_by_side(left, right), vec!["── A ─ │ ── B", "left   │ more"]);
+    }
+
+    #[test]
+    fn the_dashboard_fills_the_terminal() {
+        let mut dashboard = dashboard();
+        for line in 0..10 {
+            push_line(&mut dashboard.journal, format!("line {}", line));
+        }
+
+        let lines = dashboard.render(60, 20);
+        assert_eq!(lines.len(), 20);
+        assert_eq!(lines[0], "DE0005140008  RUNNING  ");
+        // the header and the chart take 9 lines, the panes 4 each
+        assert!(lines[9].starts_with("── POSITIONS ─"));
+        assert!(lines[13].starts_with("── JOURNAL ─"));
+        assert!(lines[13].contains("│ ── ALGORITHM LOG ─"));
+        assert!(lines[9..17].iter().all(|line| line.chars().count() == 60));
+        // the journal shows its last lines
+        assert!(lines[16].starts_with("line 9 "));
+        assert_eq!(lines[17], "RISK: waiting for prices...");
+        assert_eq!(lines[19].trim_end(), KEY_BINDINGS);
+    }
+
+    #[test]
+    fn small_terminals_drop_the_panes_first() {
+        let dashboard = dashboard();
+        let lines = dashboard.render(40, 10);
+        assert_eq!(lines.len(), 10);
+        assert!(lines.iter().all(|line| !line.starts_with("── ")));
+        assert_eq!(lines[9].trim_end(), KEY_BINDINGS);
+
+        // the footer is cut off once not even the chart fits
+        let lines = dashboard.render(40, 8);
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[7], "RISK: waiting for prices...");
+        assert_eq!(dashboard.render(20, 10)[9], "p pause   r resume  ");
+    }
+}
@@ -0,0 +1,60 @@
+use algorithm_utils::Price;
+use chrono::{DateTime, Utc};
+
+use crate::control::Command;
+use crate::report::PerformanceReport;
+use crate::trading::decimal::Decimal;
+use crate::trading::kill_switch::KillSwitchLimits;
+use crate::trading::order::{Fill, Order};
+
+/// Everything that happens during a session and might be of interest for the user
+#[derive(Debug, Clone)]
+pub enum SessionEvent {
+    Price {
+        time: DateTime<Utc>,
+        isin: String,
+        price: Price,
+    },
+    /// the position and profit after a price or a fill
+    Status {
+        position: Decimal,
+        pnl: Decimal,
+    },
+    /// the values the kill switch watches and its limits
+    Risk {
+        daily_pnl: Decimal,
+        drawdown: Decimal,
+        exposure: Decimal,
+        limits: KillSwitchLimits,
+    },
+    /// an order that passed the risk checks and was sent to the broker
+    Order(Order),
+    Rejected {
+        order: Order,
+        reason: String,
+    },
+    Fill(Fill),
+    /// an order that was cancelled before it was completely filled
+    Cancelled {
+        order: Order,
+        reason: String,
+    },
+    /// a message the algorithm runner wrote to the algorithm log
+    AlgorithmLog(String),
+    /// a command of the control API or the dashboard the session executed
+    Command(Command),
+    /// the kill switch fired and ended the session
+    KillSwitch(String),
+    /// an error that doesn't necessarily end the session
+    Error(String),
+    /// the session ended with the given message
+    Finished(String),
+    Report(PerformanceReport),
+}
+
+impl SessionEvent {
+    /// prices are sent on every tick, so they may be dropped if the output can't keep up
+    pub fn is_droppable(&self) -> bool {
+        matches!(self, SessionEvent::Price { .. } | SessionEvent::Status { .. } | SessionEvent::Risk { .. })
+    }
+}
@@ -0,0 +1,221 @@
+//! Everything that is displayed while or after trading
+//!
+//! Each output mode is a subscriber to the events of a session. The events
+//! are handed to a separate thread, so displaying them never blocks the
+//! session for long.
+//!
+//! The full output of real time sessions (and of back tests with `--dashboard`)
+//! is a dashboard that takes over the terminal and sends commands back to the
+//! session.
+
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+
+use log::warn;
+
+pub use chart::{Chart, ChartStyle, terminal_size};
+pub use event::SessionEvent;
+pub use subscriber::Subscriber;
+
+use dashboard::Dashboard;
+use subscriber::{ChartOutput, PriceOutput, TextOutput, TradeOutput};
+
+use crate::control::Command;
+use crate::notify::Notifier;
+
+mod chart;
+mod dashboard;
+mod event;
+mod subscriber;
+
+/// the number of events that may wait for the output before prices are dropped
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OutputMode {
+    Text,
+    Chart,
+    Full,
+    Trade,
+    Price,
+    None,
+}
+
+impl OutputMode {
+    /// Applies the precedence rules to the modes passed by the user
+    ///
+    /// full and none can't be specified together and override all other modes.
+    pub fn from_args<'a, I: IntoIterator<Item=&'a str>>(values: I) -> Result<Vec<Self>, String> {
+        let mut modes: Vec<Self> = Vec::new();
+        for value in values {
+            let mode: Self = value.parse()?;
+            if !modes.contains(&mode) {
+                modes.push(mode);
+            }
+        }
+
+        match (modes.contains(&OutputMode::Full), modes.contains(&OutputMode::None)) {
+            (true, true) => Err(String::from("full and none can't be specified together")),
+            (true, false) => Ok(vec![OutputMode::Full]),
+            (false, true) => Ok(vec![OutputMode::None]),
+            (false, false) => Ok(modes),
+        }
+    }
+}
+
+impl FromStr for OutputMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(OutputMode::Text),
+            "chart" => Ok(OutputMode::Chart),
+            "full" => Ok(OutputMode::Full),
+            "trade" => Ok(OutputMode::Trade),
+            "price" => Ok(OutputMode::Price),
+            "none" => Ok(OutputMode::None),
+            _ => Err(format!("unknown output {}", value))
+        }
+    }
+}
+
+/// Hands the session events to the subscribers of the selected output modes
+pub struct Output {
+    sender: Option<SyncSender<SessionEvent>>,
+    handle: Option<JoinHandle<()>>,
+    dropped: usize,
+}
+
+impl Output {
+    /// Starts the output thread
+    ///
+    /// `live` signals that the session runs in real time, so the chart can be redrawn on every price.
+    /// With `dashboard` the full output is shown as a dashboard that sends its key bindings as `commands`.
+    /// The notifier gets the events regardless of the output modes.
+    pub fn start(
+        modes: &[OutputMode], chart_style: ChartStyle, live: bool, dashboard: bool, commands: Sender<Command>,
+        notifier: Option<Notifier>,
+    ) -> Self {
+        let mut subscribers: Vec<Box<dyn Subscriber>> = Vec::new();
+        for mode in modes {
+            match mode {
+                OutputMode::Text => subscribers.push(Box::new(TextOutput)),
+                OutputMode::Trade => subscribers.push(Box::new(TradeOutput)),
+                OutputMode::Price => subscribers.push(Box::new(PriceOutput)),
+                OutputMode::Chart => subscribers.push(Box::new(ChartOutput::new(chart_style, live))),
+                OutputMode::Full if dashboard => match Dashboard::start(chart_style, commands.clone()) {
+                    Ok(dashboard) => subscribers.push(Box::new(dashboard)),
+                    Err(err) => {
+                        warn!("Could not start the dashboard, falling back to the full text output ({})", err);
+                        subscribers.extend(full_text_output());
+                    }
+                },
+                OutputMode::Full => subscribers.extend(full_text_output()),
+                OutputMode::None => {}
+            }
+        }
+        if let Some(notifier) = notifier {
+            subscribers.push(Box::new(notifier));
+        }
+
+        if subscribers.is_empty() {
+            return Self { sender: None, handle: None, dropped: 0 };
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let handle = thread::spawn(move || dispatch(receiver, subscribers));
+
+        Self {
+            sender: Some(sender),
+            handle: Some(handle),
+            dropped: 0,
+        }
+    }
+
+    /// Sends the event to the output thread
+    ///
+    /// Droppable events are discarded if the output can't keep up, all other
+    /// events wait for a free slot.
+    pub fn send(&mut self, event: SessionEvent) {
+        let sender = match self.sender {
+            Some(ref sender) => sender,
+            None => return
+        };
+
+        let result = if event.is_droppable() {
+            match sender.try_send(event) {
+                Err(TrySendError::Full(_)) => {
+                    self.dropped += 1;
+                    Ok(())
+                }
+                Err(TrySendError::Disconnected(event)) => Err(event),
+                Ok(()) => Ok(()),
+            }
+        } else {
+            sender.send(event).map_err(|err| err.0)
+        };
+
+        if result.is_err() {
+            // the output thread panicked, there is nobody left to display anything
+            self.sender = None;
+        }
+    }
+
+    /// Waits until all events were displayed
+    pub fn finish(mut self) {
+        if self.dropped > 0 {
+            let msg = format!("{} prices were not displayed since the output was too slow", self.dropped);
+            self.send(SessionEvent::Error(msg));
+        }
+
+        self.sender = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// the full output without the dashboard
+fn full_text_output() -> Vec<Box<dyn Subscriber>> {
+    vec![Box::new(TextOutput), Box::new(TradeOutput), Box::new(PriceOutput)]
+}
+
+fn dispatch(receiver: Receiver<SessionEvent>, mut subscribers: Vec<Box<dyn Subscriber>>) {
+    for event in receiver {
+        for subscriber in subscribers.iter_mut() {
+            subscriber.on_event(&event);
+        }
+    }
+
+    for subscriber in subscribers.iter_mut() {
+        subscriber.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_modes_are_parsed_in_order_without_duplicates() {
+        assert_eq!(
+            OutputMode::from_args(vec!["trade", "text", "trade", "chart"]),
+            Ok(vec![OutputMode::Trade, OutputMode::Text, OutputMode::Chart]),
+        );
+        assert_eq!(OutputMode::from_args(vec!["price"]), Ok(vec![OutputMode::Price]));
+        assert_eq!(OutputMode::from_args(vec!["fancy"]), Err(String::from("unknown output fancy")));
+    }
+
+    #[test]
+    fn full_and_none_override_all_other_modes() {
+        assert_eq!(OutputMode::from_args(vec!["text", "full", "chart"]), Ok(vec![OutputMode::Full]));
+        assert_eq!(OutputMode::from_args(vec!["none", "trade"]), Ok(vec![OutputMode::None]));
+        assert_eq!(
+            OutputMode::from_args(vec!["full", "none"]),
+            Err(String::from("full and none can't be specified together")),
+        );
+        // an unknown mode is reported even next to full
+        assert!(OutputMode::from_args(vec!["full", "fancy"]).is_err());
+    }
+}
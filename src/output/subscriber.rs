@@ -0,0 +1,87 @@
+use crate::output::{Chart, ChartStyle};
+use crate::output::event::SessionEvent;
+
+/// An output mode that displays some of the session events
+pub trait Subscriber: Send {
+    fn on_event(&mut self, event: &SessionEvent);
+
+    /// called once after the session ended and all events were delivered
+    fn finish(&mut self) {}
+}
+
+/// General information about the session like errors and the final report
+pub struct TextOutput;
+
+impl Subscriber for TextOutput {
+    fn on_event(&mut self, event: &SessionEvent) {
+        match event {
+            SessionEvent::Command(command) => println!("Received the command {}", command),
+            SessionEvent::KillSwitch(reason) => eprintln!("Kill switch fired: {}", reason),
+            SessionEvent::Error(err) => eprintln!("Error: {}", err),
+            SessionEvent::Finished(msg) => println!("\n{}", msg),
+            SessionEvent::Report(report) => println!("\n{}", report),
+            _ => {}
+        }
+    }
+}
+
+/// The orders sent to the broker and their fills
+pub struct TradeOutput;
+
+impl Subscriber for TradeOutput {
+    fn on_event(&mut self, event: &SessionEvent) {
+        match event {
+            SessionEvent::Order(order) => println!("Sent {}", order),
+            SessionEvent::Rejected { order, reason } => println!("Rejected {}: {}", order, reason),
+            SessionEvent::Fill(fill) => println!("Filled {}", fill),
+            SessionEvent::Cancelled { order, reason } => println!("Cancelled {}: {}", order, reason),
+            _ => {}
+        }
+    }
+}
+
+/// Every price the algorithm receives
+pub struct PriceOutput;
+
+impl Subscriber for PriceOutput {
+    fn on_event(&mut self, event: &SessionEvent) {
+        if let SessionEvent::Price { time, isin, price } = event {
+            println!("{} {} {}", time.format("%Y-%m-%d %H:%M:%S"), isin, price);
+        }
+    }
+}
+
+pub struct ChartOutput {
+    chart: Chart,
+    /// redraws the chart on every price instead of only once at the end
+    live: bool,
+}
+
+impl ChartOutput {
+    pub fn new(style: ChartStyle, live: bool) -> Self {
+        Self {
+            chart: Chart::new(style),
+            live,
+        }
+    }
+}
+
+impl Subscriber for ChartOutput {
+    fn on_event(&mut self, event: &SessionEvent) {
+        match event {
+            SessionEvent::Price { time, price, .. } => {
+                self.chart.push_price(*time, *price);
+                if self.live {
+                    self.chart.draw();
+                }
+            }
+            SessionEvent::Status { position, pnl } => self.chart.set_status(*position, *pnl),
+            SessionEvent::Fill(fill) => self.chart.push_fill(fill),
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self) {
+        self.chart.draw();
+    }
+}
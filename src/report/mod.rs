@@ -0,0 +1,9 @@
+//! Reports that are generated from the trade journal after a session
+
+pub use monte_carlo::{MonteCarlo, MonteCarloReport};
+pub use performance::{equity_curve, PerformanceReport};
+pub use tax::{LotHistory, TaxReport};
+
+mod monte_carlo;
+mod performance;
+mod tax;
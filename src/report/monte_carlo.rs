@@ -0,0 +1,415 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::Serialize;
+
+use crate::trading::book::Book;
+use crate::trading::journal::JournalEntry;
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Resampling {
+    /// the trades in a random order (the final return only changes with slippage)
+    Shuffle,
+    /// as many trades as the back test had, drawn with replacement
+    Bootstrap,
+}
+
+impl FromStr for Resampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shuffle" => Ok(Resampling::Shuffle),
+            "bootstrap" => Ok(Resampling::Bootstrap),
+            _ => Err(format!("Unknown resampling method {}", s))
+        }
+    }
+}
+
+impl fmt::Display for Resampling {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resampling::Shuffle => write!(formatter, "shuffle"),
+            Resampling::Bootstrap => write!(formatter, "bootstrap"),
+        }
+    }
+}
+
+/// How the trades of a journal are resampled
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MonteCarlo {
+    pub runs: usize,
+    pub resampling: Resampling,
+    /// the standard deviation of the slippage noise relative to the traded value
+    pub slippage: f64,
+    /// the width of the confidence intervals (like 0.95)
+    pub confidence: f64,
+    pub seed: Option<u64>,
+}
+
+/// The net result of a round trip
+///
+/// Fills that don't close a position are part of the trade that closes it.
+/// A position that is still open at the end of the journal counts as a trade
+/// with its unrealised profit.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+struct Trade {
+    pnl: f64,
+    traded_value: f64,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct Distribution {
+    pub mean: f64,
+    pub median: f64,
+    /// the bounds of the confidence interval
+    pub lower: f64,
+    pub upper: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    fn new(mut values: Vec<f64>, confidence: f64) -> Self {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let tail = (1.0 - confidence) / 2.0;
+
+        Self {
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            median: percentile(&values, 0.5),
+            lower: percentile(&values, tail),
+            upper: percentile(&values, 1.0 - tail),
+            min: values[0],
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// The distribution of the final return and the max drawdown over resampled trade sequences
+///
+/// The drawdowns are measured between trades, so they are usually smaller
+/// than the drawdown of the performance report, which also sees the prices
+/// in between.
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct MonteCarloReport {
+    pub algorithm: String,
+    pub runs: usize,
+    pub resampling: Resampling,
+    pub slippage: f64,
+    pub confidence: f64,
+    pub trades: usize,
+    /// the values of the back test itself
+    pub original_return: f64,
+    pub original_drawdown: f64,
+    pub total_return: Distribution,
+    pub max_drawdown: Distribution,
+    /// the fraction of the runs that lost money
+    pub probability_of_loss: f64,
+}
+
+impl MonteCarloReport {
+    pub fn from_journal(entries: &[JournalEntry], monte_carlo: &MonteCarlo) -> Result<Self, String> {
+        let (algorithm, capital) = entries
+            .iter()
+            .find_map(|entry| match entry {
+                JournalEntry::Start { algorithm, capital, .. } => Some((algorithm.clone(), *capital)),
+                _ => None
+            })
+            .ok_or_else(|| String::from("the journal has no start entry"))?;
+        if capital <= 0.0 {
+            return Err(format!("the capital needs to be positive but is {}", capital));
+        }
+        let trades = trades(entries);
+        if trades.is_empty() {
+            return Err(String::from("the journal has no trades"));
+        }
+        if monte_carlo.runs == 0 {
+            return Err(String::from("at least one run is needed"));
+        }
+
+        let mut rng = match monte_carlo.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let (original_return, original_drawdown) = simulate(capital, trades.iter().map(|trade| trade.pnl));
+
+        let mut returns = Vec::with_capacity(monte_carlo.runs);
+        let mut drawdowns = Vec::with_capacity(monte_carlo.runs);
+        let mut sample = trades.clone();
+        for _ in 0..monte_carlo.runs {
+            match monte_carlo.resampling {
+                Resampling::Shuffle => sample.shuffle(&mut rng),
+                Resampling::Bootstrap => {
+                    for trade in sample.iter_mut() {
+                        *trade = trades[rng.gen_range(0, trades.len())];
+                    }
+                }
+            }
+
+            // slippage only ever makes a fill worse
+            let pnl: Vec<f64> = sample
+                .iter()
+                .map(|trade| trade.pnl - trade.traded_value * monte_carlo.slippage * normal(&mut rng).abs())
+                .collect();
+            let (total_return, max_drawdown) = simulate(capital, pnl.into_iter());
+            returns.push(total_return);
+            drawdowns.push(max_drawdown);
+        }
+
+        let losses = returns.iter().filter(|total_return| **total_return < 0.0).count();
+        Ok(Self {
+            algorithm,
+            runs: monte_carlo.runs,
+            resampling: monte_carlo.resampling,
+            slippage: monte_carlo.slippage,
+            confidence: monte_carlo.confidence,
+            trades: trades.len(),
+            original_return,
+            original_drawdown,
+            total_return: Distribution::new(returns, monte_carlo.confidence),
+            max_drawdown: Distribution::new(drawdowns, monte_carlo.confidence),
+            probability_of_loss: losses as f64 / monte_carlo.runs as f64,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# Monte Carlo analysis of {}\n\n| metric | value |\n| --- | --- |\n", self.algorithm);
+        for (metric, value) in self.rows() {
+            markdown.push_str(&format!("| {} | {} |\n", metric, value));
+        }
+        markdown
+    }
+
+    /// the metrics as formatted (name, value) pairs
+    fn rows(&self) -> Vec<(String, String)> {
+        fn percent(value: f64) -> String { format!("{:.2}%", value * 100.0) }
+        let interval = format!("{:.0}% interval", self.confidence * 100.0);
+
+        let mut rows = vec![
+            (String::from("runs"), format!("{} ({})", self.runs, self.resampling)),
+            (String::from("slippage"), format!("{:.1} bps", self.slippage * 10_000.0)),
+            (String::from("trades"), self.trades.to_string()),
+            (String::from("probability of loss"), percent(self.probability_of_loss)),
+        ];
+        for (name, original, distribution) in [
+            ("return", self.original_return, &self.total_return),
+            ("max drawdown", self.original_drawdown, &self.max_drawdown),
+        ].iter() {
+            rows.push((format!("{} (back test)", name), percent(*original)));
+            rows.push((format!("{} mean", name), percent(distribution.mean)));
+            rows.push((format!("{} median", name), percent(distribution.median)));
+            rows.push((
+                format!("{} {}", name, interval),
+                format!("{} - {}", percent(distribution.lower), percent(distribution.upper))
+            ));
+            rows.push((
+                format!("{} range", name),
+                format!("{} - {}", percent(distribution.min), percent(distribution.max))
+            ));
+        }
+        rows
+    }
+}
+
+impl fmt::Display for MonteCarloReport {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "MONTE CARLO: {}", self.algorithm)?;
+        for (metric, value) in self.rows() {
+            write!(formatter, "\n\t{:<32}{}", format!("{}:", metric), value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replays the journal and splits it into round trips
+fn trades(entries: &[JournalEntry]) -> Vec<Trade> {
+    let mut book = Book::new();
+    let mut trades = Vec::new();
+    let mut current = Trade::default();
+
+    for entry in entries {
+        match entry {
+            JournalEntry::Start { .. } | JournalEntry::Attributed { .. } => {}
+            JournalEntry::Mark { isin, price, fx_rate, .. } => {
+                book.update_price(isin, *price);
+                book.update_rate(isin, *fx_rate);
+            }
+            JournalEntry::Fill(fill) => {
+                let closes = (book.position(&fill.isin) * fill.signed_quantity()).is_negative();
+                current.pnl += (book.apply_fill(fill) - fill.base_fee()).to_f64();
+                current.traded_value += fill.base_value().to_f64();
+                if closes {
+                    trades.push(current);
+                    current = Trade::default();
+                }
+            }
+        }
+    }
+
+    if current.traded_value > 0.0 {
+        current.pnl += book.unrealised_pnl().to_f64();
+        trades.push(current);
+    }
+    trades
+}
+
+/// the total return and the max drawdown of a sequence of trades
+fn simulate(capital: f64, pnl: impl Iterator<Item=f64>) -> (f64, f64) {
+    let mut equity = capital;
+    let mut peak = capital;
+    let mut max_drawdown: f64 = 0.0;
+
+    for pnl in pnl {
+        equity += pnl;
+        peak = peak.max(equity);
+        if peak > 0.0 {
+            max_drawdown = max_drawdown.max(1.0 - equity / peak);
+        }
+    }
+
+    (equity / capital - 1.0, max_drawdown)
+}
+
+/// a standard normal number (Box-Muller)
+fn normal<R: Rng>(rng: &mut R) -> f64 {
+    let u: f64 = 1.0 - rng.gen::<f64>();
+    let v: f64 = rng.gen();
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+/// the value at the fraction `p` of the sorted values (linearly interpolated)
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let position = p * (sorted.len() - 1) as f64;
+    let (lower, upper) = (position.floor() as usize, position.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use crate::trading::decimal::Decimal;
+    use crate::trading::order::{Fill, Side};
+    use crate::trading::TradingType;
+
+    use super::*;
+
+    fn time(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 9, minute, 0).unwrap()
+    }
+
+    fn start() -> JournalEntry {
+        JournalEntry::Start {
+            time: time(0),
+            trading_type: TradingType::Back,
+            algorithm: String::from("algorithm"),
+            profile: String::from("default"),
+            capital: 10_000.0,
+            currency: String::from("EUR"),
+            allocations: Default::default(),
+        }
+    }
+
+    fn fill(minute: u32, side: Side, quantity: f64, price: f64) -> JournalEntry {
+        JournalEntry::Fill(Fill {
+            order: minute as u64,
+            time: time(minute),
+            isin: String::from("DE0005140008"),
+            side,
+            quantity: Decimal::from_f64(quantity),
+            price: Decimal::from_f64(price),
+            fee: Decimal::from_f64(1.0),
+            fx_rate: 1.0,
+        })
+    }
+
+    fn mark(minute: u32, price: f64) -> JournalEntry {
+        JournalEntry::Mark { time: time(minute), isin: String::from("DE0005140008"), price: Decimal::from_f64(price), fx_rate: 1.0 }
+    }
+
+    /// a winner of 98, a loser of -63 and an open position that lost 51
+    fn journal() -> Vec<JournalEntry> {
+        vec![
+            start(),
+            fill(1, Side::Buy, 10.0, 100.0),
+            fill(2, Side::Sell, 10.0, 110.0),
+            fill(3, Side::Buy, 10.0, 100.0),
+            fill(4, Side::Buy, 10.0, 98.0),
+            fill(5, Side::Sell, 20.0, 96.0),
+            fill(6, Side::Buy, 5.0, 100.0),
+            mark(7, 90.0),
+        ]
+    }
+
+    fn monte_carlo(resampling: Resampling, slippage: f64, seed: u64) -> MonteCarlo {
+        MonteCarlo { runs: 200, resampling, slippage, confidence: 0.9, seed: Some(seed) }
+    }
+
+    #[test]
+    fn the_fills_are_split_into_round_trips() {
+        assert_eq!(trades(&journal()), vec![
+            // the fees of the opening and the closing fill are part of the trade
+            Trade { pnl: 98.0, traded_value: 2100.0 },
+            // adding to a position before closing it is one trade
+            Trade { pnl: -63.0, traded_value: 3900.0 },
+            // the open position counts with its unrealised loss
+            Trade { pnl: -51.0, traded_value: 500.0 },
+        ]);
+        assert!(trades(&[start(), mark(1, 100.0)]).is_empty());
+    }
+
+    #[test]
+    fn percentiles_interpolate_between_the_values() {
+        let sorted = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 0.5), 3.0);
+        assert_eq!(percentile(&sorted, 1.0), 5.0);
+        assert!((percentile(&sorted, 0.1) - 1.4).abs() < 1e-12);
+        assert_eq!(percentile(&[7.0], 0.95), 7.0);
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_are_reproducible() {
+        let journal = journal();
+        let report = |seed| MonteCarloReport::from_journal(&journal, &monte_carlo(Resampling::Bootstrap, 0.001, seed)).unwrap();
+        assert_eq!(report(7), report(7));
+        assert_ne!(report(7).total_return, report(8).total_return);
+
+        let report = report(7);
+        assert_eq!(report.trades, 3);
+        assert!((report.original_return - (98.0 - 63.0 - 51.0) / 10_000.0).abs() < 1e-12);
+        let total_return = &report.total_return;
+        assert!(total_return.min <= total_return.lower && total_return.lower <= total_return.median);
+        assert!(total_return.median <= total_return.upper && total_return.upper <= total_return.max);
+    }
+
+    #[test]
+    fn shuffling_without_slippage_keeps_the_final_return() {
+        let report = MonteCarloReport::from_journal(&journal(), &monte_carlo(Resampling::Shuffle, 0.0, 1)).unwrap();
+        assert!((report.total_return.min - report.original_return).abs() < 1e-12);
+        assert!((report.total_return.max - report.original_return).abs() < 1e-12);
+        // but the order of the trades changes the drawdowns
+        assert!(report.max_drawdown.min < report.max_drawdown.max);
+    }
+
+    #[test]
+    fn journals_without_trades_are_rejected() {
+        let monte_carlo = monte_carlo(Resampling::Bootstrap, 0.0, 1);
+        assert_eq!(
+            MonteCarloReport::from_journal(&[start()], &monte_carlo),
+            Err(String::from("the journal has no trades")),
+        );
+        assert_eq!(
+            MonteCarloReport::from_journal(&journal()[1..], &monte_carlo),
+            Err(String::from("the journal has no start entry")),
+        );
+    }
+}
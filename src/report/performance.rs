@@ -0,0 +1,516 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::trading::book::Book;
+use crate::trading::currency::DEFAULT_CURRENCY;
+use crate::trading::decimal::Decimal;
+use crate::trading::journal::JournalEntry;
+
+const SECONDS_PER_YEAR: f64 = 365.25 * 24.0 * 60.0 * 60.0;
+
+/// A summary of how an algorithm performed during a session or back test
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PerformanceReport {
+    pub algorithm: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// the base currency all amounts are in
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    pub capital: f64,
+    pub final_equity: f64,
+    pub total_return: f64,
+    /// None for sessions without any duration
+    pub annualised_return: Option<f64>,
+    pub sharpe_ratio: Option<f64>,
+    pub sortino_ratio: Option<f64>,
+    /// the largest relative drop of the equity from its peak
+    pub max_drawdown: f64,
+    /// the longest time in seconds the equity stayed below a previous peak
+    pub max_drawdown_duration: i64,
+    /// the number of fills that closed (a part of) a position
+    pub trades: usize,
+    pub win_rate: Option<f64>,
+    pub average_win: Option<f64>,
+    pub average_loss: Option<f64>,
+    /// the fraction of the time a position was held
+    pub exposure_time: f64,
+    /// the traded value relative to the capital
+    pub turnover: f64,
+    pub fees: f64,
+    /// the part of every algorithm of a session with several algorithms
+    #[serde(default)]
+    pub algorithms: Vec<AlgorithmReport>,
+}
+
+/// The profit and loss attributed to an algorithm of a session with several algorithms
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct AlgorithmReport {
+    pub algorithm: String,
+    pub capital: f64,
+    /// the realised and unrealised profit minus the fees
+    pub pnl: f64,
+    /// None without capital
+    pub total_return: Option<f64>,
+    pub trades: usize,
+    pub fees: f64,
+}
+
+/// Replays the attributed fills of an algorithm
+#[derive(Default)]
+struct SubBook {
+    book: Book,
+    realised: Decimal,
+    fees: Decimal,
+    trades: usize,
+}
+
+impl PerformanceReport {
+    /// Replays the journal and calculates the report
+    pub fn from_journal(entries: &[JournalEntry]) -> Result<Self, String> {
+        let (algorithm, capital, currency, allocations) = entries
+            .iter()
+            .find_map(|entry| match entry {
+                JournalEntry::Start { algorithm, capital, currency, allocations, .. } => {
+                    Some((algorithm.clone(), *capital, currency.clone(), allocations.clone()))
+                }
+                _ => None
+            })
+            .ok_or_else(|| String::from("the journal has no start entry"))?;
+        if capital <= 0.0 {
+            return Err(format!("the capital needs to be positive but is {}", capital));
+        }
+
+        let mut book = Book::new();
+        let mut realised = Decimal::ZERO;
+        let mut fees = Decimal::ZERO;
+        let mut traded_value = Decimal::ZERO;
+        let mut trades = Vec::new();
+        let mut exposed = Duration::zero();
+        let mut curve: Vec<(DateTime<Utc>, f64)> = Vec::new();
+        let mut sub_books: BTreeMap<String, SubBook> = BTreeMap::new();
+
+        for entry in entries {
+            // the time until this entry counts as exposed if a position was held before it
+            let was_exposed = book.gross_exposure().is_positive();
+            let time = match entry {
+                JournalEntry::Start { .. } => continue,
+                JournalEntry::Attributed { algorithm, fill } => {
+                    let sub_book = sub_books.entry(algorithm.clone()).or_default();
+                    let closes = (sub_book.book.position(&fill.isin) * fill.signed_quantity()).is_negative();
+                    // the sub-book only knows the prices since its first fill
+                    if let Some(price) = book.last_price(&fill.isin) {
+                        sub_book.book.update_price(&fill.isin, price);
+                        sub_book.book.update_rate(&fill.isin, book.rate(&fill.isin));
+                    }
+                    sub_book.realised += sub_book.book.apply_fill(fill);
+                    sub_book.fees += fill.base_fee();
+                    if closes {
+                        sub_book.trades += 1;
+                    }
+                    continue;
+                }
+                JournalEntry::Mark { time, isin, price, fx_rate } => {
+                    book.update_price(isin, *price);
+                    book.update_rate(isin, *fx_rate);
+                    for sub_book in sub_books.values_mut() {
+                        sub_book.book.update_price(isin, *price);
+                        sub_book.book.update_rate(isin, *fx_rate);
+                    }
+                    *time
+                }
+                JournalEntry::Fill(fill) => {
+                    let closes = (book.position(&fill.isin) * fill.signed_quantity()).is_negative();
+                    let pnl = book.apply_fill(fill);
+                    if closes {
+                        trades.push(pnl.to_f64());
+                    }
+                    realised += pnl;
+                    fees += fill.base_fee();
+                    traded_value += fill.base_value();
+                    fill.time
+                }
+            };
+
+            if let Some(&(previous, _)) = curve.last() {
+                if was_exposed {
+                    exposed += time - previous;
+                }
+            }
+            curve.push((time, capital + (realised - fees + book.unrealised_pnl()).to_f64()));
+        }
+
+        let (start, end) = match (curve.first(), curve.last()) {
+            (Some(&(start, _)), Some(&(end, _))) => (start, end),
+            _ => return Err(String::from("the journal is empty"))
+        };
+        let final_equity = curve.last().map(|&(_, equity)| equity).unwrap_or(capital);
+        let duration = (end - start).num_seconds() as f64;
+
+        let total_return = final_equity / capital - 1.0;
+        let annualised_return = if duration > 0.0 && final_equity > 0.0 {
+            Some((final_equity / capital).powf(SECONDS_PER_YEAR / duration) - 1.0)
+        } else { None };
+
+        let returns: Vec<f64> = curve
+            .windows(2)
+            .map(|window| window[1].1 / window[0].1 - 1.0)
+            .collect();
+        let periods_per_year = if duration > 0.0 && !returns.is_empty() {
+            SECONDS_PER_YEAR / (duration / returns.len() as f64)
+        } else { 0.0 };
+        let (sharpe_ratio, sortino_ratio) = ratios(&returns, periods_per_year);
+
+        let (max_drawdown, max_drawdown_duration) = drawdown(&curve);
+
+        // algorithms without any fill are part of the report as well
+        let mut names: Vec<&String> = allocations.keys().chain(sub_books.keys()).collect();
+        names.sort();
+        names.dedup();
+        let algorithms = names
+            .into_iter()
+            .map(|algorithm| {
+                let capital = allocations.get(algorithm).copied().unwrap_or(0.0);
+                let (pnl, trades, fees) = match sub_books.get(algorithm) {
+                    Some(sub_book) => (
+                        (sub_book.realised - sub_book.fees + sub_book.book.unrealised_pnl()).to_f64(),
+                        sub_book.trades,
+                        sub_book.fees.to_f64(),
+                    ),
+                    None => (0.0, 0, 0.0)
+                };
+                AlgorithmReport {
+                    algorithm: algorithm.clone(),
+                    capital,
+                    pnl,
+                    total_return: if capital > 0.0 { Some(pnl / capital) } else { None },
+                    trades,
+                    fees,
+                }
+            })
+            .collect();
+
+        let wins: Vec<f64> = trades.iter().copied().filter(|pnl| *pnl > 0.0).collect();
+        let losses: Vec<f64> = trades.iter().copied().filter(|pnl| *pnl <= 0.0).collect();
+
+        Ok(Self {
+            algorithm,
+            start,
+            end,
+            currency,
+            capital,
+            final_equity,
+            total_return,
+            annualised_return,
+            sharpe_ratio,
+            sortino_ratio,
+            max_drawdown,
+            max_drawdown_duration: max_drawdown_duration.num_seconds(),
+            trades: trades.len(),
+            win_rate: if trades.is_empty() { None } else { Some(wins.len() as f64 / trades.len() as f64) },
+            average_win: mean(&wins),
+            average_loss: mean(&losses),
+            exposure_time: if duration > 0.0 { exposed.num_seconds() as f64 / duration } else { 0.0 },
+            turnover: traded_value.to_f64() / capital,
+            fees: fees.to_f64(),
+            algorithms,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# Performance of {}\n\n| metric | value |\n| --- | --- |\n", self.algorithm);
+        for (metric, value) in self.rows() {
+            markdown.push_str(&format!("| {} | {} |\n", metric, value));
+        }
+        if !self.algorithms.is_empty() {
+            markdown.push_str("\n## Algorithms\n\n| algorithm | capital | pnl | return | trades | fees |\n| --- | --- | --- | --- | --- | --- |\n");
+            for algorithm in &self.algorithms {
+                markdown.push_str(&format!(
+                    "| {} | {:.2} | {:.2} | {} | {} | {:.2} |\n",
+                    algorithm.algorithm, algorithm.capital, algorithm.pnl, algorithm.formatted_return(), algorithm.trades,
+                    algorithm.fees
+                ));
+            }
+        }
+        markdown
+    }
+
+    /// the metrics as formatted (name, value) pairs
+    fn rows(&self) -> Vec<(&'static str, String)> {
+        fn percent(value: f64) -> String { format!("{:.2}%", value * 100.0) }
+        fn optional(value: Option<f64>, format: fn(f64) -> String) -> String {
+            value.map(format).unwrap_or_else(|| String::from("-"))
+        }
+        fn number(value: f64) -> String { format!("{:.2}", value) }
+
+        vec![
+            ("period", format!("{} - {}", self.start.format("%Y-%m-%d %H:%M"), self.end.format("%Y-%m-%d %H:%M"))),
+            ("capital", format!("{:.2} {}", self.capital, self.currency)),
+            ("final equity", number(self.final_equity)),
+            ("total return", percent(self.total_return)),
+            ("annualised return", optional(self.annualised_return, percent)),
+            ("sharpe ratio", optional(self.sharpe_ratio, number)),
+            ("sortino ratio", optional(self.sortino_ratio, number)),
+            ("max drawdown", percent(self.max_drawdown)),
+            ("max drawdown duration", format_duration(self.max_drawdown_duration)),
+            ("trades", self.trades.to_string()),
+            ("win rate", optional(self.win_rate, percent)),
+            ("average win", optional(self.average_win, number)),
+            ("average loss", optional(self.average_loss, number)),
+            ("exposure time", percent(self.exposure_time)),
+            ("turnover", number(self.turnover)),
+            ("fees", number(self.fees)),
+        ]
+    }
+}
+
+impl fmt::Display for PerformanceReport {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "PERFORMANCE: {}", self.algorithm)?;
+        for (metric, value) in self.rows() {
+            write!(formatter, "\n\t{:<24}{}", format!("{}:", metric), value)?;
+        }
+        if !self.algorithms.is_empty() {
+            write!(formatter, "\nALGORITHMS:")?;
+            for algorithm in &self.algorithms {
+                write!(
+                    formatter,
+                    "\n\t{:<24}{:.2} on {:.2} ({}, {} trades, fees {:.2})",
+                    format!("{}:", algorithm.algorithm), algorithm.pnl, algorithm.capital, algorithm.formatted_return(),
+                    algorithm.trades, algorithm.fees
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AlgorithmReport {
+    fn formatted_return(&self) -> String {
+        self.total_return
+            .map(|total_return| format!("{:.2}%", total_return * 100.0))
+            .unwrap_or_else(|| String::from("-"))
+    }
+}
+
+/// Replays the journal and returns the equity after every entry
+pub fn equity_curve(entries: &[JournalEntry]) -> Vec<(DateTime<Utc>, f64)> {
+    let mut capital = 0.0;
+    let mut book = Book::new();
+    let mut realised = Decimal::ZERO;
+    let mut curve = Vec::new();
+
+    for entry in entries {
+        let time = match entry {
+            JournalEntry::Start { capital: start_capital, .. } => {
+                capital = *start_capital;
+                continue;
+            }
+            JournalEntry::Attributed { .. } => continue,
+            JournalEntry::Mark { time, isin, price, fx_rate } => {
+                book.update_price(isin, *price);
+                book.update_rate(isin, *fx_rate);
+                *time
+            }
+            JournalEntry::Fill(fill) => {
+                realised += book.apply_fill(fill) - fill.base_fee();
+                fill.time
+            }
+        };
+        curve.push((time, capital + (realised + book.unrealised_pnl()).to_f64()));
+    }
+
+    curve
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+/// the annualised sharpe and sortino ratios (with a risk free rate of 0)
+fn ratios(returns: &[f64], periods_per_year: f64) -> (Option<f64>, Option<f64>) {
+    let average = match mean(returns) {
+        Some(average) if returns.len() > 1 => average,
+        _ => return (None, None)
+    };
+
+    let variance = returns
+        .iter()
+        .map(|r| (r - average).powi(2))
+        .sum::<f64>() / (returns.len() - 1) as f64;
+    let downside = returns
+        .iter()
+        .map(|r| r.min(0.0).powi(2))
+        .sum::<f64>() / returns.len() as f64;
+
+    let annualise = periods_per_year.sqrt();
+    let sharpe = if variance > 0.0 { Some(average / variance.sqrt() * annualise) } else { None };
+    let sortino = if downside > 0.0 { Some(average / downside.sqrt() * annualise) } else { None };
+
+    (sharpe, sortino)
+}
+
+/// the max relative drawdown and the longest time below a previous peak
+fn drawdown(curve: &[(DateTime<Utc>, f64)]) -> (f64, Duration) {
+    let mut max_drawdown = 0.0;
+    let mut max_duration = Duration::zero();
+    let mut peak = match curve.first() {
+        Some(&point) => point,
+        None => return (max_drawdown, max_duration)
+    };
+
+    for &(time, equity) in curve {
+        if equity >= peak.1 {
+            peak = (time, equity);
+            continue;
+        }
+
+        if peak.1 > 0.0 {
+            let drawdown = 1.0 - equity / peak.1;
+            if drawdown > max_drawdown {
+                max_drawdown = drawdown;
+            }
+        }
+        if time - peak.0 > max_duration {
+            max_duration = time - peak.0;
+        }
+    }
+
+    (max_drawdown, max_duration)
+}
+
+fn format_duration(seconds: i64) -> String {
+    let duration = Duration::seconds(seconds);
+    if duration.num_days() > 0 {
+        format!("{}d {}h", duration.num_days(), duration.num_hours() % 24)
+    } else {
+        format!("{}h {}m", duration.num_hours(), duration.num_minutes() % 60)
+    }
+}
+
+fn default_currency() -> String { String::from(DEFAULT_CURRENCY) }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::trading::order::{Fill, Side};
+    use crate::trading::TradingType;
+
+    use super::*;
+
+    fn time(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, hour, 0, 0).unwrap()
+    }
+
+    fn fill(hour: u32, side: Side, price: f64) -> JournalEntry {
+        JournalEntry::Fill(Fill {
+            order: hour as u64,
+            time: time(hour),
+            isin: String::from("DE0005140008"),
+            side,
+            quantity: Decimal::from_f64(10.0),
+            price: Decimal::from_f64(price),
+            fee: Decimal::ZERO,
+            fx_rate: 1.0,
+        })
+    }
+
+    fn mark(hour: u32, price: f64) -> JournalEntry {
+        JournalEntry::Mark { time: time(hour), isin: String::from("DE0005140008"), price: Decimal::from_f64(price), fx_rate: 1.0 }
+    }
+
+    /// a winner of 100 and a loser of 50, one entry per hour
+    ///
+    /// equity: 1000, 1000, 1100, 1100, 1100, 1050, 1050, 1050
+    fn journal() -> Vec<JournalEntry> {
+        vec![
+            JournalEntry::Start {
+                time: time(9),
+                trading_type: TradingType::Back,
+                algorithm: String::from("algorithm"),
+                profile: String::from("default"),
+                capital: 1000.0,
+                currency: String::from("EUR"),
+                allocations: BTreeMap::new(),
+            },
+            mark(9, 100.0),
+            fill(10, Side::Buy, 100.0),
+            mark(11, 110.0),
+            fill(12, Side::Sell, 110.0),
+            fill(13, Side::Buy, 110.0),
+            mark(14, 105.0),
+            fill(15, Side::Sell, 105.0),
+            mark(16, 105.0),
+        ]
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn the_report_of_a_hand_computed_journal() {
+        let report = PerformanceReport::from_journal(&journal()).unwrap();
+        assert_eq!((report.start, report.end), (time(9), time(16)));
+        assert_close(report.final_equity, 1050.0);
+        assert_close(report.total_return, 0.05);
+
+        // the hourly returns are 0, 1/10, 0, 0, -1/22, 0, 0 with a mean of 3/385
+        let periods_per_year: f64 = 365.25 * 24.0;
+        let downside = (1.0 / 22.0) / 7f64.sqrt();
+        assert_close(report.sortino_ratio.unwrap(), 3.0 / 385.0 / downside * periods_per_year.sqrt());
+        // the sample standard deviation of the returns is 0.0440475...
+        assert!((report.sharpe_ratio.unwrap() - 16.563043).abs() < 1e-6);
+
+        // from the peak of 1100 at 13:00 to 1050 until the end
+        assert_close(report.max_drawdown, 1.0 - 1050.0 / 1100.0);
+        assert_eq!(report.max_drawdown_duration, 3 * 60 * 60);
+
+        // the positions were held from 10:00 to 12:00 and from 13:00 to 15:00
+        assert_close(report.exposure_time, 4.0 / 7.0);
+        assert_eq!(report.trades, 2);
+        assert_eq!(report.win_rate, Some(0.5));
+        assert_eq!((report.average_win, report.average_loss), (Some(100.0), Some(-50.0)));
+        // 1000 + 1100 + 1100 + 1050 traded with a capital of 1000
+        assert_close(report.turnover, 4.25);
+        assert_eq!(report.fees, 0.0);
+        assert!(report.algorithms.is_empty());
+    }
+
+    #[test]
+    fn the_drawdown_lasts_until_the_equity_reaches_its_peak_again() {
+        let curve = [(time(9), 100.0), (time(10), 80.0), (time(11), 100.0), (time(12), 90.0), (time(13), 95.0), (time(15), 99.0)];
+        let (max_drawdown, duration) = drawdown(&curve);
+        assert_close(max_drawdown, 0.2);
+        // the second drawdown is shallower but lasts longer
+        assert_eq!(duration, Duration::hours(4));
+        assert_eq!(drawdown(&[]), (0.0, Duration::zero()));
+    }
+
+    #[test]
+    fn ratios_need_more_than_one_return_and_some_variation() {
+        assert_eq!(ratios(&[0.1], 252.0), (None, None));
+        assert_eq!(ratios(&[0.1, 0.1], 252.0), (None, None));
+        let (sharpe, sortino) = ratios(&[0.1, 0.2], 252.0);
+        assert!(sharpe.is_some());
+        assert_eq!(sortino, None);
+    }
+
+    #[test]
+    fn journals_without_entries_are_rejected() {
+        assert_eq!(PerformanceReport::from_journal(&journal()[..1]), Err(String::from("the journal is empty")));
+        assert_eq!(PerformanceReport::from_journal(&journal()[1..]), Err(String::from("the journal has no start entry")));
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fmt::Write;
+
+use chrono::{Datelike, DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+
+use crate::trading::decimal::Decimal;
+use crate::trading::order::{Fill, Side};
+
+/// the tax year and the dates of the report follow the local time of the tax office
+const TAX_TIMEZONE: Tz = chrono_tz::Europe::Berlin;
+
+/// A part of a position that was opened by a single fill
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct Lot {
+    pub isin: String,
+    pub opened: DateTime<Utc>,
+    /// positive for long and negative for short lots
+    pub quantity: Decimal,
+    /// in the currency of the instrument
+    pub price: Decimal,
+    /// the exchange rate to the base currency when the lot was opened
+    pub rate: f64,
+    /// the part of the opening fee that belongs to the remaining quantity (in the base currency)
+    pub fee: Decimal,
+}
+
+/// A lot (or a part of it) that was closed
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct Disposal {
+    pub isin: String,
+    pub opened: DateTime<Utc>,
+    pub closed: DateTime<Utc>,
+    pub quantity: Decimal,
+    pub short: bool,
+    pub open_price: Decimal,
+    pub close_price: Decimal,
+    /// the exchange rates to the base currency when the lot was opened and closed
+    pub open_rate: f64,
+    pub close_rate: f64,
+    /// the gain before fees in the base currency
+    pub gross_gain: Decimal,
+    /// the fees of opening and closing the lot in the base currency
+    pub fees: Decimal,
+}
+
+impl Disposal {
+    pub fn gain(&self) -> Decimal { self.gross_gain - self.fees }
+}
+
+/// Matches the fills against the open lots first in, first out
+///
+/// Both sides of a lot are converted to the base currency at the rate of their own fill.
+#[derive(Debug, Default)]
+pub struct LotLedger {
+    lots: BTreeMap<String, VecDeque<Lot>>,
+}
+
+impl LotLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Closes the oldest lots of the opposite side and opens a new lot with the rest of the fill
+    pub fn apply(&mut self, fill: &Fill) -> Vec<Disposal> {
+        let lots = self.lots.entry(fill.isin.clone()).or_default();
+        let sign = fill.side.sign();
+        let mut remaining = fill.quantity;
+        let mut disposals = Vec::new();
+
+        while remaining.is_positive() {
+            let lot = match lots.front_mut() {
+                // a lot of the opposite side
+                Some(lot) if (lot.quantity * sign).is_negative() => lot,
+                _ => break
+            };
+
+            let quantity = remaining.min(lot.quantity.abs());
+            let open_fee = lot.fee * quantity / lot.quantity.abs();
+            let short = lot.quantity.is_negative();
+            let opened = (lot.price * quantity).at_rate(lot.rate);
+            let closed = (fill.price * quantity).at_rate(fill.fx_rate);
+            let gross_gain = match fill.side {
+                Side::Sell => closed - opened,
+                Side::Buy => opened - closed,
+            };
+            disposals.push(Disposal {
+                isin: fill.isin.clone(),
+                opened: lot.opened,
+                closed: fill.time,
+                quantity,
+                short,
+                open_price: lot.price,
+                close_price: fill.price,
+                open_rate: lot.rate,
+                close_rate: fill.fx_rate,
+                gross_gain,
+                fees: open_fee + fill.base_fee() * quantity / fill.quantity,
+            });
+
+            lot.quantity += sign * quantity;
+            lot.fee -= open_fee;
+            remaining -= quantity;
+            if lot.quantity.is_zero() {
+                lots.pop_front();
+            }
+        }
+
+        if remaining.is_positive() {
+            lots.push_back(Lot {
+                isin: fill.isin.clone(),
+                opened: fill.time,
+                quantity: sign * remaining,
+                price: fill.price,
+                rate: fill.fx_rate,
+                fee: fill.base_fee() * remaining / fill.quantity,
+            });
+        }
+
+        disposals
+    }
+
+    pub fn open_lots(&self) -> impl Iterator<Item=&Lot> {
+        self.lots.values().flatten()
+    }
+
+    fn lots(&self, isin: &str) -> impl Iterator<Item=&Lot> {
+        self.lots.get(isin).into_iter().flatten()
+    }
+}
+
+/// The realised gains of a year for the tax return (in the base currency of the account)
+#[derive(Serialize, Debug, Clone)]
+pub struct TaxReport {
+    pub year: i32,
+    pub disposals: Vec<Disposal>,
+    /// the lots that are still open at the end of the year
+    pub open_lots: Vec<Lot>,
+    pub gross_gains: Decimal,
+    pub gross_losses: Decimal,
+    pub fees: Decimal,
+    /// the gains minus the losses and fees
+    pub net: Decimal,
+}
+
+impl TaxReport {
+    /// Matches all fills up to the end of the year, so lots of earlier years are closed correctly
+    pub fn from_fills(fills: &[Fill], year: i32) -> Self {
+        let mut fills: Vec<&Fill> = fills.iter().filter(|fill| tax_year(&fill.time) <= year).collect();
+        fills.sort_by_key(|fill| fill.time);
+
+        let mut ledger = LotLedger::new();
+        let disposals: Vec<Disposal> = fills
+            .into_iter()
+            .flat_map(|fill| ledger.apply(fill))
+            .filter(|disposal| tax_year(&disposal.closed) == year)
+            .collect();
+
+        let gross_gains = disposals.iter().map(|disposal| disposal.gross_gain.max(Decimal::ZERO)).sum();
+        let gross_losses = disposals.iter().map(|disposal| disposal.gross_gain.min(Decimal::ZERO)).sum();
+        let fees = disposals.iter().map(|disposal| disposal.fees).sum();
+
+        Self {
+            year,
+            open_lots: ledger.open_lots().cloned().collect(),
+            disposals,
+            gross_gains,
+            gross_losses,
+            fees,
+            net: gross_gains + gross_losses - fees,
+        }
+    }
+
+    /// One line per disposal for accounting
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "isin,opened,closed,side,quantity,open_price,close_price,open_rate,close_rate,gross_gain,fees,gain\n"
+        );
+        for disposal in self.disposals.iter() {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{:.2},{:.2},{:.2}",
+                disposal.isin, local_date(&disposal.opened), local_date(&disposal.closed),
+                if disposal.short { "short" } else { "long" }, disposal.quantity, disposal.open_price,
+                disposal.close_price, disposal.open_rate, disposal.close_rate, disposal.gross_gain, disposal.fees,
+                disposal.gain()
+            );
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl fmt::Display for TaxReport {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "TAX REPORT {}:\n\
+            \tdisposals: {}\n\
+            \tgross gains: {:.2}\n\
+            \tgross losses: {:.2}\n\
+            \tfees: {:.2}\n\
+            \tnet: {:.2}\n\
+            \topen lots at the end of the year: {}",
+            self.year, self.disposals.len(), self.gross_gains, self.gross_losses, self.fees, self.net,
+            self.open_lots.len()
+        )?;
+
+        let mut per_isin: BTreeMap<&str, (Decimal, Decimal)> = BTreeMap::new();
+        for disposal in self.disposals.iter() {
+            let (gain, fees) = per_isin.entry(&disposal.isin).or_default();
+            *gain += disposal.gross_gain;
+            *fees += disposal.fees;
+        }
+        for (isin, (gain, fees)) in per_isin {
+            write!(formatter, "\n\t{}: {:.2} (fees {:.2})", isin, gain - fees, fees)?;
+        }
+        Ok(())
+    }
+}
+
+fn tax_year(time: &DateTime<Utc>) -> i32 {
+    time.with_timezone(&TAX_TIMEZONE).year()
+}
+
+fn local_date(time: &DateTime<Utc>) -> String {
+    time.with_timezone(&TAX_TIMEZONE).format("%Y-%m-%d").to_string()
+}
+
+/// How the fills of an instrument opened and closed its lots
+pub struct LotHistory {
+    isin: String,
+    lines: Vec<String>,
+}
+
+impl LotHistory {
+    pub fn from_fills(fills: &[Fill], isin: &str) -> Self {
+        let mut fills: Vec<&Fill> = fills.iter().filter(|fill| fill.isin == isin).collect();
+        fills.sort_by_key(|fill| fill.time);
+
+        let mut ledger = LotLedger::new();
+        let mut lines = Vec::new();
+        for fill in fills {
+            lines.push(format!(
+                "{} {} {} @ {} (fee {})",
+                fill.time.format("%Y-%m-%d %H:%M:%S"), fill.side, fill.quantity, fill.price, fill.fee
+            ));
+            for disposal in ledger.apply(fill) {
+                lines.push(format!(
+                    "\tclosed {} of the lot from {} @ {}: {:.2} (fees {:.2})",
+                    disposal.quantity, disposal.opened.format("%Y-%m-%d"), disposal.open_price,
+                    disposal.gain(), disposal.fees
+                ));
+            }
+            let open: Vec<String> = ledger
+                .lots(isin)
+                .map(|lot| format!("{} @ {} from {}", lot.quantity, lot.price, lot.opened.format("%Y-%m-%d")))
+                .collect();
+            lines.push(format!("\topen lots: {}", if open.is_empty() { String::from("none") } else { open.join(", ") }));
+        }
+
+        Self { isin: isin.to_string(), lines }
+    }
+}
+
+impl fmt::Display for LotHistory {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "LOTS: {}", self.isin)?;
+        if self.lines.is_empty() {
+            write!(formatter, "\n\tno fills")?;
+        }
+        for line in self.lines.iter() {
+            write!(formatter, "\n{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn dec(value: f64) -> Decimal {
+        Decimal::from_f64(value)
+    }
+
+    fn fill(time: DateTime<Utc>, side: Side, quantity: f64, price: f64, fee: f64) -> Fill {
+        Fill {
+            order: 0,
+            time,
+            isin: String::from("DE0001"),
+            side,
+            quantity: dec(quantity),
+            price: dec(price),
+            fee: dec(fee),
+            fx_rate: 1.0,
+        }
+    }
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, day, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn closes_the_oldest_lots_first() {
+        let mut ledger = LotLedger::new();
+        assert!(ledger.apply(&fill(day(1), Side::Buy, 10.0, 100.0, 10.0)).is_empty());
+        assert!(ledger.apply(&fill(day(2), Side::Buy, 10.0, 110.0, 0.0)).is_empty());
+
+        let disposals = ledger.apply(&fill(day(3), Side::Sell, 15.0, 120.0, 15.0));
+        assert_eq!(disposals.len(), 2);
+        assert_eq!((disposals[0].opened, disposals[0].quantity), (day(1), dec(10.0)));
+        assert_eq!((disposals[0].gross_gain, disposals[0].fees), (dec(200.0), dec(20.0)));
+        assert_eq!((disposals[1].opened, disposals[1].quantity), (day(2), dec(5.0)));
+        assert_eq!((disposals[1].gross_gain, disposals[1].fees), (dec(50.0), dec(5.0)));
+
+        let open: Vec<&Lot> = ledger.open_lots().collect();
+        assert_eq!(open.len(), 1);
+        assert_eq!((open[0].opened, open[0].quantity, open[0].price), (day(2), dec(5.0), dec(110.0)));
+    }
+
+    #[test]
+    fn prorates_the_fees_of_partly_closed_lots() {
+        let mut ledger = LotLedger::new();
+        ledger.apply(&fill(day(1), Side::Buy, 10.0, 100.0, 10.0));
+
+        let first = ledger.apply(&fill(day(2), Side::Sell, 4.0, 100.0, 2.0));
+        assert_eq!((first[0].fees, first[0].gain()), (dec(6.0), dec(-6.0)));
+        assert_eq!(ledger.open_lots().next().unwrap().fee, dec(6.0));
+
+        let second = ledger.apply(&fill(day(3), Side::Sell, 6.0, 100.0, 3.0));
+        assert_eq!(second[0].fees, dec(9.0));
+        assert_eq!(ledger.open_lots().count(), 0);
+    }
+
+    #[test]
+    fn closes_short_lots_and_opens_the_rest() {
+        let mut ledger = LotLedger::new();
+        ledger.apply(&fill(day(1), Side::Sell, 10.0, 100.0, 0.0));
+        assert_eq!(ledger.open_lots().next().unwrap().quantity, dec(-10.0));
+
+        let covered = ledger.apply(&fill(day(2), Side::Buy, 4.0, 90.0, 0.0));
+        assert!(covered[0].short);
+        assert_eq!(covered[0].gross_gain, dec(40.0));
+
+        // the buy covers the rest of the short lot and opens a long one
+        let reversed = ledger.apply(&fill(day(3), Side::Buy, 10.0, 80.0, 0.0));
+        assert_eq!((reversed[0].quantity, reversed[0].gross_gain), (dec(6.0), dec(120.0)));
+        let open: Vec<&Lot> = ledger.open_lots().collect();
+        assert_eq!((open.len(), open[0].quantity, open[0].price), (1, dec(4.0), dec(80.0)));
+    }
+
+    #[test]
+    fn converts_each_side_at_its_own_rate() {
+        let mut ledger = LotLedger::new();
+        ledger.apply(&Fill { fx_rate: 0.5, ..fill(day(1), Side::Buy, 10.0, 100.0, 2.0) });
+        let disposals = ledger.apply(&Fill { fx_rate: 0.6, ..fill(day(2), Side::Sell, 10.0, 100.0, 2.0) });
+        assert_eq!(disposals[0].gross_gain, dec(100.0));
+        assert_eq!(disposals[0].fees, dec(2.2));
+    }
+
+    #[test]
+    fn the_tax_year_ends_in_the_time_of_berlin() {
+        // 2020-12-31 23:30 UTC is already 2021 in Berlin
+        let new_year = Utc.with_ymd_and_hms(2020, 12, 31, 23, 30, 0).unwrap();
+        let fills = vec![
+            fill(Utc.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap(), Side::Buy, 10.0, 100.0, 0.0),
+            fill(Utc.with_ymd_and_hms(2020, 9, 1, 12, 0, 0).unwrap(), Side::Sell, 4.0, 90.0, 1.0),
+            fill(new_year, Side::Sell, 6.0, 120.0, 0.0),
+            fill(Utc.with_ymd_and_hms(2022, 1, 3, 12, 0, 0).unwrap(), Side::Buy, 1.0, 100.0, 0.0),
+        ];
+
+        let report = TaxReport::from_fills(&fills, 2020);
+        assert_eq!(report.disposals.len(), 1);
+        assert_eq!((report.gross_gains, report.gross_losses, report.fees), (Decimal::ZERO, dec(-40.0), dec(1.0)));
+        assert_eq!(report.net, dec(-41.0));
+        assert_eq!(report.open_lots.len(), 1);
+        assert_eq!(report.open_lots[0].quantity, dec(6.0));
+
+        let report = TaxReport::from_fills(&fills, 2021);
+        assert_eq!(report.disposals.len(), 1);
+        assert_eq!((report.gross_gains, report.net), (dec(120.0), dec(120.0)));
+        assert!(report.open_lots.is_empty());
+        assert!(report.to_csv().contains(",2020-06-01,2021-01-01,long,6,"));
+    }
+}
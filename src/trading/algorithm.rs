@@ -0,0 +1,445 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use algorithm_utils::{AlgorithmInterface, Derivative, Instruction, LogLevel, Logger, Position, Price, WorkingOrder};
+use algorithm_utils::load::{Algorithm, Algorithms};
+use chrono::Duration;
+use log::{debug, error, info, warn};
+
+use crate::logging::ALGORITHM_TARGET;
+use crate::trading::bars::{Bar, BarAggregator, Input};
+use crate::trading::book::Book;
+use crate::trading::decimal::Decimal;
+use crate::trading::feed::Tick;
+use crate::trading::order::{Order, OrderType, Side, TimeInForce};
+use crate::trading::order_manager::TrackedOrder;
+use crate::trading::params::{Parameters, ParamSpec};
+
+/// Drives a loaded algorithm
+///
+/// The runner keeps the price window the algorithm works on and translates
+/// between the types of algorithm_utils and the runtime. Algorithms work with
+/// `f64` for fast math, the prices of their instructions are rounded to the
+/// tick size of the derivative before they become orders.
+///
+/// Algorithms that want bars are only called once a bar is completed. The
+/// price window is kept for them as well, since the session close and
+/// shutdown hooks receive the prices.
+pub struct AlgorithmRunner<'a> {
+    name: String,
+    algorithm: &'a mut Algorithm,
+    derivative: Derivative,
+    /// None if the prices aren't rounded
+    tick_size: Option<Decimal>,
+    prices: Vec<Price>,
+    /// None if the algorithm works on raw prices
+    aggregator: Option<BarAggregator>,
+    bars: Vec<Bar>,
+    /// the lines written to the algorithm log since the last `take_messages` (shared with the logger of the algorithm)
+    messages: Rc<RefCell<Vec<String>>>,
+}
+
+impl<'a> AlgorithmRunner<'a> {
+    pub fn new(
+        name: String, algorithm: &'a mut Algorithm, derivative: Derivative, tick_size: Option<Decimal>, input: Input,
+    ) -> Self {
+        let aggregator = match input {
+            Input::Prices => None,
+            Input::Bars(size) => Some(BarAggregator::new(size)),
+        };
+        let messages = Rc::new(RefCell::new(Vec::new()));
+        algorithm.set_logger(logger(name.clone(), messages.clone()));
+
+        Self {
+            name,
+            algorithm,
+            derivative,
+            tick_size,
+            prices: Vec::new(),
+            aggregator,
+            bars: Vec::new(),
+            messages,
+        }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+    pub fn isin(&self) -> &str { self.derivative.isin() }
+    pub fn prices(&self) -> &[Price] { &self.prices }
+    pub fn bars(&self) -> &[Bar] { &self.bars }
+
+    /// the lines written to the algorithm log since the last call, so they can be displayed
+    pub fn take_messages(&mut self) -> Vec<String> {
+        self.messages.take()
+    }
+
+    pub fn init(&mut self, interval: Duration, parameters: &Parameters) -> Result<(), String> {
+        self.info(format!("init with {} {}", self.derivative.isin(), parameters));
+        let result = parameters.to_toml().and_then(|parameters| {
+            self.algorithm
+                .init(&self.derivative, interval, &parameters)
+                .map_err(|err| err.msg().to_string())
+        });
+        self.log(result)
+    }
+
+    /// Restores the windows of a resumed session and replays them with `collect_prices` or `collect_bars`
+    ///
+    /// The window grows by one price (or bar) per call like it did while the
+    /// session was running, so algorithms that update their state with every
+    /// call end up where they were. The bar that was built when the process
+    /// died is lost, the next bar starts with the next tick.
+    pub fn resume(&mut self, prices: Vec<Price>, bars: Vec<Bar>) -> Result<(), String> {
+        self.info(format!("resume with {} prices and {} bars", prices.len(), bars.len()));
+        self.prices = prices;
+        self.bars = bars;
+        let result = if self.aggregator.is_some() {
+            let bars = self.bar_window();
+            (1..=bars.len()).try_for_each(|length| self.algorithm.collect_bars(&bars[..length]))
+        } else {
+            (1..=self.prices.len()).try_for_each(|length| self.algorithm.collect_prices(&self.prices[..length]))
+        };
+        let result = result.map_err(|err| err.msg().to_string());
+        self.log(result)
+    }
+
+    /// Adds the price of the tick to the window and returns whether the algorithm has new data
+    ///
+    /// Algorithms that work on bars only have new data once the tick completed a bar.
+    pub fn push_tick(&mut self, tick: &Tick) -> bool {
+        let max_data_length = self.algorithm.max_data_length();
+        push_limited(&mut self.prices, tick.price, max_data_length);
+
+        match self.aggregator {
+            Some(ref mut aggregator) => match aggregator.push(tick) {
+                Some(bar) => {
+                    push_limited(&mut self.bars, bar, max_data_length);
+                    true
+                }
+                None => false
+            },
+            None => true,
+        }
+    }
+
+    /// Calls `collect_prices` until the min data length is reached and `algorithm` afterwards
+    ///
+    /// Algorithms that work on bars get `collect_bars` and `algorithm_bars` instead.
+    pub fn step(&mut self, book: &Book, orders: &[&TrackedOrder]) -> Result<Vec<Order>, String> {
+        if self.aggregator.is_some() {
+            return self.step_bars(book, orders);
+        }
+
+        if self.prices.len() < self.algorithm.min_data_length() {
+            let result = self.algorithm
+                             .collect_prices(&self.prices)
+                             .map(|_| Vec::new())
+                             .map_err(|err| err.msg().to_string());
+            return self.log(result);
+        }
+
+        let positions = self.positions(book);
+        let working_orders = self.working_orders(orders);
+        let tick_size = self.tick_size;
+        let result = match self.algorithm.algorithm(&positions, &working_orders, &self.prices) {
+            Ok(instructions) => Ok(instructions.iter().map(|instruction| order(instruction, tick_size)).collect()),
+            Err(err) => Err(err.msg().to_string())
+        };
+        self.log_orders(result)
+    }
+
+    fn step_bars(&mut self, book: &Book, orders: &[&TrackedOrder]) -> Result<Vec<Order>, String> {
+        let bars = self.bar_window();
+        if self.bars.len() < self.algorithm.min_data_length() {
+            let result = self.algorithm
+                             .collect_bars(&bars)
+                             .map(|_| Vec::new())
+                             .map_err(|err| err.msg().to_string());
+            return self.log(result);
+        }
+
+        let positions = self.positions(book);
+        let working_orders = self.working_orders(orders);
+        let tick_size = self.tick_size;
+        let result = match self.algorithm.algorithm_bars(&positions, &working_orders, &bars) {
+            Ok(instructions) => Ok(instructions.iter().map(|instruction| order(instruction, tick_size)).collect()),
+            Err(err) => Err(err.msg().to_string())
+        };
+        self.log_orders(result)
+    }
+
+    /// Calls the session close hook, so the algorithm can act before the exchange closes
+    pub fn session_close(&mut self, book: &Book, orders: &[&TrackedOrder]) -> Result<Vec<Order>, String> {
+        self.info(String::from("session close"));
+        let positions = self.positions(book);
+        let working_orders = self.working_orders(orders);
+        let tick_size = self.tick_size;
+        let result = match self.algorithm.session_close(&positions, &working_orders, &self.prices) {
+            Ok(instructions) => Ok(instructions.iter().map(|instruction| order(instruction, tick_size)).collect()),
+            Err(err) => Err(err.msg().to_string())
+        };
+        self.log_orders(result)
+    }
+
+    pub fn shutdown(&mut self, book: &Book, orders: &[&TrackedOrder]) -> Result<Vec<Order>, String> {
+        self.info(String::from("shutdown"));
+        let positions = self.positions(book);
+        let working_orders = self.working_orders(orders);
+        let tick_size = self.tick_size;
+        let result = match self.algorithm.shutdown(&positions, &working_orders, &self.prices) {
+            Ok(instructions) => Ok(instructions.iter().map(|instruction| order(instruction, tick_size)).collect()),
+            Err(err) => Err(err.msg().to_string())
+        };
+        self.log_orders(result)
+    }
+
+    fn info(&mut self, message: String) {
+        info!(target: ALGORITHM_TARGET, "{}: {}", self.name, message);
+        self.messages.borrow_mut().push(message);
+    }
+
+    /// writes the errors of the algorithm to the algorithm log
+    fn log<T>(&mut self, result: Result<T, String>) -> Result<T, String> {
+        if let Err(ref err) = result {
+            error!(target: ALGORITHM_TARGET, "{}: {}", self.name, err);
+            self.messages.borrow_mut().push(format!("error: {}", err));
+        }
+        result
+    }
+
+    /// writes the instructions and errors of the algorithm to the algorithm log
+    fn log_orders(&mut self, result: Result<Vec<Order>, String>) -> Result<Vec<Order>, String> {
+        if let Ok(ref orders) = result {
+            for order in orders {
+                self.info(order.to_string());
+            }
+        }
+        self.log(result)
+    }
+
+    fn positions(&self, book: &Book) -> Vec<Position> {
+        book.holding(self.derivative.isin())
+            .map(|holding| Position::new(
+                self.derivative.clone(), holding.quantity.to_f64(), holding.average_price.to_f64(),
+            ))
+            .into_iter()
+            .collect()
+    }
+
+    /// the bar window in the types of algorithm_utils
+    fn bar_window(&self) -> Vec<algorithm_utils::Bar> {
+        self.bars
+            .iter()
+            .map(|bar| algorithm_utils::Bar::new(bar.start, bar.end, bar.open, bar.high, bar.low, bar.close, bar.volume))
+            .collect()
+    }
+
+    /// the open orders of the derivative, so the algorithm knows what is still pending
+    fn working_orders(&self, orders: &[&TrackedOrder]) -> Vec<WorkingOrder> {
+        orders.iter()
+              .filter(|tracked| tracked.order.isin == self.derivative.isin())
+              .map(|tracked| WorkingOrder::new(
+                  self.derivative.clone(),
+                  tracked.order.id,
+                  tracked.order.side == Side::Buy,
+                  tracked.remaining().to_f64(),
+                  tracked.filled.to_f64(),
+                  tracked.order.order_type.limit().map(Decimal::to_f64),
+              ))
+              .collect()
+    }
+}
+
+/// The logger the algorithm writes to the algorithm log with, the lines above debug are displayed as well
+fn logger(name: String, messages: Rc<RefCell<Vec<String>>>) -> Logger {
+    Logger::new(move |level, message| {
+        let displayed = match level {
+            LogLevel::Debug => {
+                debug!(target: ALGORITHM_TARGET, "{}: {}", name, message);
+                return;
+            }
+            LogLevel::Info => {
+                info!(target: ALGORITHM_TARGET, "{}: {}", name, message);
+                message.to_string()
+            }
+            LogLevel::Warn => {
+                warn!(target: ALGORITHM_TARGET, "{}: {}", name, message);
+                format!("warning: {}", message)
+            }
+            LogLevel::Error => {
+                error!(target: ALGORITHM_TARGET, "{}: {}", name, message);
+                format!("error: {}", message)
+            }
+        };
+        messages.borrow_mut().push(displayed);
+    })
+}
+
+/// Translates an instruction into an order (the id is assigned by the session)
+///
+/// The order type follows from the prices the instruction sets: a trailing
+/// distance makes a trailing stop, a stop a stop or stop-limit order and a
+/// limit alone a limit order. Limits are rounded to the tick that doesn't
+/// trade at a worse price, stops and distances to the nearest tick.
+fn order(instruction: &Instruction<'_>, tick_size: Option<Decimal>) -> Order {
+    let tick_size = tick_size.unwrap_or(Decimal::ZERO);
+    let is_buy = instruction.is_buy();
+    let nearest = |price: Price| Decimal::from_f64(price).round_to(tick_size);
+    let limit = instruction.limit().map(|limit| {
+        let limit = Decimal::from_f64(limit);
+        if is_buy { limit.floor_to(tick_size) } else { limit.ceil_to(tick_size) }
+    });
+
+    let order_type = match (instruction.trailing_distance().map(nearest), instruction.stop().map(nearest), limit) {
+        (Some(distance), _, _) => OrderType::TrailingStop { distance },
+        (None, Some(stop), Some(limit)) => OrderType::StopLimit { stop, limit },
+        (None, Some(stop), None) => OrderType::Stop { stop },
+        (None, None, Some(limit)) => OrderType::Limit { limit },
+        (None, None, None) => OrderType::Market,
+    };
+    let time_in_force = match instruction.time_in_force() {
+        algorithm_utils::TimeInForce::Day => TimeInForce::Day,
+        algorithm_utils::TimeInForce::Gtc => TimeInForce::Gtc,
+        algorithm_utils::TimeInForce::Gtd(date) => TimeInForce::Gtd(date),
+    };
+
+    Order {
+        id: 0,
+        isin: instruction.derivative().isin().to_string(),
+        side: if is_buy { Side::Buy } else { Side::Sell },
+        quantity: Decimal::from_f64(instruction.amount()),
+        order_type,
+        time_in_force,
+        oco: instruction.oco_group(),
+    }
+}
+
+/// Adds a value to a window and drops the oldest values beyond the max data length (0 for no limit)
+fn push_limited<T>(window: &mut Vec<T>, value: T, max_data_length: usize) {
+    window.push(value);
+    if max_data_length != 0 && window.len() > max_data_length {
+        let excess = window.len() - max_data_length;
+        window.drain(..excess);
+    }
+}
+
+/// The parameters the algorithm declares in its export metadata
+pub fn parameter_schema(algorithm: &Algorithm) -> Result<Vec<ParamSpec>, String> {
+    ParamSpec::parse_schema(algorithm.parameters())
+}
+
+/// The loaded algorithms with the names in the order of the names
+///
+/// Every algorithm has its own state, so a name may only be selected once.
+pub fn select<'a>(algorithms: &'a mut Algorithms, names: &[String]) -> Result<Vec<&'a mut Algorithm>, String> {
+    let mut selected: Vec<Option<&'a mut Algorithm>> = names.iter().map(|_| None).collect();
+    for (name, algorithm) in algorithms.iter_mut() {
+        if let Some(index) = names.iter().position(|selected| selected == name) {
+            selected[index] = Some(algorithm);
+        }
+    }
+
+    names.iter()
+         .zip(selected)
+         .map(|(name, algorithm)| algorithm.ok_or_else(|| format!("Could not find the algorithm {}", name)))
+         .collect()
+}
+
+/// Whether the algorithm wants raw prices or bars as declared in its export metadata
+pub fn input(algorithm: &Algorithm) -> Result<Input, String> {
+    Input::from_metadata(algorithm.parameters())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use algorithm_utils::error::{Error, TradingErrorKind};
+
+    use super::*;
+
+    /// remembers the length of every window it was called with and logs the windows of prices
+    struct Recording {
+        windows: Rc<RefCell<Vec<usize>>>,
+        logger: Logger,
+    }
+
+    impl AlgorithmInterface for Recording {
+        fn set_logger(&mut self, logger: Logger) {
+            self.logger = logger;
+        }
+
+        fn init(&mut self, _: &Derivative, _: Duration, _: &str) -> Result<(), Error<TradingErrorKind>> {
+            Ok(())
+        }
+
+        fn collect_prices(&mut self, prices: &[Price]) -> Result<(), Error<TradingErrorKind>> {
+            self.windows.borrow_mut().push(prices.len());
+            match prices.len() {
+                1 => self.logger.debug("first price"),
+                2 => self.logger.warn("second price"),
+                _ => self.logger.info(&format!("{} prices", prices.len())),
+            }
+            Ok(())
+        }
+
+        fn algorithm(&mut self, _: &[Position], _: &[WorkingOrder], _: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+            Ok(&[])
+        }
+
+        fn collect_bars(&mut self, bars: &[algorithm_utils::Bar]) -> Result<(), Error<TradingErrorKind>> {
+            self.windows.borrow_mut().push(bars.len());
+            Ok(())
+        }
+
+        fn shutdown(&mut self, _: &[Position], _: &[WorkingOrder], _: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+            Ok(&[])
+        }
+    }
+
+    fn recording() -> (Algorithm, Rc<RefCell<Vec<usize>>>) {
+        let windows = Rc::new(RefCell::new(Vec::new()));
+        let algorithm = Algorithm::new(
+            String::from("recording"), String::new(), 0, 0, String::new(), Box::new(Recording { windows: windows.clone(), logger: Logger::default() }),
+        );
+        (algorithm, windows)
+    }
+
+    #[test]
+    fn resuming_replays_the_prices_one_by_one() {
+        let (mut algorithm, windows) = recording();
+        let mut runner = AlgorithmRunner::new(
+            String::from("recording"), &mut algorithm, Derivative::new(String::from("DE0001")), None, Input::Prices,
+        );
+        runner.resume(vec![1.0, 2.0, 3.0], Vec::new()).unwrap();
+        assert_eq!(runner.prices(), &[1.0, 2.0, 3.0]);
+        assert_eq!(*windows.borrow(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn resuming_replays_the_bars_one_by_one() {
+        let (mut algorithm, windows) = recording();
+        let size = "1m".parse().unwrap();
+        let mut runner = AlgorithmRunner::new(
+            String::from("recording"), &mut algorithm, Derivative::new(String::from("DE0001")), None, Input::Bars(size),
+        );
+        let time = chrono::Utc::now();
+        let bar = Bar { start: time, end: time, open: 1.0, high: 1.0, low: 1.0, close: 1.0, volume: 1.0, ticks: 1 };
+        runner.resume(vec![1.0, 1.0], vec![bar.clone(), bar]).unwrap();
+        assert_eq!(*windows.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn the_log_of_the_algorithm_is_displayed_above_debug() {
+        let (mut algorithm, _) = recording();
+        let mut runner = AlgorithmRunner::new(
+            String::from("recording"), &mut algorithm, Derivative::new(String::from("DE0001")), None, Input::Prices,
+        );
+        runner.resume(vec![1.0, 2.0, 3.0], Vec::new()).unwrap();
+        assert_eq!(
+            runner.take_messages(),
+            vec!["resume with 3 prices and 0 bars", "warning: second price", "3 prices"],
+        );
+        assert!(runner.take_messages().is_empty());
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::trading::algorithm::AlgorithmRunner;
+use crate::trading::bars::Input;
+use crate::trading::book::Book;
+use crate::trading::decimal::Decimal;
+use crate::trading::order::{Fill, Order, OrderId, OrderType, Side};
+use crate::trading::params::Parameters;
+use crate::trading::pnl::PnlTracker;
+
+/// An algorithm of a session and the part of the capital it trades with
+#[derive(Debug, Clone)]
+pub struct Allocation {
+    pub algorithm: String,
+    pub parameters: Parameters,
+    pub input: Input,
+    /// the fraction of the capital of the profile (0 to 1)
+    pub share: f64,
+}
+
+/// Parses an algorithm like `name` or `name=0.4` into its name and share of the capital
+pub fn parse_share(value: &str) -> Result<(String, Option<f64>), String> {
+    let mut split = value.splitn(2, '=');
+    let name = split.next().unwrap_or_default().trim();
+    if name.is_empty() {
+        return Err(format!("{} has no algorithm name", value));
+    }
+
+    let share = match split.next() {
+        Some(share) => match share.trim().parse::<f64>() {
+            Ok(share) if share > 0.0 && share <= 1.0 => Some(share),
+            _ => return Err(format!("the share of {} needs to be a number above 0 and up to 1", name))
+        },
+        None => None
+    };
+    Ok((name.to_string(), share))
+}
+
+/// Gives the algorithms without a share equal parts of the capital that is left
+pub fn split_shares(shares: Vec<(String, Option<f64>)>) -> Result<Vec<(String, f64)>, String> {
+    let given: f64 = shares.iter().filter_map(|(_, share)| *share).sum();
+    // a little tolerance, so shares like 0.7 and 0.3 are fine
+    if given > 1.0 + 1e-9 {
+        return Err(format!("the shares of the algorithms add up to {} but may be at most 1", given));
+    }
+
+    let missing = shares.iter().filter(|(_, share)| share.is_none()).count();
+    if missing > 0 && given >= 1.0 {
+        return Err(String::from("no capital is left for the algorithms without a share"));
+    }
+    let rest = if missing == 0 { 0.0 } else { (1.0 - given) / missing as f64 };
+
+    Ok(shares.into_iter().map(|(name, share)| (name, share.unwrap_or(rest))).collect())
+}
+
+/// The part of a session that belongs to one algorithm
+///
+/// The sub-book holds the fills attributed to the algorithm. The algorithm
+/// only sees the positions of its sub-book, so the algorithms of a session
+/// don't get in each other's way.
+pub struct Account<'a> {
+    pub runner: AlgorithmRunner<'a>,
+    pub book: Book,
+    pub pnl: PnlTracker,
+    /// in the base currency
+    pub capital: f64,
+}
+
+impl<'a> Account<'a> {
+    pub fn new(runner: AlgorithmRunner<'a>, capital: f64) -> Self {
+        Self {
+            runner,
+            book: Book::new(),
+            pnl: PnlTracker::new(),
+            capital,
+        }
+    }
+
+    pub fn apply_fill(&mut self, fill: &Fill, time: DateTime<Utc>) {
+        let realised = self.book.apply_fill(fill);
+        self.pnl.record_fill(realised, fill.base_fee());
+        self.pnl.update(&self.book, time);
+    }
+
+    /// the market orders that close the positions of the sub-book
+    pub fn closing_orders(&self) -> Vec<Order> {
+        self.book
+            .holdings()
+            .map(|(isin, holding)| {
+                let side = if holding.quantity.is_positive() { Side::Sell } else { Side::Buy };
+                Order::market(isin.clone(), side, holding.quantity.abs())
+            })
+            .collect()
+    }
+}
+
+/// The orders of all algorithms after their market orders were netted
+#[derive(Debug, Default)]
+pub struct Netted {
+    /// the parts of the market orders that offset each other, they are filled internally at the last price
+    pub crossed: Vec<(usize, Order)>,
+    /// the orders for the broker with the quantities that belong to each account
+    pub orders: Vec<(Order, Vec<(usize, Decimal)>)>,
+}
+
+/// Nets the market orders of the accounts per ISIN
+///
+/// Buys and sells of different algorithms offset each other, only the rest
+/// is sent to the broker as a single market order. Other orders and market
+/// orders of a one-cancels-other group are sent as they are.
+pub fn net(orders: Vec<(usize, Order)>) -> Netted {
+    let mut netted = Netted::default();
+    let mut market: BTreeMap<String, Vec<(usize, Order)>> = BTreeMap::new();
+    for (account, order) in orders {
+        if order.order_type == OrderType::Market && order.oco.is_none() {
+            market.entry(order.isin.clone()).or_default().push((account, order));
+        } else {
+            let quantity = order.quantity;
+            netted.orders.push((order, vec![(account, quantity)]));
+        }
+    }
+
+    for (isin, orders) in market {
+        let total = |side: Side| -> Decimal {
+            orders.iter().filter(|(_, order)| order.side == side).map(|(_, order)| order.quantity).sum()
+        };
+        let crossing = total(Side::Buy).min(total(Side::Sell));
+        let (mut buys, mut sells) = (crossing, crossing);
+
+        let mut rest = Vec::new();
+        for (account, order) in orders {
+            let crossable = if order.side == Side::Buy { &mut buys } else { &mut sells };
+            let crossed = order.quantity.min(*crossable);
+            *crossable -= crossed;
+
+            if crossed.is_positive() {
+                netted.crossed.push((account, Order::market(isin.clone(), order.side, crossed)));
+            }
+            if order.quantity > crossed {
+                rest.push((account, order.side, order.quantity - crossed));
+            }
+        }
+
+        // only one side is left after the crossing
+        if let Some(&(_, side, _)) = rest.first() {
+            let quantity = rest.iter().map(|&(_, _, quantity)| quantity).sum();
+            let shares = rest.into_iter().map(|(account, _, quantity)| (account, quantity)).collect();
+            netted.orders.push((Order::market(isin, side, quantity), shares));
+        }
+    }
+
+    netted
+}
+
+/// Remembers which accounts the orders of the broker belong to
+///
+/// Orders it doesn't know (like the ones of a resumed checkpoint) belong to
+/// the account of a session with a single algorithm.
+pub struct Attribution {
+    accounts: usize,
+    /// the quantities of the order that are still to be filled per account
+    owners: BTreeMap<OrderId, Vec<(usize, Decimal)>>,
+}
+
+impl Attribution {
+    pub fn new(accounts: usize) -> Self {
+        Self {
+            accounts,
+            owners: BTreeMap::new(),
+        }
+    }
+
+    pub fn assign(&mut self, order: OrderId, shares: Vec<(usize, Decimal)>) {
+        self.owners.insert(order, shares);
+    }
+
+    /// Forgets an order that was cancelled or rejected
+    pub fn forget(&mut self, order: OrderId) {
+        self.owners.remove(&order);
+    }
+
+    pub fn owns(&self, order: OrderId, account: usize) -> bool {
+        match self.owners.get(&order) {
+            Some(shares) => shares.iter().any(|&(owner, _)| owner == account),
+            None => self.accounts == 1,
+        }
+    }
+
+    /// Splits a fill among the accounts of its order
+    ///
+    /// Every account gets a part of the fill by the quantity it still waits
+    /// for, the fee is split by the quantity.
+    pub fn split(&mut self, fill: &Fill) -> Vec<(usize, Fill)> {
+        let shares = match self.owners.get_mut(&fill.order) {
+            Some(shares) => shares,
+            None if self.accounts == 1 => return vec![(0, fill.clone())],
+            None => return Vec::new()
+        };
+
+        let mut open_total: Decimal = shares.iter().map(|&(_, open)| open).sum();
+        let mut left = fill.quantity.min(open_total);
+        let mut quantities = Vec::new();
+        for (account, open) in shares.iter_mut() {
+            if !open.is_positive() {
+                continue;
+            }
+            // the part by the share, but at least what the other accounts can't take (so rounding loses nothing)
+            let others = open_total - *open;
+            let quantity = (left * *open / open_total).max(left - others).min(*open).min(left);
+            open_total = others;
+            left -= quantity;
+            *open -= quantity;
+            if quantity.is_positive() {
+                quantities.push((*account, quantity));
+            }
+        }
+        shares.retain(|&(_, open)| open.is_positive());
+        if shares.is_empty() {
+            self.owners.remove(&fill.order);
+        }
+
+        let mut fee_left = fill.fee;
+        let last = quantities.len().saturating_sub(1);
+        quantities
+            .into_iter()
+            .enumerate()
+            .map(|(index, (account, quantity))| {
+                // the last part gets the rest of the fee, so nothing is lost to rounding
+                let fee = if index == last { fee_left } else { fill.fee * quantity / fill.quantity };
+                fee_left -= fee;
+                (account, Fill {
+                    quantity,
+                    fee,
+                    ..fill.clone()
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn dec(value: f64) -> Decimal {
+        Decimal::from_f64(value)
+    }
+
+    fn market(side: Side, quantity: f64) -> Order {
+        Order::market(String::from("DE0001"), side, dec(quantity))
+    }
+
+    fn fill(order: OrderId, quantity: f64, fee: f64) -> Fill {
+        Fill {
+            order,
+            time: Utc.with_ymd_and_hms(2021, 3, 1, 9, 0, 0).unwrap(),
+            isin: String::from("DE0001"),
+            side: Side::Buy,
+            quantity: dec(quantity),
+            price: dec(100.0),
+            fee: dec(fee),
+            fx_rate: 1.0,
+        }
+    }
+
+    /// the accounts, quantities and fees of the parts
+    fn parts(parts: Vec<(usize, Fill)>) -> Vec<(usize, Decimal, Decimal)> {
+        parts.into_iter().map(|(account, fill)| (account, fill.quantity, fill.fee)).collect()
+    }
+
+    #[test]
+    fn parses_shares() {
+        assert_eq!(parse_share("momentum"), Ok((String::from("momentum"), None)));
+        assert_eq!(parse_share(" momentum = 0.4 "), Ok((String::from("momentum"), Some(0.4))));
+        assert!(parse_share("=0.4").is_err());
+        assert!(parse_share("momentum=0").is_err());
+        assert!(parse_share("momentum=1.5").is_err());
+        assert!(parse_share("momentum=half").is_err());
+    }
+
+    #[test]
+    fn splits_the_rest_of_the_capital_equally() {
+        let shares = vec![(String::from("a"), Some(0.4)), (String::from("b"), None), (String::from("c"), None)];
+        assert_eq!(
+            split_shares(shares),
+            Ok(vec![(String::from("a"), 0.4), (String::from("b"), 0.3), (String::from("c"), 0.3)])
+        );
+        assert!(split_shares(vec![(String::from("a"), Some(0.7)), (String::from("b"), Some(0.3))]).is_ok());
+        assert!(split_shares(vec![(String::from("a"), Some(0.7)), (String::from("b"), Some(0.4))]).is_err());
+        assert!(split_shares(vec![(String::from("a"), Some(1.0)), (String::from("b"), None)]).is_err());
+    }
+
+    #[test]
+    fn nets_opposing_market_orders() {
+        let netted = net(vec![(0, market(Side::Buy, 10.0)), (1, market(Side::Sell, 4.0)), (2, market(Side::Buy, 2.0))]);
+
+        let crossed: Vec<(usize, Side, Decimal)> = netted
+            .crossed
+            .iter()
+            .map(|(account, order)| (*account, order.side, order.quantity))
+            .collect();
+        assert_eq!(crossed, vec![(0, Side::Buy, dec(4.0)), (1, Side::Sell, dec(4.0))]);
+
+        assert_eq!(netted.orders.len(), 1);
+        let (order, shares) = &netted.orders[0];
+        assert_eq!((order.side, order.quantity), (Side::Buy, dec(8.0)));
+        assert_eq!(shares, &vec![(0, dec(6.0)), (2, dec(2.0))]);
+    }
+
+    #[test]
+    fn sends_other_orders_as_they_are() {
+        let limit = Order::new(String::from("DE0001"), Side::Sell, dec(5.0), OrderType::Limit { limit: dec(101.0) });
+        let oco = Order { oco: Some(1), ..market(Side::Sell, 3.0) };
+        let netted = net(vec![(0, market(Side::Buy, 5.0)), (1, limit.clone()), (1, oco.clone())]);
+
+        assert!(netted.crossed.is_empty());
+        assert_eq!(netted.orders.len(), 3);
+        assert_eq!(netted.orders[0], (limit, vec![(1, dec(5.0))]));
+        assert_eq!(netted.orders[1], (oco, vec![(1, dec(3.0))]));
+        assert_eq!(netted.orders[2].1, vec![(0, dec(5.0))]);
+    }
+
+    #[test]
+    fn splits_partial_fills_by_the_open_quantities() {
+        let mut attribution = Attribution::new(2);
+        attribution.assign(1, vec![(0, dec(6.0)), (1, dec(2.0))]);
+
+        assert_eq!(parts(attribution.split(&fill(1, 4.0, 2.0))), vec![(0, dec(3.0), dec(1.5)), (1, dec(1.0), dec(0.5))]);
+        assert!(attribution.owns(1, 0) && attribution.owns(1, 1));
+        assert_eq!(parts(attribution.split(&fill(1, 4.0, 1.0))), vec![(0, dec(3.0), dec(0.75)), (1, dec(1.0), dec(0.25))]);
+        // the order is forgotten once it is filled
+        assert!(!attribution.owns(1, 0));
+        assert!(attribution.split(&fill(1, 1.0, 0.0)).is_empty());
+    }
+
+    #[test]
+    fn rounding_loses_no_quantity_or_fee() {
+        let mut attribution = Attribution::new(3);
+        attribution.assign(1, vec![(0, dec(1.0)), (1, dec(1.0)), (2, dec(1.0))]);
+
+        let split = parts(attribution.split(&fill(1, 1.0, 0.1)));
+        assert_eq!(split.iter().map(|&(_, quantity, _)| quantity).sum::<Decimal>(), dec(1.0));
+        assert_eq!(split.iter().map(|&(_, _, fee)| fee).sum::<Decimal>(), dec(0.1));
+
+        let rest = parts(attribution.split(&fill(1, 2.0, 0.0)));
+        assert_eq!(rest.iter().map(|&(_, quantity, _)| quantity).sum::<Decimal>(), dec(2.0));
+        assert!(!attribution.owns(1, 0));
+    }
+
+    #[test]
+    fn unknown_orders_belong_to_a_single_account() {
+        let mut single = Attribution::new(1);
+        assert!(single.owns(7, 0));
+        assert_eq!(parts(single.split(&fill(7, 2.0, 1.0))), vec![(0, dec(2.0), dec(1.0))]);
+
+        let mut several = Attribution::new(2);
+        assert!(!several.owns(7, 0));
+        assert!(several.split(&fill(7, 2.0, 1.0)).is_empty());
+    }
+}
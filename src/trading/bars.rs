@@ -0,0 +1,312 @@
+use std::fmt;
+use std::str::FromStr;
+
+use algorithm_utils::Price;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::trading::feed::Tick;
+
+/// How many ticks make up a bar
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum BarSize {
+    /// a bar per period of seconds, aligned to the start of the day (UTC)
+    Time(u64),
+    /// a bar per number of ticks
+    Ticks(u32),
+    /// a bar per traded volume
+    Volume(f64),
+}
+
+impl FromStr for BarSize {
+    type Err = String;
+
+    /// parses sizes like `30s`, `5m`, `1h` and `1d` (time), `100t` (ticks) or `5000v` (volume)
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{} is not a bar size like 5m, 100t or 5000v", value);
+        let value = value.trim();
+        if value.len() < 2 || !value.is_ascii() {
+            return Err(invalid());
+        }
+        let (number, unit) = value.split_at(value.len() - 1);
+
+        let size = match unit {
+            "s" | "m" | "h" | "d" => {
+                let number: u64 = number.parse().map_err(|_| invalid())?;
+                let factor = match unit {
+                    "s" => 1,
+                    "m" => 60,
+                    "h" => 60 * 60,
+                    _ => 24 * 60 * 60,
+                };
+                // the periods are computed on timestamps
+                let seconds = number
+                    .checked_mul(factor)
+                    .filter(|seconds| *seconds <= i64::MAX as u64)
+                    .ok_or_else(|| format!("{} is too long for a bar", value))?;
+                BarSize::Time(seconds)
+            }
+            "t" => BarSize::Ticks(number.parse().map_err(|_| invalid())?),
+            "v" => BarSize::Volume(number.parse().map_err(|_| invalid())?),
+            _ => return Err(invalid())
+        };
+
+        match size {
+            BarSize::Time(0) | BarSize::Ticks(0) => Err(format!("{} is an empty bar size", value)),
+            BarSize::Volume(volume) if volume.is_nan() || volume <= 0.0 => Err(format!("{} is an empty bar size", value)),
+            size => Ok(size)
+        }
+    }
+}
+
+impl fmt::Display for BarSize {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BarSize::Time(seconds) if seconds % (24 * 60 * 60) == 0 => write!(formatter, "{}d", seconds / (24 * 60 * 60)),
+            BarSize::Time(seconds) if seconds % (60 * 60) == 0 => write!(formatter, "{}h", seconds / (60 * 60)),
+            BarSize::Time(seconds) if seconds % 60 == 0 => write!(formatter, "{}m", seconds / 60),
+            BarSize::Time(seconds) => write!(formatter, "{}s", seconds),
+            BarSize::Ticks(ticks) => write!(formatter, "{}t", ticks),
+            BarSize::Volume(volume) => write!(formatter, "{}v", volume),
+        }
+    }
+}
+
+/// What an algorithm wants to be called with
+///
+/// Algorithms declare it in the `[data]` table of their export metadata next
+/// to the parameters. Algorithms without one receive the raw prices:
+/// ```toml
+/// [data]
+/// bars = "5m"
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Input {
+    Prices,
+    Bars(BarSize),
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    #[serde(default)]
+    data: Option<DataSpec>,
+}
+
+#[derive(Deserialize)]
+struct DataSpec {
+    bars: Option<String>,
+}
+
+impl Input {
+    pub fn from_metadata(metadata: &str) -> Result<Self, String> {
+        let metadata: Metadata = toml::from_str(metadata).map_err(|err| format!("invalid metadata ({})", err))?;
+        match metadata.data.and_then(|data| data.bars) {
+            Some(bars) => bars.parse().map(Input::Bars).map_err(|err| format!("invalid bar size ({})", err)),
+            None => Ok(Input::Prices)
+        }
+    }
+
+    /// Replaces the declared bar size with the one of the user
+    pub fn with_bars(self, bars: Option<BarSize>) -> Result<Self, String> {
+        match (self, bars) {
+            (_, None) => Ok(self),
+            (Input::Bars(_), Some(bars)) => Ok(Input::Bars(bars)),
+            (Input::Prices, Some(_)) => Err(String::from("the algorithm works on raw prices instead of bars")),
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Prices => write!(formatter, "raw prices"),
+            Input::Bars(size) => write!(formatter, "{} bars", size),
+        }
+    }
+}
+
+/// The open, high, low and close price and the volume of a part of the ticks
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Bar {
+    /// the time of the first and the last tick
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: f64,
+    pub ticks: u32,
+}
+
+impl Bar {
+    fn new(tick: &Tick) -> Self {
+        Self {
+            start: tick.time,
+            end: tick.time,
+            open: tick.price,
+            high: tick.price,
+            low: tick.price,
+            close: tick.price,
+            volume: tick.volume,
+            ticks: 1,
+        }
+    }
+
+    fn add(&mut self, tick: &Tick) {
+        self.end = tick.time;
+        self.high = self.high.max(tick.price);
+        self.low = self.low.min(tick.price);
+        self.close = tick.price;
+        self.volume += tick.volume;
+        self.ticks += 1;
+    }
+}
+
+/// Aggregates the ticks of an instrument into bars
+///
+/// Time bars are only completed by the first tick after their period, since
+/// back tests have no clock that could close them. Tick and volume bars are
+/// completed by the tick that fills them up.
+pub struct BarAggregator {
+    size: BarSize,
+    current: Option<Bar>,
+}
+
+impl BarAggregator {
+    pub fn new(size: BarSize) -> Self {
+        Self {
+            size,
+            current: None,
+        }
+    }
+
+    /// Adds the tick and returns the bar it completed
+    pub fn push(&mut self, tick: &Tick) -> Option<Bar> {
+        let new_period = match (self.size, &self.current) {
+            (BarSize::Time(seconds), Some(bar)) => period_start(tick.time, seconds) != period_start(bar.start, seconds),
+            _ => false
+        };
+        if new_period {
+            return self.current.replace(Bar::new(tick));
+        }
+
+        match self.current {
+            Some(ref mut bar) => bar.add(tick),
+            None => self.current = Some(Bar::new(tick)),
+        }
+
+        let full = match (self.size, &self.current) {
+            (BarSize::Ticks(ticks), Some(bar)) => bar.ticks >= ticks,
+            (BarSize::Volume(volume), Some(bar)) => bar.volume >= volume,
+            _ => false
+        };
+        if full { self.current.take() } else { None }
+    }
+}
+
+/// the start of the period of the time
+fn period_start(time: DateTime<Utc>, seconds: u64) -> DateTime<Utc> {
+    let timestamp = time.timestamp();
+    // the start is before the time, so it is always a valid timestamp
+    Utc.timestamp_opt(timestamp - timestamp.rem_euclid(seconds as i64), 0).single().unwrap_or(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, 1, hour, minute, second).unwrap()
+    }
+
+    fn tick(hour: u32, minute: u32, second: u32, price: Price, volume: f64) -> Tick {
+        Tick {
+            time: time(hour, minute, second),
+            isin: String::from("DE0001"),
+            price,
+            volume,
+            bid: None,
+            ask: None,
+        }
+    }
+
+    /// the bars the ticks complete as (open, high, low, close, ticks)
+    fn bars(size: BarSize, ticks: &[Tick]) -> Vec<(Price, Price, Price, Price, u32)> {
+        let mut aggregator = BarAggregator::new(size);
+        ticks
+            .iter()
+            .filter_map(|tick| aggregator.push(tick))
+            .map(|bar| (bar.open, bar.high, bar.low, bar.close, bar.ticks))
+            .collect()
+    }
+
+    #[test]
+    fn parses_bar_sizes() {
+        assert_eq!("30s".parse(), Ok(BarSize::Time(30)));
+        assert_eq!("5m".parse(), Ok(BarSize::Time(300)));
+        assert_eq!("1h".parse(), Ok(BarSize::Time(3600)));
+        assert_eq!("2d".parse(), Ok(BarSize::Time(2 * 24 * 60 * 60)));
+        assert_eq!("100t".parse(), Ok(BarSize::Ticks(100)));
+        assert_eq!("5000.5v".parse(), Ok(BarSize::Volume(5000.5)));
+        for size in &["5m", "90s", "1h", "3d", "100t", "5000v"] {
+            assert_eq!(size.parse::<BarSize>().unwrap().to_string(), *size);
+        }
+
+        assert!("0m".parse::<BarSize>().is_err());
+        assert!("0v".parse::<BarSize>().is_err());
+        assert!("NaNv".parse::<BarSize>().is_err());
+        assert!("m".parse::<BarSize>().is_err());
+        assert!("5x".parse::<BarSize>().is_err());
+        assert!("-5m".parse::<BarSize>().is_err());
+        assert_eq!("999999999999999999d".parse::<BarSize>(), Err(String::from("999999999999999999d is too long for a bar")));
+        assert!("9223372036854775808s".parse::<BarSize>().is_err());
+    }
+
+    #[test]
+    fn time_bars_are_completed_by_the_first_tick_of_the_next_period() {
+        let ticks = vec![
+            tick(9, 0, 0, 10.0, 1.0),
+            tick(9, 2, 0, 12.0, 1.0),
+            tick(9, 4, 59, 9.0, 1.0),
+            // the periods start at multiples of 5 minutes since midnight
+            tick(9, 5, 0, 11.0, 1.0),
+            tick(9, 7, 0, 13.0, 1.0),
+            // a gap of several periods completes only one bar
+            tick(9, 31, 0, 14.0, 1.0),
+        ];
+        assert_eq!(bars(BarSize::Time(300), &ticks), vec![(10.0, 12.0, 9.0, 9.0, 3), (11.0, 13.0, 11.0, 13.0, 2)]);
+    }
+
+    #[test]
+    fn time_bars_are_aligned_to_the_start_of_the_day() {
+        let mut aggregator = BarAggregator::new(BarSize::Time(60 * 60));
+        assert_eq!(aggregator.push(&tick(9, 30, 0, 10.0, 1.0)), None);
+        let bar = aggregator.push(&tick(10, 0, 0, 11.0, 1.0)).unwrap();
+        assert_eq!((bar.start, bar.end), (time(9, 30, 0), time(9, 30, 0)));
+        assert_eq!(period_start(time(10, 59, 59), 60 * 60), time(10, 0, 0));
+    }
+
+    #[test]
+    fn tick_bars_are_completed_by_their_last_tick() {
+        let ticks: Vec<Tick> = (0..7).map(|second| tick(9, 0, second, 10.0 + second as f64, 1.0)).collect();
+        assert_eq!(bars(BarSize::Ticks(3), &ticks), vec![(10.0, 12.0, 10.0, 12.0, 3), (13.0, 15.0, 13.0, 15.0, 3)]);
+    }
+
+    #[test]
+    fn volume_bars_are_completed_by_the_tick_that_fills_them_up() {
+        let ticks = vec![
+            tick(9, 0, 0, 10.0, 40.0),
+            tick(9, 0, 1, 11.0, 50.0),
+            // the bar may get more volume than its size
+            tick(9, 0, 2, 12.0, 30.0),
+            tick(9, 0, 3, 13.0, 100.0),
+            tick(9, 0, 4, 14.0, 10.0),
+        ];
+        let mut aggregator = BarAggregator::new(BarSize::Volume(100.0));
+        let volumes: Vec<f64> = ticks.iter().filter_map(|tick| aggregator.push(tick)).map(|bar| bar.volume).collect();
+        assert_eq!(volumes, vec![120.0, 100.0]);
+        assert_eq!(bars(BarSize::Volume(100.0), &ticks)[0], (10.0, 12.0, 10.0, 12.0, 3));
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::trading::decimal::Decimal;
+use crate::trading::order::Fill;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Holding {
+    /// positive for long and negative for short positions
+    pub quantity: Decimal,
+    /// in the currency of the instrument
+    pub average_price: Decimal,
+    /// the exchange rate to the base currency the holding was entered at on average
+    #[serde(default = "default_rate")]
+    pub average_rate: f64,
+}
+
+impl Holding {
+    /// the entry value in the base currency
+    fn cost(&self) -> Decimal {
+        (self.quantity * self.average_price).at_rate(self.average_rate)
+    }
+}
+
+/// The positions and latest prices known to the runtime
+///
+/// Prices are in the currency of the instrument, all values and profits
+/// are converted to the base currency of the account.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Book {
+    holdings: HashMap<String, Holding>,
+    last_prices: HashMap<String, Decimal>,
+    /// the latest exchange rates to the base currency (1 if not set)
+    #[serde(default)]
+    rates: HashMap<String, f64>,
+}
+
+impl Book {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update_price(&mut self, isin: &str, price: Decimal) {
+        self.last_prices.insert(isin.to_string(), price);
+    }
+
+    pub fn update_rate(&mut self, isin: &str, rate: f64) {
+        self.rates.insert(isin.to_string(), rate);
+    }
+
+    /// the latest exchange rate of the currency of the instrument to the base currency
+    pub fn rate(&self, isin: &str) -> f64 {
+        self.rates.get(isin).copied().unwrap_or(1.0)
+    }
+
+    pub fn last_price(&self, isin: &str) -> Option<Decimal> {
+        self.last_prices.get(isin).copied()
+    }
+
+    pub fn holding(&self, isin: &str) -> Option<&Holding> {
+        self.holdings.get(isin)
+    }
+
+    /// the signed quantity held of `isin`
+    pub fn position(&self, isin: &str) -> Decimal {
+        self.holdings
+            .get(isin)
+            .map(|holding| holding.quantity)
+            .unwrap_or(Decimal::ZERO)
+    }
+
+    /// the market value of a single holding in the base currency (falls back to the entry price if no price is known)
+    fn market_value(&self, isin: &str, holding: &Holding) -> Decimal {
+        let price = self.last_price(isin).unwrap_or(holding.average_price);
+        (holding.quantity * price).at_rate(self.rate(isin))
+    }
+
+    pub fn holdings(&self) -> impl Iterator<Item=(&String, &Holding)> {
+        self.holdings.iter()
+    }
+
+    /// the sum of the absolute market values of all holdings
+    pub fn gross_exposure(&self) -> Decimal {
+        self.holdings
+            .iter()
+            .map(|(isin, holding)| self.market_value(isin, holding).abs())
+            .sum()
+    }
+
+    /// the profit or loss of all holdings if they were closed at the last price
+    pub fn unrealised_pnl(&self) -> Decimal {
+        self.holdings
+            .iter()
+            .map(|(isin, holding)| self.market_value(isin, holding) - holding.cost())
+            .sum()
+    }
+
+    /// Applies a fill to the holdings and returns the realised profit or loss (before fees) in the base currency
+    pub fn apply_fill(&mut self, fill: &Fill) -> Decimal {
+        let signed = fill.signed_quantity();
+        if signed.is_zero() {
+            return Decimal::ZERO;
+        }
+        let holding = self.holdings
+                          .entry(fill.isin.clone())
+                          .or_default();
+        let mut realised = Decimal::ZERO;
+
+        if holding.quantity.is_zero() || holding.quantity.signum() == signed.signum() {
+            let quantity = holding.quantity + signed;
+            let cost = holding.cost().abs() + (fill.price * signed.abs()).at_rate(fill.fx_rate);
+            holding.average_price = (holding.average_price * holding.quantity.abs() + fill.price * signed.abs())
+                / quantity.abs();
+            holding.average_rate = if holding.average_price.is_zero() {
+                fill.fx_rate
+            } else {
+                cost.to_f64() / (holding.average_price * quantity.abs()).to_f64()
+            };
+            holding.quantity = quantity;
+        } else {
+            let closed = signed.abs().min(holding.quantity.abs());
+            realised = (closed * fill.price).at_rate(fill.fx_rate)
+                - (closed * holding.average_price).at_rate(holding.average_rate);
+            if holding.quantity.is_negative() {
+                realised = -realised;
+            }
+
+            let quantity = holding.quantity + signed;
+            if !quantity.is_zero() && quantity.signum() != holding.quantity.signum() {
+                // the position was flipped, so the remainder was entered at the fill price
+                holding.average_price = fill.price;
+                holding.average_rate = fill.fx_rate;
+            }
+            holding.quantity = quantity;
+        }
+
+        if holding.quantity.is_zero() {
+            self.holdings.remove(&fill.isin);
+        }
+
+        realised
+    }
+}
+
+fn default_rate() -> f64 { 1.0 }
@@ -0,0 +1,385 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::trading::decimal::Decimal;
+use crate::trading::feed::Tick;
+use crate::trading::fill_model::FillModel;
+use crate::trading::order::{Fill, Order, OrderId, OrderType, Side, TimeInForce};
+
+/// The execution backend orders are sent to after they passed the risk checks
+pub trait Broker {
+    /// Accepts an order and returns the fills that happened right away
+    fn submit(&mut self, order: Order, time: DateTime<Utc>) -> Result<Vec<Fill>, String>;
+
+    /// Passes a new tick to the broker and returns the fills of the open orders
+    fn update(&mut self, tick: &Tick) -> Result<Vec<Fill>, String>;
+
+    /// Sends the orders still waiting for their latency to the market on the last tick and returns their fills
+    ///
+    /// The session calls this once at its end, so the orders closing the positions can fill before
+    /// the rest is cancelled although no tick follows.
+    fn flush(&mut self) -> Result<Vec<Fill>, String>;
+
+    /// Takes over the open orders of a resumed session
+    ///
+    /// A backend that keeps the orders itself (like a real broker) doesn't need them.
+    fn restore(&mut self, orders: Vec<Order>, time: DateTime<Utc>);
+
+    /// Cancels an open order and returns what was left of it
+    fn cancel(&mut self, id: OrderId) -> Option<Order>;
+
+    /// Cancels all open orders and returns what was left of them
+    fn cancel_all(&mut self) -> Vec<Order>;
+
+    /// The orders that are not completely filled yet with what is left of them
+    fn open_orders(&self) -> Vec<Order>;
+
+    /// The orders the backend cancelled on its own since the last call and why
+    fn cancelled(&mut self) -> Vec<(Order, String)>;
+
+    /// Whether the backend handles the order type and the time in force of the order itself
+    fn supports(&self, order: &Order) -> bool;
+}
+
+/// An order that is not completely filled yet
+struct OpenOrder {
+    /// the quantity is what is left to fill
+    order: Order,
+    /// the order doesn't reach the market before this time and this number of ticks
+    active_at: DateTime<Utc>,
+    ticks_left: u32,
+    /// the fees are charged on the value of the whole order
+    filled_value: Decimal,
+    fees: Decimal,
+    /// a limit order that wasn't marketable when it reached the market
+    resting: bool,
+}
+
+/// Fills orders against the recorded prices as described by the fill model
+///
+/// This broker is used for paper trading and back tests.
+pub struct SimulatedBroker {
+    model: FillModel,
+    /// ISIN = tick size (prices of the other instruments aren't rounded)
+    tick_sizes: BTreeMap<String, Decimal>,
+    last_ticks: HashMap<String, Tick>,
+    /// ISIN = the quantity the orders already took from the volume of the last tick
+    taken: HashMap<String, Decimal>,
+    open: Vec<OpenOrder>,
+}
+
+impl SimulatedBroker {
+    pub fn new(model: FillModel, tick_sizes: BTreeMap<String, Decimal>) -> Self {
+        Self {
+            model,
+            tick_sizes,
+            last_ticks: HashMap::new(),
+            taken: HashMap::new(),
+            open: Vec::new(),
+        }
+    }
+
+    /// Fills as much of the order as the tick allows
+    ///
+    /// Market orders and limit orders that are marketable when they reach the
+    /// market pay the quote. Limit orders that rest in the market only fill at
+    /// their limit once the price trades through it. All orders share the
+    /// volume of the tick, `taken` is what the orders before took of it.
+    fn fill(
+        model: &FillModel, tick_size: Option<Decimal>, open: &mut OpenOrder, tick: &Tick, taken: &mut Decimal,
+    ) -> Option<Fill> {
+        let side = open.order.side;
+        let quote = model.quote(tick, side, tick_size);
+
+        let price = match open.order.limit() {
+            None => quote,
+            Some(limit) => {
+                let (marketable, crossed) = match side {
+                    Side::Buy => (quote <= limit, quote < limit),
+                    Side::Sell => (quote >= limit, quote > limit),
+                };
+                if open.resting {
+                    if !crossed {
+                        return None;
+                    }
+                    limit
+                } else if marketable {
+                    quote
+                } else {
+                    open.resting = true;
+                    return None;
+                }
+            }
+        };
+
+        let quantity = match model.available(tick) {
+            Some(available) => open.order.quantity.min(available - *taken),
+            None => open.order.quantity,
+        };
+        if !quantity.is_positive() {
+            return None;
+        }
+        *taken += quantity;
+
+        open.order.quantity -= quantity;
+        open.filled_value += quantity * price;
+        let fee = model.fees.fee(open.filled_value) - open.fees;
+        open.fees += fee;
+
+        Some(Fill {
+            order: open.order.id,
+            time: tick.time,
+            isin: open.order.isin.clone(),
+            side,
+            quantity,
+            price,
+            fee,
+            // set by the session
+            fx_rate: 1.0,
+        })
+    }
+}
+
+impl Broker for SimulatedBroker {
+    fn submit(&mut self, order: Order, time: DateTime<Utc>) -> Result<Vec<Fill>, String> {
+        if !self.supports(&order) {
+            return Err(format!("{} orders that are {} are not supported", order.order_type, order.time_in_force));
+        }
+        let last_tick = match self.last_ticks.get(&order.isin) {
+            Some(tick) => tick,
+            None => return Err(format!("no price known for {}", order.isin))
+        };
+
+        let mut open = OpenOrder {
+            order,
+            active_at: time + Duration::milliseconds(self.model.latency_ms as i64),
+            ticks_left: self.model.latency_ticks,
+            filled_value: Decimal::ZERO,
+            fees: Decimal::ZERO,
+            resting: false,
+        };
+
+        // without latency the order reaches the market on the tick it was created on
+        let mut fills = Vec::new();
+        if self.model.latency_ms == 0 && self.model.latency_ticks == 0 {
+            let tick_size = self.tick_sizes.get(&open.order.isin).copied();
+            let taken = self.taken.entry(open.order.isin.clone()).or_insert(Decimal::ZERO);
+            fills.extend(Self::fill(&self.model, tick_size, &mut open, last_tick, taken));
+        }
+        if open.order.quantity.is_positive() {
+            self.open.push(open);
+        }
+
+        Ok(fills)
+    }
+
+    fn update(&mut self, tick: &Tick) -> Result<Vec<Fill>, String> {
+        self.last_ticks.insert(tick.isin.clone(), tick.clone());
+        let taken = self.taken.entry(tick.isin.clone()).or_insert(Decimal::ZERO);
+        *taken = Decimal::ZERO;
+
+        let tick_size = self.tick_sizes.get(&tick.isin).copied();
+        let mut fills = Vec::new();
+        for open in self.open.iter_mut().filter(|open| open.order.isin == tick.isin) {
+            open.ticks_left = open.ticks_left.saturating_sub(1);
+            if open.ticks_left > 0 || tick.time < open.active_at {
+                continue;
+            }
+            fills.extend(Self::fill(&self.model, tick_size, open, tick, taken));
+        }
+        self.open.retain(|open| open.order.quantity.is_positive());
+
+        Ok(fills)
+    }
+
+    fn flush(&mut self) -> Result<Vec<Fill>, String> {
+        let mut fills = Vec::new();
+        for open in self.open.iter_mut() {
+            let last_tick = match self.last_ticks.get(&open.order.isin) {
+                Some(tick) => tick,
+                None => continue
+            };
+            open.ticks_left = 0;
+            open.active_at = last_tick.time;
+            let tick_size = self.tick_sizes.get(&open.order.isin).copied();
+            let taken = self.taken.entry(open.order.isin.clone()).or_insert(Decimal::ZERO);
+            fills.extend(Self::fill(&self.model, tick_size, open, last_tick, taken));
+        }
+        self.open.retain(|open| open.order.quantity.is_positive());
+
+        Ok(fills)
+    }
+
+    /// The restored orders already reached the market, so limit orders only fill once the price trades through them.
+    /// The fees of their earlier fills are forgotten.
+    fn restore(&mut self, orders: Vec<Order>, time: DateTime<Utc>) {
+        self.open.extend(orders.into_iter().map(|order| OpenOrder {
+            resting: order.limit().is_some(),
+            order,
+            active_at: time,
+            ticks_left: 0,
+            filled_value: Decimal::ZERO,
+            fees: Decimal::ZERO,
+        }));
+    }
+
+    fn cancel(&mut self, id: OrderId) -> Option<Order> {
+        let index = self.open.iter().position(|open| open.order.id == id)?;
+        Some(self.open.remove(index).order)
+    }
+
+    fn cancel_all(&mut self) -> Vec<Order> {
+        self.open.drain(..).map(|open| open.order).collect()
+    }
+
+    fn open_orders(&self) -> Vec<Order> {
+        self.open.iter().map(|open| open.order.clone()).collect()
+    }
+
+    /// the simulated broker never cancels an order on its own
+    fn cancelled(&mut self) -> Vec<(Order, String)> {
+        Vec::new()
+    }
+
+    /// stops and the time in force are left to the runtime
+    fn supports(&self, order: &Order) -> bool {
+        matches!(order.order_type, OrderType::Market | OrderType::Limit { .. })
+            && order.time_in_force == TimeInForce::Gtc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::trading::fill_model::FeeSchedule;
+
+    const ISIN: &str = "DE0001";
+
+    fn time(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, 1, 9, 0, second).unwrap()
+    }
+
+    fn dec(value: f64) -> Decimal {
+        Decimal::from_f64(value)
+    }
+
+    fn tick(second: u32, price: f64) -> Tick {
+        Tick { time: time(second), isin: ISIN.to_string(), price, volume: 10.0, bid: None, ask: None }
+    }
+
+    fn order(id: OrderId, side: Side, quantity: f64, order_type: OrderType) -> Order {
+        Order { id, ..Order::new(ISIN.to_string(), side, dec(quantity), order_type) }
+    }
+
+    /// a broker that knows the price 100
+    fn broker(model: FillModel) -> SimulatedBroker {
+        let mut broker = SimulatedBroker::new(model, BTreeMap::new());
+        broker.update(&tick(0, 100.0)).unwrap();
+        broker
+    }
+
+    #[test]
+    fn market_orders_pay_the_spread_rounded_against_them() {
+        let model = FillModel { spread: 10.0, ..FillModel::default() };
+        let tick_sizes = vec![(ISIN.to_string(), dec(0.1))].into_iter().collect();
+        let mut broker = SimulatedBroker::new(model, tick_sizes);
+        broker.update(&tick(0, 100.0)).unwrap();
+
+        let buys = broker.submit(order(1, Side::Buy, 1.0, OrderType::Market), time(0)).unwrap();
+        assert_eq!(buys[0].price, dec(100.1));
+        let sells = broker.submit(order(2, Side::Sell, 1.0, OrderType::Market), time(0)).unwrap();
+        assert_eq!(sells[0].price, dec(99.9));
+    }
+
+    #[test]
+    fn orders_without_a_price_are_rejected() {
+        let mut broker = SimulatedBroker::new(FillModel::default(), BTreeMap::new());
+        assert!(broker.submit(order(1, Side::Buy, 1.0, OrderType::Market), time(0)).is_err());
+    }
+
+    #[test]
+    fn orders_wait_for_the_latency() {
+        let mut broker = broker(FillModel { latency_ticks: 2, ..FillModel::default() });
+        assert!(broker.submit(order(1, Side::Buy, 1.0, OrderType::Market), time(0)).unwrap().is_empty());
+        assert!(broker.update(&tick(1, 101.0)).unwrap().is_empty());
+        let fills = broker.update(&tick(2, 102.0)).unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].price, dec(102.0));
+        assert!(broker.open_orders().is_empty());
+    }
+
+    #[test]
+    fn flushing_fills_the_orders_waiting_for_the_latency_on_the_last_tick() {
+        let mut broker = broker(FillModel { latency_ms: 60_000, ..FillModel::default() });
+        broker.submit(order(1, Side::Sell, 1.0, OrderType::Market), time(0)).unwrap();
+        broker.submit(order(2, Side::Buy, 1.0, OrderType::Limit { limit: dec(90.0) }), time(0)).unwrap();
+        let fills = broker.flush().unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].order, fills[0].price), (1, dec(100.0)));
+        // the limit isn't marketable and rests in the market
+        assert_eq!(broker.open_orders().len(), 1);
+    }
+
+    #[test]
+    fn marketable_limit_orders_pay_the_quote() {
+        let mut broker = broker(FillModel::default());
+        let fills = broker.submit(order(1, Side::Buy, 1.0, OrderType::Limit { limit: dec(101.0) }), time(0)).unwrap();
+        assert_eq!(fills[0].price, dec(100.0));
+    }
+
+    #[test]
+    fn resting_limit_orders_fill_at_their_limit_once_the_price_trades_through_it() {
+        let mut broker = broker(FillModel::default());
+        assert!(broker.submit(order(1, Side::Buy, 1.0, OrderType::Limit { limit: dec(99.0) }), time(0)).unwrap().is_empty());
+        // touching the limit isn't enough, the queue in front of the order fills first
+        assert!(broker.update(&tick(1, 99.0)).unwrap().is_empty());
+        let fills = broker.update(&tick(2, 98.5)).unwrap();
+        assert_eq!(fills[0].price, dec(99.0));
+        assert!(broker.open_orders().is_empty());
+    }
+
+    #[test]
+    fn orders_on_the_same_tick_share_its_volume() {
+        let mut broker = broker(FillModel { max_volume_share: Some(0.4), ..FillModel::default() });
+
+        let mut fills = broker.submit(order(1, Side::Buy, 3.0, OrderType::Market), time(0)).unwrap();
+        fills.extend(broker.submit(order(2, Side::Sell, 3.0, OrderType::Market), time(0)).unwrap());
+        let filled: Vec<(OrderId, Decimal)> = fills.iter().map(|fill| (fill.order, fill.quantity)).collect();
+        assert_eq!(filled, vec![(1, dec(3.0)), (2, dec(1.0))]);
+
+        // the next tick brings new volume
+        let fills = broker.update(&tick(1, 100.0)).unwrap();
+        let filled: Vec<(OrderId, Decimal)> = fills.iter().map(|fill| (fill.order, fill.quantity)).collect();
+        assert_eq!(filled, vec![(2, dec(2.0))]);
+        assert!(broker.open_orders().is_empty());
+    }
+
+    #[test]
+    fn partial_fills_are_charged_the_fee_of_the_whole_order() {
+        let fees = FeeSchedule { percentage: 1.0, min: Some(5.0), ..FeeSchedule::default() };
+        let mut broker = broker(FillModel { max_volume_share: Some(0.4), fees: fees.clone(), ..FillModel::default() });
+
+        let mut fills = broker.submit(order(1, Side::Buy, 10.0, OrderType::Market), time(0)).unwrap();
+        fills.extend(broker.update(&tick(1, 100.0)).unwrap());
+        fills.extend(broker.update(&tick(2, 100.0)).unwrap());
+
+        let quantities: Vec<Decimal> = fills.iter().map(|fill| fill.quantity).collect();
+        assert_eq!(quantities, vec![dec(4.0), dec(4.0), dec(2.0)]);
+        // the minimum is charged with the first fill, the rest as the value grows
+        let charged: Vec<Decimal> = fills.iter().map(|fill| fill.fee).collect();
+        assert_eq!(charged, vec![dec(5.0), dec(3.0), dec(2.0)]);
+        assert_eq!(charged.iter().sum::<Decimal>(), fees.fee(dec(1000.0)));
+        assert!(broker.open_orders().is_empty());
+    }
+
+    #[test]
+    fn cancelled_orders_return_what_was_left() {
+        let mut broker = broker(FillModel { max_volume_share: Some(0.4), ..FillModel::default() });
+        broker.submit(order(1, Side::Buy, 10.0, OrderType::Market), time(0)).unwrap();
+        assert_eq!(broker.cancel(1).map(|order| order.quantity), Some(dec(6.0)));
+        assert!(broker.cancel(1).is_none());
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, DateTime, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+pub const CALENDAR_DIR: &str = "./data/calendars/";
+pub const EXCHANGES: [&str; 3] = ["xetra", "tradegate", "nyse"];
+
+/// how far `next_open` looks ahead before it gives up
+const MAX_CLOSED_DAYS: usize = 366;
+
+/// The trading hours and holidays of an exchange
+///
+/// The built-in exchanges only know their regular hours. Holidays and early
+/// closes are loaded from `CALENDAR_DIR/<exchange>.toml`, which can also
+/// describe exchanges that aren't built in:
+///
+/// ```toml
+/// timezone = "Europe/Berlin"
+/// open = "09:00"
+/// close = "17:30"
+/// holidays = ["2020-12-24", "2020-12-25", "2020-12-31"]
+///
+/// [early_closes]
+/// "2020-12-30" = "14:00"
+/// ```
+#[derive(Debug, Clone)]
+pub struct Calendar {
+    pub name: String,
+    timezone: Tz,
+    /// in the local time of the exchange
+    open: NaiveTime,
+    close: NaiveTime,
+    holidays: BTreeSet<NaiveDate>,
+    early_closes: BTreeMap<NaiveDate, NaiveTime>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CalendarFile {
+    timezone: Option<String>,
+    open: Option<String>,
+    close: Option<String>,
+    holidays: Vec<NaiveDate>,
+    early_closes: BTreeMap<NaiveDate, String>,
+}
+
+impl Calendar {
+    /// the regular hours of a built-in exchange
+    pub fn builtin(name: &str) -> Option<Self> {
+        let (timezone, open, close) = match name {
+            "xetra" => (chrono_tz::Europe::Berlin, (9, 0), (17, 30)),
+            "tradegate" => (chrono_tz::Europe::Berlin, (8, 0), (22, 0)),
+            "nyse" => (chrono_tz::America::New_York, (9, 30), (16, 0)),
+            _ => return None
+        };
+
+        Some(Self {
+            name: name.to_string(),
+            timezone,
+            open: NaiveTime::from_hms_opt(open.0, open.1, 0)?,
+            close: NaiveTime::from_hms_opt(close.0, close.1, 0)?,
+            holidays: BTreeSet::new(),
+            early_closes: BTreeMap::new(),
+        })
+    }
+
+    pub fn calendar_file(name: &str) -> PathBuf {
+        Path::new(CALENDAR_DIR).join(format!("{}.toml", name))
+    }
+
+    /// Loads the calendar of an exchange from its built-in hours and its calendar file
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = Self::calendar_file(name);
+        let file = match fs::read_to_string(&path) {
+            Ok(data) => Some(
+                toml::from_str::<CalendarFile>(&data)
+                    .map_err(|err| format!("Could not parse the calendar {} ({})", path.display(), err))?
+            ),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(format!("Could not read the calendar {} ({})", path.display(), err))
+        };
+
+        match (Self::builtin(name), file) {
+            (Some(calendar), None) => Ok(calendar),
+            (builtin, Some(file)) => Self::from_file(name, builtin, file)
+                .map_err(|err| format!("Invalid calendar {} ({})", path.display(), err)),
+            (None, None) => Err(format!(
+                "Unknown exchange {}\nthe built-in exchanges are {}, others need a calendar file in {}",
+                name, EXCHANGES.join(", "), CALENDAR_DIR
+            ))
+        }
+    }
+
+    /// the values of the file override the ones of the built-in calendar
+    fn from_file(name: &str, builtin: Option<Self>, file: CalendarFile) -> Result<Self, String> {
+        fn time(value: &str) -> Result<NaiveTime, String> {
+            NaiveTime::parse_from_str(value, "%H:%M").map_err(|err| format!("{} is not a time like 17:30 ({})", value, err))
+        }
+        let missing = |field| format!("{} is not a built-in exchange, so the calendar needs a {}", name, field);
+
+        let timezone = match (file.timezone, builtin.as_ref()) {
+            (Some(timezone), _) => timezone.parse::<Tz>()?,
+            (None, Some(builtin)) => builtin.timezone,
+            (None, None) => return Err(missing("timezone"))
+        };
+        let open = match (file.open, builtin.as_ref()) {
+            (Some(open), _) => time(&open)?,
+            (None, Some(builtin)) => builtin.open,
+            (None, None) => return Err(missing("open"))
+        };
+        let close = match (file.close, builtin.as_ref()) {
+            (Some(close), _) => time(&close)?,
+            (None, Some(builtin)) => builtin.close,
+            (None, None) => return Err(missing("close"))
+        };
+        if open >= close {
+            return Err(format!("the exchange opens at {} but closes at {}", open, close));
+        }
+
+        let mut early_closes = BTreeMap::new();
+        for (date, close) in file.early_closes {
+            early_closes.insert(date, time(&close)?);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            timezone,
+            open,
+            close,
+            holidays: file.holidays.into_iter().collect(),
+            early_closes,
+        })
+    }
+
+    /// weekends and holidays are no trading days
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+
+    /// the open and close of a day in the local time of the exchange (None if it's no trading day)
+    pub fn hours(&self, date: NaiveDate) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        if !self.is_trading_day(date) {
+            return None;
+        }
+        let close = self.early_closes.get(&date).copied().unwrap_or(self.close);
+        Some((self.to_utc(date, self.open)?, self.to_utc(date, close)?))
+    }
+
+    pub fn is_open(&self, time: DateTime<Utc>) -> bool {
+        match self.hours(self.local_date(time)) {
+            Some((open, close)) => time >= open && time < close,
+            None => false
+        }
+    }
+
+    /// the close of the trading session the time is in (None if the exchange is closed)
+    pub fn close(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.hours(self.local_date(time)) {
+            Some((open, close)) if time >= open && time < close => Some(close),
+            _ => None
+        }
+    }
+
+    /// the time itself if the exchange is open, otherwise the next time it opens
+    pub fn next_open(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut date = self.local_date(time);
+        for _ in 0..MAX_CLOSED_DAYS {
+            if let Some((open, close)) = self.hours(date) {
+                if time < close {
+                    return Some(open.max(time));
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    /// the time zone of the exchange
+    pub fn timezone(&self) -> Tz { self.timezone }
+
+    fn local_date(&self, time: DateTime<Utc>) -> NaiveDate {
+        time.with_timezone(&self.timezone).naive_local().date()
+    }
+
+    fn to_utc(&self, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+        self.timezone
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+    }
+}
+
+impl fmt::Display for Calendar {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "CALENDAR: {}\n\
+            \thours: {} - {} ({})\n\
+            \tholidays: {}\n\
+            \tearly closes: {}",
+            self.name, self.open.format("%H:%M"), self.close.format("%H:%M"), self.timezone.name(),
+            self.holidays.len(), self.early_closes.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    /// xetra with a holiday on Monday 2021-04-05 and an early close on 2021-12-30
+    fn xetra() -> Calendar {
+        let file = CalendarFile {
+            holidays: vec![date(2021, 4, 5)],
+            early_closes: vec![(date(2021, 12, 30), String::from("14:00"))].into_iter().collect(),
+            ..CalendarFile::default()
+        };
+        Calendar::from_file("xetra", Calendar::builtin("xetra"), file).unwrap()
+    }
+
+    #[test]
+    fn the_hours_follow_the_daylight_saving_time_of_the_exchange() {
+        let xetra = xetra();
+        // Berlin switched to summer time on 2021-03-28
+        assert_eq!(xetra.hours(date(2021, 3, 26)), Some((utc(2021, 3, 26, 8, 0), utc(2021, 3, 26, 16, 30))));
+        assert_eq!(xetra.hours(date(2021, 3, 29)), Some((utc(2021, 3, 29, 7, 0), utc(2021, 3, 29, 15, 30))));
+
+        // New York switched two weeks earlier
+        let nyse = Calendar::builtin("nyse").unwrap();
+        assert_eq!(nyse.hours(date(2021, 3, 12)).unwrap().0, utc(2021, 3, 12, 14, 30));
+        assert_eq!(nyse.hours(date(2021, 3, 15)).unwrap().0, utc(2021, 3, 15, 13, 30));
+        // and back a week later
+        assert_eq!(nyse.hours(date(2021, 11, 5)).unwrap().0, utc(2021, 11, 5, 13, 30));
+        assert_eq!(nyse.hours(date(2021, 11, 8)).unwrap().0, utc(2021, 11, 8, 14, 30));
+    }
+
+    #[test]
+    fn times_that_occur_twice_are_the_earlier_one() {
+        let xetra = xetra();
+        let twice = NaiveTime::from_hms_opt(2, 30, 0).unwrap();
+        // 02:30 happened in summer and in winter time on 2021-10-31
+        assert_eq!(xetra.to_utc(date(2021, 10, 31), twice), Some(utc(2021, 10, 31, 0, 30)));
+    }
+
+    #[test]
+    fn the_exchange_is_closed_on_weekends_and_holidays() {
+        let xetra = xetra();
+        assert!(xetra.is_open(utc(2021, 4, 1, 10, 0)));
+        assert!(!xetra.is_open(utc(2021, 4, 3, 10, 0)));
+        assert!(!xetra.is_open(utc(2021, 4, 5, 10, 0)));
+        assert_eq!(xetra.hours(date(2021, 4, 5)), None);
+        // the open belongs to the session, the close doesn't
+        assert!(xetra.is_open(utc(2021, 4, 6, 7, 0)));
+        assert!(!xetra.is_open(utc(2021, 4, 6, 15, 30)));
+    }
+
+    #[test]
+    fn early_closes_end_the_session_early() {
+        let xetra = xetra();
+        assert_eq!(xetra.close(utc(2021, 12, 30, 10, 0)), Some(utc(2021, 12, 30, 13, 0)));
+        assert!(!xetra.is_open(utc(2021, 12, 30, 13, 30)));
+        assert_eq!(xetra.close(utc(2021, 12, 29, 10, 0)), Some(utc(2021, 12, 29, 16, 30)));
+        assert_eq!(xetra.close(utc(2021, 12, 29, 17, 0)), None);
+    }
+
+    #[test]
+    fn the_next_open_skips_the_closed_days() {
+        let xetra = xetra();
+        // open right now
+        assert_eq!(xetra.next_open(utc(2021, 4, 1, 10, 0)), Some(utc(2021, 4, 1, 10, 0)));
+        // before the open of the day
+        assert_eq!(xetra.next_open(utc(2021, 4, 1, 5, 0)), Some(utc(2021, 4, 1, 7, 0)));
+        // after the close on Thursday, Good Friday isn't a holiday here but Easter Monday is
+        assert_eq!(xetra.next_open(utc(2021, 4, 1, 18, 0)), Some(utc(2021, 4, 2, 7, 0)));
+        assert_eq!(xetra.next_open(utc(2021, 4, 2, 18, 0)), Some(utc(2021, 4, 6, 7, 0)));
+        // after the early close
+        assert_eq!(xetra.next_open(utc(2021, 12, 30, 13, 30)), Some(utc(2021, 12, 31, 8, 0)));
+    }
+
+    #[test]
+    fn the_next_open_gives_up_if_the_exchange_never_opens() {
+        let mut closed = xetra();
+        let mut day = date(2021, 1, 1);
+        for _ in 0..=MAX_CLOSED_DAYS {
+            closed.holidays.insert(day);
+            day = day.succ_opt().unwrap();
+        }
+        assert_eq!(closed.next_open(utc(2021, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn calendar_files_describe_other_exchanges() {
+        let file = CalendarFile {
+            timezone: Some(String::from("Asia/Tokyo")),
+            open: Some(String::from("09:00")),
+            close: Some(String::from("15:00")),
+            ..CalendarFile::default()
+        };
+        let tokyo = Calendar::from_file("tokyo", None, file).unwrap();
+        assert_eq!(tokyo.hours(date(2021, 4, 1)), Some((utc(2021, 4, 1, 0, 0), utc(2021, 4, 1, 6, 0))));
+
+        let incomplete = CalendarFile { open: Some(String::from("09:00")), ..CalendarFile::default() };
+        assert!(Calendar::from_file("tokyo", None, incomplete).is_err());
+        let reversed = CalendarFile { close: Some(String::from("08:00")), ..CalendarFile::default() };
+        assert!(Calendar::from_file("xetra", Calendar::builtin("xetra"), reversed).is_err());
+    }
+}
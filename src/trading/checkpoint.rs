@@ -0,0 +1,246 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use algorithm_utils::Price;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::trading::bars::{Bar, BarSize};
+use crate::trading::book::Book;
+use crate::trading::emulator::{HeldOrder, SentOrder};
+use crate::trading::order_manager::OrderManager;
+use crate::trading::params::Parameters;
+use crate::trading::pnl::PnlTracker;
+use crate::trading::TradingType;
+
+pub const CHECKPOINT_DIR: &str = "./data/checkpoints/";
+
+/// how often the state of a running session is written to disk
+const CHECKPOINT_INTERVAL_SECS: i64 = 60;
+
+/// What a session was started with
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SessionInfo {
+    pub trading_type: TradingType,
+    pub isin: String,
+    pub algorithm: String,
+    pub parameters: Parameters,
+    pub profile: String,
+    /// in seconds
+    pub interval: u64,
+    /// the journal the resumed session continues
+    pub journal: Option<PathBuf>,
+    /// the bar size passed with `--bars` (None for the one the algorithm declares)
+    #[serde(default)]
+    pub bars: Option<BarSize>,
+}
+
+/// Everything needed to continue a session after the process died
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Checkpoint {
+    pub time: DateTime<Utc>,
+    pub session: SessionInfo,
+    /// the price window of the algorithm
+    pub prices: Vec<Price>,
+    /// the bar window of algorithms that work on bars
+    #[serde(default)]
+    pub bars: Vec<Bar>,
+    pub book: Book,
+    pub orders: OrderManager,
+    /// the stops that were emulated locally, since no broker knows them
+    #[serde(default)]
+    pub held: Vec<HeldOrder>,
+    /// the orders that were sent to the broker and aren't filled yet
+    #[serde(default)]
+    pub sent: Vec<SentOrder>,
+    pub pnl: PnlTracker,
+    /// the number of journal entries written up to the checkpoint (None for older checkpoints)
+    #[serde(default)]
+    pub journal_entries: Option<usize>,
+}
+
+impl Checkpoint {
+    /// the location of the checkpoint of the sessions of an ISIN and trading type
+    pub fn checkpoint_file(isin: &str, trading_type: TradingType) -> PathBuf {
+        Path::new(CHECKPOINT_DIR).join(format!("{}-{}.json", isin, trading_type))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let data = fs::read_to_string(path)?;
+        serde_json::from_str(&data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Writes the checkpoint to a temporary file first, so a crash while writing doesn't destroy the last one
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let json = serde_json::to_string(self).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(tmp, path)
+    }
+}
+
+/// Writes the checkpoints of a session
+pub struct Checkpointer {
+    path: PathBuf,
+    session: SessionInfo,
+    last: Option<DateTime<Utc>>,
+}
+
+impl Checkpointer {
+    pub fn new(session: SessionInfo) -> Self {
+        Self::with_path(Checkpoint::checkpoint_file(&session.isin, session.trading_type), session)
+    }
+
+    /// Writes the checkpoints to another location than the one of the ISIN and trading type
+    pub fn with_path(path: PathBuf, session: SessionInfo) -> Self {
+        Self {
+            path,
+            session,
+            last: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+    pub fn session(&self) -> &SessionInfo { &self.session }
+
+    /// whether the last checkpoint is old enough to be replaced
+    pub fn due(&self, time: DateTime<Utc>) -> bool {
+        match self.last {
+            Some(last) => time - last >= Duration::seconds(CHECKPOINT_INTERVAL_SECS),
+            None => true,
+        }
+    }
+
+    pub fn save(&mut self, checkpoint: &Checkpoint) -> Result<(), io::Error> {
+        self.last = Some(checkpoint.time);
+        checkpoint.save(&self.path)
+    }
+
+    /// Removes the checkpoint once there is nothing left to resume
+    pub fn remove(&self) -> Result<(), io::Error> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::trading::broker::{Broker, SimulatedBroker};
+    use crate::trading::decimal::Decimal;
+    use crate::trading::emulator::EmulatingBroker;
+    use crate::trading::feed::Tick;
+    use crate::trading::fill_model::FillModel;
+    use crate::trading::order::{Order, OrderType, Side, TimeInForce};
+    use crate::trading::params::Parameters;
+
+    fn session() -> SessionInfo {
+        SessionInfo {
+            trading_type: TradingType::Paper,
+            isin: String::from("DE0001"),
+            algorithm: String::from("template"),
+            parameters: Parameters::default(),
+            profile: String::from("default"),
+            interval: 60,
+            journal: None,
+            bars: None,
+        }
+    }
+
+    fn tick(minute: u32, price: f64) -> Tick {
+        Tick {
+            time: Utc.with_ymd_and_hms(2021, 3, 1, 9, minute, 0).unwrap(),
+            isin: String::from("DE0001"),
+            price,
+            volume: 10.0,
+            bid: None,
+            ask: None,
+        }
+    }
+
+    #[test]
+    fn the_held_orders_survive_a_checkpoint() {
+        let time = Utc.with_ymd_and_hms(2021, 3, 1, 9, 0, 0).unwrap();
+        let mut emulator = EmulatingBroker::new(Box::new(SimulatedBroker::new(FillModel::default(), Default::default())));
+        let stop = Order {
+            id: 3,
+            ..Order::new(String::from("DE0001"), Side::Sell, Decimal::ONE, OrderType::Stop { stop: Decimal::from_f64(95.0) })
+        };
+        emulator.submit(stop.clone(), time).unwrap();
+
+        let checkpoint = Checkpoint {
+            time,
+            session: session(),
+            prices: vec![100.0],
+            bars: Vec::new(),
+            book: Book::new(),
+            orders: OrderManager::new(),
+            held: emulator.held().to_vec(),
+            sent: Vec::new(),
+            pnl: PnlTracker::new(),
+            journal_entries: Some(1),
+        };
+        let path = env::temp_dir().join(format!("trading-desk-{}-checkpoint.json", process::id()));
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let mut resumed = EmulatingBroker::new(Box::new(SimulatedBroker::new(FillModel::default(), Default::default())));
+        resumed.restore_held(loaded.held);
+        assert_eq!(resumed.open_orders(), vec![stop]);
+        assert_eq!(loaded.session, checkpoint.session);
+    }
+
+    #[test]
+    fn the_orders_at_the_broker_survive_a_checkpoint() {
+        let mut emulator = EmulatingBroker::new(Box::new(SimulatedBroker::new(FillModel::default(), Default::default())));
+        emulator.update(&tick(0, 100.0)).unwrap();
+        let limit = Order {
+            id: 4,
+            time_in_force: TimeInForce::Day,
+            ..Order::new(String::from("DE0001"), Side::Buy, Decimal::ONE, OrderType::Limit { limit: Decimal::from_f64(90.0) })
+        };
+        emulator.submit(limit.clone(), tick(0, 100.0).time).unwrap();
+
+        let checkpoint = Checkpoint {
+            time: tick(1, 100.0).time,
+            session: session(),
+            prices: vec![100.0],
+            bars: Vec::new(),
+            book: Book::new(),
+            orders: OrderManager::new(),
+            held: Vec::new(),
+            sent: emulator.sent(),
+            pnl: PnlTracker::new(),
+            journal_entries: Some(1),
+        };
+        let path = env::temp_dir().join(format!("trading-desk-{}-sent-checkpoint.json", process::id()));
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.sent, checkpoint.sent);
+
+        let mut resumed = EmulatingBroker::new(Box::new(SimulatedBroker::new(FillModel::default(), Default::default())));
+        resumed.restore_sent(loaded.sent, loaded.time);
+        assert_eq!(resumed.open_orders().iter().map(|order| order.id).collect::<Vec<_>>(), vec![4]);
+        // the order still ends with the day the emulator keeps track of
+        assert_eq!(resumed.sent(), checkpoint.sent);
+
+        let fills = resumed.update(&tick(2, 89.0)).unwrap();
+        assert_eq!(fills.iter().map(|fill| (fill.order, fill.quantity)).collect::<Vec<_>>(), vec![(4, Decimal::ONE)]);
+        assert!(resumed.open_orders().is_empty());
+    }
+}
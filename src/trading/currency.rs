@@ -0,0 +1,212 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+
+pub const FX_DIR: &str = "./data/fx/";
+/// the currency of comdirect accounts
+pub const DEFAULT_CURRENCY: &str = "EUR";
+const CSV_HEADER: &str = "time,rate";
+
+/// Exchange rates from the currencies of the instruments to the base currency of the account
+///
+/// The rates of a currency pair are stored in `FX_DIR/<currency><base>.csv`,
+/// one `time,rate` line per rate. The rate is the amount of the base
+/// currency one unit of the other currency is worth.
+///
+/// The price feeds only deliver the prices of the instruments, so the rates aren't recorded with
+/// them. They have to be imported with `settings currencies --import`.
+#[derive(Debug, Clone)]
+pub struct FxRates {
+    base: String,
+    /// ISIN = currency (instruments that aren't listed trade in the base currency)
+    instruments: BTreeMap<String, String>,
+    /// the rates of each currency sorted by time
+    rates: HashMap<String, Vec<(DateTime<Utc>, f64)>>,
+}
+
+impl FxRates {
+    pub fn rate_file(currency: &str, base: &str) -> PathBuf {
+        Path::new(FX_DIR).join(format!("{}{}.csv", currency, base))
+    }
+
+    /// Loads the rates of all currencies the instruments trade in
+    pub fn load(base: &str, instruments: BTreeMap<String, String>) -> Result<Self, String> {
+        let mut rates = HashMap::new();
+        for currency in instruments.values() {
+            if currency == base || rates.contains_key(currency) {
+                continue;
+            }
+
+            let path = Self::rate_file(currency, base);
+            let pair = read_rates(&path).map_err(|err| format!(
+                "Could not read the {}/{} rates from {} ({})\n\
+                rates can be imported with `settings currencies --import`",
+                currency, base, path.display(), err
+            ))?;
+            if pair.is_empty() {
+                return Err(format!("{} contains no rates", path.display()));
+            }
+            rates.insert(currency.clone(), pair);
+        }
+
+        Ok(Self {
+            base: base.to_string(),
+            instruments,
+            rates,
+        })
+    }
+
+    pub fn currency(&self, isin: &str) -> &str {
+        self.instruments.get(isin).map(String::as_str).unwrap_or(&self.base)
+    }
+
+    /// the rate of the currency of the instrument at the time
+    ///
+    /// The last rate before the time is used. There is no rate for times before the first one,
+    /// since using a later rate would be look-ahead.
+    pub fn rate(&self, isin: &str, time: DateTime<Utc>) -> Result<f64, String> {
+        let currency = self.currency(isin);
+        let rates = match self.rates.get(currency) {
+            Some(rates) => rates,
+            None => return Ok(1.0)
+        };
+
+        match rates.binary_search_by(|(rate_time, _)| rate_time.cmp(&time)) {
+            Ok(index) => Ok(rates[index].1),
+            Err(0) => Err(format!(
+                "There is no {}/{} rate at {}, the first one is from {}\n\
+                older rates can be imported with `settings currencies --import`",
+                currency, self.base, time.to_rfc3339(), rates[0].0.to_rfc3339()
+            )),
+            Err(index) => Ok(rates[index - 1].1),
+        }
+    }
+
+    /// Imports rates from a csv file with `time,rate` or `date,rate` lines and returns how many were imported
+    ///
+    /// Dates like 2020-05-04 are taken as the start of the day. The imported
+    /// rates are merged with the existing ones, imported rates win if both have the same time.
+    pub fn import<P: AsRef<Path>>(source: P, currency: &str, base: &str) -> Result<usize, String> {
+        let source = source.as_ref();
+        let data = fs::read_to_string(source).map_err(|err| format!("Could not read {} ({})", source.display(), err))?;
+
+        let mut imported = BTreeMap::new();
+        for (number, line) in data.lines().enumerate() {
+            let line = line.trim();
+            // a header or an empty line
+            if line.is_empty() || line.starts_with(|c: char| c.is_alphabetic()) {
+                continue;
+            }
+            let (time, rate) = parse_line(line).map_err(|err| format!("{} line {}: {}", source.display(), number + 1, err))?;
+            imported.insert(time, rate);
+        }
+        if imported.is_empty() {
+            return Err(format!("{} contains no rates", source.display()));
+        }
+        let count = imported.len();
+
+        let path = Self::rate_file(currency, base);
+        let mut rates: BTreeMap<DateTime<Utc>, f64> = if path.is_file() {
+            read_rates(&path)
+                .map_err(|err| format!("Could not read {} ({})", path.display(), err))?
+                .into_iter()
+                .collect()
+        } else { BTreeMap::new() };
+        rates.extend(imported);
+
+        let mut csv = format!("{}\n", CSV_HEADER);
+        for (time, rate) in rates {
+            csv.push_str(&format!("{},{}\n", time.to_rfc3339(), rate));
+        }
+        fs::create_dir_all(FX_DIR)
+            .and_then(|_| fs::write(&path, csv))
+            .map_err(|err| format!("Could not write {} ({})", path.display(), err))?;
+
+        Ok(count)
+    }
+}
+
+fn read_rates(path: &Path) -> Result<Vec<(DateTime<Utc>, f64)>, io::Error> {
+    let data = fs::read_to_string(path)?;
+    let mut rates = data
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && *line != CSV_HEADER)
+        .map(|line| parse_line(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)))
+        .collect::<Result<Vec<_>, _>>()?;
+    rates.sort_by_key(|(time, _)| *time);
+    Ok(rates)
+}
+
+fn parse_line(line: &str) -> Result<(DateTime<Utc>, f64), String> {
+    let mut fields = line.split(',');
+    let (time, rate) = match (fields.next(), fields.next(), fields.next()) {
+        (Some(time), Some(rate), None) => (time.trim(), rate.trim()),
+        _ => return Err(format!("expected `time,rate` but got `{}`", line))
+    };
+
+    let time = match DateTime::parse_from_rfc3339(time) {
+        Ok(time) => time.with_timezone(&Utc),
+        Err(_) => NaiveDate::parse_from_str(time, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|midnight| Utc.from_utc_datetime(&midnight))
+            .ok_or_else(|| format!("invalid time `{}`", time))?
+    };
+    let rate = match rate.parse::<f64>() {
+        Ok(rate) if rate > 0.0 => rate,
+        _ => return Err(format!("invalid rate `{}`", rate))
+    };
+    Ok((time, rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, day, 0, 0, 0).unwrap()
+    }
+
+    fn rates() -> FxRates {
+        let mut instruments = BTreeMap::new();
+        instruments.insert(String::from("US0378331005"), String::from("USD"));
+        let mut rates = HashMap::new();
+        rates.insert(String::from("USD"), vec![(day(2), 0.8), (day(4), 0.9)]);
+        FxRates { base: String::from(DEFAULT_CURRENCY), instruments, rates }
+    }
+
+    #[test]
+    fn uses_the_last_rate_before_the_time() {
+        let fx = rates();
+        assert_eq!(fx.rate("US0378331005", day(2)), Ok(0.8));
+        assert_eq!(fx.rate("US0378331005", day(3)), Ok(0.8));
+        assert_eq!(fx.rate("US0378331005", day(4)), Ok(0.9));
+        assert_eq!(fx.rate("US0378331005", day(20)), Ok(0.9));
+    }
+
+    #[test]
+    fn has_no_rate_before_the_first_one() {
+        let err = rates().rate("US0378331005", day(1)).unwrap_err();
+        assert!(err.starts_with("There is no USD/EUR rate at 2021-03-01T00:00:00+00:00"), "{}", err);
+    }
+
+    #[test]
+    fn instruments_in_the_base_currency_need_no_rates() {
+        let fx = rates();
+        assert_eq!(fx.currency("DE0005140008"), DEFAULT_CURRENCY);
+        assert_eq!(fx.rate("DE0005140008", day(1)), Ok(1.0));
+    }
+
+    #[test]
+    fn parses_times_and_dates() {
+        assert_eq!(parse_line("2021-03-02T00:00:00+00:00,0.8"), Ok((day(2), 0.8)));
+        assert_eq!(parse_line("2021-03-02, 0.8"), Ok((day(2), 0.8)));
+        assert!(parse_line("2021-03-02,0").is_err());
+        assert!(parse_line("2021-03-02,0.8,1").is_err());
+        assert!(parse_line("yesterday,0.8").is_err());
+    }
+}
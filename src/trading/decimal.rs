@@ -0,0 +1,435 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use log::error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, Visitor};
+
+/// the number of decimal places a `Decimal` keeps
+pub const PLACES: u32 = 8;
+const SCALE: i64 = 100_000_000;
+
+/// A fixed-point number with eight decimal places for prices, quantities and money
+///
+/// Sums and differences are exact, so the accounting doesn't drift like it
+/// does with `f64`. Products and quotients are rounded half away from zero
+/// to the last place. The values range up to about 92 billion, results
+/// beyond that are clamped to the limits and logged as errors.
+///
+/// Decimals are serialized as strings with all their places, so they survive
+/// the round trip exactly. Plain numbers are read as well, since the journals
+/// and checkpoints written before stored them that way.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct Decimal(i64);
+
+impl Decimal {
+    pub const ZERO: Decimal = Decimal(0);
+    pub const ONE: Decimal = Decimal(SCALE);
+
+    /// rounds the value to the last place, NaN and values beyond the limits are rejected
+    pub fn try_from_f64(value: f64) -> Result<Self, String> {
+        let units = (value * SCALE as f64).round();
+        if value.is_nan() {
+            Err(String::from("NaN is not a decimal number"))
+        } else if units < i64::MIN as f64 || units >= i64::MAX as f64 {
+            Err(format!("{} is too large", value))
+        } else {
+            Ok(Decimal(units as i64))
+        }
+    }
+
+    /// Rounds the value to the last place
+    ///
+    /// NaN becomes zero and values beyond the limits are clamped to them, both are logged as errors.
+    pub fn from_f64(value: f64) -> Self {
+        Self::try_from_f64(value).unwrap_or_else(|err| {
+            let decimal = match value {
+                _ if value.is_nan() => Decimal::ZERO,
+                _ if value < 0.0 => Decimal(i64::MIN),
+                _ => Decimal(i64::MAX),
+            };
+            error!("Could not convert a number to a decimal ({}), it became {}", err, decimal);
+            decimal
+        })
+    }
+
+    /// the value for fast math like in the algorithms and the reports
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / SCALE as f64
+    }
+
+    pub fn abs(self) -> Self { Decimal(self.0.saturating_abs()) }
+    pub fn is_zero(self) -> bool { self.0 == 0 }
+    pub fn is_positive(self) -> bool { self.0 > 0 }
+    pub fn is_negative(self) -> bool { self.0 < 0 }
+    /// false for the limits that infinite and too large values are clamped to
+    pub fn is_finite(self) -> bool { self.0 != i64::MAX && self.0 != i64::MIN }
+
+    /// 1, 0 or -1
+    pub fn signum(self) -> Self { Decimal(self.0.signum() * SCALE) }
+
+    /// Converts an amount into another currency
+    pub fn at_rate(self, rate: f64) -> Self {
+        self * Decimal::from_f64(rate)
+    }
+
+    /// the nearest multiple of the tick (halves round away from zero)
+    pub fn round_to(self, tick: Decimal) -> Self {
+        self.to_tick(tick, div_round)
+    }
+
+    /// the next multiple of the tick towards negative infinity
+    pub fn floor_to(self, tick: Decimal) -> Self {
+        self.to_tick(tick, |units, tick| {
+            let quotient = units / tick;
+            if units % tick < 0 { quotient - 1 } else { quotient }
+        })
+    }
+
+    /// the next multiple of the tick towards positive infinity
+    pub fn ceil_to(self, tick: Decimal) -> Self {
+        self.to_tick(tick, |units, tick| {
+            let quotient = units / tick;
+            if units % tick > 0 { quotient + 1 } else { quotient }
+        })
+    }
+
+    fn to_tick<F: Fn(i64, i64) -> i64>(self, tick: Decimal, ticks: F) -> Self {
+        if tick.0 <= 0 {
+            return self;
+        }
+        saturate(ticks(self.0, tick.0) as i128 * tick.0 as i128, || format!("rounding {} to the tick {}", self, tick))
+    }
+}
+
+/// the decimal with the units, results out of range are clamped to the limits
+fn saturate<F: FnOnce() -> String>(units: i128, operation: F) -> Decimal {
+    match i64::try_from(units) {
+        Ok(units) => Decimal(units),
+        Err(_) => {
+            error!("A decimal overflowed in {}, the result is clamped to the limit", operation());
+            Decimal(if units < 0 { i64::MIN } else { i64::MAX })
+        }
+    }
+}
+
+/// divides and rounds half away from zero
+fn div_round(dividend: i64, divisor: i64) -> i64 {
+    div_round_wide(dividend as i128, divisor as i128) as i64
+}
+
+fn div_round_wide(dividend: i128, divisor: i128) -> i128 {
+    let quotient = dividend / divisor;
+    let remainder = dividend % divisor;
+    if remainder.abs() * 2 >= divisor.abs() {
+        quotient + if (dividend < 0) == (divisor < 0) { 1 } else { -1 }
+    } else {
+        quotient
+    }
+}
+
+impl Add for Decimal {
+    type Output = Decimal;
+    fn add(self, other: Decimal) -> Decimal {
+        saturate(self.0 as i128 + other.0 as i128, || format!("{} + {}", self, other))
+    }
+}
+
+impl Sub for Decimal {
+    type Output = Decimal;
+    fn sub(self, other: Decimal) -> Decimal {
+        saturate(self.0 as i128 - other.0 as i128, || format!("{} - {}", self, other))
+    }
+}
+
+impl Mul for Decimal {
+    type Output = Decimal;
+    fn mul(self, other: Decimal) -> Decimal {
+        saturate(
+            div_round_wide(self.0 as i128 * other.0 as i128, SCALE as i128),
+            || format!("{} * {}", self, other),
+        )
+    }
+}
+
+/// panics on a division by zero like the integers do
+#[allow(clippy::suspicious_arithmetic_impl)] // the dividend is scaled up to keep the places
+impl Div for Decimal {
+    type Output = Decimal;
+    fn div(self, other: Decimal) -> Decimal {
+        saturate(
+            div_round_wide(self.0 as i128 * SCALE as i128, other.0 as i128),
+            || format!("{} / {}", self, other),
+        )
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Decimal;
+    fn neg(self) -> Decimal { Decimal(self.0.saturating_neg()) }
+}
+
+impl AddAssign for Decimal {
+    fn add_assign(&mut self, other: Decimal) { *self = *self + other; }
+}
+
+impl SubAssign for Decimal {
+    fn sub_assign(&mut self, other: Decimal) { *self = *self - other; }
+}
+
+impl Sum for Decimal {
+    fn sum<I: Iterator<Item=Decimal>>(iter: I) -> Decimal {
+        iter.fold(Decimal::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Decimal> for Decimal {
+    fn sum<I: Iterator<Item=&'a Decimal>>(iter: I) -> Decimal {
+        iter.copied().sum()
+    }
+}
+
+impl PartialEq<f64> for Decimal {
+    fn eq(&self, other: &f64) -> bool { self.to_f64() == *other }
+}
+
+/// compares with the limits of the settings, which are plain numbers
+impl PartialOrd<f64> for Decimal {
+    fn partial_cmp(&self, other: &f64) -> Option<Ordering> { self.to_f64().partial_cmp(other) }
+}
+
+/// Prints all places that aren't zero or as many as the precision asks for
+impl fmt::Display for Decimal {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the places beyond the eighth are zeros
+        let (units, places, zeros) = match formatter.precision() {
+            Some(precision) if precision < PLACES as usize => {
+                let factor = 10i64.pow(PLACES - precision as u32);
+                (div_round(self.0, factor), precision, 0)
+            }
+            Some(precision) => (self.0, PLACES as usize, precision - PLACES as usize),
+            None => {
+                let mut units = self.0;
+                let mut places = PLACES as usize;
+                while places > 0 && units % 10 == 0 {
+                    units /= 10;
+                    places -= 1;
+                }
+                (units, places, 0)
+            }
+        };
+
+        let digits = units.unsigned_abs().to_string();
+        let digits = if places == 0 {
+            digits
+        } else {
+            let digits = format!("{:0>width$}", digits, width = places + 1);
+            let (integer, fraction) = digits.split_at(digits.len() - places);
+            format!("{}.{}{}", integer, fraction, "0".repeat(zeros))
+        };
+        formatter.pad_integral(units >= 0, "", &digits)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = String;
+
+    /// parses numbers like `12`, `-0.5` or `1.25` without rounding through `f64`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("`{}` is not a decimal number", value);
+        let trimmed = value.trim();
+        let (negative, digits) = match trimmed.chars().next() {
+            Some('-') => (true, &trimmed[1..]),
+            Some('+') => (false, &trimmed[1..]),
+            _ => (false, trimmed),
+        };
+        let (integer, fraction) = match digits.find('.') {
+            Some(index) => (&digits[..index], &digits[index + 1..]),
+            None => (digits, ""),
+        };
+        if integer.is_empty() && fraction.is_empty()
+            || !integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        if fraction.len() > PLACES as usize {
+            return Err(format!("`{}` has more than {} decimal places", value, PLACES));
+        }
+
+        // only digits are left, so parsing can only fail if the number is too large
+        let too_large = || format!("`{}` is too large", value);
+        let integer: i128 = if integer.is_empty() { 0 } else { integer.parse().map_err(|_| too_large())? };
+        let fraction: i128 = if fraction.is_empty() {
+            0
+        } else {
+            fraction.parse::<i128>().map_err(|_| invalid())? * 10i128.pow(PLACES - fraction.len() as u32)
+        };
+        // the negative limit has one more unit than the positive one
+        let units = integer
+            .checked_mul(SCALE as i128)
+            .and_then(|units| units.checked_add(fraction))
+            .map(|units| if negative { -units } else { units })
+            .ok_or_else(too_large)?;
+        i64::try_from(units).map(Decimal).map_err(|_| too_large())
+    }
+}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DecimalVisitor)
+    }
+}
+
+struct DecimalVisitor;
+
+impl<'de> Visitor<'de> for DecimalVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "a number or a string with a decimal number")
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Decimal, E> {
+        Decimal::try_from_f64(value).map_err(E::custom)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Decimal, E> {
+        value.checked_mul(SCALE).map(Decimal).ok_or_else(|| E::custom(format!("{} is too large", value)))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Decimal, E> {
+        i64::try_from(value)
+            .ok()
+            .and_then(|value| value.checked_mul(SCALE))
+            .map(Decimal)
+            .ok_or_else(|| E::custom(format!("{} is too large", value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Decimal, E> {
+        value.parse().map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    const MAX: Decimal = Decimal(i64::MAX);
+    const MIN: Decimal = Decimal(i64::MIN);
+
+    #[test]
+    fn overflows_are_clamped_to_the_limits() {
+        assert_eq!(MAX + Decimal::ONE, MAX);
+        assert_eq!(MIN - Decimal::ONE, MIN);
+        assert_eq!(MAX * dec("2"), MAX);
+        assert_eq!(MAX * dec("-2"), MIN);
+        assert_eq!(MAX / dec("0.5"), MAX);
+        assert_eq!(-MIN, MAX);
+        assert_eq!(MIN.abs(), MAX);
+
+        let mut sum = MAX;
+        sum += Decimal::ONE;
+        assert_eq!(sum, MAX);
+        sum -= MAX;
+        assert_eq!(sum, Decimal::ZERO);
+        assert_eq!(MAX.ceil_to(dec("7")), MAX);
+    }
+
+    #[test]
+    fn products_and_quotients_round_half_away_from_zero() {
+        assert_eq!(dec("0.00000001") * dec("0.5"), dec("0.00000001"));
+        assert_eq!(dec("-0.00000001") * dec("0.5"), dec("-0.00000001"));
+        assert_eq!(dec("0.00000001") * dec("0.49"), Decimal::ZERO);
+        assert_eq!(dec("1") / dec("3"), dec("0.33333333"));
+        assert_eq!(dec("-2") / dec("3"), dec("-0.66666667"));
+    }
+
+    #[test]
+    fn rounds_negative_values_to_ticks() {
+        let tick = dec("0.05");
+        assert_eq!(dec("-1.025").round_to(tick), dec("-1.05"));
+        assert_eq!(dec("-1.02").round_to(tick), dec("-1"));
+        assert_eq!(dec("-1.02").floor_to(tick), dec("-1.05"));
+        assert_eq!(dec("-1.02").ceil_to(tick), dec("-1"));
+        assert_eq!(dec("-1.05").floor_to(tick), dec("-1.05"));
+        assert_eq!(dec("-1.05").ceil_to(tick), dec("-1.05"));
+        assert_eq!(dec("1.02").floor_to(tick), dec("1"));
+        assert_eq!(dec("1.02").ceil_to(tick), dec("1.05"));
+        // without a tick the value stays as it is
+        assert_eq!(dec("-1.02").round_to(Decimal::ZERO), dec("-1.02"));
+    }
+
+    #[test]
+    fn parses_decimal_numbers() {
+        assert_eq!(dec("12"), Decimal(12 * SCALE));
+        assert_eq!(dec(" -0.5 "), Decimal(-SCALE / 2));
+        assert_eq!(dec("+.25"), Decimal(SCALE / 4));
+        assert_eq!(dec("3."), Decimal(3 * SCALE));
+        assert_eq!(dec("0.00000001"), Decimal(1));
+        assert_eq!(dec("-92233720368.54775807"), Decimal(-i64::MAX));
+
+        for invalid in &["", "-", ".", "1e5", "--1", "1.2.3", "0x10", "١"] {
+            assert_eq!(invalid.parse::<Decimal>(), Err(format!("`{}` is not a decimal number", invalid)));
+        }
+        assert_eq!("0.000000001".parse::<Decimal>(), Err(String::from("`0.000000001` has more than 8 decimal places")));
+        assert_eq!("92233720368.54775808".parse::<Decimal>(), Err(String::from("`92233720368.54775808` is too large")));
+        assert_eq!("99999999999999999999".parse::<Decimal>(), Err(String::from("`99999999999999999999` is too large")));
+    }
+
+    #[test]
+    fn prints_the_places_of_the_precision() {
+        assert_eq!(dec("1.5").to_string(), "1.5");
+        assert_eq!(dec("-0.05").to_string(), "-0.05");
+        assert_eq!(dec("100").to_string(), "100");
+        assert_eq!(format!("{:.2}", dec("1.005")), "1.01");
+        assert_eq!(format!("{:.2}", dec("-1.005")), "-1.01");
+        assert_eq!(format!("{:.0}", dec("2.5")), "3");
+        assert_eq!(format!("{:.10}", dec("1.5")), "1.5000000000");
+        assert_eq!(format!("{:.20}", dec("-1.5")), "-1.50000000000000000000");
+        assert_eq!(format!("{:.2}", MAX), "92233720368.55");
+        assert_eq!(MIN.to_string(), "-92233720368.54775808");
+        assert_eq!(format!("{:>8.1}", dec("-2")), "    -2.0");
+    }
+
+    #[test]
+    fn serializes_every_place_exactly() {
+        // more significant digits than an f64 keeps
+        for value in &["92233720368.54775807", "-92233720368.54775808", "123456789.12345678", "0.1", "-3"] {
+            let json = serde_json::to_string(&dec(value)).unwrap();
+            assert_eq!(json, format!("\"{}\"", value));
+            assert_eq!(serde_json::from_str::<Decimal>(&json).unwrap(), dec(value));
+        }
+        // written before decimals were strings
+        assert_eq!(serde_json::from_str::<Decimal>("100.25").unwrap(), dec("100.25"));
+        assert_eq!(serde_json::from_str::<Decimal>("-7").unwrap(), dec("-7"));
+        assert!(serde_json::from_str::<Decimal>("1e30").is_err());
+        assert!(serde_json::from_str::<Decimal>("100000000000").is_err());
+    }
+
+    #[test]
+    fn numbers_that_are_no_decimals_are_rejected() {
+        assert_eq!(Decimal::try_from_f64(0.125), Ok(dec("0.125")));
+        assert_eq!(Decimal::try_from_f64(f64::NAN), Err(String::from("NaN is not a decimal number")));
+        assert_eq!(Decimal::try_from_f64(f64::INFINITY), Err(String::from("inf is too large")));
+        assert_eq!(Decimal::try_from_f64(-1e12), Err(String::from("-1000000000000 is too large")));
+
+        // the conversions that can't fail clamp them
+        assert_eq!(Decimal::from_f64(f64::NAN), Decimal::ZERO);
+        assert_eq!(Decimal::from_f64(f64::INFINITY), MAX);
+        assert_eq!(Decimal::from_f64(f64::NEG_INFINITY), MIN);
+        assert!(!Decimal::from_f64(1e12).is_finite());
+    }
+}
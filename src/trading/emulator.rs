@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use crate::logging::SESSION_TARGET;
+use crate::trading::broker::Broker;
+use crate::trading::decimal::Decimal;
+use crate::trading::feed::Tick;
+use crate::trading::order::{Fill, Order, OrderId, OrderType, Side, TimeInForce};
+
+/// Emulates the orders the broker can't handle itself
+///
+/// Stop, stop-limit and trailing stop orders are held locally and sent to
+/// the broker as market or limit orders once they are triggered. The time in
+/// force and one-cancels-other groups are enforced here for every order the
+/// broker doesn't support natively, so all backends behave the same.
+pub struct EmulatingBroker {
+    broker: Box<dyn Broker>,
+    /// the orders waiting for their trigger
+    held: Vec<HeldOrder>,
+    /// the orders sent to the broker that aren't filled yet
+    working: HashMap<OrderId, Working>,
+    /// the orders that were cancelled here and why
+    cancelled: Vec<(Order, String)>,
+    /// the day orders expire at midnight in this time zone
+    timezone: Tz,
+}
+
+/// An order waiting for its trigger (kept in the checkpoints, since it only exists here)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct HeldOrder {
+    order: Order,
+    placed: DateTime<Utc>,
+    /// the best price since the order was placed (for trailing stops)
+    best: Option<Decimal>,
+}
+
+impl HeldOrder {
+    fn triggered(&mut self, price: Decimal) -> bool {
+        let stop = match self.order.order_type {
+            OrderType::Stop { stop } | OrderType::StopLimit { stop, .. } => stop,
+            OrderType::TrailingStop { distance } => {
+                let best = match (self.best, self.order.side) {
+                    (Some(best), Side::Sell) => best.max(price),
+                    (Some(best), Side::Buy) => best.min(price),
+                    (None, _) => price,
+                };
+                self.best = Some(best);
+                best + self.order.side.sign() * distance
+            }
+            OrderType::Market | OrderType::Limit { .. } => return true,
+        };
+
+        match self.order.side {
+            Side::Buy => price >= stop,
+            Side::Sell => price <= stop,
+        }
+    }
+
+    /// the order that is sent to the broker once the stop is triggered
+    fn triggered_order(&self) -> Order {
+        let order_type = match self.order.order_type {
+            OrderType::StopLimit { limit, .. } => OrderType::Limit { limit },
+            _ => OrderType::Market,
+        };
+        Order { order_type, ..self.order.clone() }
+    }
+}
+
+/// An order that was sent to the broker (kept in the checkpoints with the time in force emulated here)
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SentOrder {
+    /// with the quantity that is left
+    order: Order,
+    placed: DateTime<Utc>,
+    time_in_force: Option<TimeInForce>,
+}
+
+struct Working {
+    remaining: Decimal,
+    placed: DateTime<Utc>,
+    /// None if the broker enforces the time in force itself
+    time_in_force: Option<TimeInForce>,
+    oco: Option<u64>,
+}
+
+impl EmulatingBroker {
+    pub fn new(broker: Box<dyn Broker>) -> Self {
+        Self {
+            broker,
+            held: Vec::new(),
+            working: HashMap::new(),
+            cancelled: Vec::new(),
+            timezone: chrono_tz::UTC,
+        }
+    }
+
+    /// Lets the day orders expire at midnight in the time zone of the exchange instead of UTC
+    pub fn with_timezone(self, timezone: Tz) -> Self {
+        Self { timezone, ..self }
+    }
+
+    /// the orders waiting for their trigger
+    pub fn held(&self) -> &[HeldOrder] {
+        &self.held
+    }
+
+    /// Holds the orders of a resumed session again
+    pub fn restore_held(&mut self, held: Vec<HeldOrder>) {
+        self.held.extend(held);
+    }
+
+    /// the orders at the broker that aren't filled yet
+    pub fn sent(&self) -> Vec<SentOrder> {
+        self.broker
+            .open_orders()
+            .into_iter()
+            .filter_map(|order| {
+                let working = self.working.get(&order.id)?;
+                Some(SentOrder { placed: working.placed, time_in_force: working.time_in_force, order })
+            })
+            .collect()
+    }
+
+    /// Sends the orders of a resumed session to the broker again (with the time of the checkpoint)
+    pub fn restore_sent(&mut self, sent: Vec<SentOrder>, time: DateTime<Utc>) {
+        let orders = sent
+            .into_iter()
+            .map(|sent| {
+                self.working.insert(sent.order.id, Working {
+                    remaining: sent.order.quantity,
+                    placed: sent.placed,
+                    time_in_force: sent.time_in_force,
+                    oco: sent.order.oco,
+                });
+                match sent.time_in_force {
+                    Some(_) => Order { time_in_force: TimeInForce::Gtc, ..sent.order },
+                    None => sent.order,
+                }
+            })
+            .collect();
+        self.broker.restore(orders, time);
+    }
+
+    /// Sends the order to the broker and takes over the time in force if the broker can't handle it
+    ///
+    /// The time in force counts from `placed`, for triggered stops that's when the stop was placed.
+    fn forward(&mut self, order: Order, placed: DateTime<Utc>, time: DateTime<Utc>) -> Result<Vec<Fill>, String> {
+        let mut sent = order.clone();
+        let mut time_in_force = None;
+        if !self.broker.supports(&sent) {
+            sent.time_in_force = TimeInForce::Gtc;
+            time_in_force = Some(order.time_in_force);
+        }
+        if !self.broker.supports(&sent) {
+            return Err(format!("the broker does not support {} orders", order.order_type));
+        }
+
+        self.working.insert(order.id, Working {
+            remaining: order.quantity,
+            placed,
+            time_in_force,
+            oco: order.oco,
+        });
+        let fills = match self.broker.submit(sent, time) {
+            Ok(fills) => fills,
+            Err(err) => {
+                self.working.remove(&order.id);
+                return Err(err);
+            }
+        };
+
+        self.after_fills(&fills);
+        Ok(fills)
+    }
+
+    /// Updates the working orders and cancels the rest of their one-cancels-other groups
+    fn after_fills(&mut self, fills: &[Fill]) {
+        for fill in fills {
+            let group = match self.working.get_mut(&fill.order) {
+                Some(working) => {
+                    working.remaining -= fill.quantity;
+                    let group = working.oco;
+                    if !working.remaining.is_positive() {
+                        self.working.remove(&fill.order);
+                    }
+                    group
+                }
+                None => None
+            };
+
+            if let Some(group) = group {
+                self.cancel_group(group, fill.order);
+            }
+        }
+    }
+
+    fn cancel_group(&mut self, group: u64, filled: OrderId) {
+        let (cancelled, held): (Vec<HeldOrder>, Vec<HeldOrder>) = self.held
+                                    .drain(..)
+                                    .partition(|held| held.order.oco == Some(group));
+        self.held = held;
+        for held in cancelled {
+            info!(target: SESSION_TARGET, "Cancelled {} (oco {} was filled)", held.order, group);
+            self.cancelled.push((held.order, format!("oco {} was filled", group)));
+        }
+
+        let ids: Vec<OrderId> = self.working
+                                    .iter()
+                                    .filter(|(id, working)| working.oco == Some(group) && **id != filled)
+                                    .map(|(id, _)| *id)
+                                    .collect();
+        for id in ids {
+            self.cancel_working(id, &format!("oco {} was filled", group));
+        }
+    }
+
+    fn cancel_working(&mut self, id: OrderId, reason: &str) -> Option<Order> {
+        self.working.remove(&id);
+        let order = self.broker.cancel(id);
+        if let Some(ref order) = order {
+            info!(target: SESSION_TARGET, "Cancelled {} ({})", order, reason);
+            self.cancelled.push((order.clone(), reason.to_string()));
+        }
+        order
+    }
+
+    fn expire(&mut self, now: DateTime<Utc>) {
+        let timezone = self.timezone;
+        let (expired, held): (Vec<HeldOrder>, Vec<HeldOrder>) = self.held
+                                  .drain(..)
+                                  .partition(|held| held.order.time_in_force.expired(held.placed, now, timezone));
+        self.held = held;
+        for held in expired {
+            info!(target: SESSION_TARGET, "Cancelled {} (expired)", held.order);
+            self.cancelled.push((held.order, String::from("expired")));
+        }
+
+        let ids: Vec<OrderId> = self.working
+                                    .iter()
+                                    .filter(|(_, working)| {
+                                        working.time_in_force.is_some_and(|tif| tif.expired(working.placed, now, timezone))
+                                    })
+                                    .map(|(id, _)| *id)
+                                    .collect();
+        for id in ids {
+            self.cancel_working(id, "expired");
+        }
+    }
+}
+
+impl Broker for EmulatingBroker {
+    fn submit(&mut self, order: Order, time: DateTime<Utc>) -> Result<Vec<Fill>, String> {
+        match order.order_type {
+            OrderType::Market | OrderType::Limit { .. } => self.forward(order, time, time),
+            _ if self.broker.supports(&order) => self.forward(order, time, time),
+            // the stop is checked from the next tick on
+            _ => {
+                self.held.push(HeldOrder { order, placed: time, best: None });
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Expired orders are cancelled before the tick, so they can't be filled or triggered by it
+    fn update(&mut self, tick: &Tick) -> Result<Vec<Fill>, String> {
+        self.expire(tick.time);
+        let mut fills = self.broker.update(tick)?;
+        self.after_fills(&fills);
+
+        let price = Decimal::from_f64(tick.price);
+        let mut triggered = Vec::new();
+        let mut index = 0;
+        while index < self.held.len() {
+            if self.held[index].order.isin == tick.isin && self.held[index].triggered(price) {
+                let held = self.held.remove(index);
+                triggered.push((held.triggered_order(), held.placed));
+            } else {
+                index += 1;
+            }
+        }
+        for (order, placed) in triggered {
+            info!(target: SESSION_TARGET, "Triggered {} at {}", order, tick.price);
+            match self.forward(order.clone(), placed, tick.time) {
+                Ok(triggered_fills) => fills.extend(triggered_fills),
+                Err(err) => error!(target: SESSION_TARGET, "Could not execute the triggered {} ({})", order, err)
+            }
+        }
+        Ok(fills)
+    }
+
+    /// the held orders aren't triggered, there is no new price
+    fn flush(&mut self) -> Result<Vec<Fill>, String> {
+        let fills = self.broker.flush()?;
+        self.after_fills(&fills);
+        Ok(fills)
+    }
+
+    /// the broker handles the time in force of the orders itself
+    fn restore(&mut self, orders: Vec<Order>, time: DateTime<Utc>) {
+        let sent = orders
+            .into_iter()
+            .map(|order| SentOrder { placed: time, time_in_force: None, order })
+            .collect();
+        self.restore_sent(sent, time);
+    }
+
+    fn cancel(&mut self, id: OrderId) -> Option<Order> {
+        match self.held.iter().position(|held| held.order.id == id) {
+            Some(index) => Some(self.held.remove(index).order),
+            None => {
+                self.working.remove(&id);
+                self.broker.cancel(id)
+            }
+        }
+    }
+
+    fn cancel_all(&mut self) -> Vec<Order> {
+        self.working.clear();
+        let mut cancelled: Vec<Order> = self.held.drain(..).map(|held| held.order).collect();
+        cancelled.extend(self.broker.cancel_all());
+        cancelled
+    }
+
+    /// the held orders are open as well, they just didn't reach the broker yet
+    fn open_orders(&self) -> Vec<Order> {
+        let mut orders: Vec<Order> = self.held.iter().map(|held| held.order.clone()).collect();
+        orders.extend(self.broker.open_orders());
+        orders
+    }
+
+    fn cancelled(&mut self) -> Vec<(Order, String)> {
+        let mut cancelled: Vec<(Order, String)> = self.cancelled.drain(..).collect();
+        cancelled.extend(self.broker.cancelled());
+        cancelled
+    }
+
+    fn supports(&self, _order: &Order) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::trading::broker::SimulatedBroker;
+    use crate::trading::fill_model::FillModel;
+
+    const ISIN: &str = "DE0001";
+
+    fn time(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, day, hour, minute, 0).unwrap()
+    }
+
+    fn dec(value: f64) -> Decimal {
+        Decimal::from_f64(value)
+    }
+
+    fn tick(time: DateTime<Utc>, price: f64) -> Tick {
+        Tick { time, isin: ISIN.to_string(), price, volume: 100.0, bid: None, ask: None }
+    }
+
+    fn order(id: OrderId, side: Side, order_type: OrderType) -> Order {
+        Order { id, ..Order::new(ISIN.to_string(), side, Decimal::ONE, order_type) }
+    }
+
+    /// an emulator in front of a simulated broker that knows the price 100
+    fn emulator() -> EmulatingBroker {
+        let mut emulator = EmulatingBroker::new(Box::new(SimulatedBroker::new(FillModel::default(), BTreeMap::new())));
+        emulator.update(&tick(time(1, 9, 0), 100.0)).unwrap();
+        emulator
+    }
+
+    /// the ids and prices of the fills
+    fn filled(fills: Vec<Fill>) -> Vec<(OrderId, Decimal)> {
+        fills.into_iter().map(|fill| (fill.order, fill.price)).collect()
+    }
+
+    #[test]
+    fn stops_become_market_orders_once_the_price_reaches_them() {
+        let mut emulator = emulator();
+        emulator.submit(order(1, Side::Sell, OrderType::Stop { stop: dec(95.0) }), time(1, 9, 0)).unwrap();
+        assert_eq!(emulator.held().len(), 1);
+
+        assert!(emulator.update(&tick(time(1, 9, 1), 96.0)).unwrap().is_empty());
+        assert_eq!(filled(emulator.update(&tick(time(1, 9, 2), 94.0)).unwrap()), vec![(1, dec(94.0))]);
+        assert!(emulator.open_orders().is_empty());
+    }
+
+    #[test]
+    fn stop_limits_become_limit_orders_once_the_price_reaches_them() {
+        let mut emulator = emulator();
+        let stop_limit = OrderType::StopLimit { stop: dec(105.0), limit: dec(106.0) };
+        emulator.submit(order(1, Side::Buy, stop_limit), time(1, 9, 0)).unwrap();
+
+        // the price jumped over the limit, so the triggered order rests in the market
+        assert!(emulator.update(&tick(time(1, 9, 1), 108.0)).unwrap().is_empty());
+        assert!(emulator.held().is_empty());
+        assert_eq!(emulator.open_orders()[0].order_type, OrderType::Limit { limit: dec(106.0) });
+        assert_eq!(filled(emulator.update(&tick(time(1, 9, 2), 105.5)).unwrap()), vec![(1, dec(106.0))]);
+    }
+
+    #[test]
+    fn trailing_stops_follow_the_best_price() {
+        let mut emulator = emulator();
+        emulator.submit(order(1, Side::Sell, OrderType::TrailingStop { distance: dec(5.0) }), time(1, 9, 0)).unwrap();
+
+        // the stop rises to 105 with the high of 110 and doesn't fall back with the price
+        for (minute, price) in [(1, 104.0), (2, 110.0), (3, 106.0)].iter() {
+            assert!(emulator.update(&tick(time(1, 9, *minute), *price)).unwrap().is_empty());
+        }
+        assert_eq!(filled(emulator.update(&tick(time(1, 9, 4), 104.5)).unwrap()), vec![(1, dec(104.5))]);
+    }
+
+    #[test]
+    fn a_fill_cancels_the_rest_of_the_oco_group() {
+        let mut emulator = emulator();
+        let take_profit = Order { oco: Some(7), ..order(1, Side::Sell, OrderType::Limit { limit: dec(110.0) }) };
+        let stop_loss = Order { oco: Some(7), ..order(2, Side::Sell, OrderType::Stop { stop: dec(90.0) }) };
+        emulator.submit(take_profit, time(1, 9, 0)).unwrap();
+        emulator.submit(stop_loss.clone(), time(1, 9, 0)).unwrap();
+
+        assert_eq!(filled(emulator.update(&tick(time(1, 9, 1), 111.0)).unwrap()), vec![(1, dec(110.0))]);
+        assert!(emulator.open_orders().is_empty());
+        assert_eq!(emulator.cancelled(), vec![(stop_loss, String::from("oco 7 was filled"))]);
+    }
+
+    #[test]
+    fn orders_expire_before_the_first_tick_after_their_time_in_force() {
+        let mut emulator = emulator();
+        let day = Order { time_in_force: TimeInForce::Day, ..order(1, Side::Buy, OrderType::Limit { limit: dec(85.0) }) };
+        let until = TimeInForce::Gtd(time(1, 12, 0));
+        let stop = Order { time_in_force: until, ..order(2, Side::Sell, OrderType::Stop { stop: dec(90.0) }) };
+        emulator.submit(day.clone(), time(1, 9, 0)).unwrap();
+        emulator.submit(stop.clone(), time(1, 9, 0)).unwrap();
+
+        // the stop expires at noon, even though the price would trigger it
+        assert!(emulator.update(&tick(time(1, 12, 0), 89.0)).unwrap().is_empty());
+        assert_eq!(emulator.cancelled(), vec![(stop, String::from("expired"))]);
+
+        // the limit order would fill on the first tick of the next day
+        assert!(emulator.update(&tick(time(2, 9, 0), 84.0)).unwrap().is_empty());
+        assert_eq!(emulator.cancelled(), vec![(Order { time_in_force: TimeInForce::Gtc, ..day }, String::from("expired"))]);
+        assert!(emulator.open_orders().is_empty());
+    }
+
+    #[test]
+    fn day_orders_end_with_the_day_of_the_exchange() {
+        let berlin = || emulator().with_timezone(chrono_tz::Europe::Berlin);
+        let day = Order { time_in_force: TimeInForce::Day, ..order(1, Side::Sell, OrderType::Stop { stop: dec(90.0) }) };
+
+        // 23:30 UTC is already the next day in Berlin
+        let mut emulator = berlin();
+        emulator.submit(day.clone(), time(1, 22, 0)).unwrap();
+        emulator.update(&tick(time(1, 23, 30), 100.0)).unwrap();
+        assert_eq!(emulator.cancelled().len(), 1);
+
+        // a triggered stop keeps the day it was placed on
+        let mut emulator = berlin();
+        let stop_limit = OrderType::StopLimit { stop: dec(90.0), limit: dec(85.0) };
+        emulator.submit(Order { order_type: stop_limit, ..day }, time(1, 20, 0)).unwrap();
+        emulator.update(&tick(time(1, 22, 0), 80.0)).unwrap();
+        assert_eq!(emulator.held().len(), 0);
+        emulator.update(&tick(time(1, 23, 30), 80.0)).unwrap();
+        assert_eq!(emulator.cancelled().len(), 1);
+        assert!(emulator.open_orders().is_empty());
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use algorithm_utils::Price;
+use chrono::{DateTime, Utc};
+
+pub const PRICE_DIR: &str = "./data/prices/";
+const CSV_HEADER: &str = "time,isin,price,volume";
+/// how long a following feed waits for new prices before the session gets control back
+const FOLLOW_POLL_MS: u64 = 1000;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tick {
+    pub time: DateTime<Utc>,
+    pub isin: String,
+    pub price: Price,
+    pub volume: f64,
+    /// the quotes if they were recorded
+    pub bid: Option<Price>,
+    pub ask: Option<Price>,
+}
+
+impl Tick {
+    /// parses a line of the form `time,isin,price,volume` or `time,isin,price,volume,bid,ask`
+    pub fn from_csv(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(format!("expected 4 or 6 fields but got {} in `{}`", fields.len(), line));
+        }
+
+        let time = DateTime::parse_from_rfc3339(fields[0])
+            .map_err(|err| format!("invalid time `{}` ({})", fields[0], err))?
+            .with_timezone(&Utc);
+        let number = |index: usize, name: &str| fields[index]
+            .parse::<f64>()
+            .map_err(|_| format!("invalid {} `{}`", name, fields[index]));
+        let (bid, ask) = if fields.len() == 6 {
+            (Some(number(4, "bid")?), Some(number(5, "ask")?))
+        } else { (None, None) };
+
+        Ok(Self {
+            time,
+            isin: fields[1].to_string(),
+            price: number(2, "price")?,
+            volume: number(3, "volume")?,
+            bid,
+            ask,
+        })
+    }
+}
+
+/// What a price feed delivered
+#[derive(Debug, PartialEq)]
+pub enum FeedEvent {
+    Tick(Tick),
+    /// there is no new price yet, the session handles its commands and asks again
+    Waiting,
+    /// there are no prices left
+    Exhausted,
+}
+
+/// A source of prices that drives a session
+pub trait PriceFeed {
+    /// Returns the next tick
+    ///
+    /// Live feeds wait a moment for the next price and return `Waiting` if there is none yet.
+    fn next_tick(&mut self) -> Result<FeedEvent, String>;
+}
+
+/// Replays recorded prices (used for back tests)
+pub struct RecordedFeed {
+    ticks: std::vec::IntoIter<Tick>,
+}
+
+impl RecordedFeed {
+    pub fn new(ticks: Vec<Tick>) -> Self {
+        Self { ticks: ticks.into_iter() }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Ok(Self::new(read_ticks(path)?))
+    }
+
+    /// Drops the ticks before `from` and from `to` on
+    pub fn between(self, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Self {
+        let ticks = self.ticks
+                        .filter(|tick| from.is_none_or(|from| tick.time >= from))
+                        .filter(|tick| to.is_none_or(|to| tick.time < to))
+                        .collect();
+        Self::new(ticks)
+    }
+
+    /// the time of the next tick
+    pub fn first_time(&self) -> Option<DateTime<Utc>> {
+        self.ticks.as_slice().first().map(|tick| tick.time)
+    }
+
+    /// the default location of the recorded prices of an ISIN
+    pub fn price_file(isin: &str) -> PathBuf {
+        Path::new(PRICE_DIR).join(format!("{}.csv", isin))
+    }
+}
+
+impl PriceFeed for RecordedFeed {
+    fn next_tick(&mut self) -> Result<FeedEvent, String> {
+        Ok(self.ticks.next().map_or(FeedEvent::Exhausted, FeedEvent::Tick))
+    }
+}
+
+/// Follows the recorded prices of an ISIN while they are written (used for paper trading)
+///
+/// Only the ticks after `after` are delivered, so a resumed session catches
+/// up from its checkpoint. Lines are read once the recorder finished them.
+pub struct FollowingFeed {
+    path: PathBuf,
+    after: DateTime<Utc>,
+    /// the bytes of the file that were read
+    offset: u64,
+    ticks: VecDeque<Tick>,
+    poll: std::time::Duration,
+}
+
+impl FollowingFeed {
+    pub fn new(path: PathBuf, after: DateTime<Utc>) -> Self {
+        Self {
+            path,
+            after,
+            offset: 0,
+            ticks: VecDeque::new(),
+            poll: std::time::Duration::from_millis(FOLLOW_POLL_MS),
+        }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// reads the lines that were completed since the last call
+    fn read_new(&mut self) -> Result<(), io::Error> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            // the recorder didn't start yet
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err)
+        };
+        // a shorter file was replaced, the ticks that were delivered already are skipped by their time
+        if file.metadata()?.len() < self.offset {
+            self.offset = 0;
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let complete = match data.iter().rposition(|byte| *byte == b'\n') {
+            Some(end) => end + 1,
+            None => return Ok(())
+        };
+        self.offset += complete as u64;
+
+        let after = self.after;
+        for line in String::from_utf8_lossy(&data[..complete]).lines() {
+            if line.trim().is_empty() || line.starts_with(CSV_HEADER) {
+                continue;
+            }
+            let tick = Tick::from_csv(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            if tick.time > after {
+                self.after = self.after.max(tick.time);
+                self.ticks.push_back(tick);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PriceFeed for FollowingFeed {
+    fn next_tick(&mut self) -> Result<FeedEvent, String> {
+        if self.ticks.is_empty() {
+            self.read_new()
+                .map_err(|err| format!("Could not read the prices from {} ({})", self.path.display(), err))?;
+        }
+        match self.ticks.pop_front() {
+            Some(tick) => Ok(FeedEvent::Tick(tick)),
+            None => {
+                thread::sleep(self.poll);
+                Ok(FeedEvent::Waiting)
+            }
+        }
+    }
+}
+
+/// reads all ticks from a csv file sorted by time
+pub fn read_ticks<P: AsRef<Path>>(path: P) -> Result<Vec<Tick>, io::Error> {
+    let data = fs::read_to_string(path)?;
+    let mut ticks = Vec::new();
+
+    for line in data.lines() {
+        if line.trim().is_empty() || line.starts_with(CSV_HEADER) {
+            continue;
+        }
+        match Tick::from_csv(line) {
+            Ok(tick) => ticks.push(tick),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err))
+        }
+    }
+
+    ticks.sort_by_key(|tick| tick.time);
+    Ok(ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::process;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn time(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, 1, 9, minute, 0).unwrap()
+    }
+
+    fn line(minute: u32, price: f64) -> String {
+        format!("{},DE0001,{},10\n", time(minute).to_rfc3339(), price)
+    }
+
+    fn prices(feed: &mut dyn PriceFeed) -> Vec<Price> {
+        let mut prices = Vec::new();
+        while let FeedEvent::Tick(tick) = feed.next_tick().unwrap() {
+            prices.push(tick.price);
+        }
+        prices
+    }
+
+    /// a feed that follows a new file in the temporary directory
+    fn following(name: &str, after: DateTime<Utc>) -> FollowingFeed {
+        let path = env::temp_dir().join(format!("trading-desk-{}-{}.csv", process::id(), name));
+        let _ = fs::remove_file(&path);
+        let mut feed = FollowingFeed::new(path, after);
+        feed.poll = std::time::Duration::from_millis(1);
+        feed
+    }
+
+    fn append(feed: &FollowingFeed, data: &str) {
+        let mut file = OpenOptions::new().create(true).append(true).open(feed.path()).unwrap();
+        file.write_all(data.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn recorded_feeds_are_exhausted_after_the_last_tick() {
+        let ticks = vec![Tick::from_csv(&line(1, 100.0)).unwrap()];
+        let mut feed = RecordedFeed::new(ticks).between(Some(time(1)), Some(time(2)));
+        assert_eq!(feed.first_time(), Some(time(1)));
+        assert_eq!(prices(&mut feed), vec![100.0]);
+        assert_eq!(feed.first_time(), None);
+        assert_eq!(feed.next_tick(), Ok(FeedEvent::Exhausted));
+    }
+
+    #[test]
+    fn following_feeds_wait_for_the_file() {
+        let mut feed = following("missing", time(0));
+        assert_eq!(feed.next_tick(), Ok(FeedEvent::Waiting));
+    }
+
+    #[test]
+    fn following_feeds_deliver_the_ticks_after_the_start() {
+        let mut feed = following("after", time(1));
+        append(&feed, &format!("{}\n{}{}{}", CSV_HEADER, line(0, 99.0), line(1, 100.0), line(2, 101.0)));
+        assert_eq!(prices(&mut feed), vec![101.0]);
+        let _ = fs::remove_file(feed.path());
+    }
+
+    #[test]
+    fn following_feeds_deliver_appended_ticks_once_their_line_is_complete() {
+        let mut feed = following("appended", time(0));
+        append(&feed, &line(1, 100.0));
+        assert_eq!(prices(&mut feed), vec![100.0]);
+
+        let next = line(2, 101.0);
+        let (first, second) = next.split_at(10);
+        append(&feed, first);
+        assert_eq!(feed.next_tick(), Ok(FeedEvent::Waiting));
+        append(&feed, second);
+        assert_eq!(prices(&mut feed), vec![101.0]);
+        let _ = fs::remove_file(feed.path());
+    }
+
+    #[test]
+    fn following_feeds_skip_the_delivered_ticks_of_a_replaced_file() {
+        let mut feed = following("replaced", time(0));
+        append(&feed, &format!("{}{}", line(1, 100.0), line(2, 101.0)));
+        assert_eq!(prices(&mut feed), vec![100.0, 101.0]);
+
+        // the new file is shorter than the old one and repeats a delivered tick
+        fs::write(feed.path(), format!("{}{}", line(2, 101.0), line(3, 102.0)).replace(",10\n", ",1\n")).unwrap();
+        assert_eq!(prices(&mut feed), vec![102.0]);
+        let _ = fs::remove_file(feed.path());
+    }
+
+    #[test]
+    fn following_feeds_report_invalid_lines() {
+        let mut feed = following("invalid", time(0));
+        append(&feed, "not a tick\n");
+        assert!(feed.next_tick().is_err());
+        let _ = fs::remove_file(feed.path());
+    }
+}
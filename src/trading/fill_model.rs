@@ -0,0 +1,139 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::trading::decimal::Decimal;
+use crate::trading::feed::Tick;
+use crate::trading::order::Side;
+
+/// How the simulated broker executes orders
+///
+/// The default fills every order immediately, completely and free of charge
+/// at the last price. Each part can be made more realistic on its own.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct FillModel {
+    /// the spread in basis points around the last price for ticks without recorded quotes
+    pub spread: f64,
+    /// the number of ticks an order waits before it reaches the market
+    pub latency_ticks: u32,
+    /// the milliseconds an order waits before it reaches the market
+    pub latency_ms: u64,
+    /// the share of the volume of a tick all orders together can take (unlimited if not set)
+    pub max_volume_share: Option<f64>,
+    pub fees: FeeSchedule,
+}
+
+impl FillModel {
+    /// the price a market order of the side pays on this tick
+    ///
+    /// Quotes that fall between two ticks are rounded against the order.
+    pub fn quote(&self, tick: &Tick, side: Side, tick_size: Option<Decimal>) -> Decimal {
+        let half_spread = tick.price * self.spread / 10_000.0 / 2.0;
+        let tick_size = tick_size.unwrap_or(Decimal::ZERO);
+        match side {
+            Side::Buy => Decimal::from_f64(tick.ask.unwrap_or(tick.price + half_spread)).ceil_to(tick_size),
+            Side::Sell => Decimal::from_f64(tick.bid.unwrap_or(tick.price - half_spread)).floor_to(tick_size),
+        }
+    }
+
+    /// the quantity all orders together can fill on this tick (None if it's unlimited)
+    pub fn available(&self, tick: &Tick) -> Option<Decimal> {
+        self.max_volume_share.map(|share| Decimal::from_f64(tick.volume * share))
+    }
+}
+
+impl fmt::Display for FillModel {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let volume = match self.max_volume_share {
+            Some(share) => format!("{}%", share * 100.0),
+            None => String::from("unlimited")
+        };
+
+        write!(
+            formatter,
+            "FILL MODEL:\n\
+            \tspread: {} bps\n\
+            \tlatency: {} ticks, {} ms\n\
+            \tmax volume share: {}\n\
+            \t{}",
+            self.spread, self.latency_ticks, self.latency_ms, volume, self.fees
+        )
+    }
+}
+
+/// A fixed fee plus a percentage of the order value, limited by a minimum and a maximum
+///
+/// comdirect for example charges `fixed = 4.9`, `percentage = 0.25`, `min = 9.9` and `max = 59.9`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct FeeSchedule {
+    pub fixed: f64,
+    /// in percent of the order value
+    pub percentage: f64,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl FeeSchedule {
+    /// the fee of an order with the value (nothing is charged for nothing)
+    pub fn fee(&self, value: Decimal) -> Decimal {
+        if !value.is_positive() {
+            return Decimal::ZERO;
+        }
+
+        let mut fee = Decimal::from_f64(self.fixed) + value * Decimal::from_f64(self.percentage / 100.0);
+        if let Some(min) = self.min {
+            fee = fee.max(Decimal::from_f64(min));
+        }
+        if let Some(max) = self.max {
+            fee = fee.min(Decimal::from_f64(max));
+        }
+        fee
+    }
+}
+
+impl fmt::Display for FeeSchedule {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "fees: {} + {}%", self.fixed, self.percentage)?;
+        if let Some(min) = self.min {
+            write!(formatter, ", min {}", min)?;
+        }
+        if let Some(max) = self.max {
+            write!(formatter, ", max {}", max)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(value: f64) -> Decimal {
+        Decimal::from_f64(value)
+    }
+
+    fn comdirect() -> FeeSchedule {
+        FeeSchedule { fixed: 4.9, percentage: 0.25, min: Some(9.9), max: Some(59.9) }
+    }
+
+    #[test]
+    fn the_fee_is_the_fixed_part_plus_the_percentage() {
+        assert_eq!(comdirect().fee(dec(4000.0)), dec(14.9));
+        assert_eq!(FeeSchedule { fixed: 1.0, percentage: 0.1, ..FeeSchedule::default() }.fee(dec(100.0)), dec(1.1));
+    }
+
+    #[test]
+    fn the_fee_is_limited_by_the_minimum_and_the_maximum() {
+        assert_eq!(comdirect().fee(dec(100.0)), dec(9.9));
+        assert_eq!(comdirect().fee(dec(100_000.0)), dec(59.9));
+    }
+
+    #[test]
+    fn nothing_is_charged_for_nothing() {
+        assert_eq!(comdirect().fee(Decimal::ZERO), Decimal::ZERO);
+        assert_eq!(comdirect().fee(dec(-100.0)), Decimal::ZERO);
+        assert_eq!(FeeSchedule::default().fee(dec(100.0)), Decimal::ZERO);
+    }
+}
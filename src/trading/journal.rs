@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::trading::currency::DEFAULT_CURRENCY;
+use crate::trading::decimal::Decimal;
+use crate::trading::order::Fill;
+use crate::trading::TradingType;
+
+pub const JOURNAL_DIR: &str = "./data/journal/";
+
+/// A single line of the trade journal
+///
+/// The journal is stored as one json object per line, so it can be read
+/// even if the session died while writing it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum JournalEntry {
+    Start {
+        time: DateTime<Utc>,
+        trading_type: TradingType,
+        algorithm: String,
+        profile: String,
+        capital: f64,
+        /// the base currency of the capital and all reports
+        #[serde(default = "default_currency")]
+        currency: String,
+        /// the capital of every algorithm of a session with several algorithms
+        #[serde(default)]
+        allocations: BTreeMap<String, f64>,
+    },
+    /// the market price of an instrument at a point in time
+    Mark {
+        time: DateTime<Utc>,
+        isin: String,
+        price: Decimal,
+        /// the exchange rate of the currency of the instrument to the base currency
+        #[serde(default = "default_fx_rate")]
+        fx_rate: f64,
+    },
+    Fill(Fill),
+    /// the part of a fill that belongs to an algorithm of a session with several algorithms
+    ///
+    /// Market orders of the algorithms that offset each other are crossed
+    /// internally, their fills only show up here.
+    Attributed {
+        algorithm: String,
+        fill: Fill,
+    },
+}
+
+/// Records the events of a session
+///
+/// The entries are always kept in memory for the report at the end of the
+/// session and additionally written to a file if saving orders is turned on.
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+    file: Option<File>,
+}
+
+impl Journal {
+    pub fn in_memory() -> Self {
+        Self {
+            entries: Vec::new(),
+            file: None,
+        }
+    }
+
+    pub fn to_file<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        Ok(Self {
+            entries: Vec::new(),
+            file: Some(File::create(path)?),
+        })
+    }
+
+    /// Continues an existing journal (the journal of a resumed session)
+    ///
+    /// Only the first `keep` entries (all if None) are kept. The entries after them were written
+    /// after the checkpoint, the resumed session writes them again.
+    pub fn append_to_file<P: AsRef<Path>>(path: P, keep: Option<usize>) -> Result<Self, io::Error> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)?;
+
+        let mut entries = Vec::new();
+        let mut length = 0;
+        for line in data.split_inclusive('\n') {
+            if keep.is_some_and(|keep| entries.len() >= keep) {
+                break;
+            }
+            length += line.len();
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?);
+            }
+        }
+
+        let file = OpenOptions::new().append(true).open(path)?;
+        file.set_len(length as u64)?;
+        Ok(Self {
+            entries,
+            file: Some(file),
+        })
+    }
+
+    /// the default location of the journal of a session
+    pub fn journal_file(time: DateTime<Utc>, isin: &str, trading_type: TradingType) -> PathBuf {
+        Path::new(JOURNAL_DIR).join(format!("{}-{}-{}.jsonl", time.format("%Y%m%d-%H%M%S"), isin, trading_type))
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] { &self.entries }
+
+    pub fn record(&mut self, entry: JournalEntry) {
+        if let Some(ref mut file) = self.file {
+            let written = serde_json::to_string(&entry)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+                .and_then(|line| writeln!(file, "{}", line));
+
+            if let Err(err) = written {
+                // a broken journal must not stop the session, so it is only kept in memory from now on
+                warn!("Could not write to the journal, it will only be kept in memory ({})", err);
+                self.file = None;
+            }
+        }
+
+        self.entries.push(entry);
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<JournalEntry>, io::Error> {
+        let data = fs::read_to_string(path)?;
+        data.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            })
+            .collect()
+    }
+}
+
+fn default_currency() -> String { String::from(DEFAULT_CURRENCY) }
+
+fn default_fx_rate() -> f64 { 1.0 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn mark(minute: u32) -> JournalEntry {
+        JournalEntry::Mark {
+            time: Utc.with_ymd_and_hms(2021, 3, 1, 9, minute, 0).unwrap(),
+            isin: String::from("DE0001"),
+            price: Decimal::from_f64(100.0 + minute as f64),
+            fx_rate: 1.0,
+        }
+    }
+
+    #[test]
+    fn a_continued_journal_drops_the_entries_after_the_checkpoint() {
+        let path = env::temp_dir().join(format!("trading-desk-{}-journal.jsonl", process::id()));
+        let mut journal = Journal::to_file(&path).unwrap();
+        (0..3).for_each(|minute| journal.record(mark(minute)));
+        drop(journal);
+
+        let mut continued = Journal::append_to_file(&path, Some(2)).unwrap();
+        assert_eq!(continued.entries(), &[mark(0), mark(1)]);
+        continued.record(mark(5));
+        drop(continued);
+        let entries = Journal::read(&path).unwrap();
+        let all = Journal::append_to_file(&path, None).map(|journal| journal.entries().to_vec());
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(entries, vec![mark(0), mark(1), mark(5)]);
+        assert_eq!(all.unwrap(), entries);
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::init::settings::{ConfigFile, Profile};
+use crate::trading::pnl::PnlTracker;
+
+/// Loss limits that halt the algorithm once they are breached
+///
+/// Both limits are absolute amounts of money in the base currency of the profile and optional.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct KillSwitchLimits {
+    /// the maximum distance between the highest equity of the session and the current equity
+    pub max_drawdown: Option<f64>,
+    /// the maximum loss since the start of the day
+    pub max_daily_loss: Option<f64>,
+}
+
+impl KillSwitchLimits {
+    /// Returns the reason for halting if a limit was breached
+    pub fn check(&self, pnl: &PnlTracker) -> Option<String> {
+        if let Some(max) = self.max_drawdown {
+            if pnl.drawdown() > max {
+                return Some(format!("drawdown of {:.2} exceeded the maximum of {}", pnl.drawdown(), max));
+            }
+        }
+
+        if let Some(max) = self.max_daily_loss {
+            if -pnl.daily_pnl() > max {
+                return Some(format!("daily loss of {:.2} exceeded the maximum of {}", -pnl.daily_pnl(), max));
+            }
+        }
+
+        None
+    }
+}
+
+impl fmt::Display for KillSwitchLimits {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn limit(value: &Option<f64>) -> String {
+            match value {
+                Some(value) => value.to_string(),
+                None => String::from("none")
+            }
+        }
+
+        write!(
+            formatter,
+            "KILL SWITCH:\n\
+            \tmax drawdown: {}\n\
+            \tmax daily loss: {}",
+            limit(&self.max_drawdown),
+            limit(&self.max_daily_loss)
+        )
+    }
+}
+
+/// Marks the profile as halted in the configuration file
+///
+/// A halted profile can't be started again until the user re-arms it.
+pub fn halt_profile(name: &str, reason: String) -> Result<(), io::Error> {
+    let mut config = ConfigFile::from_config_file()?;
+    halt(&mut config, name, reason);
+    config.to_config_file()
+}
+
+/// the profile is added if it was only used with its defaults so far
+fn halt(config: &mut ConfigFile, name: &str, reason: String) {
+    match config.profiles.iter_mut().find(|profile| profile.name == name) {
+        Some(profile) => profile.halted = Some(reason),
+        None => {
+            let mut profile = Profile::new(name.to_string());
+            profile.halted = Some(reason);
+            config.profiles.push(profile);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::init::settings::Settings;
+    use crate::trading::book::Book;
+    use crate::trading::decimal::Decimal;
+
+    use super::*;
+
+    /// a session that made 100 on the first day and lost 150 of it on the second
+    fn pnl() -> PnlTracker {
+        let mut pnl = PnlTracker::new();
+        pnl.record_fill(Decimal::from_f64(100.0), Decimal::ZERO);
+        pnl.update(&Book::new(), Utc.with_ymd_and_hms(2024, 1, 2, 17, 0, 0).unwrap());
+        pnl.record_fill(Decimal::from_f64(-150.0), Decimal::ZERO);
+        pnl.update(&Book::new(), Utc.with_ymd_and_hms(2024, 1, 3, 9, 0, 0).unwrap());
+        pnl
+    }
+
+    #[test]
+    fn limits_are_breached_once_the_loss_exceeds_them() {
+        let limits = |max_drawdown, max_daily_loss| KillSwitchLimits { max_drawdown, max_daily_loss };
+        assert_eq!(limits(None, None).check(&pnl()), None);
+        assert_eq!(limits(Some(150.0), Some(150.0)).check(&pnl()), None);
+        assert_eq!(
+            limits(Some(149.0), None).check(&pnl()),
+            Some(String::from("drawdown of 150.00 exceeded the maximum of 149")),
+        );
+        assert_eq!(
+            limits(None, Some(100.5)).check(&pnl()),
+            Some(String::from("daily loss of 150.00 exceeded the maximum of 100.5")),
+        );
+        // the drawdown is reported first
+        assert!(limits(Some(10.0), Some(10.0)).check(&pnl()).unwrap().starts_with("drawdown"));
+    }
+
+    #[test]
+    fn a_halted_profile_stays_halted_until_it_is_re_armed() {
+        let mut config = ConfigFile::default();
+        config.profiles.push(Profile::new(String::from("live")));
+        halt(&mut config, "live", String::from("daily loss of 150.00 exceeded the maximum of 100"));
+        // the default profile isn't saved until it is halted
+        halt(&mut config, "default", String::from("drawdown of 150.00 exceeded the maximum of 100"));
+
+        let mut settings = Settings::from(config);
+        let halted = |settings: &Settings, name: &str| settings.profile(name).and_then(|profile| profile.halted.clone());
+        assert_eq!(halted(&settings, "live"), Some(String::from("daily loss of 150.00 exceeded the maximum of 100")));
+        assert!(halted(&settings, "default").is_some());
+
+        assert_eq!(settings.rearm_profile("live"), Ok(()));
+        assert_eq!(halted(&settings, "live"), None);
+        assert!(halted(&settings, "default").is_some());
+        assert_eq!(settings.rearm_profile("unknown"), Err(()));
+    }
+}
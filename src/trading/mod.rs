@@ -0,0 +1,85 @@
+//! The trading runtime
+//!
+//! A session feeds prices into the algorithm, converts the returned
+//! instructions into orders, checks them against the risk limits of the
+//! profile and finally hands them to the broker.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+pub use algorithm::{input, parameter_schema};
+pub use session::start;
+
+mod algorithm;
+pub mod allocation;
+pub mod bars;
+pub mod book;
+mod broker;
+pub mod calendar;
+pub mod checkpoint;
+pub mod currency;
+pub mod decimal;
+mod emulator;
+pub mod feed;
+pub mod fill_model;
+pub mod journal;
+pub mod kill_switch;
+pub mod order;
+pub mod order_manager;
+pub mod params;
+mod pnl;
+pub mod risk;
+mod session;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TradingType {
+    Live,
+    Paper,
+    Back,
+}
+
+impl FromStr for TradingType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "live" => Ok(TradingType::Live),
+            "paper" => Ok(TradingType::Paper),
+            "back" => Ok(TradingType::Back),
+            _ => Err(format!("unknown trading type {}", value))
+        }
+    }
+}
+
+impl fmt::Display for TradingType {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradingType::Live => write!(formatter, "live"),
+            TradingType::Paper => write!(formatter, "paper"),
+            TradingType::Back => write!(formatter, "back"),
+        }
+    }
+}
+
+/// What happens to the positions that are still open when a session ends
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ShutdownPolicy {
+    /// closes all open positions with market orders
+    #[default]
+    Close,
+    /// leaves the open positions untouched
+    Keep,
+}
+
+impl fmt::Display for ShutdownPolicy {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownPolicy::Close => write!(formatter, "close"),
+            ShutdownPolicy::Keep => write!(formatter, "keep"),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::trading::decimal::Decimal;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    /// returns 1 for buys and -1 for sells
+    pub fn sign(&self) -> Decimal {
+        match self {
+            Side::Buy => Decimal::ONE,
+            Side::Sell => -Decimal::ONE,
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Side::Buy => write!(formatter, "buy"),
+            Side::Sell => write!(formatter, "sell"),
+        }
+    }
+}
+
+/// assigned by the runtime when an order is submitted
+pub type OrderId = u64;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderType {
+    Market,
+    /// executed at the limit or better
+    Limit { limit: Decimal },
+    /// becomes a market order once the price reaches the stop
+    Stop { stop: Decimal },
+    /// becomes a limit order once the price reaches the stop
+    StopLimit { stop: Decimal, limit: Decimal },
+    /// a stop that follows the best price since the order was placed at the distance
+    TrailingStop { distance: Decimal },
+}
+
+impl OrderType {
+    /// the worst price the order may be executed at (None if there is none)
+    pub fn limit(&self) -> Option<Decimal> {
+        match *self {
+            OrderType::Limit { limit } | OrderType::StopLimit { limit, .. } => Some(limit),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for OrderType {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderType::Market => write!(formatter, "market"),
+            OrderType::Limit { limit } => write!(formatter, "limit {}", limit),
+            OrderType::Stop { stop } => write!(formatter, "stop {}", stop),
+            OrderType::StopLimit { stop, limit } => write!(formatter, "stop {} limit {}", stop, limit),
+            OrderType::TrailingStop { distance } => write!(formatter, "trailing stop {}", distance),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeInForce {
+    /// until the end of the day the order was placed on (in the time zone of the exchange)
+    Day,
+    /// good till cancelled
+    Gtc,
+    /// good till date
+    Gtd(DateTime<Utc>),
+}
+
+impl TimeInForce {
+    /// whether an order placed at `placed` is expired at `now`, the days end at midnight in the time zone
+    pub fn expired(&self, placed: DateTime<Utc>, now: DateTime<Utc>, timezone: Tz) -> bool {
+        match *self {
+            TimeInForce::Day => now.with_timezone(&timezone).date_naive() != placed.with_timezone(&timezone).date_naive(),
+            TimeInForce::Gtc => false,
+            TimeInForce::Gtd(until) => now >= until,
+        }
+    }
+}
+
+impl fmt::Display for TimeInForce {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeInForce::Day => write!(formatter, "day"),
+            TimeInForce::Gtc => write!(formatter, "gtc"),
+            TimeInForce::Gtd(until) => write!(formatter, "gtd {}", until.format("%Y-%m-%d %H:%M")),
+        }
+    }
+}
+
+/// The runtime representation of an `Instruction`
+///
+/// Every instruction returned by an algorithm is converted into an order
+/// before it is checked by the risk layer and handed to the broker.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Order {
+    pub id: OrderId,
+    pub isin: String,
+    pub side: Side,
+    pub quantity: Decimal,
+    pub order_type: OrderType,
+    pub time_in_force: TimeInForce,
+    /// the orders of a one-cancels-other group are cancelled once one of them fills
+    pub oco: Option<u64>,
+}
+
+impl Order {
+    pub fn new(isin: String, side: Side, quantity: Decimal, order_type: OrderType) -> Self {
+        Self {
+            id: 0,
+            isin,
+            side,
+            quantity,
+            order_type,
+            time_in_force: TimeInForce::Gtc,
+            oco: None,
+        }
+    }
+
+    pub fn market(isin: String, side: Side, quantity: Decimal) -> Self {
+        Self::new(isin, side, quantity, OrderType::Market)
+    }
+
+    /// the worst price the order may be executed at (None if there is none)
+    pub fn limit(&self) -> Option<Decimal> {
+        self.order_type.limit()
+    }
+
+    /// the quantity with the sign of the side (positive for buys)
+    pub fn signed_quantity(&self) -> Decimal {
+        self.side.sign() * self.quantity
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "#{} {} {} {} ({}, {})",
+            self.id, self.side, self.quantity, self.isin, self.order_type, self.time_in_force
+        )?;
+        if let Some(group) = self.oco {
+            write!(formatter, " oco {}", group)?;
+        }
+        Ok(())
+    }
+}
+
+/// An executed order
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Fill {
+    /// the order the fill belongs to (0 in journals written before orders had ids)
+    #[serde(default)]
+    pub order: OrderId,
+    pub time: DateTime<Utc>,
+    pub isin: String,
+    pub side: Side,
+    pub quantity: Decimal,
+    /// in the currency of the instrument like the fee
+    pub price: Decimal,
+    pub fee: Decimal,
+    /// the exchange rate to the base currency of the account at the time of the fill
+    #[serde(default = "default_fx_rate")]
+    pub fx_rate: f64,
+}
+
+impl Fill {
+    pub fn signed_quantity(&self) -> Decimal {
+        self.side.sign() * self.quantity
+    }
+
+    /// the traded value in the base currency
+    pub fn base_value(&self) -> Decimal {
+        (self.quantity * self.price).at_rate(self.fx_rate)
+    }
+
+    /// the fee in the base currency
+    pub fn base_fee(&self) -> Decimal {
+        self.fee.at_rate(self.fx_rate)
+    }
+}
+
+impl fmt::Display for Fill {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} #{} {} {} {} @ {} (fee {})",
+            self.time.format("%Y-%m-%d %H:%M:%S"), self.order, self.side, self.quantity, self.isin, self.price, self.fee
+        )
+    }
+}
+
+fn default_fx_rate() -> f64 { 1.0 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn time(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn day_orders_expire_at_midnight_in_the_time_zone() {
+        let placed = time(1, 10, 0);
+        assert!(!TimeInForce::Day.expired(placed, time(1, 23, 30), chrono_tz::UTC));
+        assert!(TimeInForce::Day.expired(placed, time(2, 0, 0), chrono_tz::UTC));
+
+        // Berlin is an hour ahead of UTC in March
+        assert!(!TimeInForce::Day.expired(placed, time(1, 22, 59), chrono_tz::Europe::Berlin));
+        assert!(TimeInForce::Day.expired(placed, time(1, 23, 0), chrono_tz::Europe::Berlin));
+        // New York is still on the day the order was placed
+        assert!(!TimeInForce::Day.expired(placed, time(2, 4, 0), chrono_tz::America::New_York));
+    }
+
+    #[test]
+    fn good_till_date_orders_expire_at_their_date() {
+        let until = TimeInForce::Gtd(time(3, 12, 0));
+        assert!(!until.expired(time(1, 10, 0), time(3, 11, 59), chrono_tz::UTC));
+        assert!(until.expired(time(1, 10, 0), time(3, 12, 0), chrono_tz::UTC));
+        assert!(!TimeInForce::Gtc.expired(time(1, 10, 0), time(31, 10, 0), chrono_tz::UTC));
+    }
+
+    #[test]
+    fn only_limit_and_stop_limit_orders_have_a_limit() {
+        let limit = Decimal::from_f64(95.0);
+        assert_eq!(OrderType::Limit { limit }.limit(), Some(limit));
+        assert_eq!(OrderType::StopLimit { stop: Decimal::from_f64(96.0), limit }.limit(), Some(limit));
+        assert_eq!(OrderType::Stop { stop: limit }.limit(), None);
+        assert_eq!(OrderType::TrailingStop { distance: limit }.limit(), None);
+    }
+}
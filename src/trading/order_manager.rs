@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::trading::decimal::Decimal;
+use crate::trading::order::{Fill, Order, OrderId};
+
+/// how often the local orders are compared with the orders the broker knows
+const RECONCILE_INTERVAL_SECS: i64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    /// sent to the broker without an answer yet
+    Submitted,
+    /// accepted by the broker
+    Acknowledged,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+impl OrderStatus {
+    /// whether the order can still be filled
+    pub fn is_open(self) -> bool {
+        matches!(self, OrderStatus::Submitted | OrderStatus::Acknowledged | OrderStatus::PartiallyFilled)
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderStatus::Submitted => write!(formatter, "submitted"),
+            OrderStatus::Acknowledged => write!(formatter, "acknowledged"),
+            OrderStatus::PartiallyFilled => write!(formatter, "partially filled"),
+            OrderStatus::Filled => write!(formatter, "filled"),
+            OrderStatus::Cancelled => write!(formatter, "cancelled"),
+            OrderStatus::Rejected => write!(formatter, "rejected"),
+        }
+    }
+}
+
+/// An order and everything that happened to it so far
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct TrackedOrder {
+    pub order: Order,
+    pub status: OrderStatus,
+    pub filled: Decimal,
+    /// the average price of the fills (None before the first fill)
+    pub average_price: Option<Decimal>,
+    /// why the order was rejected or cancelled
+    pub reason: Option<String>,
+    pub updated: DateTime<Utc>,
+    /// the quantity the broker has left if it differs from the one left locally
+    #[serde(default)]
+    pub broker_remaining: Option<Decimal>,
+}
+
+impl TrackedOrder {
+    pub fn remaining(&self) -> Decimal {
+        (self.order.quantity - self.filled).max(Decimal::ZERO)
+    }
+}
+
+impl fmt::Display for TrackedOrder {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}: {} ({} of {} filled", self.order, self.status, self.filled, self.order.quantity)?;
+        if let Some(price) = self.average_price {
+            write!(formatter, " at {:.4}", price)?;
+        }
+        write!(formatter, ")")?;
+        if let Some(remaining) = self.broker_remaining {
+            write!(formatter, " ({} left at the broker)", remaining)?;
+        }
+        if let Some(ref reason) = self.reason {
+            write!(formatter, " ({})", reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Tracks every order of a session from its submission until it is done
+///
+/// The manager assigns the order ids. Its view of the open orders is
+/// periodically compared with the open orders of the broker, since the
+/// broker is what actually counts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OrderManager {
+    orders: BTreeMap<OrderId, TrackedOrder>,
+    /// the last id that was assigned
+    last_id: OrderId,
+    last_reconciliation: Option<DateTime<Utc>>,
+}
+
+impl OrderManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assigns an id to the order and starts tracking it
+    pub fn submitted(&mut self, order: &mut Order, time: DateTime<Utc>) {
+        self.last_id += 1;
+        order.id = self.last_id;
+
+        self.orders.insert(order.id, TrackedOrder {
+            order: order.clone(),
+            status: OrderStatus::Submitted,
+            filled: Decimal::ZERO,
+            average_price: None,
+            reason: None,
+            updated: time,
+            broker_remaining: None,
+        });
+    }
+
+    pub fn acknowledged(&mut self, id: OrderId, time: DateTime<Utc>) {
+        if let Some(tracked) = self.orders.get_mut(&id) {
+            if tracked.status == OrderStatus::Submitted {
+                tracked.status = OrderStatus::Acknowledged;
+                tracked.updated = time;
+            }
+        }
+    }
+
+    pub fn rejected(&mut self, id: OrderId, reason: String, time: DateTime<Utc>) {
+        self.close(id, OrderStatus::Rejected, reason, time);
+    }
+
+    pub fn cancelled(&mut self, id: OrderId, reason: String, time: DateTime<Utc>) {
+        self.close(id, OrderStatus::Cancelled, reason, time);
+    }
+
+    /// Adds the fill to its order and returns the new status (None for unknown orders)
+    pub fn filled(&mut self, fill: &Fill) -> Option<OrderStatus> {
+        let tracked = self.orders.get_mut(&fill.order)?;
+
+        let value = tracked.average_price.unwrap_or(Decimal::ZERO) * tracked.filled + fill.price * fill.quantity;
+        tracked.filled += fill.quantity;
+        if tracked.filled.is_positive() {
+            tracked.average_price = Some(value / tracked.filled);
+        }
+        tracked.status = if tracked.remaining().is_zero() {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
+        tracked.updated = fill.time;
+        if tracked.broker_remaining == Some(tracked.remaining()) {
+            tracked.broker_remaining = None;
+        }
+
+        Some(tracked.status)
+    }
+
+    /// the orders that can still be filled
+    pub fn open_orders(&self) -> impl Iterator<Item=&TrackedOrder> {
+        self.orders.values().filter(|tracked| tracked.status.is_open())
+    }
+
+    /// whether it's time to compare the local orders with the broker
+    pub fn reconciliation_due(&self, time: DateTime<Utc>) -> bool {
+        match self.last_reconciliation {
+            Some(last) => time - last >= Duration::seconds(RECONCILE_INTERVAL_SECS),
+            None => true,
+        }
+    }
+
+    /// Compares the open orders with the ones the broker reports and returns the new mismatches
+    ///
+    /// Orders the broker doesn't know anymore are cancelled locally. Orders
+    /// with another remaining quantity at the broker are only flagged, the
+    /// filled quantity changes with the fills alone, so it always matches the
+    /// book. A mismatch is reported once until the quantities differ again.
+    pub fn reconcile(&mut self, broker_orders: &[Order], time: DateTime<Utc>) -> Vec<String> {
+        self.last_reconciliation = Some(time);
+        let mut mismatches = Vec::new();
+
+        let open: Vec<OrderId> = self.open_orders().map(|tracked| tracked.order.id).collect();
+        for id in open {
+            match broker_orders.iter().find(|order| order.id == id) {
+                Some(order) => {
+                    // the unwrap is fine since the id was taken from the open orders
+                    let tracked = self.orders.get_mut(&id).unwrap();
+                    let broker_remaining = Some(order.quantity).filter(|&remaining| remaining != tracked.remaining());
+                    if broker_remaining.is_some() && broker_remaining != tracked.broker_remaining {
+                        mismatches.push(format!(
+                            "#{} has {} left locally but {} at the broker", id, tracked.remaining(), order.quantity
+                        ));
+                        tracked.updated = time;
+                    }
+                    tracked.broker_remaining = broker_remaining;
+                }
+                None => {
+                    mismatches.push(format!("#{} is open locally but unknown to the broker", id));
+                    self.cancelled(id, String::from("unknown to the broker"), time);
+                }
+            }
+        }
+
+        for order in broker_orders {
+            match self.orders.get(&order.id) {
+                Some(tracked) if tracked.status.is_open() => {}
+                Some(tracked) => mismatches.push(format!("#{} is {} locally but open at the broker", order.id, tracked.status)),
+                None => mismatches.push(format!("{} is open at the broker but unknown locally", order)),
+            }
+        }
+
+        mismatches
+    }
+
+    fn close(&mut self, id: OrderId, status: OrderStatus, reason: String, time: DateTime<Utc>) {
+        if let Some(tracked) = self.orders.get_mut(&id) {
+            tracked.status = status;
+            tracked.reason = Some(reason);
+            tracked.updated = time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::trading::order::Side;
+
+    fn time(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, 1, 9, 0, second).unwrap()
+    }
+
+    fn dec(value: f64) -> Decimal {
+        Decimal::from_f64(value)
+    }
+
+    /// a manager with a submitted market order of `quantity`
+    fn manager(quantity: f64) -> (OrderManager, Order) {
+        let mut manager = OrderManager::new();
+        let mut order = Order::market(String::from("DE0001"), Side::Buy, dec(quantity));
+        manager.submitted(&mut order, time(0));
+        (manager, order)
+    }
+
+    fn fill(order: &Order, quantity: f64, price: f64) -> Fill {
+        Fill {
+            order: order.id,
+            time: time(1),
+            isin: order.isin.clone(),
+            side: order.side,
+            quantity: dec(quantity),
+            price: dec(price),
+            fee: Decimal::ZERO,
+            fx_rate: 1.0,
+        }
+    }
+
+    fn tracked(manager: &OrderManager, id: OrderId) -> &TrackedOrder {
+        &manager.orders[&id]
+    }
+
+    #[test]
+    fn submitted_orders_get_increasing_ids() {
+        let (mut manager, first) = manager(1.0);
+        let mut second = Order::market(String::from("DE0001"), Side::Sell, dec(1.0));
+        manager.submitted(&mut second, time(0));
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(manager.open_orders().count(), 2);
+    }
+
+    #[test]
+    fn fills_update_the_status_and_the_average_price() {
+        let (mut manager, order) = manager(10.0);
+        manager.acknowledged(order.id, time(0));
+        assert_eq!(tracked(&manager, order.id).status, OrderStatus::Acknowledged);
+
+        assert_eq!(manager.filled(&fill(&order, 4.0, 100.0)), Some(OrderStatus::PartiallyFilled));
+        assert_eq!(manager.filled(&fill(&order, 6.0, 105.0)), Some(OrderStatus::Filled));
+        let tracked = tracked(&manager, order.id);
+        assert_eq!((tracked.filled, tracked.average_price), (dec(10.0), Some(dec(103.0))));
+        assert_eq!(manager.open_orders().count(), 0);
+    }
+
+    #[test]
+    fn fills_of_unknown_orders_are_ignored() {
+        let (mut manager, mut order) = manager(1.0);
+        order.id = 7;
+        assert_eq!(manager.filled(&fill(&order, 1.0, 100.0)), None);
+    }
+
+    #[test]
+    fn closed_orders_are_not_acknowledged_again() {
+        let (mut manager, order) = manager(1.0);
+        manager.rejected(order.id, String::from("no money"), time(1));
+        manager.acknowledged(order.id, time(2));
+        let tracked = tracked(&manager, order.id);
+        assert_eq!(tracked.status, OrderStatus::Rejected);
+        assert_eq!(tracked.reason.as_deref(), Some("no money"));
+        assert_eq!(tracked.updated, time(1));
+    }
+
+    #[test]
+    fn reconciliation_is_due_every_minute() {
+        let (mut manager, _) = manager(1.0);
+        assert!(manager.reconciliation_due(time(0)));
+        manager.reconcile(&[], time(0));
+        assert!(!manager.reconciliation_due(time(59)));
+        assert!(manager.reconciliation_due(time(0) + Duration::minutes(1)));
+    }
+
+    #[test]
+    fn reconciling_flags_other_quantities_at_the_broker_without_filling() {
+        let (mut manager, order) = manager(10.0);
+        manager.filled(&fill(&order, 2.0, 100.0));
+        let at_broker = Order { quantity: dec(5.0), ..order.clone() };
+
+        let mismatches = manager.reconcile(std::slice::from_ref(&at_broker), time(2));
+        assert_eq!(mismatches, vec!["#1 has 8 left locally but 5 at the broker"]);
+        let tracked_order = tracked(&manager, order.id);
+        assert_eq!((tracked_order.filled, tracked_order.remaining()), (dec(2.0), dec(8.0)));
+        assert_eq!(tracked_order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(tracked_order.average_price, Some(dec(100.0)));
+        assert_eq!(tracked_order.broker_remaining, Some(dec(5.0)));
+        assert_eq!(tracked_order.updated, time(2));
+
+        // the mismatch is only reported once and cleared by the fill that was missing
+        assert!(manager.reconcile(&[at_broker], time(3)).is_empty());
+        manager.filled(&fill(&order, 3.0, 101.0));
+        assert_eq!(tracked(&manager, order.id).broker_remaining, None);
+    }
+
+    #[test]
+    fn reconciling_keeps_the_fills_the_broker_doesnt_know() {
+        let (mut manager, order) = manager(10.0);
+        manager.filled(&fill(&order, 2.0, 100.0));
+
+        assert_eq!(manager.reconcile(std::slice::from_ref(&order), time(2)).len(), 1);
+        let tracked = tracked(&manager, order.id);
+        assert_eq!(tracked.filled, dec(2.0));
+        assert_eq!(tracked.status, OrderStatus::PartiallyFilled);
+        assert_eq!(tracked.average_price, Some(dec(100.0)));
+        assert_eq!(tracked.broker_remaining, Some(dec(10.0)));
+    }
+
+    #[test]
+    fn matching_orders_are_no_mismatch() {
+        let (mut manager, order) = manager(10.0);
+        manager.filled(&fill(&order, 2.0, 100.0));
+        let at_broker = Order { quantity: dec(8.0), ..order.clone() };
+        assert!(manager.reconcile(&[at_broker], time(2)).is_empty());
+        assert_eq!(tracked(&manager, order.id).status, OrderStatus::PartiallyFilled);
+    }
+
+    #[test]
+    fn orders_unknown_to_the_broker_are_cancelled() {
+        let (mut manager, order) = manager(10.0);
+        assert_eq!(manager.reconcile(&[], time(2)).len(), 1);
+        assert_eq!(tracked(&manager, order.id).status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    fn orders_only_open_at_the_broker_are_reported() {
+        let (mut manager, order) = manager(10.0);
+        manager.cancelled(order.id, String::from("stopped"), time(1));
+        let unknown = Order { id: 9, ..order.clone() };
+        let mismatches = manager.reconcile(&[order, unknown], time(2));
+        assert_eq!(mismatches.len(), 2);
+        assert!(mismatches[0].contains("cancelled locally"));
+        assert!(mismatches[1].contains("unknown locally"));
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The type of an algorithm parameter
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamKind {
+    Integer,
+    Float,
+    Bool,
+    String,
+}
+
+impl fmt::Display for ParamKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamKind::Integer => write!(formatter, "integer"),
+            ParamKind::Float => write!(formatter, "float"),
+            ParamKind::Bool => write!(formatter, "bool"),
+            ParamKind::String => write!(formatter, "string"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl ParamValue {
+    /// the numeric value used for range checks
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ParamValue::Integer(value) => Some(*value as f64),
+            ParamValue::Float(value) => Some(*value),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::Bool(value) => write!(formatter, "{}", value),
+            ParamValue::Integer(value) => write!(formatter, "{}", value),
+            ParamValue::Float(value) => write!(formatter, "{}", value),
+            ParamValue::String(value) => write!(formatter, "{}", value),
+        }
+    }
+}
+
+/// A single parameter an algorithm declares in its export metadata
+///
+/// The schema is a toml string with one `[[parameter]]` table per parameter:
+/// ```toml
+/// [[parameter]]
+/// name = "window"
+/// type = "integer"
+/// default = 20
+/// min = 5
+/// max = 100
+/// description = "the number of prices the average is calculated over"
+/// ```
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ParamSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: ParamKind,
+    pub default: ParamValue,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Deserialize)]
+struct Schema {
+    #[serde(default)]
+    parameter: Vec<ParamSpec>,
+}
+
+impl ParamSpec {
+    pub fn parse_schema(schema: &str) -> Result<Vec<Self>, String> {
+        let schema: Schema = toml::from_str(schema).map_err(|err| format!("invalid parameter schema ({})", err))?;
+        for spec in schema.parameter.iter() {
+            spec.validate(&spec.default)
+                .map_err(|err| format!("invalid default of {} ({})", spec.name, err))?;
+        }
+        Ok(schema.parameter)
+    }
+
+    /// Parses a value passed on the command line according to the type of the parameter
+    pub fn parse_value(&self, value: &str) -> Result<ParamValue, String> {
+        let parsed = match self.kind {
+            ParamKind::Integer => value.parse().map(ParamValue::Integer).map_err(|_| ()),
+            ParamKind::Float => value.parse().map(ParamValue::Float).map_err(|_| ()),
+            ParamKind::Bool => value.parse().map(ParamValue::Bool).map_err(|_| ()),
+            ParamKind::String => Ok(ParamValue::String(value.to_string())),
+        };
+        let parsed = parsed.map_err(|_| format!("{} is not a valid {}", value, self.kind))?;
+
+        self.validate(&parsed)?;
+        Ok(parsed)
+    }
+
+    /// Checks the type and the range of a value (integers are accepted for floats, NaN and infinity are not)
+    pub fn validate(&self, value: &ParamValue) -> Result<ParamValue, String> {
+        let value = match (self.kind, value) {
+            (ParamKind::Integer, ParamValue::Integer(_))
+            | (ParamKind::Float, ParamValue::Float(_))
+            | (ParamKind::Bool, ParamValue::Bool(_))
+            | (ParamKind::String, ParamValue::String(_)) => value.clone(),
+            (ParamKind::Float, ParamValue::Integer(integer)) => ParamValue::Float(*integer as f64),
+            _ => return Err(format!("{} is not a valid {}", value, self.kind))
+        };
+
+        if let Some(number) = value.as_f64() {
+            if !number.is_finite() {
+                return Err(format!("{} is not a finite number", value));
+            }
+            if self.min.is_some_and(|min| number < min) || self.max.is_some_and(|max| number > max) {
+                return Err(format!("{} is not in the range {}", value, self.range()));
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn range(&self) -> String {
+        let bound = |bound: Option<f64>| bound.map(|bound| bound.to_string()).unwrap_or_default();
+        format!("[{}, {}]", bound(self.min), bound(self.max))
+    }
+}
+
+impl fmt::Display for ParamSpec {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{} ({}, default {}", self.name, self.kind, self.default)?;
+        if self.min.is_some() || self.max.is_some() {
+            write!(formatter, ", range {}", self.range())?;
+        }
+        write!(formatter, ")")?;
+        if !self.description.is_empty() {
+            write!(formatter, "\n\t\t{}", self.description)?;
+        }
+        Ok(())
+    }
+}
+
+/// The validated parameter values handed to `init`
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Parameters(BTreeMap<String, ParamValue>);
+
+impl Parameters {
+    /// Resolves the values of all parameters
+    ///
+    /// The defaults of the schema are overridden by the profile values which
+    /// are overridden by the values passed on the command line (`key=value`).
+    pub fn resolve(
+        schema: &[ParamSpec],
+        profile: Option<&BTreeMap<String, ParamValue>>,
+        args: &[&str],
+    ) -> Result<Self, String> {
+        let find = |name: &str| {
+            schema
+                .iter()
+                .find(|spec| spec.name == name)
+                .ok_or_else(|| format!("unknown parameter {}", name))
+        };
+
+        let mut values: BTreeMap<String, ParamValue> = schema
+            .iter()
+            .map(|spec| (spec.name.clone(), spec.default.clone()))
+            .collect();
+
+        for (name, value) in profile.into_iter().flatten() {
+            let value = find(name)?
+                .validate(value)
+                .map_err(|err| format!("invalid value for {} in the profile ({})", name, err))?;
+            values.insert(name.clone(), value);
+        }
+
+        for arg in args {
+            let mut split = arg.splitn(2, '=');
+            let (name, value) = match (split.next(), split.next()) {
+                (Some(name), Some(value)) => (name.trim(), value.trim()),
+                _ => return Err(format!("parameters have to be passed as key=value but got {}", arg))
+            };
+            let value = find(name)?
+                .parse_value(value)
+                .map_err(|err| format!("invalid value for {} ({})", name, err))?;
+            values.insert(name.to_string(), value);
+        }
+
+        Ok(Self(values))
+    }
+
+    /// the toml table the algorithm receives in `init`
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(&self.0).map_err(|err| format!("Could not write the parameters {} as toml ({})", self, err))
+    }
+}
+
+impl fmt::Display for Parameters {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values: Vec<String> = self.0
+                                      .iter()
+                                      .map(|(name, value)| format!("{}={}", name, value))
+                                      .collect();
+        write!(formatter, "{}", values.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+[[parameter]]
+name = "window"
+type = "integer"
+default = 20
+min = 5
+max = 100
+
+[[parameter]]
+name = "threshold"
+type = "float"
+default = 0.5
+min = 0.0
+max = 1.0
+description = "how far the price has to move"
+
+[[parameter]]
+name = "short"
+type = "bool"
+default = false
+"#;
+
+    fn schema() -> Vec<ParamSpec> {
+        ParamSpec::parse_schema(SCHEMA).unwrap()
+    }
+
+    fn spec(name: &str) -> ParamSpec {
+        schema().into_iter().find(|spec| spec.name == name).unwrap()
+    }
+
+    #[test]
+    fn parses_the_schema() {
+        let schema = schema();
+        assert_eq!(schema.len(), 3);
+        assert_eq!((schema[0].kind, schema[0].min, schema[0].max), (ParamKind::Integer, Some(5.0), Some(100.0)));
+        assert_eq!(schema[1].default, ParamValue::Float(0.5));
+        assert_eq!(schema[1].description, "how far the price has to move");
+        assert_eq!(schema[2].default, ParamValue::Bool(false));
+        assert!(ParamSpec::parse_schema("").unwrap().is_empty());
+
+        let invalid_default = "[[parameter]]\nname = \"window\"\ntype = \"integer\"\ndefault = 2\nmin = 5";
+        assert_eq!(
+            ParamSpec::parse_schema(invalid_default),
+            Err(String::from("invalid default of window (2 is not in the range [5, ])")),
+        );
+        assert!(ParamSpec::parse_schema("[[parameter]]\nname = \"x\"\ntype = \"date\"\ndefault = 1").is_err());
+    }
+
+    #[test]
+    fn parses_the_values_of_the_command_line_by_type() {
+        assert_eq!(spec("window").parse_value("30"), Ok(ParamValue::Integer(30)));
+        assert_eq!(spec("threshold").parse_value("0.25"), Ok(ParamValue::Float(0.25)));
+        assert_eq!(spec("short").parse_value("true"), Ok(ParamValue::Bool(true)));
+        assert_eq!(spec("window").parse_value("3.5"), Err(String::from("3.5 is not a valid integer")));
+        assert_eq!(spec("short").parse_value("yes"), Err(String::from("yes is not a valid bool")));
+        assert_eq!(spec("window").parse_value("101"), Err(String::from("101 is not in the range [5, 100]")));
+    }
+
+    #[test]
+    fn validation_rejects_other_types_and_numbers_that_are_not_finite() {
+        let threshold = spec("threshold");
+        assert_eq!(threshold.validate(&ParamValue::Integer(1)), Ok(ParamValue::Float(1.0)));
+        assert_eq!(threshold.validate(&ParamValue::Bool(true)), Err(String::from("true is not a valid float")));
+        assert_eq!(spec("window").validate(&ParamValue::Float(10.0)), Err(String::from("10 is not a valid integer")));
+
+        // NaN passes every range check, so it has to be rejected before them
+        for value in &["NaN", "inf", "-inf"] {
+            assert!(threshold.parse_value(value).unwrap_err().contains("is not a finite number"));
+        }
+        let unbounded = ParamSpec { min: None, max: None, ..threshold };
+        assert_eq!(unbounded.validate(&ParamValue::Float(f64::INFINITY)), Err(String::from("inf is not a finite number")));
+    }
+
+    #[test]
+    fn the_command_line_overrides_the_profile_which_overrides_the_defaults() {
+        let schema = schema();
+        let profile: BTreeMap<String, ParamValue> = vec![
+            (String::from("window"), ParamValue::Integer(10)),
+            (String::from("threshold"), ParamValue::Float(0.7)),
+        ].into_iter().collect();
+
+        let parameters = Parameters::resolve(&schema, Some(&profile), &["threshold = 0.9"]).unwrap();
+        assert_eq!(parameters.to_string(), "short=false threshold=0.9 window=10");
+        assert_eq!(Parameters::resolve(&schema, None, &[]).unwrap().to_string(), "short=false threshold=0.5 window=20");
+    }
+
+    #[test]
+    fn resolving_rejects_unknown_and_invalid_parameters() {
+        let schema = schema();
+        assert_eq!(Parameters::resolve(&schema, None, &["size=3"]), Err(String::from("unknown parameter size")));
+        assert_eq!(
+            Parameters::resolve(&schema, None, &["window"]),
+            Err(String::from("parameters have to be passed as key=value but got window")),
+        );
+        assert_eq!(
+            Parameters::resolve(&schema, None, &["window=1"]),
+            Err(String::from("invalid value for window (1 is not in the range [5, 100])")),
+        );
+
+        let profile = vec![(String::from("threshold"), ParamValue::Float(f64::NAN))].into_iter().collect();
+        assert_eq!(
+            Parameters::resolve(&schema, Some(&profile), &[]),
+            Err(String::from("invalid value for threshold in the profile (NaN is not a finite number)")),
+        );
+    }
+
+    #[test]
+    fn the_algorithm_receives_a_toml_table() {
+        let parameters = Parameters::resolve(&schema(), None, &["window=30"]).unwrap();
+        let table: toml::value::Table = toml::from_str(&parameters.to_toml().unwrap()).unwrap();
+        assert_eq!(table["window"].as_integer(), Some(30));
+        assert_eq!(table["threshold"].as_float(), Some(0.5));
+        assert_eq!(table["short"].as_bool(), Some(false));
+    }
+}
@@ -0,0 +1,133 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::trading::book::Book;
+use crate::trading::decimal::Decimal;
+
+/// Tracks the profit and loss of a session
+///
+/// The equity of a session is the realised profit minus the fees plus
+/// the unrealised profit of all open positions.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PnlTracker {
+    realised: Decimal,
+    fees: Decimal,
+    equity: Decimal,
+    /// the highest equity reached during the session
+    peak: Decimal,
+    day: Option<NaiveDate>,
+    /// the equity the previous day closed with
+    day_start: Decimal,
+}
+
+impl PnlTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_fill(&mut self, realised: Decimal, fee: Decimal) {
+        self.realised += realised;
+        self.fees += fee;
+    }
+
+    /// Recalculates the equity based on the current prices in the book
+    ///
+    /// The first update of a day starts the day with the equity the previous
+    /// day closed with, so the fills and price moves of its first tick (like
+    /// an overnight gap) count towards the new day.
+    pub fn update(&mut self, book: &Book, time: DateTime<Utc>) {
+        let today = time.naive_utc().date();
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.day_start = self.equity;
+        }
+
+        self.equity = self.realised - self.fees + book.unrealised_pnl();
+
+        if self.equity > self.peak {
+            self.peak = self.equity;
+        }
+    }
+
+    pub fn realised(&self) -> Decimal { self.realised }
+    pub fn fees(&self) -> Decimal { self.fees }
+
+    /// the profit or loss since the start of the session
+    pub fn session_pnl(&self) -> Decimal { self.equity }
+
+    /// the profit or loss since the start of the current day
+    pub fn daily_pnl(&self) -> Decimal { self.equity - self.day_start }
+
+    /// the distance between the highest equity and the current equity
+    pub fn drawdown(&self) -> Decimal { self.peak - self.equity }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::trading::order::{Fill, Side};
+
+    use super::*;
+
+    fn time(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, day, hour, 0, 0).unwrap()
+    }
+
+    fn dec(value: f64) -> Decimal {
+        Decimal::from_f64(value)
+    }
+
+    /// a book holding 10 bought at 100
+    fn book() -> Book {
+        let mut book = Book::new();
+        book.apply_fill(&Fill {
+            order: 1,
+            time: time(2, 9),
+            isin: String::from("DE0005140008"),
+            side: Side::Buy,
+            quantity: dec(10.0),
+            price: dec(100.0),
+            fee: Decimal::ZERO,
+            fx_rate: 1.0,
+        });
+        book
+    }
+
+    #[test]
+    fn the_first_tick_of_a_day_counts_towards_the_day() {
+        let mut book = book();
+        let mut pnl = PnlTracker::new();
+        book.update_price("DE0005140008", dec(104.0));
+        pnl.update(&book, time(2, 9));
+        assert_eq!(pnl.daily_pnl(), dec(40.0));
+
+        // the price opens lower the next day and a fill is charged on the same tick
+        book.update_price("DE0005140008", dec(95.0));
+        pnl.record_fill(Decimal::ZERO, dec(2.0));
+        pnl.update(&book, time(3, 9));
+        assert_eq!(pnl.daily_pnl(), dec(-92.0));
+        assert_eq!(pnl.session_pnl(), dec(-52.0));
+
+        book.update_price("DE0005140008", dec(97.0));
+        pnl.update(&book, time(3, 10));
+        assert_eq!(pnl.daily_pnl(), dec(-72.0));
+    }
+
+    #[test]
+    fn the_drawdown_is_measured_from_the_highest_equity() {
+        let mut book = book();
+        let mut pnl = PnlTracker::new();
+        for (hour, price) in [(9, 100.0), (10, 108.0), (11, 101.0), (12, 105.0)].iter() {
+            book.update_price("DE0005140008", dec(*price));
+            pnl.update(&book, time(2, *hour));
+        }
+        assert_eq!(pnl.session_pnl(), dec(50.0));
+        assert_eq!(pnl.drawdown(), dec(30.0));
+
+        pnl.record_fill(dec(50.0), dec(1.0));
+        pnl.update(&book, time(2, 13));
+        assert_eq!((pnl.realised(), pnl.fees()), (dec(50.0), dec(1.0)));
+        assert_eq!(pnl.drawdown(), Decimal::ZERO);
+    }
+}
@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::trading::book::Book;
+use crate::trading::decimal::Decimal;
+use crate::trading::order::{Order, OrderType};
+
+/// Pre-trade limits that are checked for every order before it reaches the broker
+///
+/// All limits are optional. A limit that is not set is not checked.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct RiskLimits {
+    /// the maximum quantity of a single order
+    pub max_order_size: Option<f64>,
+    /// the maximum absolute quantity held per ISIN
+    pub max_position: Option<f64>,
+    /// the maximum sum of the absolute market values of all positions in the base currency
+    pub max_gross_exposure: Option<f64>,
+    pub max_orders_per_minute: Option<u32>,
+    /// the maximum relative deviation of a limit price from the last trade (0.05 = 5%)
+    pub price_collar: Option<f64>,
+}
+
+impl fmt::Display for RiskLimits {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn limit<T: ToString>(value: &Option<T>) -> String {
+            match value {
+                Some(value) => value.to_string(),
+                None => String::from("none")
+            }
+        }
+
+        write!(
+            formatter,
+            "RISK:\n\
+            \tmax order size: {}\n\
+            \tmax position: {}\n\
+            \tmax gross exposure: {}\n\
+            \tmax orders per minute: {}\n\
+            \tprice collar: {}",
+            limit(&self.max_order_size),
+            limit(&self.max_position),
+            limit(&self.max_gross_exposure),
+            limit(&self.max_orders_per_minute),
+            limit(&self.price_collar)
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RiskViolation {
+    /// a quantity or price that can't be traded (like zero, negative or NaN values of an algorithm)
+    InvalidOrder { reason: String },
+    OrderSize { quantity: Decimal, max: f64 },
+    Position { isin: String, position: Decimal, max: f64 },
+    GrossExposure { exposure: Decimal, max: f64 },
+    OrderRate { max: u32 },
+    PriceCollar { limit: Decimal, last: Decimal, collar: f64 },
+    NoPrice { isin: String },
+    /// the gross exposure of an algorithm of a session with several algorithms
+    Allocation { algorithm: String, exposure: Decimal, capital: f64 },
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskViolation::InvalidOrder { reason } =>
+                write!(formatter, "invalid order ({})", reason),
+            RiskViolation::OrderSize { quantity, max } =>
+                write!(formatter, "order size {} exceeds the maximum of {}", quantity, max),
+            RiskViolation::Position { isin, position, max } =>
+                write!(formatter, "position of {} in {} would exceed the maximum of {}", position, isin, max),
+            RiskViolation::GrossExposure { exposure, max } =>
+                write!(formatter, "gross exposure of {:.2} would exceed the maximum of {}", exposure, max),
+            RiskViolation::OrderRate { max } =>
+                write!(formatter, "more than {} orders per minute", max),
+            RiskViolation::PriceCollar { limit, last, collar } =>
+                write!(formatter, "limit {} is more than {}% away from the last trade at {}", limit, collar * 100.0, last),
+            RiskViolation::NoPrice { isin } =>
+                write!(formatter, "no price known for {}", isin),
+            RiskViolation::Allocation { algorithm, exposure, capital } =>
+                write!(formatter, "gross exposure of {:.2} of {} would exceed its capital of {:.2}", exposure, algorithm, capital),
+        }
+    }
+}
+
+pub struct RiskManager {
+    limits: RiskLimits,
+    /// the times of all accepted orders of the last minute
+    recent_orders: VecDeque<DateTime<Utc>>,
+}
+
+impl RiskManager {
+    pub fn new(limits: RiskLimits) -> Self {
+        Self {
+            limits,
+            recent_orders: VecDeque::new(),
+        }
+    }
+
+    /// Checks the order against all limits and records it if it was accepted
+    ///
+    /// The pending orders (with their remaining quantity) count toward the
+    /// position as if they were filled, so resting orders can't add up to more
+    /// than the max position.
+    pub fn check(&mut self, order: &Order, book: &Book, pending: &[Order], time: DateTime<Utc>) -> Result<(), RiskViolation> {
+        validate(order)?;
+
+        if let Some(max) = self.limits.max_order_size {
+            if order.quantity > max {
+                return Err(RiskViolation::OrderSize { quantity: order.quantity, max });
+            }
+        }
+
+        if let Some(max) = self.limits.max_position {
+            let position = book.position(&order.isin) + pending_quantity(order, pending) + order.signed_quantity();
+            if position.abs() > max {
+                return Err(RiskViolation::Position { isin: order.isin.clone(), position, max });
+            }
+        }
+
+        let last_price = book.last_price(&order.isin);
+
+        if let Some(max) = self.limits.max_gross_exposure {
+            let exposure = exposure_after(order, book)?;
+            // orders that reduce the exposure are always fine, so the book can get back within the limit
+            if exposure > max && exposure > book.gross_exposure() {
+                return Err(RiskViolation::GrossExposure { exposure, max });
+            }
+        }
+
+        if let (Some(collar), Some(limit)) = (self.limits.price_collar, order.limit()) {
+            let last = match last_price {
+                Some(last) => last,
+                None => return Err(RiskViolation::NoPrice { isin: order.isin.clone() })
+            };
+            if last.is_zero() || ((limit - last) / last).abs() > collar {
+                return Err(RiskViolation::PriceCollar { limit, last, collar });
+            }
+        }
+
+        if let Some(max) = self.limits.max_orders_per_minute {
+            let minute_ago = time - Duration::minutes(1);
+            while self.recent_orders.front().is_some_and(|sent| *sent <= minute_ago) {
+                self.recent_orders.pop_front();
+            }
+            if self.recent_orders.len() >= max as usize {
+                return Err(RiskViolation::OrderRate { max });
+            }
+            self.recent_orders.push_back(time);
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the quantity and the prices of the order are positive and finite
+///
+/// NaN values of an algorithm arrive as zero and infinite ones at the limits of `Decimal`.
+pub fn validate(order: &Order) -> Result<(), RiskViolation> {
+    let invalid = |name: &str, value: Decimal| -> Result<(), RiskViolation> {
+        if value.is_positive() && value.is_finite() {
+            Ok(())
+        } else {
+            Err(RiskViolation::InvalidOrder { reason: format!("the {} {} isn't a positive finite number", name, value) })
+        }
+    };
+
+    invalid("quantity", order.quantity)?;
+    match order.order_type {
+        OrderType::Market => Ok(()),
+        OrderType::Limit { limit } => invalid("limit", limit),
+        OrderType::Stop { stop } => invalid("stop", stop),
+        OrderType::StopLimit { stop, limit } => invalid("stop", stop).and_then(|_| invalid("limit", limit)),
+        OrderType::TrailingStop { distance } => invalid("trailing distance", distance),
+    }
+}
+
+/// Checks that an algorithm of a session with several algorithms stays within its part of the capital
+pub fn check_allocation(order: &Order, book: &Book, algorithm: &str, capital: f64) -> Result<(), RiskViolation> {
+    let exposure = exposure_after(order, book)?;
+    // orders that reduce the exposure are always fine, so an algorithm can get back within its capital
+    if exposure > capital && exposure > book.gross_exposure() {
+        return Err(RiskViolation::Allocation { algorithm: algorithm.to_string(), exposure, capital });
+    }
+    Ok(())
+}
+
+/// the quantity of the pending orders on the side of the order, signed like the order
+///
+/// Only one order of a one-cancels-other group can be filled, so a group counts with its largest order.
+fn pending_quantity(order: &Order, pending: &[Order]) -> Decimal {
+    let mut quantity = Decimal::ZERO;
+    let mut groups: BTreeMap<u64, Decimal> = BTreeMap::new();
+    for other in pending.iter().filter(|other| other.isin == order.isin && other.side == order.side) {
+        match other.oco {
+            Some(group) => {
+                let largest = groups.entry(group).or_insert(Decimal::ZERO);
+                *largest = (*largest).max(other.quantity);
+            }
+            None => quantity += other.quantity,
+        }
+    }
+    order.side.sign() * (quantity + groups.values().copied().sum())
+}
+
+/// the gross exposure of the book after the order was filled at its limit or the last price
+fn exposure_after(order: &Order, book: &Book) -> Result<Decimal, RiskViolation> {
+    let last_price = book.last_price(&order.isin);
+    let price = match order.limit().or(last_price) {
+        Some(price) => price,
+        None => return Err(RiskViolation::NoPrice { isin: order.isin.clone() })
+    };
+    // the exposure is in the base currency like the limits
+    let rate = book.rate(&order.isin);
+    let held = book.position(&order.isin);
+    let current = book.holding(&order.isin)
+                      .map(|holding| (holding.quantity * last_price.unwrap_or(holding.average_price)).at_rate(rate).abs())
+                      .unwrap_or(Decimal::ZERO);
+    Ok(book.gross_exposure() - current + ((held + order.signed_quantity()) * price).at_rate(rate).abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::trading::order::{Fill, OrderType, Side};
+
+    const ISIN: &str = "DE0001";
+
+    fn time(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2021, 3, 1, 9, 0, second).unwrap()
+    }
+
+    fn dec(value: f64) -> Decimal {
+        Decimal::from_f64(value)
+    }
+
+    /// a book holding `quantity` bought at and last traded at `price`
+    fn book(quantity: f64, price: f64) -> Book {
+        let mut book = Book::new();
+        book.update_price(ISIN, dec(price));
+        if quantity != 0.0 {
+            book.apply_fill(&Fill {
+                order: 1,
+                time: time(0),
+                isin: ISIN.to_string(),
+                side: if quantity > 0.0 { Side::Buy } else { Side::Sell },
+                quantity: dec(quantity.abs()),
+                price: dec(price),
+                fee: Decimal::ZERO,
+                fx_rate: 1.0,
+            });
+        }
+        book
+    }
+
+    fn buy(quantity: f64) -> Order {
+        Order::market(ISIN.to_string(), Side::Buy, dec(quantity))
+    }
+
+    fn sell(quantity: f64) -> Order {
+        Order::market(ISIN.to_string(), Side::Sell, dec(quantity))
+    }
+
+    fn manager(limits: RiskLimits) -> RiskManager {
+        RiskManager::new(limits)
+    }
+
+    #[test]
+    fn orders_larger_than_the_max_order_size_are_rejected() {
+        let mut risk = manager(RiskLimits { max_order_size: Some(10.0), ..RiskLimits::default() });
+        let book = book(0.0, 100.0);
+        assert!(risk.check(&buy(10.0), &book, &[], time(1)).is_ok());
+        assert_eq!(
+            risk.check(&buy(11.0), &book, &[], time(2)),
+            Err(RiskViolation::OrderSize { quantity: dec(11.0), max: 10.0 })
+        );
+    }
+
+    #[test]
+    fn quantities_that_arent_positive_and_finite_are_rejected() {
+        let mut risk = manager(RiskLimits { max_order_size: Some(10.0), ..RiskLimits::default() });
+        let book = book(0.0, 100.0);
+        let invalid = |quantity: &str| Err(RiskViolation::InvalidOrder {
+            reason: format!("the quantity {} isn't a positive finite number", quantity),
+        });
+        assert_eq!(risk.check(&buy(0.0), &book, &[], time(1)), invalid("0"));
+        // a negative sell doesn't slip past the max order size
+        assert_eq!(risk.check(&sell(-20.0), &book, &[], time(2)), invalid("-20"));
+        assert_eq!(risk.check(&buy(f64::NAN), &book, &[], time(3)), invalid("0"));
+        assert!(risk.check(&buy(f64::INFINITY), &book, &[], time(4)).is_err());
+        assert!(risk.check(&sell(f64::NEG_INFINITY), &book, &[], time(5)).is_err());
+    }
+
+    #[test]
+    fn prices_that_arent_positive_and_finite_are_rejected() {
+        let mut risk = manager(RiskLimits::default());
+        let book = book(0.0, 100.0);
+        let order = |order_type: OrderType| Order::new(ISIN.to_string(), Side::Buy, dec(1.0), order_type);
+        let invalid = |name: &str, value: &str| Err(RiskViolation::InvalidOrder {
+            reason: format!("the {} {} isn't a positive finite number", name, value),
+        });
+
+        assert_eq!(risk.check(&order(OrderType::Limit { limit: dec(0.0) }), &book, &[], time(1)), invalid("limit", "0"));
+        assert_eq!(risk.check(&order(OrderType::Stop { stop: dec(-5.0) }), &book, &[], time(2)), invalid("stop", "-5"));
+        assert_eq!(
+            risk.check(&order(OrderType::StopLimit { stop: dec(101.0), limit: dec(-1.0) }), &book, &[], time(3)),
+            invalid("limit", "-1")
+        );
+        assert_eq!(
+            risk.check(&order(OrderType::TrailingStop { distance: dec(0.0) }), &book, &[], time(4)),
+            invalid("trailing distance", "0")
+        );
+        assert!(risk.check(&order(OrderType::Limit { limit: dec(f64::INFINITY) }), &book, &[], time(5)).is_err());
+        assert!(risk.check(&order(OrderType::StopLimit { stop: dec(101.0), limit: dec(102.0) }), &book, &[], time(6)).is_ok());
+        assert!(risk.check(&order(OrderType::TrailingStop { distance: dec(2.0) }), &book, &[], time(7)).is_ok());
+    }
+
+    #[test]
+    fn the_max_position_includes_the_holding() {
+        let mut risk = manager(RiskLimits { max_position: Some(10.0), ..RiskLimits::default() });
+        let book = book(8.0, 100.0);
+        assert!(risk.check(&buy(2.0), &book, &[], time(1)).is_ok());
+        assert!(risk.check(&buy(3.0), &book, &[], time(2)).is_err());
+        // closing and flipping within the limit is fine
+        assert!(risk.check(&sell(18.0), &book, &[], time(3)).is_ok());
+        assert!(risk.check(&sell(19.0), &book, &[], time(4)).is_err());
+    }
+
+    #[test]
+    fn pending_orders_count_toward_the_max_position() {
+        let mut risk = manager(RiskLimits { max_position: Some(10.0), ..RiskLimits::default() });
+        let book = book(0.0, 100.0);
+        let pending = [buy(6.0), sell(20.0), Order::market(String::from("DE0002"), Side::Buy, dec(50.0))];
+        assert!(risk.check(&buy(4.0), &book, &pending, time(1)).is_ok());
+        assert_eq!(
+            risk.check(&buy(5.0), &book, &pending, time(2)),
+            Err(RiskViolation::Position { isin: ISIN.to_string(), position: dec(11.0), max: 10.0 })
+        );
+    }
+
+    #[test]
+    fn one_cancels_other_groups_count_with_their_largest_order() {
+        let mut risk = manager(RiskLimits { max_position: Some(10.0), ..RiskLimits::default() });
+        let book = book(0.0, 100.0);
+        let mut first = Order::new(ISIN.to_string(), Side::Buy, dec(4.0), OrderType::Limit { limit: dec(95.0) });
+        first.oco = Some(1);
+        let mut second = Order::new(ISIN.to_string(), Side::Buy, dec(6.0), OrderType::Stop { stop: dec(105.0) });
+        second.oco = Some(1);
+        let pending = [first, second];
+        assert!(risk.check(&buy(4.0), &book, &pending, time(1)).is_ok());
+        assert!(risk.check(&buy(5.0), &book, &pending, time(2)).is_err());
+    }
+
+    #[test]
+    fn orders_above_the_max_gross_exposure_are_rejected() {
+        let mut risk = manager(RiskLimits { max_gross_exposure: Some(1000.0), ..RiskLimits::default() });
+        let book = book(5.0, 100.0);
+        assert!(risk.check(&buy(5.0), &book, &[], time(1)).is_ok());
+        assert_eq!(
+            risk.check(&buy(6.0), &book, &[], time(2)),
+            Err(RiskViolation::GrossExposure { exposure: dec(1100.0), max: 1000.0 })
+        );
+    }
+
+    #[test]
+    fn orders_reducing_the_exposure_pass_while_it_is_above_the_max() {
+        let mut risk = manager(RiskLimits { max_gross_exposure: Some(1000.0), ..RiskLimits::default() });
+        // the price went up after the holding was entered
+        let mut book = book(10.0, 100.0);
+        book.update_price(ISIN, dec(150.0));
+        assert!(risk.check(&sell(2.0), &book, &[], time(1)).is_ok());
+        assert!(risk.check(&buy(1.0), &book, &[], time(2)).is_err());
+        // flipping to a larger short position increases the exposure
+        assert!(risk.check(&sell(21.0), &book, &[], time(3)).is_err());
+    }
+
+    #[test]
+    fn the_order_rate_is_limited_per_minute() {
+        let mut risk = manager(RiskLimits { max_orders_per_minute: Some(2), ..RiskLimits::default() });
+        let book = book(0.0, 100.0);
+        assert!(risk.check(&buy(1.0), &book, &[], time(0)).is_ok());
+        assert!(risk.check(&buy(1.0), &book, &[], time(30)).is_ok());
+        assert_eq!(risk.check(&buy(1.0), &book, &[], time(59)), Err(RiskViolation::OrderRate { max: 2 }));
+        // the first order is a minute old and rejected orders are not recorded
+        let later = time(0) + Duration::minutes(1);
+        assert!(risk.check(&buy(1.0), &book, &[], later).is_ok());
+        assert!(risk.check(&buy(1.0), &book, &[], later).is_err());
+    }
+
+    #[test]
+    fn limits_outside_the_price_collar_are_rejected() {
+        let mut risk = manager(RiskLimits { price_collar: Some(0.05), ..RiskLimits::default() });
+        let book = book(0.0, 100.0);
+        let limit = |limit: f64| Order::new(ISIN.to_string(), Side::Buy, dec(1.0), OrderType::Limit { limit: dec(limit) });
+        assert!(risk.check(&limit(105.0), &book, &[], time(1)).is_ok());
+        assert!(risk.check(&limit(95.0), &book, &[], time(2)).is_ok());
+        assert_eq!(
+            risk.check(&limit(106.0), &book, &[], time(3)),
+            Err(RiskViolation::PriceCollar { limit: dec(106.0), last: dec(100.0), collar: 0.05 })
+        );
+        // market orders have no limit
+        assert!(risk.check(&buy(1.0), &book, &[], time(4)).is_ok());
+    }
+
+    #[test]
+    fn limits_that_need_a_price_reject_orders_without_one() {
+        let mut risk = manager(RiskLimits { max_gross_exposure: Some(1000.0), ..RiskLimits::default() });
+        assert_eq!(
+            risk.check(&buy(1.0), &Book::new(), &[], time(1)),
+            Err(RiskViolation::NoPrice { isin: ISIN.to_string() })
+        );
+        // without limits nothing is checked
+        assert!(manager(RiskLimits::default()).check(&buy(1.0), &Book::new(), &[], time(1)).is_ok());
+    }
+
+    #[test]
+    fn the_allocation_limits_the_exposure_of_an_algorithm_to_its_capital() {
+        let book = book(5.0, 100.0);
+        assert!(check_allocation(&buy(5.0), &book, "a", 1000.0).is_ok());
+        assert_eq!(
+            check_allocation(&buy(6.0), &book, "a", 1000.0),
+            Err(RiskViolation::Allocation { algorithm: String::from("a"), exposure: dec(1100.0), capital: 1000.0 })
+        );
+        assert!(check_allocation(&sell(1.0), &book, "a", 100.0).is_ok());
+    }
+}
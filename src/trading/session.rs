@@ -0,0 +1,1063 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::thread;
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+
+use crate::init::Start;
+use crate::logging::SESSION_TARGET;
+use crate::notify::Notifier;
+use crate::output::{Output, SessionEvent};
+use crate::report::PerformanceReport;
+use crate::control::{Command, Control, Pnl, PositionView, Status};
+use crate::trading::{ShutdownPolicy, TradingType};
+use crate::trading::algorithm::{self, AlgorithmRunner};
+use crate::trading::allocation::{self, Account, Attribution};
+use crate::trading::book::Book;
+use crate::trading::broker::{Broker, SimulatedBroker};
+use crate::trading::calendar::Calendar;
+use crate::trading::checkpoint::{Checkpoint, Checkpointer, SessionInfo};
+use crate::trading::currency::FxRates;
+use crate::trading::decimal::Decimal;
+use crate::trading::emulator::EmulatingBroker;
+use crate::trading::feed::{FeedEvent, FollowingFeed, PriceFeed, RecordedFeed};
+use crate::trading::journal::{Journal, JournalEntry};
+use crate::trading::kill_switch::{self, KillSwitchLimits};
+use crate::trading::order::{Fill, Order};
+use crate::trading::order_manager::{OrderManager, TrackedOrder};
+use crate::trading::pnl::PnlTracker;
+use crate::trading::risk::{self, RiskManager};
+
+/// how long a closed session sleeps before it looks for commands again
+const COMMAND_POLL_MS: u64 = 500;
+
+/// Runs a session as described by `start` until the price feed is exhausted,
+/// an algorithm fails or the kill switch fires
+pub fn start(start: Start) -> Result<(), String> {
+    let Start {
+        trading_type, derivative, interval, allocations, mut algorithms, profile, save_config, output, dashboard,
+        chart_style, until, from, to, journal: journal_file, report: report_file, fill_model, calendar, fx, tick_sizes,
+        bars, resume, control: control_port, ..
+    } = start;
+
+    if resume.is_some() && allocations.len() > 1 {
+        return Err(String::from("Sessions with several algorithms can't be resumed"));
+    }
+    let names: Vec<String> = allocations.iter().map(|allocation| allocation.algorithm.clone()).collect();
+    let algorithm_name = names.join(", ");
+    let loaded = algorithm::select(&mut algorithms, &names)?;
+
+    let feed: Box<dyn PriceFeed> = match trading_type {
+        TradingType::Back => {
+            let path = RecordedFeed::price_file(derivative.isin());
+            match RecordedFeed::from_file(&path) {
+                Ok(feed) => {
+                    let feed = feed.between(from, to);
+                    // the whole back test needs exchange rates
+                    if let Some(first) = feed.first_time() {
+                        fx.rate(derivative.isin(), first)?;
+                    }
+                    Box::new(feed)
+                }
+                Err(err) => return Err(format!("Could not read the prices from {} ({})", path.display(), err))
+            }
+        }
+        // paper sessions trade on the prices while they are recorded, a resumed one catches up from its checkpoint
+        TradingType::Paper => {
+            let after = resume.as_ref().map_or_else(Utc::now, |checkpoint| checkpoint.time);
+            let feed = FollowingFeed::new(RecordedFeed::price_file(derivative.isin()), after);
+            info!(target: SESSION_TARGET, "Following the prices recorded to {}", feed.path().display());
+            Box::new(feed)
+        }
+        TradingType::Live => return Err(format!("{} trading is not supported yet", trading_type))
+    };
+    let mut control = Control::new();
+    if let Some((port, token)) = control_port {
+        let address = control.serve(port, token)?;
+        info!(target: SESSION_TARGET, "The control API listens on http://{}", address);
+    }
+    let tick_size = tick_sizes.get(derivative.isin()).copied();
+    let mut broker = EmulatingBroker::new(Box::new(SimulatedBroker::new(fill_model, tick_sizes)));
+    if let Some(ref calendar) = calendar {
+        broker = broker.with_timezone(calendar.timezone());
+    }
+    let interval_secs = interval.as_secs();
+    let interval = chrono::Duration::from_std(interval)
+        .map_err(|err| format!("Invalid interval ({})", err))?;
+
+    // back tests (and the runs of `optimize`) don't notify anybody
+    let notifier = match trading_type {
+        TradingType::Back => None,
+        _ if profile.notifications.hooks.is_empty() => None,
+        TradingType::Live | TradingType::Paper => Some(Notifier::start(
+            profile.name.clone(), derivative.isin().to_string(), profile.notifications.clone(),
+        )?),
+    };
+    // back tests run as fast as possible, so the output is only updated live in real time sessions
+    let live = trading_type != TradingType::Back;
+    let mut output = Output::start(&output, chart_style, live, live || dashboard, control.sender(), notifier);
+
+    // a resumed session continues its journal
+    let journal_file = match (&resume, journal_file) {
+        (Some(checkpoint), _) => checkpoint.session.journal.clone(),
+        (None, Some(path)) => Some(path),
+        (None, None) if save_config.order => Some(Journal::journal_file(Utc::now(), derivative.isin(), trading_type)),
+        (None, None) => None
+    };
+    let mut journal = if let Some(ref path) = journal_file {
+        let journal = match resume {
+            // the entries after the checkpoint are written again
+            Some(ref checkpoint) => Journal::append_to_file(path, checkpoint.journal_entries),
+            None => Journal::to_file(path),
+        };
+        match journal {
+            Ok(journal) => journal,
+            Err(err) => {
+                output.send(SessionEvent::Error(format!(
+                    "Could not create the journal {}, it will only be kept in memory ({})", path.display(), err
+                )));
+                Journal::in_memory()
+            }
+        }
+    } else { Journal::in_memory() };
+    if resume.is_none() {
+        // a single algorithm trades with the whole capital like before
+        let allocations = if allocations.len() > 1 {
+            allocations
+                .iter()
+                .map(|allocation| (allocation.algorithm.clone(), profile.capital * allocation.share))
+                .collect()
+        } else { BTreeMap::new() };
+        journal.record(JournalEntry::Start {
+            time: Utc::now(),
+            trading_type,
+            algorithm: algorithm_name.clone(),
+            profile: profile.name.clone(),
+            capital: profile.capital,
+            currency: profile.currency.clone(),
+            allocations,
+        });
+    }
+
+    // back tests are fast and deterministic, so they are just run again instead of being resumed
+    let checkpointer = match trading_type {
+        TradingType::Back => None,
+        // the checkpoint of an earlier session of the ISIN must not be resumed after this one traded
+        TradingType::Live | TradingType::Paper if allocations.len() > 1 => {
+            let path = Checkpoint::checkpoint_file(derivative.isin(), trading_type);
+            match fs::remove_file(&path) {
+                Ok(()) => warn!(target: SESSION_TARGET, "Removed the checkpoint {} of an earlier session", path.display()),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(format!("Could not remove the checkpoint {} ({})", path.display(), err))
+            }
+            warn!(target: SESSION_TARGET, "Sessions with several algorithms don't write checkpoints and can't be resumed");
+            None
+        }
+        TradingType::Live | TradingType::Paper => Some(Checkpointer::new(SessionInfo {
+            trading_type,
+            isin: derivative.isin().to_string(),
+            algorithm: algorithm_name.clone(),
+            parameters: allocations[0].parameters.clone(),
+            profile: profile.name.clone(),
+            interval: interval_secs,
+            journal: journal_file,
+            bars,
+        })),
+    };
+
+    let accounts = allocations
+        .iter()
+        .zip(loaded)
+        .map(|(allocation, algorithm)| {
+            let runner = AlgorithmRunner::new(
+                allocation.algorithm.clone(), algorithm, derivative.clone(), tick_size, allocation.input,
+            );
+            Account::new(runner, profile.capital * allocation.share)
+        })
+        .collect::<Vec<_>>();
+
+    let mut session = Session {
+        attribution: Attribution::new(accounts.len()),
+        accounts,
+        feed,
+        broker,
+        book: Book::new(),
+        orders: OrderManager::new(),
+        risk: RiskManager::new(profile.risk),
+        pnl: PnlTracker::new(),
+        kill_switch: profile.kill_switch,
+        on_shutdown: profile.on_shutdown,
+        journal,
+        output,
+        checkpointer,
+        trading_type,
+        before_close: profile.before_close.map(|minutes| chrono::Duration::minutes(minutes as i64)),
+        last_close_hook: None,
+        calendar,
+        fx,
+        currency: profile.currency.clone(),
+        until,
+        control,
+        paused: false,
+    };
+
+    info!(target: SESSION_TARGET, "Starting {} session with {} (profile {})", trading_type, algorithm_name, profile.name);
+    for (account, allocation) in session.accounts.iter_mut().zip(&allocations) {
+        info!(
+            target: SESSION_TARGET, "{} works on {} with {:.2} {}",
+            allocation.algorithm, allocation.input, account.capital, profile.currency
+        );
+        account.runner.init(interval, &allocation.parameters)?;
+    }
+    if let Some(ref calendar) = session.calendar {
+        info!(target: SESSION_TARGET, "Trading during the hours of {}", calendar.name);
+    }
+
+    if let Some(checkpoint) = resume {
+        session.resume(checkpoint)?;
+    }
+    session.forward_algorithm_log();
+    let end = session.run();
+
+    let Session { journal, mut output, .. } = session;
+    match PerformanceReport::from_journal(journal.entries()) {
+        Ok(report) => {
+            if let Some(ref path) = report_file {
+                let written = report
+                    .to_json()
+                    .map_err(|err| err.to_string())
+                    .and_then(|json| fs::write(path, json).map_err(|err| err.to_string()));
+                if let Err(err) = written {
+                    output.send(SessionEvent::Error(format!("Could not write the report to {} ({})", path.display(), err)));
+                }
+            }
+            output.send(SessionEvent::Report(report))
+        }
+        Err(err) => output.send(SessionEvent::Error(format!("Could not create the performance report ({})", err)))
+    }
+    output.finish();
+
+    match end {
+        SessionEnd::Finished => Ok(()),
+        SessionEnd::Failed(err) => Err(err),
+        // back tests (and the runs of `optimize`) must not halt the profile used for trading
+        SessionEnd::Halted(reason) if trading_type == TradingType::Back => {
+            Err(format!("The kill switch stopped the back test ({})", reason))
+        }
+        SessionEnd::Halted(reason) => {
+            if let Err(err) = kill_switch::halt_profile(&profile.name, reason.clone()) {
+                error!("Could not mark the profile {} as halted ({})", profile.name, err);
+            }
+            Err(format!("The kill switch halted the profile {} ({})", profile.name, reason))
+        }
+    }
+}
+
+pub enum SessionEnd {
+    /// the price feed was exhausted
+    Finished,
+    /// the price feed or the algorithm failed
+    Failed(String),
+    /// the kill switch fired
+    Halted(String),
+}
+
+pub struct Session<'a> {
+    /// the algorithms with their sub-books (all of them trade the same derivative)
+    accounts: Vec<Account<'a>>,
+    /// which accounts the orders of the broker belong to
+    attribution: Attribution,
+    feed: Box<dyn PriceFeed>,
+    /// every backend is wrapped, so all of them support the same orders
+    broker: EmulatingBroker,
+    book: Book,
+    orders: OrderManager,
+    risk: RiskManager,
+    pnl: PnlTracker,
+    kill_switch: KillSwitchLimits,
+    on_shutdown: ShutdownPolicy,
+    journal: Journal,
+    output: Output,
+    /// None for back tests
+    checkpointer: Option<Checkpointer>,
+    trading_type: TradingType,
+    /// None to trade around the clock
+    calendar: Option<Calendar>,
+    /// the rates the prices and fills are converted to the base currency with
+    fx: FxRates,
+    /// the base currency of the profile
+    currency: String,
+    /// how long before the close the session close hook is called
+    before_close: Option<chrono::Duration>,
+    /// the close the session close hook was last called for
+    last_close_hook: Option<DateTime<Utc>>,
+    /// the time a real time session stops
+    until: Option<DateTime<Utc>>,
+    control: Control,
+    /// the algorithms aren't called while the session is paused
+    paused: bool,
+}
+
+impl<'a> Session<'a> {
+    pub fn run(&mut self) -> SessionEnd {
+        let mut time = Utc::now();
+
+        let end = loop {
+            if self.trading_type != TradingType::Back {
+                if self.wait_for_open(time) {
+                    break SessionEnd::Finished;
+                }
+                if self.until.is_some_and(|until| Utc::now() >= until) {
+                    info!(target: SESSION_TARGET, "Stopping the session as planned");
+                    break SessionEnd::Finished;
+                }
+            }
+
+            let tick = match self.feed.next_tick() {
+                Ok(FeedEvent::Tick(tick)) => tick,
+                Ok(FeedEvent::Waiting) => {
+                    if self.handle_commands(time) {
+                        break SessionEnd::Finished;
+                    }
+                    continue;
+                }
+                Ok(FeedEvent::Exhausted) => break SessionEnd::Finished,
+                Err(err) => break SessionEnd::Failed(format!("Price feed failed ({})", err))
+            };
+            time = tick.time;
+
+            // back tests skip the nights, weekends and holidays this way
+            if self.calendar.as_ref().is_some_and(|calendar| !calendar.is_open(time)) {
+                continue;
+            }
+
+            let fx_rate = match self.fx.rate(&tick.isin, time) {
+                Ok(fx_rate) => fx_rate,
+                Err(err) => break SessionEnd::Failed(err)
+            };
+            self.book.update_rate(&tick.isin, fx_rate);
+            let price = Decimal::from_f64(tick.price);
+            self.book.update_price(&tick.isin, price);
+            for account in &mut self.accounts {
+                account.book.update_rate(&tick.isin, fx_rate);
+                account.book.update_price(&tick.isin, price);
+            }
+            self.journal.record(JournalEntry::Mark { time, isin: tick.isin.clone(), price, fx_rate });
+            match self.broker.update(&tick) {
+                Ok(fills) => fills.into_iter().for_each(|fill| self.apply_fill(fill, time)),
+                Err(err) => break SessionEnd::Failed(format!("Broker failed ({})", err))
+            }
+            for (order, reason) in self.broker.cancelled() {
+                self.orders.cancelled(order.id, reason.clone(), time);
+                self.attribution.forget(order.id);
+                self.output.send(SessionEvent::Cancelled { order, reason });
+            }
+            if self.orders.reconciliation_due(time) {
+                self.reconcile(time);
+            }
+            self.pnl.update(&self.book, time);
+            for account in &mut self.accounts {
+                account.pnl.update(&account.book, time);
+            }
+            if let Some(reason) = self.kill_switch.check(&self.pnl) {
+                break SessionEnd::Halted(reason);
+            }
+            if self.handle_commands(time) {
+                break SessionEnd::Finished;
+            }
+
+            if tick.isin != self.isin() {
+                self.publish(time);
+                continue;
+            }
+
+            self.output.send(SessionEvent::Status {
+                position: self.book.position(&tick.isin),
+                pnl: self.pnl.session_pnl(),
+            });
+            self.output.send(SessionEvent::Risk {
+                daily_pnl: self.pnl.daily_pnl(),
+                drawdown: self.pnl.drawdown(),
+                exposure: self.book.gross_exposure(),
+                limits: self.kill_switch.clone(),
+            });
+            if self.control.is_serving() {
+                self.control.push_price(time, tick.price);
+            }
+
+            let new_data: Vec<bool> = self.accounts.iter_mut().map(|account| account.runner.push_tick(&tick)).collect();
+            self.output.send(SessionEvent::Price { time, isin: tick.isin, price: tick.price });
+            if self.paused {
+                self.publish(time);
+                continue;
+            }
+            // algorithms that work on bars are only called once the tick completed one
+            let (orders, errors) = self.call_algorithms(&new_data, AlgorithmRunner::step);
+            if !errors.is_empty() {
+                break SessionEnd::Failed(errors.join(", "));
+            }
+            self.submit(orders, time);
+
+            if let Some(close) = self.close_hook_due(time) {
+                self.last_close_hook = Some(close);
+                let all = vec![true; self.accounts.len()];
+                let (orders, errors) = self.call_algorithms(&all, AlgorithmRunner::session_close);
+                if !errors.is_empty() {
+                    break SessionEnd::Failed(errors.join(", "));
+                }
+                self.submit(orders, time);
+            }
+            self.forward_algorithm_log();
+
+            if self.checkpointer.as_ref().is_some_and(|checkpointer| checkpointer.due(time)) {
+                self.checkpoint(time);
+            }
+            self.publish(time);
+        };
+
+        // halted algorithms are not called again, otherwise they get the chance to close their positions
+        match end {
+            SessionEnd::Halted(ref reason) => {
+                error!(target: SESSION_TARGET, "Kill switch fired: {}", reason);
+                self.output.send(SessionEvent::KillSwitch(reason.clone()));
+            }
+            _ => {
+                if let SessionEnd::Failed(ref err) = end {
+                    error!(target: SESSION_TARGET, "{}", err);
+                }
+                // every algorithm gets the chance to shut down, even if another one failed
+                let all = vec![true; self.accounts.len()];
+                let (orders, errors) = self.call_algorithms(&all, AlgorithmRunner::shutdown);
+                for err in errors {
+                    self.output.send(SessionEvent::Error(format!("{} while shutting down", err)));
+                }
+                self.submit(orders, time);
+            }
+        }
+        self.forward_algorithm_log();
+
+        self.apply_shutdown_policy(time);
+        // no tick follows, so the orders of the shutdown would never get past the latency
+        match self.broker.flush() {
+            Ok(fills) => fills.into_iter().for_each(|fill| self.apply_fill(fill, time)),
+            Err(err) => self.output.send(SessionEvent::Error(format!("Broker failed while shutting down ({})", err)))
+        }
+        for order in self.broker.cancel_all() {
+            self.orders.cancelled(order.id, String::from("end of the session"), time);
+            self.attribution.forget(order.id);
+            warn!(target: SESSION_TARGET, "Cancelled the rest of {} at the end of the session", order);
+            self.output.send(SessionEvent::Error(format!("Cancelled the rest of {} at the end of the session", order)));
+        }
+
+        // only a session that finished normally has nothing left to resume
+        match end {
+            SessionEnd::Finished => {
+                if let Some(Err(err)) = self.checkpointer.as_ref().map(Checkpointer::remove) {
+                    warn!(target: SESSION_TARGET, "Could not remove the checkpoint ({})", err);
+                }
+            }
+            _ => self.checkpoint(time),
+        }
+
+        let mut summary = format!(
+            "Session PnL: {:.2} {} (realised {:.2}, fees {:.2})",
+            self.pnl.session_pnl(), self.currency, self.pnl.realised(), self.pnl.fees()
+        );
+        if self.accounts.len() > 1 {
+            for account in &self.accounts {
+                summary.push_str(&format!(
+                    "\n\t{}: {:.2} {} (realised {:.2}, fees {:.2})",
+                    account.runner.name(), account.pnl.session_pnl(), self.currency, account.pnl.realised(),
+                    account.pnl.fees()
+                ));
+            }
+        }
+        info!(target: SESSION_TARGET, "{}", summary);
+        self.output.send(SessionEvent::Finished(summary));
+
+        end
+    }
+
+    /// Executes the commands of the control API and the dashboard and returns whether the session should stop
+    fn handle_commands(&mut self, time: DateTime<Utc>) -> bool {
+        for command in self.control.commands() {
+            info!(target: SESSION_TARGET, "Received the command {}", command);
+            self.output.send(SessionEvent::Command(command));
+            match command {
+                Command::Pause => self.paused = true,
+                Command::Resume => self.paused = false,
+                Command::Flatten => self.flatten(time, "flattened"),
+                Command::Stop => return true,
+            }
+        }
+        false
+    }
+
+    /// the derivative all algorithms trade
+    fn isin(&self) -> &str { self.accounts[0].runner.isin() }
+
+    /// the names of the algorithms
+    fn algorithm_names(&self) -> String {
+        self.accounts
+            .iter()
+            .map(|account| account.runner.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Calls the active algorithms with their sub-books and open orders and returns their orders and errors
+    fn call_algorithms<F>(&mut self, active: &[bool], mut call: F) -> (Vec<(usize, Order)>, Vec<String>)
+        where F: FnMut(&mut AlgorithmRunner<'a>, &Book, &[&TrackedOrder]) -> Result<Vec<Order>, String> {
+        let mut orders = Vec::new();
+        let mut errors = Vec::new();
+
+        for (index, account) in self.accounts.iter_mut().enumerate() {
+            if !active[index] {
+                continue;
+            }
+            let attribution = &self.attribution;
+            let working: Vec<&TrackedOrder> = self.orders
+                                                  .open_orders()
+                                                  .filter(|tracked| attribution.owns(tracked.order.id, index))
+                                                  .collect();
+            match call(&mut account.runner, &account.book, &working) {
+                Ok(account_orders) => orders.extend(account_orders.into_iter().map(|order| (index, order))),
+                Err(err) => errors.push(format!("Algorithm {} failed ({})", account.runner.name(), err)),
+            }
+        }
+
+        (orders, errors)
+    }
+
+    /// Hands the messages of the algorithm runners to the output
+    fn forward_algorithm_log(&mut self) {
+        for account in &mut self.accounts {
+            for message in account.runner.take_messages() {
+                self.output.send(SessionEvent::AlgorithmLog(message));
+            }
+        }
+    }
+
+    /// Hands the current state to the control API
+    fn publish(&self, time: DateTime<Utc>) {
+        if !self.control.is_serving() {
+            return;
+        }
+
+        let status = Status {
+            algorithm: self.algorithm_names(),
+            isin: self.isin().to_string(),
+            trading_type: self.trading_type.to_string(),
+            paused: self.paused,
+            time,
+            position: self.book.position(self.isin()),
+        };
+        let pnl = Pnl {
+            session: self.pnl.session_pnl(),
+            daily: self.pnl.daily_pnl(),
+            realised: self.pnl.realised(),
+            fees: self.pnl.fees(),
+            drawdown: self.pnl.drawdown(),
+        };
+        let positions = self.book
+                            .holdings()
+                            .map(|(isin, holding)| PositionView {
+                                isin: isin.clone(),
+                                quantity: holding.quantity,
+                                average_price: holding.average_price,
+                                last_price: self.book.last_price(isin),
+                            })
+                            .collect();
+        let orders = self.orders.open_orders().cloned().collect();
+
+        self.control.publish(status, pnl, positions, orders);
+    }
+
+    /// Pauses a real time session until the exchange opens (or the session is planned to stop)
+    ///
+    /// Returns whether the session was stopped while it waited.
+    fn wait_for_open(&mut self, time: DateTime<Utc>) -> bool {
+        let mut announced = false;
+        loop {
+            let calendar = match self.calendar {
+                Some(ref calendar) => calendar,
+                None => return false
+            };
+            let now = Utc::now();
+            let open = match calendar.next_open(now) {
+                Some(open) => self.until.map_or(open, |until| open.min(until)),
+                None => return false
+            };
+            if open <= now {
+                return false;
+            }
+            if !announced {
+                info!(target: SESSION_TARGET, "{} is closed, pausing until {}", calendar.name, open);
+                announced = true;
+            }
+
+            // the commands are handled while waiting, so a stop doesn't have to wait for the open
+            let slice = StdDuration::from_millis(COMMAND_POLL_MS);
+            thread::sleep((open - now).to_std().unwrap_or_default().min(slice));
+            if self.handle_commands(time) {
+                return true;
+            }
+        }
+    }
+
+    /// the close the session close hook has to be called for now (once per trading day)
+    fn close_hook_due(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let before_close = self.before_close?;
+        let close = self.calendar.as_ref()?.close(time)?;
+
+        if time >= close - before_close && self.last_close_hook != Some(close) {
+            Some(close)
+        } else { None }
+    }
+
+    /// Restores the state of a checkpoint and compares its orders with the broker
+    fn resume(&mut self, checkpoint: Checkpoint) -> Result<(), String> {
+        info!(target: SESSION_TARGET, "Resuming the session from {}", checkpoint.time);
+
+        // only sessions with a single algorithm write checkpoints, so its sub-book is the whole book
+        let account = &mut self.accounts[0];
+        account.book = checkpoint.book.clone();
+        account.pnl = checkpoint.pnl.clone();
+        account.runner.resume(checkpoint.prices, checkpoint.bars)?;
+        self.book = checkpoint.book;
+        self.orders = checkpoint.orders;
+        self.pnl = checkpoint.pnl;
+        self.broker.restore_held(checkpoint.held);
+        self.broker.restore_sent(checkpoint.sent, checkpoint.time);
+
+        // orders the broker doesn't know anymore were lost with the process
+        self.reconcile(Utc::now());
+        Ok(())
+    }
+
+    fn checkpoint(&mut self, time: DateTime<Utc>) {
+        if let Some(ref mut checkpointer) = self.checkpointer {
+            let runner = &self.accounts[0].runner;
+            let checkpoint = Checkpoint {
+                time,
+                session: checkpointer.session().clone(),
+                prices: runner.prices().to_vec(),
+                bars: runner.bars().to_vec(),
+                book: self.book.clone(),
+                orders: self.orders.clone(),
+                held: self.broker.held().to_vec(),
+                sent: self.broker.sent(),
+                pnl: self.pnl.clone(),
+                journal_entries: Some(self.journal.entries().len()),
+            };
+            if let Err(err) = checkpointer.save(&checkpoint) {
+                let msg = format!("Could not write the checkpoint {} ({})", checkpointer.path().display(), err);
+                warn!(target: SESSION_TARGET, "{}", msg);
+                self.output.send(SessionEvent::Error(msg));
+            }
+        }
+    }
+
+    /// Checks the orders of the accounts against the risk limits and routes the accepted ones
+    ///
+    /// With several algorithms every one of them is limited to its part of the capital as well.
+    fn submit(&mut self, orders: Vec<(usize, Order)>, time: DateTime<Utc>) {
+        // the open orders and the ones accepted before count toward the max position
+        let mut pending: Vec<Order> = self.orders
+                                          .open_orders()
+                                          .map(|tracked| Order { quantity: tracked.remaining(), ..tracked.order.clone() })
+                                          .collect();
+        let mut accepted = Vec::new();
+        for (index, mut order) in orders {
+            let account = &self.accounts[index];
+            let checked = risk::validate(&order).and_then(|_| if self.accounts.len() > 1 {
+                risk::check_allocation(&order, &account.book, account.runner.name(), account.capital)
+            } else { Ok(()) });
+            if let Err(violation) = checked.and_then(|_| self.risk.check(&order, &self.book, &pending, time)) {
+                self.orders.submitted(&mut order, time);
+                self.orders.rejected(order.id, violation.to_string(), time);
+                warn!(target: SESSION_TARGET, "Rejected {}: {}", order, violation);
+                self.output.send(SessionEvent::Rejected { order, reason: violation.to_string() });
+                continue;
+            }
+            pending.push(order.clone());
+            accepted.push((index, order));
+        }
+        self.route(accepted, time);
+    }
+
+    /// Nets the orders of several algorithms and sends the rest to the broker
+    ///
+    /// The orders of a single algorithm are sent as they are.
+    fn route(&mut self, orders: Vec<(usize, Order)>, time: DateTime<Utc>) {
+        if self.accounts.len() == 1 {
+            for (index, order) in orders {
+                let quantity = order.quantity;
+                self.execute(order, vec![(index, quantity)], time);
+            }
+            return;
+        }
+
+        let netted = allocation::net(orders);
+        for (index, order) in netted.crossed {
+            self.cross(index, order, time);
+        }
+        for (order, shares) in netted.orders {
+            self.execute(order, shares, time);
+        }
+    }
+
+    /// Fills the part of a market order that was offset by the order of another algorithm at the last price
+    fn cross(&mut self, index: usize, order: Order, time: DateTime<Utc>) {
+        let price = match self.book.last_price(&order.isin) {
+            Some(price) => price,
+            // without a price there is nothing to cross at, so the broker has to fill it
+            None => {
+                let quantity = order.quantity;
+                return self.execute(order, vec![(index, quantity)], time);
+            }
+        };
+
+        let fill = Fill {
+            order: 0,
+            time,
+            isin: order.isin.clone(),
+            side: order.side,
+            quantity: order.quantity,
+            price,
+            fee: Decimal::ZERO,
+            // the rate of the current tick
+            fx_rate: self.book.rate(&order.isin),
+        };
+        let account = &mut self.accounts[index];
+        info!(target: SESSION_TARGET, "Crossed {} of {} internally", fill, account.runner.name());
+        account.apply_fill(&fill, time);
+        self.journal.record(JournalEntry::Attributed { algorithm: account.runner.name().to_string(), fill });
+    }
+
+    /// Sends an order to the broker, the shares tell which accounts its fills belong to
+    fn execute(&mut self, mut order: Order, shares: Vec<(usize, Decimal)>, time: DateTime<Utc>) {
+        self.orders.submitted(&mut order, time);
+        self.attribution.assign(order.id, shares);
+
+        match self.broker.submit(order.clone(), time) {
+            Ok(fills) => {
+                self.orders.acknowledged(order.id, time);
+                info!(target: SESSION_TARGET, "Submitted {}", order);
+                self.output.send(SessionEvent::Order(order));
+                for fill in fills {
+                    self.apply_fill(fill, time);
+                }
+            }
+            Err(err) => {
+                self.orders.rejected(order.id, err.clone(), time);
+                self.attribution.forget(order.id);
+                let msg = format!("Could not execute {} ({})", order, err);
+                error!(target: SESSION_TARGET, "{}", msg);
+                self.output.send(SessionEvent::Error(msg));
+            }
+        }
+    }
+
+    fn apply_fill(&mut self, mut fill: Fill, time: DateTime<Utc>) {
+        fill.fx_rate = self.book.rate(&fill.isin);
+        info!(target: SESSION_TARGET, "Filled {}", fill);
+        match self.orders.filled(&fill) {
+            Some(status) => info!(target: SESSION_TARGET, "Order #{} is {}", fill.order, status),
+            None => warn!(target: SESSION_TARGET, "Got a fill for the unknown order #{}", fill.order),
+        }
+
+        let realised = self.book.apply_fill(&fill);
+        self.pnl.record_fill(realised, fill.base_fee());
+        self.pnl.update(&self.book, time);
+
+        self.output.send(SessionEvent::Fill(fill.clone()));
+        self.output.send(SessionEvent::Status {
+            position: self.book.position(&fill.isin),
+            pnl: self.pnl.session_pnl(),
+        });
+        let parts = self.attribution.split(&fill);
+        self.journal.record(JournalEntry::Fill(fill));
+
+        // a single algorithm gets the whole fill, so the journal doesn't repeat it
+        let attributed = self.accounts.len() > 1;
+        for (index, part) in parts {
+            let account = &mut self.accounts[index];
+            account.apply_fill(&part, time);
+            if attributed {
+                self.journal.record(JournalEntry::Attributed { algorithm: account.runner.name().to_string(), fill: part });
+            }
+        }
+    }
+
+    /// Compares the open orders with the broker and reports every mismatch
+    fn reconcile(&mut self, time: DateTime<Utc>) {
+        let broker_orders = self.broker.open_orders();
+        for mismatch in self.orders.reconcile(&broker_orders, time) {
+            warn!(target: SESSION_TARGET, "Order mismatch: {}", mismatch);
+            self.output.send(SessionEvent::Error(format!("Order mismatch: {}", mismatch)));
+        }
+    }
+
+    /// Closes the open positions if the profile asks for it
+    fn apply_shutdown_policy(&mut self, time: DateTime<Utc>) {
+        if self.on_shutdown == ShutdownPolicy::Keep {
+            return;
+        }
+        self.close_positions(time);
+    }
+
+    /// Cancels the open orders and closes all positions
+    fn flatten(&mut self, time: DateTime<Utc>, reason: &str) {
+        for order in self.broker.cancel_all() {
+            self.orders.cancelled(order.id, reason.to_string(), time);
+            self.attribution.forget(order.id);
+            self.output.send(SessionEvent::Cancelled { order, reason: reason.to_string() });
+        }
+        self.close_positions(time);
+    }
+
+    /// Closes all positions with market orders, bypassing the risk limits since they reduce the risk
+    ///
+    /// Every algorithm closes its own positions, the ones that offset each other are crossed internally.
+    fn close_positions(&mut self, time: DateTime<Utc>) {
+        let orders = self.accounts
+                         .iter()
+                         .enumerate()
+                         .flat_map(|(index, account)| account.closing_orders().into_iter().map(move |order| (index, order)))
+                         .collect();
+        self.route(orders, time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::path::Path;
+    use std::process;
+
+    use algorithm_utils::{AlgorithmInterface, Derivative, Instruction, Position, Price, WorkingOrder};
+    use algorithm_utils::error::{Error, TradingErrorKind};
+    use algorithm_utils::load::Algorithm;
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::output::{ChartStyle, OutputMode};
+    use crate::trading::bars::Input;
+    use crate::trading::feed::Tick;
+    use crate::trading::fill_model::FillModel;
+    use crate::trading::order::{OrderType, Side};
+    use crate::trading::order_manager::OrderStatus;
+    use crate::trading::params::Parameters;
+    use crate::trading::risk::RiskLimits;
+
+    const ISIN: &str = "DE0001";
+
+    /// buys 2 at the market and places a limit order to buy 3 at 90 on the first tick
+    struct Scripted {
+        instructions: Vec<Instruction<'static>>,
+    }
+
+    impl AlgorithmInterface for Scripted {
+        fn init(&mut self, _: &Derivative, _: chrono::Duration, _: &str) -> Result<(), Error<TradingErrorKind>> {
+            Ok(())
+        }
+
+        fn collect_prices(&mut self, _: &[Price]) -> Result<(), Error<TradingErrorKind>> {
+            Ok(())
+        }
+
+        fn algorithm(&mut self, _: &[Position], _: &[WorkingOrder], prices: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+            self.instructions = if prices.len() == 1 {
+                let derivative = Derivative::new(ISIN.to_string());
+                vec![Instruction::buy(derivative.clone(), 2.0), Instruction::buy(derivative, 3.0).with_limit(90.0)]
+            } else { Vec::new() };
+            Ok(&self.instructions)
+        }
+
+        fn shutdown(&mut self, _: &[Position], _: &[WorkingOrder], _: &[Price]) -> Result<&[Instruction<'_>], Error<TradingErrorKind>> {
+            Ok(&[])
+        }
+    }
+
+    /// replays the ticks and lets the process die afterwards if asked to
+    struct Script {
+        ticks: std::vec::IntoIter<Tick>,
+        crash: bool,
+    }
+
+    impl PriceFeed for Script {
+        fn next_tick(&mut self) -> Result<FeedEvent, String> {
+            match self.ticks.next() {
+                Some(tick) => Ok(FeedEvent::Tick(tick)),
+                None if self.crash => panic!("the process died"),
+                None => Ok(FeedEvent::Exhausted),
+            }
+        }
+    }
+
+    /// a tick every 30 seconds, so only every second one is checkpointed
+    fn ticks() -> Vec<Tick> {
+        [100.0, 100.0, 99.0, 98.0, 92.0, 89.0, 91.0]
+            .iter()
+            .enumerate()
+            .map(|(index, &price)| Tick {
+                time: Utc.with_ymd_and_hms(2021, 3, 1, 9, 0, 0).unwrap() + chrono::Duration::seconds(30 * index as i64),
+                isin: ISIN.to_string(),
+                price,
+                volume: 100.0,
+                bid: None,
+                ask: None,
+            })
+            .collect()
+    }
+
+    fn algorithm() -> Algorithm {
+        Algorithm::new(
+            String::from("scripted"), String::new(), 1, 0, String::new(), Box::new(Scripted { instructions: Vec::new() }),
+        )
+    }
+
+    fn session<'a>(algorithm: &'a mut Algorithm, feed: Script, journal: Journal, checkpoint: &Path) -> Session<'a> {
+        let mut runner = AlgorithmRunner::new(
+            String::from("scripted"), algorithm, Derivative::new(ISIN.to_string()), None, Input::Prices,
+        );
+        runner.init(chrono::Duration::seconds(30), &Parameters::default()).unwrap();
+        let control = Control::new();
+        let info = SessionInfo {
+            trading_type: TradingType::Paper,
+            isin: ISIN.to_string(),
+            algorithm: String::from("scripted"),
+            parameters: Parameters::default(),
+            profile: String::from("default"),
+            interval: 30,
+            journal: None,
+            bars: None,
+        };
+
+        Session {
+            accounts: vec![Account::new(runner, 10_000.0)],
+            attribution: Attribution::new(1),
+            feed: Box::new(feed),
+            broker: EmulatingBroker::new(Box::new(SimulatedBroker::new(FillModel::default(), BTreeMap::new()))),
+            book: Book::new(),
+            orders: OrderManager::new(),
+            risk: RiskManager::new(RiskLimits::default()),
+            pnl: PnlTracker::new(),
+            kill_switch: KillSwitchLimits::default(),
+            on_shutdown: ShutdownPolicy::Keep,
+            journal,
+            output: Output::start(&[OutputMode::None], ChartStyle::Line, false, false, control.sender(), None),
+            checkpointer: Some(Checkpointer::with_path(checkpoint.to_path_buf(), info)),
+            // the feed decides when the session ends
+            trading_type: TradingType::Back,
+            calendar: None,
+            fx: FxRates::load("EUR", BTreeMap::new()).unwrap(),
+            currency: String::from("EUR"),
+            before_close: None,
+            last_close_hook: None,
+            until: None,
+            control,
+            paused: false,
+        }
+    }
+
+    #[test]
+    fn a_resumed_session_continues_where_the_crashed_one_stopped() {
+        let temp = |name: &str| env::temp_dir().join(format!("trading-desk-{}-{}", process::id(), name));
+        let (journal_file, checkpoint_file) = (temp("crashed.jsonl"), temp("crashed-checkpoint.json"));
+        let (uninterrupted_file, uninterrupted_checkpoint) = (temp("uninterrupted.jsonl"), temp("uninterrupted-checkpoint.json"));
+
+        let mut algorithm_uninterrupted = algorithm();
+        let feed = Script { ticks: ticks().into_iter(), crash: false };
+        let journal = Journal::to_file(&uninterrupted_file).unwrap();
+        session(&mut algorithm_uninterrupted, feed, journal, &uninterrupted_checkpoint).run();
+        let expected = Journal::read(&uninterrupted_file).unwrap();
+
+        // the process dies after the fourth tick, the last checkpoint was written at the third one
+        let mut crashed_algorithm = algorithm();
+        let feed = Script { ticks: ticks()[..4].to_vec().into_iter(), crash: true };
+        let journal = Journal::to_file(&journal_file).unwrap();
+        let mut crashed = session(&mut crashed_algorithm, feed, journal, &checkpoint_file);
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| crashed.run())).is_err());
+        drop(crashed);
+
+        let checkpoint = Checkpoint::load(&checkpoint_file).unwrap();
+        assert_eq!(checkpoint.time, ticks()[2].time);
+        assert_eq!(checkpoint.sent.len(), 1);
+        assert!(Journal::read(&journal_file).unwrap().len() > checkpoint.journal_entries.unwrap());
+
+        let mut resumed_algorithm = algorithm();
+        let later: Vec<Tick> = ticks().into_iter().filter(|tick| tick.time > checkpoint.time).collect();
+        let feed = Script { ticks: later.into_iter(), crash: false };
+        let journal = Journal::append_to_file(&journal_file, checkpoint.journal_entries).unwrap();
+        let mut resumed = session(&mut resumed_algorithm, feed, journal, &checkpoint_file);
+        resumed.resume(checkpoint).unwrap();
+        resumed.run();
+        let limit = resumed.orders.open_orders().count();
+        drop(resumed);
+        let entries = Journal::read(&journal_file).unwrap();
+
+        for path in &[journal_file, checkpoint_file, uninterrupted_file, uninterrupted_checkpoint] {
+            let _ = fs::remove_file(path);
+        }
+        // no tick or fill is journaled twice and the limit order filled after the resume
+        assert_eq!(entries, expected);
+        assert_eq!(entries.iter().filter(|entry| matches!(entry, JournalEntry::Fill(_))).count(), 2);
+        assert_eq!(limit, 0);
+    }
+
+    #[test]
+    fn reconciling_leaves_the_book_and_the_fills_alone() {
+        let path = env::temp_dir().join(format!("trading-desk-{}-reconciled-checkpoint.json", process::id()));
+        let mut algorithm = algorithm();
+        let feed = Script { ticks: Vec::new().into_iter(), crash: false };
+        let mut session = session(&mut algorithm, feed, Journal::in_memory(), &path);
+        // the broker fills 2 of a tick with the volume 100
+        let model = FillModel { max_volume_share: Some(0.02), ..FillModel::default() };
+        session.broker = EmulatingBroker::new(Box::new(SimulatedBroker::new(model, BTreeMap::new())));
+        let ticks = ticks();
+        session.broker.update(&ticks[0]).unwrap();
+        session.book.update_price(ISIN, Decimal::from_f64(ticks[0].price));
+        let limit = Order::new(ISIN.to_string(), Side::Buy, Decimal::from_f64(5.0), OrderType::Limit { limit: Decimal::from_f64(95.0) });
+        session.execute(limit, vec![(0, Decimal::from_f64(5.0))], ticks[0].time);
+
+        // a fill the session never got
+        let lost = session.broker.update(&Tick { price: 94.0, ..ticks[1].clone() }).unwrap();
+        assert_eq!(lost.len(), 1);
+        session.reconcile(ticks[1].time);
+
+        let tracked = session.orders.open_orders().next().unwrap();
+        assert_eq!((tracked.filled, tracked.status), (Decimal::ZERO, OrderStatus::Acknowledged));
+        assert_eq!(tracked.broker_remaining, Some(Decimal::from_f64(3.0)));
+        assert_eq!(session.book.position(ISIN), Decimal::ZERO);
+        assert!(session.journal.entries().is_empty());
+    }
+
+    #[test]
+    fn the_kill_switch_halts_the_session_once_a_limit_is_breached() {
+        let path = env::temp_dir().join(format!("trading-desk-{}-halted-checkpoint.json", process::id()));
+        let mut algorithm = algorithm();
+        let feed = Script { ticks: ticks().into_iter(), crash: false };
+        let mut session = session(&mut algorithm, feed, Journal::in_memory(), &path);
+        session.kill_switch = KillSwitchLimits { max_drawdown: Some(20.0), max_daily_loss: None };
+
+        let end = session.run();
+        let _ = fs::remove_file(&path);
+        match end {
+            SessionEnd::Halted(reason) => assert!(reason.starts_with("drawdown of"), "{}", reason),
+            _ => panic!("the session wasn't halted"),
+        }
+        // the prices after the breach are never seen
+        let last_mark = session.journal.entries().iter().rev().find_map(|entry| match entry {
+            JournalEntry::Mark { time, .. } => Some(*time),
+            _ => None,
+        });
+        assert_eq!(last_mark, Some(ticks()[5].time));
+        assert!(session.pnl.drawdown() > Decimal::from_f64(20.0));
+    }
+}